@@ -0,0 +1,8 @@
+// Record when the crate was built, for the reproducibility manifest (see `layout::Manifest`)
+fn main() {
+    let build_unix_time = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    println!("cargo:rustc-env=COMRADE_BUILD_UNIX_TIME={}", build_unix_time);
+}
@@ -0,0 +1,367 @@
+use crate::{
+    layout,
+    sim,
+    args,
+    io,
+    geo_3d::*,
+    warnings,
+    ComradeResult,
+};
+
+/// Run an analysis tool.
+/// Returns a `ComradeResult` with `()` or an `Err`.
+pub fn run_analysis(analyze_args: args::AnalyzeArgs) -> ComradeResult<()> {
+    match analyze_args.analysis {
+        args::Analysis::CouplingVsDistance(kd_args) => coupling_vs_distance(kd_args)?,
+        args::Analysis::Overlap(overlap_args) => overlap_heatmap(overlap_args)?,
+        args::Analysis::Spice(spice_args) => spice_netlist(spice_args)?,
+        args::Analysis::Sensitivity(sensitivity_args) => sensitivity_map(sensitivity_args)?,
+        args::Analysis::Stats(stats_args) => layout_statistics(stats_args)?,
+        args::Analysis::Uniformity(uniformity_args) => {
+            fov_uniformity(uniformity_args)?;
+        },
+    }
+    Ok(())
+}
+
+/// Compare two layouts and print the differences.
+/// Returns a `ComradeResult` with `()` or an `Err`.
+pub fn run_diff(diff_args: args::DiffArgs) -> ComradeResult<()> {
+    diff_layouts(diff_args)?;
+    Ok(())
+}
+
+/// Load two layouts, print per-coil and coupling differences, and optionally save the full diff.
+fn diff_layouts(diff_args: args::DiffArgs) -> layout::ProcResult<()> {
+    for path in [&diff_args.layout_a, &diff_args.layout_b] {
+        if !path.ends_with(".json") {
+            return layout::err_str(&format!("Layout file must be a .json file: {}", path));
+        }
+    }
+
+    println!("Loading layouts: {} and {}", diff_args.layout_a, diff_args.layout_b);
+    let layout_a = layout::load_layout(&diff_args.layout_a)?;
+    let layout_b = layout::load_layout(&diff_args.layout_b)?;
+
+    let diff = layout::diff_layouts(&layout_a, &layout_b, diff_args.close_cutoff);
+
+    println!();
+    println!("coil, label, group, dx, dy, dz, |d|, d_radius, d_wire_length");
+    for coil_diff in diff.coils.iter() {
+        let coil = &layout_a.coils[coil_diff.coil_id];
+        println!("{}, {}, {}, {:.3}, {:.3}, {:.3}, {:.3}, {:.3}, {:.3}",
+            coil_diff.coil_id,
+            crate::io::escape_csv_field(coil.label.as_deref().unwrap_or("")),
+            coil.group.map_or(String::new(), |group| group.to_string()),
+            coil_diff.center_delta.x, coil_diff.center_delta.y, coil_diff.center_delta.z,
+            coil_diff.center_delta.norm(),
+            coil_diff.radius_delta,
+            coil_diff.wire_length_delta,
+        );
+    }
+    println!();
+
+    if diff.count_changed() {
+        warnings::warn(&format!("Coil counts differ ({} vs. {}) -- coils {}..{} are only in the {} layout",
+            diff.coil_count_a, diff.coil_count_b,
+            diff.coils.len(), diff.coil_count_a.max(diff.coil_count_b),
+            if diff.coil_count_a > diff.coil_count_b {"first"} else {"second"},
+        ));
+    }
+
+    let fmt_k = |k: Option<f32>| k.map_or("n/a".to_string(), |k| format!("{:.4}", k));
+    println!("Max close coupling: {} -> {} (delta {})", fmt_k(diff.max_coupling.0), fmt_k(diff.max_coupling.1), fmt_k(diff.max_coupling_delta()));
+    println!("RMS close coupling: {} -> {} (delta {})", fmt_k(diff.rms_coupling.0), fmt_k(diff.rms_coupling.1), fmt_k(diff.rms_coupling_delta()));
+
+    if let Some(output_path) = diff_args.output.as_ref() {
+        println!("Saving diff to {}...", output_path);
+        io::save_ser_to(output_path, &diff)?;
+    }
+
+    Ok(())
+}
+
+/// Sweep two coils apart and print the coupling factor against relative separation.
+fn coupling_vs_distance(kd_args: args::CouplingVsDistanceArgs) -> layout::ProcResult<()> {
+    if kd_args.steps < 2 {
+        return layout::err_str("Coupling sweep needs at least 2 steps");
+    }
+    if kd_args.radius <= 0.0 {
+        return layout::err_str("Coupling sweep radius must be positive");
+    }
+
+    println!("Loading STL file: {}", kd_args.input_path);
+    let surface = io::stl::load_stl(&kd_args.input_path)?;
+
+    let center = Point::new(kd_args.center[0], kd_args.center[1], kd_args.center[2]);
+    let direction = GeoVector::new(kd_args.direction[0], kd_args.direction[1], kd_args.direction[2]);
+    if direction.norm() <= 0.0 {
+        return layout::err_str("Coupling sweep direction must be nonzero");
+    }
+
+    let circle_a = layout::CircleArgs{
+        center,
+        coil_radius: kd_args.radius,
+        ..layout::CircleArgs::default()
+    };
+    let circle_b = layout::CircleArgs{
+        center: center + direction.normalize() * kd_args.radius,
+        ..circle_a
+    };
+
+    let distances: Vec<f32> = (0..kd_args.steps).map(|i| {
+        let d_rel = kd_args.min_rel + (kd_args.max_rel - kd_args.min_rel) * i as f32 / (kd_args.steps - 1) as f32;
+        d_rel * 2.0 * kd_args.radius
+    }).collect();
+
+    println!("Sweeping coupling over {} separations...", distances.len());
+    let coupling_curve = layout::coupling_vs_distance(&surface, circle_a, circle_b, &distances)?;
+
+    println!();
+    println!("d_rel, k");
+    for (d_rel, k) in coupling_curve.iter() {
+        println!("{:.3}, {:.4}", d_rel, k);
+    }
+
+    if let Some(output_path) = kd_args.output.as_ref() {
+        println!("Saving coupling curve to {}...", output_path);
+        io::save_ser_to(output_path, &coupling_curve)?;
+    }
+
+    Ok(())
+}
+
+/// Count the coils covering each surface vertex, print a summary, and save the counts as a VTK scalar field.
+/// Optionally also save the owning coil of each vertex.
+fn overlap_heatmap(overlap_args: args::OverlapArgs) -> layout::ProcResult<()> {
+    if !overlap_args.layout_path.ends_with(".json") {
+        return layout::err_str(&format!("Layout file must be a .json file: {}", overlap_args.layout_path));
+    }
+    if !overlap_args.output.ends_with(".vtk") {
+        return layout::err_str(&format!("Overlap heatmap output must be a .vtk file: {}", overlap_args.output));
+    }
+    if let Some(ownership_path) = overlap_args.ownership.as_ref() {
+        if !ownership_path.ends_with(".vtk") {
+            return layout::err_str(&format!("Coil ownership output must be a .vtk file: {}", ownership_path));
+        }
+    }
+    if let Some(footprint_path) = overlap_args.footprint.as_ref() {
+        if !footprint_path.ends_with(".vtk") {
+            return layout::err_str(&format!("Footprint outline output must be a .vtk file: {}", footprint_path));
+        }
+    }
+
+    let surface = layout::load_surface(&overlap_args.input_path)?;
+    println!("Loading layout: {}", overlap_args.layout_path);
+    let layout_in = layout::load_layout(&overlap_args.layout_path)?;
+
+    let coverage = layout_in.vertex_coverage(&surface);
+    let max_count = coverage.iter().copied().max().unwrap_or(0);
+    let crowded = coverage.iter().filter(|count| **count >= 3).count();
+    println!();
+    println!("Max coils over a vertex: {}", max_count);
+    println!("Vertices under 3 or more coils: {} / {}", crowded, coverage.len());
+
+    println!("Saving overlap heatmap to {}...", overlap_args.output);
+    let values: Vec<f32> = coverage.iter().map(|count| *count as f32).collect();
+    io::vtk::save_point_scalars(&overlap_args.output, &surface, "coil_count", &values)?;
+
+    if let Some(ownership_path) = overlap_args.ownership.as_ref() {
+        println!("Saving coil ownership to {}...", ownership_path);
+        io::vtk::save_point_ints(ownership_path, &surface, "coil_id", &layout_in.vertex_ownership(&surface))?;
+    }
+
+    if let Some(footprint_path) = overlap_args.footprint.as_ref() {
+        let outline = layout_in.footprint_outline(&surface);
+        if outline.is_empty() {
+            warnings::warn("No surface vertex is covered, the footprint outline is empty");
+        }
+        println!("Saving footprint outline ({} points) to {}...", outline.len(), footprint_path);
+        io::vtk::save_closed_polyline(footprint_path, &outline)?;
+    }
+
+    Ok(())
+}
+
+/// Load a layout and print its statistics, with the coverage and lift-off if a surface is given.
+/// Optionally save the full statistics.
+fn layout_statistics(stats_args: args::StatsArgs) -> layout::ProcResult<()> {
+    if !stats_args.layout_path.ends_with(".json") {
+        return layout::err_str(&format!("Layout file must be a .json file: {}", stats_args.layout_path));
+    }
+
+    println!("Loading layout: {}", stats_args.layout_path);
+    let layout_in = layout::load_layout_in(&stats_args.layout_path, stats_args.length_unit)?;
+    let surface = match stats_args.surface.as_ref() {
+        Some(surface_path) => Some(layout::load_surface(surface_path)?),
+        None => None,
+    };
+    if stats_args.max_liftoff.is_some() && surface.is_none() {
+        return layout::err_str("Checking the lift-off needs the surface");
+    }
+    // The dipole approximation is for far pairs, so gather those too when it's asked for
+    let options = layout::StatisticsOptions{
+        surface: surface.as_ref(),
+        dipole_cutoff: stats_args.dipole_cutoff,
+        all_pairs: stats_args.dipole_cutoff.is_some(),
+        ..Default::default()
+    };
+    options.check()?;
+    let stats = layout::statistics(&layout_in, stats_args.close_cutoff, &options);
+
+    println!();
+    println!("Coils:");
+    for (coil_id, coil) in stats.coils.iter().enumerate() {
+        let liftoff = coil.max_liftoff.map_or(String::new(), |liftoff| format!(", Lift-off [{:.2}]", liftoff));
+        println!("Coil {}: Radius [{:.2}], Center [{:.2}], Length [{:.2}], Min Bend [{:.2}], Self-inductance [{:.2}nH]{}",
+            coil_id, coil.radius, coil.center, coil.wire_length, coil.min_bend_radius, coil.self_inductance, liftoff
+        );
+    }
+    println!();
+
+    println!("Close pairs:");
+    for (coil_id, other_id, m, k) in stats.close_pairs.iter() {
+        println!("Coil {} with Coil {}: {:.2}nH (k = {:.4})", coil_id, other_id, m, k);
+    }
+    println!();
+
+    if options.all_pairs {
+        let max_far_k = stats.far_pairs.iter().fold(0.0, |max: f32, (_, _, _, k)| max.max(k.abs()));
+        println!("Far pairs: {} (max coupling {:.4})", stats.far_pairs.len(), max_far_k);
+        println!();
+    }
+
+    let fmt_k = |k: Option<f32>| k.map_or("n/a".to_string(), |k| format!("{:.4}", k));
+    println!("Max close coupling: {}", fmt_k(stats.max_coupling));
+    println!("RMS close coupling: {}", fmt_k(stats.rms_coupling));
+    println!("Total wire length: {:.2}", stats.coils.iter().map(|coil| coil.wire_length).sum::<f32>());
+
+    if let Some(surface) = surface.as_ref() {
+        let coverage = layout_in.vertex_coverage(surface);
+        let covered = coverage.iter().filter(|count| **count > 0).count();
+        println!("Covered vertices: {} / {} ({:.1}%)", covered, coverage.len(), 100.0 * covered as f32 / coverage.len().max(1) as f32);
+        let gaps = layout_in.coverage_gaps(surface);
+        if let Some(largest_gap) = gaps.first() {
+            let vertex_areas = surface.vertex_areas();
+            let largest_area = largest_gap.iter().map(|vertex_id| vertex_areas[*vertex_id]).sum::<f32>();
+            println!("Coverage gaps: {} (largest {:.1} mm^2 around {:.1})", gaps.len(), largest_area, surface.vertices[largest_gap[0]].point);
+        }
+        if let Some(max_liftoff) = stats_args.max_liftoff {
+            for (coil_id, liftoff) in layout_in.coils_above_liftoff(surface, max_liftoff) {
+                warnings::warn_grouped("Coil lifts off the surface", &format!("Coil {} lifts {:.2} off the surface, above the limit of {:.2}", coil_id, liftoff, max_liftoff));
+            }
+        }
+    }
+
+    if let Some(output_path) = stats_args.output.as_ref() {
+        println!("Saving statistics to {}...", output_path);
+        io::save_ser_to(output_path, &stats)?;
+    }
+
+    Ok(())
+}
+
+/// Load a layout and write it as a SPICE netlist.
+fn spice_netlist(spice_args: args::SpiceArgs) -> layout::ProcResult<()> {
+    if !spice_args.layout_path.ends_with(".json") {
+        return layout::err_str(&format!("Layout file must be a .json file: {}", spice_args.layout_path));
+    }
+
+    println!("Loading layout: {}", spice_args.layout_path);
+    let layout_in = layout::load_layout_in(&spice_args.layout_path, spice_args.length_unit)?;
+
+    println!("Saving SPICE netlist to {}...", spice_args.output);
+    let netlist = layout::export_spice_netlist(&layout_in, &spice_args.output, spice_args.larmor_mhz, spice_args.close_cutoff)?;
+    println!("Wrote {} coil subcircuits and {} coupling statements",
+        layout_in.coils.len(), netlist.lines().filter(|line| line.starts_with('K')).count()
+    );
+
+    Ok(())
+}
+
+/// Load a layout, combine the coil B1 maps over the region of interest, and save the combined sensitivity volume.
+fn sensitivity_map(sensitivity_args: args::SensitivityArgs) -> ComradeResult<()> {
+    if !sensitivity_args.layout_path.ends_with(".json") {
+        return Err(format!("Layout file must be a .json file: {}", sensitivity_args.layout_path).into());
+    }
+    if !sensitivity_args.output.ends_with(".vtk") {
+        return Err(format!("Sensitivity output must be a .vtk file: {}", sensitivity_args.output).into());
+    }
+
+    println!("Loading layout: {}", sensitivity_args.layout_path);
+    let layout_in = layout::load_layout(&sensitivity_args.layout_path)?;
+
+    let roi = roi_box(&sensitivity_args.roi_min, &sensitivity_args.roi_max, sensitivity_args.samples)?;
+    println!("Computing the B1 maps of {} coils over {} samples...", layout_in.coils.len(), roi.grid_points().len());
+    let field_maps = sim::coil_field_maps(&layout_in.coils, &roi, sensitivity_args.subdivisions);
+    let sensitivity = sim::combined_sensitivity(&field_maps, sensitivity_args.phases.as_deref())?;
+
+    println!("Saving sensitivity volume to {}...", sensitivity_args.output);
+    let field_name = if sensitivity_args.phases.is_some() { "phased_b1" } else { "rss_b1" };
+    sim::save_roi_map(&sensitivity_args.output, &roi, field_name, &sensitivity)?;
+
+    Ok(())
+}
+
+/// Load a layout and report the uniformity of the combined field over the region of interest.
+/// Returns a `ComradeResult` with the uniformity (std. dev. over mean of the combined |B1|) or an `Err`.
+fn fov_uniformity(uniformity_args: args::UniformityArgs) -> ComradeResult<f32> {
+    if !uniformity_args.layout_path.ends_with(".json") {
+        return Err(format!("Layout file must be a .json file: {}", uniformity_args.layout_path).into());
+    }
+
+    println!("Loading layout: {}", uniformity_args.layout_path);
+    let layout_in = layout::load_layout(&uniformity_args.layout_path)?;
+    let roi = roi_box(&uniformity_args.roi_min, &uniformity_args.roi_max, uniformity_args.samples)?;
+
+    println!("Combining the B1 of {} coils over {} samples...", layout_in.coils.len(), roi.grid_points().len());
+    let uniformity = sim::fov_uniformity(&layout_in.coils, &roi, uniformity_args.combination, uniformity_args.subdivisions)?;
+    println!("FOV uniformity (std. dev. / mean): {:.4}", uniformity);
+
+    Ok(uniformity)
+}
+
+/// Build the region of interest from its corners on the command line, and check it.
+fn roi_box(roi_min: &[f32], roi_max: &[f32], samples: usize) -> ComradeResult<sim::RoiBox> {
+    let roi = sim::RoiBox{
+        min: Point::new(roi_min[0], roi_min[1], roi_min[2]),
+        max: Point::new(roi_max[0], roi_max[1], roi_max[2]),
+        samples_per_axis: samples,
+    };
+    roi.check()?;
+    Ok(roi)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::{circle_coil, temp_dir};
+
+    fn uniformity_args(cli_args: &[&str]) -> args::UniformityArgs {
+        match args::parse_cli_from(cli_args.iter().copied()).subcommand {
+            args::SubCommand::Analyze(args::AnalyzeArgs{analysis: args::Analysis::Uniformity(uniformity_args)}) => uniformity_args,
+            _ => panic!("Expected the uniformity analysis"),
+        }
+    }
+
+    #[test]
+    fn uniformity_analysis_reports_the_fov_uniformity() {
+        let test_dir = temp_dir("uniformity_test");
+        let layout_path = test_dir.path().join("layout.json").to_string_lossy().to_string();
+        let coils = vec![circle_coil(Point::zero(), 50.0)];
+        layout::save_layout(&layout::Layout{coils: coils.clone()}, &layout_path).unwrap();
+
+        let uniformity = fov_uniformity(uniformity_args(&[
+            "comrade", "analyze", "uniformity", &layout_path,
+            "--roi-min", "-5", "-5", "25", "--roi-max", "5", "5", "35", "--samples", "4", "--combination", "sum-of-magnitudes",
+        ])).unwrap();
+        let roi = sim::RoiBox{min: Point::new(-5.0, -5.0, 25.0), max: Point::new(5.0, 5.0, 35.0), samples_per_axis: 4};
+        let expected = sim::fov_uniformity(&coils, &roi, sim::FieldCombination::SumOfMagnitudes, 1).unwrap();
+        assert_eq!(uniformity, expected);
+        assert!(uniformity > 0.0 && uniformity < 1.0, "Uniformity {}", uniformity);
+
+        // An inverted region is rejected
+        let inverted = uniformity_args(&["comrade", "analyze", "uniformity", &layout_path, "--roi-min", "5", "5", "35", "--roi-max", "-5", "-5", "25"]);
+        assert!(fov_uniformity(inverted).is_err());
+    }
+}
@@ -44,9 +44,19 @@ pub enum SubCommand {
     /// Output example config file for a stage.
     #[command(name = "example-cfg")]
     Example(ExampleArgs),
+    /// Run an analysis tool.
+    #[command(name = "analyze")]
+    Analyze(AnalyzeArgs),
+    /// Compare two layouts and report geometric and coupling differences.
+    #[command(name = "diff")]
+    Diff(DiffArgs),
+    /// List the available methods for each stage.
+    #[command(name = "list")]
+    List,
 }
 
 /// Run command arguments.
+/// The filepaths fall back to `COMRADE_*` environment variables when their flags are absent (see `RunArgs::with_env_fallbacks`).
 #[derive(Debug, Clone)]
 #[derive(Args)]
 pub struct RunArgs {
@@ -59,21 +69,55 @@ pub struct RunArgs {
     #[arg(long = "to")]
     pub end_stage: Option<RunStage>,
     
-    /// Layout config filepath.
+    /// Layout config filepath [env: COMRADE_LAYOUT_CFG].
     #[arg(long)]
     pub layout_cfg: Option<String>,
     
-    /// Mesh config filepath.
+    /// Mesh config filepath [env: COMRADE_MESH_CFG].
     #[arg(long)]
     pub mesh_cfg: Option<String>,
     
-    /// Simulation config filepath.
+    /// Simulation config filepath [env: COMRADE_SIM_CFG].
     #[arg(long)]
     pub sim_cfg: Option<String>,
     
-    /// Matching config filepath.
+    /// Matching config filepath [env: COMRADE_MATCH_CFG].
     #[arg(long = "match_cfg")]
     pub matching_cfg: Option<String>,
+
+    /// Bundle filepath (.comrade tar or zip archive with the surface, stage configs, and other inputs) [env: COMRADE_BUNDLE].
+    /// Configs given on the command line take precedence over bundled ones.
+    #[arg(long)]
+    pub bundle: Option<String>,
+
+    /// Surface filepath for the layout stage, replacing the input path in the layout config [env: COMRADE_SURFACE].
+    #[arg(long)]
+    pub surface: Option<String>,
+
+    /// Write the layout config as it will run, with every default filled in, to this filepath (json, toml, or yaml).
+    /// The written config can be run again as a layout config.
+    #[arg(long)]
+    pub dump_effective_config: Option<String>,
+}
+impl RunArgs {
+    /// Fill the filepaths missing from the command line from environment variables, e.g. for containerized or batch runs.
+    /// Precedence is the command line flag, then the environment variable, then the bundle (for configs) or the layout config (for the surface).
+    /// `env_var` looks up a variable, and empty values count as unset.
+    pub fn with_env_fallbacks(mut self, env_var: impl Fn(&str) -> Option<String>) -> Self {
+        for (arg, key) in [
+            (&mut self.layout_cfg, "COMRADE_LAYOUT_CFG"),
+            (&mut self.mesh_cfg, "COMRADE_MESH_CFG"),
+            (&mut self.sim_cfg, "COMRADE_SIM_CFG"),
+            (&mut self.matching_cfg, "COMRADE_MATCH_CFG"),
+            (&mut self.bundle, "COMRADE_BUNDLE"),
+            (&mut self.surface, "COMRADE_SURFACE"),
+        ] {
+            if arg.is_none() {
+                *arg = env_var(key).filter(|value| !value.is_empty());
+            }
+        }
+        self
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -91,6 +135,233 @@ pub struct ExampleArgs {
     pub format: Format,
 }
 
+/// Analyze command arguments.
+#[derive(Debug, Clone)]
+#[derive(Args)]
+pub struct AnalyzeArgs {
+    #[command(subcommand)]
+    pub analysis: Analysis,
+}
+
+/// Diff command arguments.
+#[derive(Debug, Clone)]
+#[derive(Args)]
+pub struct DiffArgs {
+    /// First layout filepath (JSON).
+    pub layout_a: String,
+
+    /// Second layout filepath (JSON).
+    pub layout_b: String,
+
+    /// Coupling is summarized over coil pairs closer than this, relative to the sum of their radii.
+    #[arg(long, default_value_t = 0.95)]
+    pub close_cutoff: f32,
+
+    /// Optional output filepath for the full diff.
+    #[arg(short, long)]
+    pub output: Option<String>,
+}
+
+/// Available analyses.
+#[derive(Debug, Clone)]
+#[derive(Subcommand)]
+pub enum Analysis {
+    /// Sweep two coils apart on a surface and print the coupling factor against relative separation.
+    #[command(name = "kd")]
+    CouplingVsDistance(CouplingVsDistanceArgs),
+    /// Count the coils covering each surface vertex and save the counts as a VTK heatmap.
+    #[command(name = "overlap")]
+    Overlap(OverlapArgs),
+    /// Write the layout as a SPICE netlist of coil subcircuits coupled by K statements.
+    #[command(name = "spice")]
+    Spice(SpiceArgs),
+    /// Combine the coil B1 maps over a box-shaped region into one sensitivity volume (VTK).
+    #[command(name = "sensitivity")]
+    Sensitivity(SensitivityArgs),
+    /// Load a layout and print its coil, inductance and coupling statistics.
+    #[command(name = "stats")]
+    Stats(StatsArgs),
+    /// Report how uniform the combined |B1| of the coils is over a box-shaped region (std. dev. over mean).
+    #[command(name = "uniformity")]
+    Uniformity(UniformityArgs),
+}
+
+/// Coupling vs. distance (k vs. d) analysis arguments.
+#[derive(Debug, Clone)]
+#[derive(Args)]
+pub struct CouplingVsDistanceArgs {
+    /// Input surface filepath (STL).
+    pub input_path: String,
+
+    /// Center of the fixed coil.
+    #[arg(long, required = true, num_args = 3, value_names = ["X", "Y", "Z"], allow_negative_numbers = true)]
+    pub center: Vec<f32>,
+
+    /// Direction to sweep the second coil along.
+    #[arg(long, num_args = 3, value_names = ["X", "Y", "Z"], allow_negative_numbers = true, default_values_t = [1.0, 0.0, 0.0])]
+    pub direction: Vec<f32>,
+
+    /// Radius of both coils.
+    #[arg(short, long, default_value_t = 5.0)]
+    pub radius: f32,
+
+    /// Smallest separation, relative to the sum of the radii.
+    #[arg(long, default_value_t = 0.5)]
+    pub min_rel: f32,
+
+    /// Largest separation, relative to the sum of the radii.
+    #[arg(long, default_value_t = 1.5)]
+    pub max_rel: f32,
+
+    /// Number of separations to sample.
+    #[arg(long, default_value_t = 11)]
+    pub steps: usize,
+
+    /// Optional output filepath for the (d_rel, k) pairs.
+    #[arg(short, long)]
+    pub output: Option<String>,
+}
+
+/// Coil overlap heatmap analysis arguments.
+#[derive(Debug, Clone)]
+#[derive(Args)]
+pub struct OverlapArgs {
+    /// Input surface filepath (STL or MSH).
+    pub input_path: String,
+
+    /// Layout filepath (JSON).
+    pub layout_path: String,
+
+    /// Output filepath for the heatmap (VTK).
+    #[arg(short, long, default_value = "overlap.vtk")]
+    pub output: String,
+
+    /// Optional output filepath for the surface colored by owning coil id, -1 where uncovered (VTK).
+    #[arg(long)]
+    pub ownership: Option<String>,
+
+    /// Optional output filepath for the outline of the array footprint on the surface (VTK polyline).
+    #[arg(long)]
+    pub footprint: Option<String>,
+}
+
+/// SPICE netlist export arguments.
+#[derive(Debug, Clone)]
+#[derive(Args)]
+pub struct SpiceArgs {
+    /// Layout filepath (JSON).
+    pub layout_path: String,
+
+    /// Output filepath for the netlist.
+    #[arg(short, long, default_value = "array.cir")]
+    pub output: String,
+
+    /// Larmor frequency (MHz) to tune the coils to.
+    #[arg(long, default_value_t = 127.73)]
+    pub larmor_mhz: f32,
+
+    /// Coil pairs closer than this, relative to the sum of their radii, get a K statement.
+    #[arg(long, default_value_t = 0.95)]
+    pub close_cutoff: f32,
+
+    /// Length unit of the layout coordinates, converted to mm for the inductances.
+    #[arg(long, value_enum, default_value_t = crate::layout::LengthUnit::Millimeters)]
+    pub length_unit: crate::layout::LengthUnit,
+}
+
+/// Combined sensitivity map arguments.
+#[derive(Debug, Clone)]
+#[derive(Args)]
+pub struct SensitivityArgs {
+    /// Layout filepath (JSON).
+    pub layout_path: String,
+
+    /// Minimum corner of the region of interest.
+    #[arg(long, required = true, num_args = 3, value_names = ["X", "Y", "Z"], allow_negative_numbers = true)]
+    pub roi_min: Vec<f32>,
+
+    /// Maximum corner of the region of interest.
+    #[arg(long, required = true, num_args = 3, value_names = ["X", "Y", "Z"], allow_negative_numbers = true)]
+    pub roi_max: Vec<f32>,
+
+    /// Number of samples along each axis of the region.
+    #[arg(short, long, default_value_t = 16)]
+    pub samples: usize,
+
+    /// Optional phase of each coil (degrees), to sum the fields coherently instead of by root sum of squares.
+    #[arg(long, num_args = 1.., allow_negative_numbers = true)]
+    pub phases: Option<Vec<f32>>,
+
+    /// Number of segments to split each coil span into for the field calculation.
+    #[arg(long, default_value_t = 1)]
+    pub subdivisions: usize,
+
+    /// Output filepath for the sensitivity volume (VTK).
+    #[arg(short, long, default_value = "sensitivity.vtk")]
+    pub output: String,
+}
+
+/// Field of view uniformity arguments.
+#[derive(Debug, Clone)]
+#[derive(Args)]
+pub struct UniformityArgs {
+    /// Layout filepath (JSON).
+    pub layout_path: String,
+
+    /// Minimum corner of the region of interest.
+    #[arg(long, required = true, num_args = 3, value_names = ["X", "Y", "Z"], allow_negative_numbers = true)]
+    pub roi_min: Vec<f32>,
+
+    /// Maximum corner of the region of interest.
+    #[arg(long, required = true, num_args = 3, value_names = ["X", "Y", "Z"], allow_negative_numbers = true)]
+    pub roi_max: Vec<f32>,
+
+    /// Number of samples along each axis of the region.
+    #[arg(short, long, default_value_t = 8)]
+    pub samples: usize,
+
+    /// How the coil |B1| are combined at each sample.
+    #[arg(long, value_enum, default_value_t = crate::sim::FieldCombination::Rss)]
+    pub combination: crate::sim::FieldCombination,
+
+    /// Number of segments to split each coil span into for the field calculation.
+    #[arg(long, default_value_t = 1)]
+    pub subdivisions: usize,
+}
+
+/// Layout statistics arguments.
+#[derive(Debug, Clone)]
+#[derive(Args)]
+pub struct StatsArgs {
+    /// Layout filepath (JSON).
+    pub layout_path: String,
+
+    /// Coupling is summarized over coil pairs closer than this, relative to the sum of their radii.
+    #[arg(long, default_value_t = 0.95)]
+    pub close_cutoff: f32,
+
+    /// Optional input surface filepath (STL or MSH), to report the coverage and how far the coils lift off it.
+    #[arg(long)]
+    pub surface: Option<String>,
+
+    /// Warn about coils lifting off the surface by more than this, in mm (needs the surface).
+    #[arg(long)]
+    pub max_liftoff: Option<f32>,
+
+    /// Use the dipole approximation for pairs farther apart than this, relative to the sum of their radii.
+    /// The pairs that aren't close are gathered and reported too.
+    #[arg(long)]
+    pub dipole_cutoff: Option<f32>,
+
+    /// Length unit of the layout coordinates, converted to mm for the inductances.
+    #[arg(long, value_enum, default_value_t = crate::layout::LengthUnit::Millimeters)]
+    pub length_unit: crate::layout::LengthUnit,
+
+    /// Optional output filepath for the full statistics (JSON).
+    #[arg(short, long)]
+    pub output: Option<String>,
+}
+
 /// Comrade stage to run or demonstrate
 #[derive(Debug, Clone)]
 #[derive(ValueEnum, EnumIter)]
@@ -144,11 +415,16 @@ pub struct SharedArgs {
 }
 
 /// Re-export clap CLI parse method.
+/// Run arguments missing from the command line fall back to the environment (see `RunArgs::with_env_fallbacks`).
 pub fn parse_cli_args() -> ComradeCli {
-    ComradeCli::parse()
+    let mut cli = ComradeCli::parse();
+    if let SubCommand::Run(run_args) = cli.subcommand {
+        cli.subcommand = SubCommand::Run(run_args.with_env_fallbacks(|key| std::env::var(key).ok()));
+    }
+    cli
 }
 
-// Re-export clap CLI parse_from method
+// Re-export clap CLI parse_from method (without the environment fallbacks)
 pub fn parse_cli_from<I, T>(itr: I) -> ComradeCli 
 where
     I: IntoIterator<Item = T>,
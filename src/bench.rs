@@ -0,0 +1,157 @@
+/*!
+*   Repeatable layout benchmark on a parametric sphere array (a dome, like a head array).
+*   Times the layout and the inductance matrix computation, so performance regressions are catchable.
+*   Run with `cargo run --release --features bench --bin comrade-bench`.
+*
+!*/
+
+use crate::{
+    layout,
+    geo_3d::*,
+};
+use layout::LayoutMethodTrait;
+use std::time::{Duration, Instant};
+
+/// Benchmark size parameters.
+#[derive(Debug, Clone, Copy)]
+pub struct BenchConfig {
+    /// Number of coils spread over the sphere.
+    pub coil_count: usize,
+    /// Sphere radius (mm).
+    pub sphere_radius: f32,
+    /// Latitude bands of the sphere mesh (see `Surface::sphere`).
+    pub surface_rings: usize,
+    /// ADAM iterations for the layout.
+    pub iterations: usize,
+}
+impl Default for BenchConfig {
+    fn default() -> Self {
+        BenchConfig{
+            coil_count: 16,
+            sphere_radius: 100.0,
+            surface_rings: 64,
+            iterations: 10,
+        }
+    }
+}
+
+/// Benchmark timings.
+#[derive(Debug, Clone)]
+pub struct BenchResult {
+    pub coil_count: usize,
+    pub surface_vertices: usize,
+    pub iterations: usize,
+    pub layout_time: Duration,
+    pub inductance_time: Duration,
+    /// Number of coil pairs in the inductance matrix (including self-inductances).
+    pub inductance_entries: usize,
+}
+impl BenchResult {
+    /// Print the timings and throughput.
+    pub fn print(&self) {
+        println!("Benchmark: {} coils on a {} vertex dome, {} iterations", self.coil_count, self.surface_vertices, self.iterations);
+        println!("Layout: {:.3}s ({:.2} iterations/s)",
+            self.layout_time.as_secs_f64(), self.iterations.max(1) as f64 / self.layout_time.as_secs_f64()
+        );
+        println!("Inductance matrix: {:.3}s ({:.0} entries/s)",
+            self.inductance_time.as_secs_f64(), self.inductance_entries as f64 / self.inductance_time.as_secs_f64()
+        );
+    }
+}
+
+/// Generate the benchmark surface: the upper hemisphere of a `Surface::sphere` centered on the origin.
+/// The dome is open at the equator, so the layout methods have a boundary to work with.
+pub fn sphere_dome(sphere_radius: f32, surface_rings: usize) -> Surface {
+    let sphere = Surface::sphere(Point::zero(), sphere_radius, surface_rings);
+    let mut vertex_map = vec![None; sphere.vertices.len()];
+    let mut points = Vec::new();
+    for (vertex_id, vertex) in sphere.vertices.iter().enumerate() {
+        if vertex.point.z >= -1e-4 * sphere_radius {
+            vertex_map[vertex_id] = Some(points.len());
+            points.push(vertex.point);
+        }
+    }
+    let triangles = sphere.faces.iter()
+        .filter_map(|face| {
+            let [a, b, c] = face.vertices.map(|vertex_id| vertex_map[vertex_id]);
+            Some([a?, b?, c?])
+        })
+        .collect();
+    Surface::from_triangles(points, triangles)
+}
+
+/// Spread circles evenly over the dome (Fibonacci lattice on the upper hemisphere).
+/// Radii are set so neighboring circles slightly overlap, up to half the sphere radius.
+pub fn sphere_array(coil_count: usize, sphere_radius: f32) -> Vec<layout::CircleArgs> {
+    let golden_angle = std::f32::consts::PI * (3.0 - 5.0_f32.sqrt());
+    let coil_radius = (1.4 * sphere_radius / (coil_count.max(1) as f32).sqrt()).min(0.5 * sphere_radius);
+    (0..coil_count).map(|coil_id| {
+        let z = 1.0 - (coil_id as f32 + 0.5) / coil_count as f32;
+        let ring_radius = (1.0 - z * z).sqrt();
+        let phi = golden_angle * coil_id as f32;
+        layout::CircleArgs{
+            center: Point::zero() + sphere_radius * GeoVector::new(ring_radius * phi.cos(), ring_radius * phi.sin(), z),
+            coil_radius,
+            ..layout::CircleArgs::default()
+        }
+    }).collect()
+}
+
+/// Run the benchmark: lay out the sphere array with the ADAM circles method, then compute the full inductance matrix.
+/// Returns a `ProcResult` with the `BenchResult` or an `Err`.
+pub fn run_benchmark(bench_cfg: &BenchConfig) -> layout::ProcResult<BenchResult> {
+    let surface = sphere_dome(bench_cfg.sphere_radius, bench_cfg.surface_rings);
+    let method = layout::AdamCirclesMethod{
+        symmetry_plane: None,
+        layout_in_path: None,
+        circles: sphere_array(bench_cfg.coil_count, bench_cfg.sphere_radius),
+        iterations: bench_cfg.iterations,
+        coarse_stages: Vec::new(),
+        coupling_sign_check: None,
+        final_cfg_output: None,
+        ..Default::default()
+    };
+
+    let start = Instant::now();
+    let layout_out = method.do_layout(&surface)?;
+    let layout_time = start.elapsed();
+
+    let start = Instant::now();
+    let mut inductance_entries = 0;
+    for (coil_id, coil) in layout_out.coils.iter().enumerate() {
+        std::hint::black_box(coil.self_inductance(1.0));
+        inductance_entries += 1;
+        for other_coil in layout_out.coils.iter().skip(coil_id + 1) {
+            std::hint::black_box(coil.mutual_inductance(other_coil, 1.0));
+            inductance_entries += 1;
+        }
+    }
+    let inductance_time = start.elapsed();
+
+    Ok(BenchResult{
+        coil_count: layout_out.coils.len(),
+        surface_vertices: surface.vertices.len(),
+        iterations: bench_cfg.iterations,
+        layout_time,
+        inductance_time,
+        inductance_entries,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tiny_benchmark_runs_to_completion() {
+        let bench_cfg = BenchConfig{
+            coil_count: 4,
+            sphere_radius: 50.0,
+            surface_rings: 24,
+            iterations: 1,
+        };
+        let result = run_benchmark(&bench_cfg).unwrap();
+        assert_eq!(result.coil_count, 4);
+        assert_eq!(result.inductance_entries, 10);
+    }
+}
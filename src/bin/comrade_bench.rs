@@ -0,0 +1,40 @@
+use clap::Parser;
+
+/// Layout benchmark on a parametric sphere array.
+#[derive(Parser, Debug)]
+#[command(name = "comrade-bench")]
+struct BenchArgs {
+    /// Number of coils spread over the sphere.
+    #[arg(short, long, default_value_t = 16)]
+    coils: usize,
+
+    /// Sphere radius (mm).
+    #[arg(short, long, default_value_t = 100.0)]
+    radius: f32,
+
+    /// Latitude bands of the sphere mesh.
+    #[arg(long, default_value_t = 64)]
+    rings: usize,
+
+    /// ADAM iterations for the layout.
+    #[arg(short, long, default_value_t = 10)]
+    iterations: usize,
+}
+
+fn main() {
+    let bench_args = BenchArgs::parse();
+    let bench_cfg = comrade::bench::BenchConfig{
+        coil_count: bench_args.coils,
+        sphere_radius: bench_args.radius,
+        surface_rings: bench_args.rings,
+        iterations: bench_args.iterations,
+    };
+
+    match comrade::bench::run_benchmark(&bench_cfg) {
+        Ok(result) => {
+            println!();
+            result.print();
+        },
+        Err(err) => println!("{}", err),
+    }
+}
@@ -7,15 +7,26 @@ use crate::{
     ComradeResult,
 };
 use strum::IntoEnumIterator;
+use layout::LayoutMethodTrait;
+use mesh::MeshMethodTrait;
+use sim::SimMethodTrait;
 
 /// Macro for constructing a full stage config from the method and stage.
 macro_rules! construct_stage_cfg {
     (layout, $method:expr) => {
         layout::LayoutTarget{
-            method: $method,
+            method: layout::LayoutMethod::from($method),
             input_path: "PATH/TO/INPUT/FILE".to_string(),
             output_path: Some("OPTIONAL/PATH/TO/OUTPUT/FILE".to_string()),
             save: false,
+            manifest: false,
+            capacitor_summary: None,
+            output_precision: None,
+            smooth_iterations: 0,
+            normalize_winding: false,
+            dedup_vertices: layout::LayoutTarget::default_dedup_vertices(),
+            dedup_tolerance: layout::LayoutTarget::default_dedup_tolerance(),
+            max_liftoff: None,
         }
     };
     (mesh, $method:expr) => {
@@ -40,11 +51,7 @@ macro_rules! construct_stage_cfg {
 /// Macro for displaying the full config for a stage.
 macro_rules! display_stage_cfg {
     ($stage:ident, $target_method_name:expr, $cfg_format:expr) => {
-        let mut method_names = Vec::<String>::new();
-        for method in $stage::MethodEnum::iter() {
-            let method_name = serde_yaml::to_string(&method).unwrap().split_whitespace().collect::<Vec<_>>()[1].to_string();
-            method_names.push(method_name);
-        }
+        let method_names: Vec<String> = $stage::MethodEnum::iter().map(|method| method_config_name(&method)).collect();
         let available_methods_str = format!("Available methods:\n{:#?}", method_names).replace(&['[', ']', ','][..], "");
         if $target_method_name.is_none() {
             println!("{}", available_methods_str);
@@ -67,6 +74,57 @@ macro_rules! display_stage_cfg {
     };
 }
 
+/// Get the config name of a method (the `name` key in config files).
+fn method_config_name(method: &impl serde::Serialize) -> String {
+    let method_value = serde_json::to_value(method).expect("BUG: Method failed to serialize");
+    match method_value.get("name").and_then(|name| name.as_str()) {
+        Some(name) => name.to_string(),
+        None => panic!("BUG: Method enum is missing its name tag"),
+    }
+}
+
+/// Listing entry for an available method.
+#[derive(Debug, Clone)]
+pub struct MethodListing {
+    /// Config name of the method (the `name` key in config files).
+    pub name: String,
+    pub display_name: &'static str,
+    pub description: &'static str,
+}
+
+/// List the available methods for each stage.
+pub fn list_methods() -> Vec<(args::RunStage, Vec<MethodListing>)> {
+    vec![
+        (args::RunStage::Layout, layout::MethodEnum::iter().map(|method| MethodListing{
+            name: method_config_name(&method),
+            display_name: method.get_method_display_name(),
+            description: method.get_method_description(),
+        }).collect()),
+        (args::RunStage::Mesh, mesh::MethodEnum::iter().map(|method| MethodListing{
+            name: method_config_name(&method),
+            display_name: method.get_method_display_name(),
+            description: method.get_method_description(),
+        }).collect()),
+        (args::RunStage::Sim, sim::MethodEnum::iter().map(|method| MethodListing{
+            name: method_config_name(&method),
+            display_name: method.get_method_display_name(),
+            description: method.get_method_description(),
+        }).collect()),
+    ]
+}
+
+/// Display the available methods for each stage.
+pub fn display_methods() {
+    for (stage, methods) in list_methods() {
+        println!("{} methods:", stage);
+        for method in methods {
+            println!("  {:<24}{} -- {}", method.name, method.display_name, method.description);
+        }
+        println!();
+    }
+    println!("Use `comrade example-cfg <STAGE> <METHOD>` for an example config.");
+}
+
 /// Display an example config file for a stage.
 /// Returns a `ProcResult` with `()` or an `Err`.
 pub fn display_config(example_args: args::ExampleArgs) -> ComradeResult<()> {
@@ -86,3 +144,21 @@ pub fn display_config(example_args: args::ExampleArgs) -> ComradeResult<()> {
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn list_includes_adam_and_gmsh() {
+        let listing = list_methods();
+        let display_names: Vec<&str> = listing.iter()
+            .flat_map(|(_, methods)| methods.iter().map(|method| method.display_name))
+            .collect();
+        assert!(display_names.iter().any(|name| name.starts_with("ADAM Circles")));
+        assert!(display_names.contains(&"GMSH"));
+
+        let (_, layout_methods) = &listing[0];
+        assert!(layout_methods.iter().any(|method| method.name == "adam_circles"));
+    }
+}
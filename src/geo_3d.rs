@@ -3,12 +3,17 @@ mod point;
 mod vector;
 mod plane;
 mod surface;
+mod rotation;
 
 // Re-export the modules
 pub use point::*;
 pub use vector::*;
 pub use plane::*;
 pub use surface::*;
+pub use rotation::*;
 
 /// Angle type (alias for f32).
 pub type Angle = f32;
+
+/// Default tolerance for exact geometric checks (mm), e.g. whether a point sits on a plane (see `Plane::contains_point`).
+pub const GEO_TOLERANCE: f32 = 1.0e-6;
@@ -42,6 +42,11 @@ impl Plane {
         self.normal.dot(&(*point).into()) - self.offset
     }
 
+    /// Check if a point is within a distance `tol` of the plane (on either side).
+    pub fn contains_point(&self, point: &Point, tol: f32) -> bool {
+        self.distance_to_point(point).abs() <= tol
+    }
+
     /// Get the projection of a point onto the plane.
     pub fn project_point(&self, point: &Point) -> Point {
         *point - self.normal * self.distance_to_point(point)
@@ -36,6 +36,11 @@ impl Point {
 
         (dx*dx + dy*dy + dz*dz).sqrt()
     }
+
+    /// Check if two points are within a distance `tol` of each other.
+    pub fn approx_eq(&self, other: &Point, tol: f32) -> bool {
+        self.distance(other) <= tol
+    }
     
     /// Get the index of the nearest point on the surface to this point.
     pub fn nearest_point_idx(&self, surface: &Surface) -> usize {
@@ -75,30 +80,36 @@ impl Point {
         sign_1 == sign_2 && sign_2 == sign_3
     }
 
-    /// Project a point onto a triangular face
+    /// Project a point onto a triangular face, giving the closest point on the face (edges and corners included)
     pub fn project_to_surface_face(&self, surface: &Surface, face_idx: usize) -> Point {
         let face = &surface.faces[face_idx];
         let normal = face.get_normal();
 
         // Project the point onto the plane of the face
-        let mut proj_point = *self - (*self - surface.vertices[face.vertices[0]].point).proj_onto(&normal);
+        let proj_point = *self - (*self - surface.vertices[face.vertices[0]].point).proj_onto(&normal);
 
-        // For each edge, check if the point is outside the edge
-        // If so, project the point onto the edge
+        // If the projection lands inside the face, it is the closest point
+        if proj_point.is_above_surface_face(surface, face_idx) {
+            return proj_point;
+        }
+
+        // Otherwise, the closest point is on one of the edges (clamped to the edge ends)
+        let mut closest = proj_point;
+        let mut closest_distance = f32::MAX;
         for i in 0..3 {
             let p1 = surface.vertices[face.vertices[i]].point;
             let p2 = surface.vertices[face.vertices[(i + 1) % 3]].point;
-            let p3 = surface.vertices[face.vertices[(i + 2) % 3]].point;
 
             let edge = p2 - p1;
-            let vec_to_point = proj_point - p1;
-            let cross = edge.cross(&normal);
-            if cross.dot(&vec_to_point).signum() != cross.dot(&(p3 - p1)).signum() {
-                proj_point = proj_point - vec_to_point.proj_onto(&cross);
+            let t = ((proj_point - p1).dot(&edge) / edge.norm_sq()).clamp(0.0, 1.0);
+            let edge_point = p1 + edge * t;
+            let distance = edge_point.distance(&proj_point);
+            if distance < closest_distance {
+                closest = edge_point;
+                closest_distance = distance;
             }
         }
-
-        proj_point
+        closest
     }
 
     /// Reflect this point across a plane.
@@ -205,3 +216,53 @@ impl std::convert::From<GeoVector> for Point {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geo_3d::GEO_TOLERANCE;
+
+    #[test]
+    fn approx_eq_holds_up_to_the_tolerance() {
+        let tol = 1.0e-3;
+        let direction = GeoVector::new(1.0, 2.0, -2.0).normalize();
+        let point = Point::new(10.0, -5.0, 3.0);
+        let vector = GeoVector::new(10.0, -5.0, 3.0);
+
+        // Just inside the tolerance, in any direction, and just outside it
+        assert!(point.approx_eq(&(point + direction * 0.9 * tol), tol));
+        assert!(point.approx_eq(&(point - direction * 0.9 * tol), tol));
+        assert!(!point.approx_eq(&(point + direction * 1.1 * tol), tol));
+        assert!(vector.approx_eq(&(vector + direction * 0.9 * tol), tol));
+        assert!(!vector.approx_eq(&(vector - direction * 1.1 * tol), tol));
+
+        // Per-axis differences under the tolerance can still add up past it
+        let diagonal = GeoVector::new(0.7, 0.7, 0.7) * tol;
+        assert!(!point.approx_eq(&(point + diagonal), tol));
+
+        // Planes check the distance on either side
+        let plane = Plane::from_normal_and_offset(GeoVector::zhat(), 3.0);
+        assert!(plane.contains_point(&point, GEO_TOLERANCE));
+        assert!(plane.contains_point(&(point + GeoVector::zhat() * 0.5 * GEO_TOLERANCE), GEO_TOLERANCE));
+        assert!(!plane.contains_point(&(point - GeoVector::zhat() * 0.1), GEO_TOLERANCE));
+    }
+
+    #[test]
+    fn face_projection_stays_on_the_face() {
+        let surface = Surface::from_triangles(
+            vec![Point::new(0.0, 0.0, 0.0), Point::new(10.0, 0.0, 0.0), Point::new(0.0, 10.0, 0.0)],
+            vec![[0, 1, 2]],
+        );
+
+        // Above the face, straight down
+        assert!(Point::new(2.0, 3.0, 4.0).project_to_surface_face(&surface, 0).approx_eq(&Point::new(2.0, 3.0, 0.0), GEO_TOLERANCE));
+
+        // Beside an edge, onto the edge
+        assert!(Point::new(12.0, 5.0, 1.0).project_to_surface_face(&surface, 0).approx_eq(&Point::new(8.5, 1.5, 0.0), GEO_TOLERANCE));
+
+        // Past a corner, outside two edges, onto the corner
+        // (projecting onto each edge's line in turn would land at (12.5, -2.5, 0), off the face)
+        let projected = Point::new(15.0, -1.0, 1.0).project_to_surface_face(&surface, 0);
+        assert!(projected.approx_eq(&Point::new(10.0, 0.0, 0.0), GEO_TOLERANCE), "Projected to {}", projected);
+    }
+}
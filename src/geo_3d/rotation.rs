@@ -0,0 +1,131 @@
+use std::ops::Mul;
+use serde::{Serialize, Deserialize};
+
+use crate::geo_3d::{Angle, GeoVector};
+
+/// A rotation in 3D space, stored as a unit quaternion `w + xi + yj + zk`.
+/// Composing rotations multiplies the quaternions and renormalizes,
+/// so a long chain of small rotations stays a rotation rather than drifting into a scaling.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Serialize, Deserialize)]
+pub struct Quaternion {
+    pub w: f32,
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+}
+
+/// Rotation type (alias for a unit `Quaternion`).
+pub type Rotation = Quaternion;
+
+impl Quaternion {
+    /// Create the identity rotation.
+    pub fn identity() -> Self {
+        Quaternion{w: 1.0, x: 0.0, y: 0.0, z: 0.0}
+    }
+
+    /// Create a rotation by `angle` (radians, right-handed) around `axis`.
+    /// The axis doesn't need to be normalized, and a zero axis gives the identity.
+    pub fn from_axis_angle(axis: &GeoVector, angle: Angle) -> Self {
+        let norm = axis.norm();
+        if norm == 0.0 {
+            return Quaternion::identity();
+        }
+        let (s, c) = (angle * 0.5).sin_cos();
+        let axis = *axis * (s / norm);
+        Quaternion{w: c, x: axis.x, y: axis.y, z: axis.z}
+    }
+
+    /// Get the rotation that applies `self` first, then `other`.
+    pub fn then(&self, other: &Quaternion) -> Quaternion {
+        *other * *self
+    }
+
+    /// Get the inverse rotation.
+    pub fn inverse(&self) -> Quaternion {
+        Quaternion{w: self.w, x: -self.x, y: -self.y, z: -self.z}
+    }
+
+    /// Get the rotation angle, in radians from 0 to 2 pi.
+    pub fn angle(&self) -> Angle {
+        2.0 * self.w.clamp(-1.0, 1.0).acos()
+    }
+
+    /// Rotate a vector.
+    pub fn apply(&self, vector: &GeoVector) -> GeoVector {
+        // v' = v + 2w (q x v) + 2 q x (q x v), for the vector part q
+        let q = GeoVector::new(self.x, self.y, self.z);
+        let t = q.cross(vector) * 2.0;
+        *vector + t * self.w + q.cross(&t)
+    }
+
+    /// Scale back to unit length, to remove rounding drift.
+    fn normalized(&self) -> Quaternion {
+        let norm = (self.w * self.w + self.x * self.x + self.y * self.y + self.z * self.z).sqrt();
+        if norm == 0.0 {
+            return Quaternion::identity();
+        }
+        Quaternion{w: self.w / norm, x: self.x / norm, y: self.y / norm, z: self.z / norm}
+    }
+}
+impl Default for Quaternion {
+    fn default() -> Self {
+        Quaternion::identity()
+    }
+}
+impl Mul for Quaternion {
+    type Output = Quaternion;
+
+    /// Compose two rotations (Hamilton product): `a * b` applies `b` first, then `a`.
+    fn mul(self, other: Quaternion) -> Quaternion {
+        Quaternion{
+            w: self.w * other.w - self.x * other.x - self.y * other.y - self.z * other.z,
+            x: self.w * other.x + self.x * other.w + self.y * other.z - self.z * other.y,
+            y: self.w * other.y - self.x * other.z + self.y * other.w + self.z * other.x,
+            z: self.w * other.z + self.x * other.y - self.y * other.x + self.z * other.w,
+        }.normalized()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn composed_small_rotations_drift_less_than_repeated_vector_rotations() {
+        let axis = GeoVector::new(1.0, 2.0, 3.0).normalize();
+        let start = GeoVector::new(0.3, -0.8, 0.5).normalize();
+        let (steps, step_angle) = (1000, 0.0137_f32);
+
+        // Naive: rotate the vector itself by each small step (the old Rodrigues formula)
+        let mut naive = start;
+        for _ in 0..steps {
+            let (s, c) = step_angle.sin_cos();
+            naive = naive * c + axis.cross(&naive) * s + axis * axis.dot(&naive) * (1.0 - c);
+        }
+
+        // Quaternions: compose the steps, then rotate once
+        let step = Rotation::from_axis_angle(&axis, step_angle);
+        let mut composed = Rotation::identity();
+        for _ in 0..steps {
+            composed = composed.then(&step);
+        }
+        let rotated = composed.apply(&start);
+
+        // Reference in double precision
+        let total = steps as f64 * step_angle as f64;
+        let (ax, st) = ([axis.x as f64, axis.y as f64, axis.z as f64], [start.x as f64, start.y as f64, start.z as f64]);
+        let dot = ax[0] * st[0] + ax[1] * st[1] + ax[2] * st[2];
+        let cross = [ax[1] * st[2] - ax[2] * st[1], ax[2] * st[0] - ax[0] * st[2], ax[0] * st[1] - ax[1] * st[0]];
+        let exact: Vec<f64> = (0..3).map(|i| st[i] * total.cos() + cross[i] * total.sin() + ax[i] * dot * (1.0 - total.cos())).collect();
+        let error = |v: &GeoVector| ((v.x as f64 - exact[0]).powi(2) + (v.y as f64 - exact[1]).powi(2) + (v.z as f64 - exact[2]).powi(2)).sqrt();
+
+        assert!(error(&rotated) < error(&naive), "Composed error {}, naive error {}", error(&rotated), error(&naive));
+        assert!((rotated.norm() - 1.0).abs() < 1e-5);
+
+        // The public rotation matches the composition, and undoes with the inverse
+        let direct = start.rotate_around(&axis, 0.7);
+        assert!(direct.approx_eq(&Rotation::from_axis_angle(&axis, 0.7).apply(&start), 1e-6));
+        assert!(Rotation::from_axis_angle(&axis, 0.7).inverse().apply(&direct).approx_eq(&start, 1e-6));
+    }
+}
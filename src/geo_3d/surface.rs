@@ -1,5 +1,12 @@
 use crate::geo_3d::{Point, GeoVector, Plane};
 
+/// Maximum number of vertices reflected to measure the symmetry residual.
+const SYMMETRY_SAMPLES: usize = 100;
+/// Vertex count the surface is decimated to for the symmetry plane search.
+const SYMMETRY_SEARCH_VERTICES: usize = 1000;
+/// Number of candidate normals in the coarse symmetry plane search.
+const SYMMETRY_CANDIDATES: usize = 64;
+
 /// A surface in 3D space. Contains vertices, edges, and faces, linked to each other.
 #[derive(Debug, Clone)]
 pub struct Surface {
@@ -16,6 +23,420 @@ impl Surface {
         }
     }
 
+    /// Build a surface from a list of points and triangles (indices into the points).
+    /// Face normals follow the right-hand rule on the triangle winding.
+    /// Vertex normals are the normalized sum of the adjacent face normals.
+    pub fn from_triangles(points: Vec<Point>, triangles: Vec<[usize; 3]>) -> Self {
+        let mut surface = Surface::empty();
+        for point in points.into_iter() {
+            surface.vertices.push(SurfaceVertex::new_from_point(point));
+        }
+
+        // Initialize all edges from the triangles, then sort and dedup them
+        let mut edges = Vec::<SurfaceEdge>::new();
+        for triangle in triangles.iter() {
+            for i in 0..3 {
+                edges.push(SurfaceEdge::new([triangle[i], triangle[(i + 1) % 3]]));
+            }
+        }
+        edges.sort_by(|a, b| a.vertices[0].cmp(&b.vertices[0]).then(a.vertices[1].cmp(&b.vertices[1])));
+        edges.dedup();
+
+        let mut edge_indices = std::collections::HashMap::<(usize, usize), usize>::new();
+        for (i, edge) in edges.iter().enumerate() {
+            edge_indices.insert((edge.vertices[0], edge.vertices[1]), i);
+        }
+
+        // Add faces, linking them to the edges and vertices
+        for (face_id, triangle) in triangles.iter().enumerate() {
+            let mut face_edges: [usize; 3] = [0; 3];
+            for i in 0..3 {
+                let pid1 = triangle[i];
+                let pid2 = triangle[(i + 1) % 3];
+                surface.vertices[pid1].adj_faces.push(face_id);
+
+                let edge_index = edge_indices[&(pid1.min(pid2), pid1.max(pid2))];
+                face_edges[i] = edge_index;
+                if edges[edge_index].adj_faces[0].is_none() {
+                    edges[edge_index].adj_faces[0] = Some(face_id);
+                } else if edges[edge_index].adj_faces[1].is_none() {
+                    edges[edge_index].adj_faces[1] = Some(face_id);
+                } else {
+                    panic!("Edge {:?} has more than 2 faces!", edges[edge_index]);
+                }
+            }
+
+            let p1 = surface.vertices[triangle[0]].point;
+            let p2 = surface.vertices[triangle[1]].point;
+            let p3 = surface.vertices[triangle[2]].point;
+            let cross = (p2 - p1).cross(&(p3 - p1));
+            surface.faces.push(SurfaceFace::new(*triangle, face_edges, cross, 0.5 * cross.norm()));
+        }
+
+        // Add adjacent edges to the vertices
+        for (edge_index, edge) in edges.iter().enumerate() {
+            for vid in edge.vertices.iter() {
+                surface.vertices[*vid].adj_edges.push(edge_index);
+            }
+        }
+        surface.edges = edges;
+
+        // Sort adjacencies and calculate the vertex normals
+        for vertex in surface.vertices.iter_mut() {
+            vertex.adj_edges.sort();
+            vertex.adj_faces.sort();
+            let mut normal = GeoVector::zero();
+            for face_id in vertex.adj_faces.iter() {
+                normal += surface.faces[*face_id].get_normal();
+            }
+            vertex.normal = normal.normalize();
+        }
+
+        surface
+    }
+
+    /// Load a surface from a GMSH `.msh` file (ASCII, format 2.2 or 4.1).
+    /// See `io::msh::load_msh`.
+    pub fn from_msh(path: &str) -> crate::io::IoResult<Self> {
+        crate::io::msh::load_msh(path)
+    }
+
+    /// Generate a square grid in the xy-plane, centered on the origin with normals along +z.
+    /// The square has side length `2 * half_width`, and is split into `divisions` squares per side.
+    pub fn square_grid(half_width: f32, divisions: usize) -> Self {
+        let n = divisions.max(1);
+        let step = 2.0 * half_width / n as f32;
+
+        let mut points = Vec::<Point>::with_capacity((n + 1) * (n + 1));
+        for j in 0..=n {
+            for i in 0..=n {
+                points.push(Point::new(-half_width + i as f32 * step, -half_width + j as f32 * step, 0.0));
+            }
+        }
+
+        let id = |i: usize, j: usize| j * (n + 1) + i;
+        let mut triangles = Vec::<[usize; 3]>::with_capacity(2 * n * n);
+        for j in 0..n {
+            for i in 0..n {
+                triangles.push([id(i, j), id(i + 1, j), id(i + 1, j + 1)]);
+                triangles.push([id(i, j), id(i + 1, j + 1), id(i, j + 1)]);
+            }
+        }
+
+        Surface::from_triangles(points, triangles)
+    }
+
+    /// Generate a closed latitude-longitude sphere with outward normals.
+    /// `rings` is the number of latitude bands (pole to pole), with twice as many longitude segments.
+    pub fn sphere(center: Point, radius: f32, rings: usize) -> Self {
+        use std::f32::consts::PI;
+        let rings = rings.max(2);
+        let segments = 2 * rings;
+
+        // North pole, then each ring of latitude, then the south pole
+        let mut points = Vec::<Point>::with_capacity((rings - 1) * segments + 2);
+        points.push(center + GeoVector::zhat() * radius);
+        for i in 1..rings {
+            let theta = PI * i as f32 / rings as f32;
+            for j in 0..segments {
+                let phi = 2.0 * PI * j as f32 / segments as f32;
+                points.push(center + radius * GeoVector::new(theta.sin() * phi.cos(), theta.sin() * phi.sin(), theta.cos()));
+            }
+        }
+        let south = points.len();
+        points.push(center - GeoVector::zhat() * radius);
+
+        let id = |i: usize, j: usize| 1 + (i - 1) * segments + (j % segments);
+        let mut triangles = Vec::<[usize; 3]>::new();
+        for j in 0..segments {
+            triangles.push([0, id(1, j), id(1, j + 1)]);
+            for i in 1..(rings - 1) {
+                triangles.push([id(i, j), id(i + 1, j), id(i + 1, j + 1)]);
+                triangles.push([id(i, j), id(i + 1, j + 1), id(i, j + 1)]);
+            }
+            triangles.push([id(rings - 1, j), south, id(rings - 1, j + 1)]);
+        }
+
+        Surface::from_triangles(points, triangles)
+    }
+
+    /// Get the mean edge length of the surface.
+    pub fn mean_edge_length(&self) -> f32 {
+        self.edge_length_stats().1
+    }
+
+    /// Get the axis-aligned bounding box of the surface vertices, as (min corner, max corner).
+    /// Both corners are the origin if the surface has no vertices.
+    pub fn bounding_box(&self) -> (Point, Point) {
+        if self.vertices.is_empty() {
+            return (Point::zero(), Point::zero());
+        }
+        let mut min = self.vertices[0].point;
+        let mut max = self.vertices[0].point;
+        for vertex in self.vertices.iter() {
+            let point = vertex.point;
+            min = Point::new(min.x.min(point.x), min.y.min(point.y), min.z.min(point.z));
+            max = Point::new(max.x.max(point.x), max.y.max(point.y), max.z.max(point.z));
+        }
+        (min, max)
+    }
+
+    /// Get the (min, mean, max) edge length of the surface, or zeros if it has no edges.
+    pub fn edge_length_stats(&self) -> (f32, f32, f32) {
+        if self.edges.is_empty() {
+            return (0.0, 0.0, 0.0);
+        }
+        let (min, max, sum) = self.edges.iter()
+            .map(|edge| self.vertices[edge.vertices[0]].point.distance(&self.vertices[edge.vertices[1]].point))
+            .fold((f32::INFINITY, 0.0f32, 0.0), |(min, max, sum), length| (min.min(length), max.max(length), sum + length));
+        (min, sum / self.edges.len() as f32, max)
+    }
+
+    /// Get the area each vertex represents: a third of the area of each adjacent face.
+    /// The vertex areas sum to the surface area.
+    pub fn vertex_areas(&self) -> Vec<f32> {
+        let mut areas = vec![0.0; self.vertices.len()];
+        for face in self.faces.iter() {
+            for vertex_id in face.vertices.iter() {
+                areas[*vertex_id] += face.area / 3.0;
+            }
+        }
+        areas
+    }
+
+    /// Estimate the curvature at each vertex.
+    /// Returns (mean curvature, Gaussian curvature) per vertex, in 1/length and 1/length^2.
+    /// Mean curvature is the average normal change along the adjacent edges, positive where the surface curves away from its normals (e.g. outside of a sphere).
+    /// Gaussian curvature is the angle deficit over a third of the adjacent face area, and is zero on the boundary.
+    pub fn vertex_curvature(&self) -> Vec<(f32, f32)> {
+        use std::f32::consts::PI;
+        let boundary: std::collections::HashSet<usize> = self.get_boundary_vertex_indices().into_iter().collect();
+
+        self.vertices.iter().enumerate().map(|(vertex_id, vertex)| {
+            // Mean curvature from the normal change along each adjacent edge
+            let mut mean = 0.0;
+            let mut edge_count = 0;
+            for edge_id in vertex.adj_edges.iter() {
+                let edge = &self.edges[*edge_id];
+                let other = &self.vertices[if edge.vertices[0] == vertex_id {edge.vertices[1]} else {edge.vertices[0]}];
+                let offset = vertex.point - other.point;
+                let length_sq = offset.norm_sq();
+                if length_sq > 0.0 {
+                    mean += (vertex.normal - other.normal).dot(&offset) / length_sq;
+                    edge_count += 1;
+                }
+            }
+            if edge_count > 0 {
+                mean /= edge_count as f32;
+            }
+
+            // Gaussian curvature from the angle deficit
+            if boundary.contains(&vertex_id) || vertex.adj_faces.is_empty() {
+                return (mean, 0.0);
+            }
+            let mut angle_sum = 0.0;
+            let mut area = 0.0;
+            for face_id in vertex.adj_faces.iter() {
+                let face = &self.faces[*face_id];
+                let others: Vec<&Point> = face.vertices.iter()
+                    .filter(|id| **id != vertex_id)
+                    .map(|id| &self.vertices[*id].point)
+                    .collect();
+                angle_sum += (*others[0] - vertex.point).angle_to(&(*others[1] - vertex.point));
+                area += face.area;
+            }
+            let gaussian = if area > 0.0 {(2.0 * PI - angle_sum) / (area / 3.0)} else {0.0};
+            (mean, gaussian)
+        }).collect()
+    }
+
+    /// Get the RMS mismatch (distance to the surface, near the nearest vertex) of surface vertices reflected across a plane.
+    /// Up to `SYMMETRY_SAMPLES` evenly strided vertices are reflected, so this stays cheap on large surfaces.
+    /// Zero for a surface that is exactly symmetric about the plane.
+    pub fn symmetry_residual(&self, plane: &Plane) -> f32 {
+        if self.vertices.is_empty() {
+            return 0.0;
+        }
+        let stride = self.vertices.len().div_ceil(SYMMETRY_SAMPLES);
+        let mut sum_sq = 0.0;
+        let mut count = 0;
+        for vertex in self.vertices.iter().step_by(stride) {
+            // Distance to the faces around the nearest vertex
+            let reflected = vertex.point.reflect_across(plane);
+            let nearest = &self.vertices[reflected.nearest_point_idx(self)];
+            let distance = nearest.adj_faces.iter()
+                .map(|face_id| reflected.project_to_surface_face(self, *face_id).distance(&reflected))
+                .fold(nearest.point.distance(&reflected), f32::min);
+            sum_sq += distance * distance;
+            count += 1;
+        }
+        (sum_sq / count as f32).sqrt()
+    }
+
+    /// Find the plane the surface is most nearly symmetric about, minimizing `symmetry_residual`.
+    /// Candidate normals spread over a hemisphere are tried through the vertex centroid,
+    /// then the best is refined by a shrinking pattern search over the normal direction and offset.
+    /// Searches a decimated copy of large surfaces.
+    /// Returns `None` for an empty surface.
+    pub fn best_symmetry_plane(&self) -> Option<Plane> {
+        if self.vertices.is_empty() {
+            return None;
+        }
+        let surface = self.decimate(SYMMETRY_SEARCH_VERTICES);
+
+        let centroid = surface.vertices.iter()
+            .fold(GeoVector::zero(), |sum, vertex| sum + GeoVector::from(vertex.point)) / surface.vertices.len() as f32;
+        let extent = surface.vertices.iter()
+            .map(|vertex| (GeoVector::from(vertex.point) - centroid).norm())
+            .fold(0.0, f32::max)
+            .max(f32::EPSILON);
+
+        // Coarse search over normals spread over a hemisphere (Fibonacci lattice), through the centroid
+        let golden_angle = std::f32::consts::PI * (3.0 - 5.0_f32.sqrt());
+        let mut best_normal = GeoVector::zhat();
+        let mut best_offset = centroid.z;
+        let mut best_residual = f32::MAX;
+        for i in 0..SYMMETRY_CANDIDATES {
+            let z = 1.0 - (i as f32 + 0.5) / SYMMETRY_CANDIDATES as f32;
+            let ring_radius = (1.0 - z * z).sqrt();
+            let phi = golden_angle * i as f32;
+            let normal = GeoVector::new(ring_radius * phi.cos(), ring_radius * phi.sin(), z);
+            let offset = normal.dot(&centroid);
+            let residual = surface.symmetry_residual(&Plane::from_normal_and_offset(normal, offset));
+            if residual < best_residual {
+                (best_normal, best_offset, best_residual) = (normal, offset, residual);
+            }
+        }
+
+        // Refine by pattern search, tilting the normal about two tangent axes and shifting the offset
+        let mut step = 0.5 * golden_angle / (SYMMETRY_CANDIDATES as f32).sqrt();
+        while step > 1e-3 {
+            let tangent = if best_normal.cross(&GeoVector::xhat()).norm() > 0.5 {
+                best_normal.cross(&GeoVector::xhat()).normalize()
+            } else {
+                best_normal.cross(&GeoVector::yhat()).normalize()
+            };
+            let bitangent = best_normal.cross(&tangent);
+
+            let mut improved = false;
+            for (tilt, shift) in [(tangent, 0.0), (-tangent, 0.0), (bitangent, 0.0), (-bitangent, 0.0), (GeoVector::zero(), 1.0), (GeoVector::zero(), -1.0)] {
+                let normal = (best_normal + tilt * step).normalize();
+                let offset = best_offset + (normal.dot(&centroid) - best_normal.dot(&centroid)) + shift * step * extent;
+                let residual = surface.symmetry_residual(&Plane::from_normal_and_offset(normal, offset));
+                if residual < best_residual {
+                    (best_normal, best_offset, best_residual) = (normal, offset, residual);
+                    improved = true;
+                }
+            }
+            if !improved {
+                step *= 0.5;
+            }
+        }
+
+        Some(Plane::from_normal_and_offset(best_normal, best_offset))
+    }
+
+    /// Decimate the surface to roughly `target_vertex_count` vertices by vertex clustering.
+    /// Vertices are binned on a uniform grid, and each bin is represented by its vertex closest to the bin mean,
+    /// so the decimated vertices stay on the original surface.
+    /// Triangles that collapse or would make an edge non-manifold are dropped.
+    /// Returns a clone if the surface already has no more than `target_vertex_count` vertices.
+    pub fn decimate(&self, target_vertex_count: usize) -> Self {
+        if self.vertices.len() <= target_vertex_count || target_vertex_count < 3 {
+            return self.clone();
+        }
+
+        // Bounding box for the clustering grid
+        let mut min = self.vertices[0].point;
+        let mut max = self.vertices[0].point;
+        for vertex in self.vertices.iter() {
+            let p = vertex.point;
+            min = Point::new(min.x.min(p.x), min.y.min(p.y), min.z.min(p.z));
+            max = Point::new(max.x.max(p.x), max.y.max(p.y), max.z.max(p.z));
+        }
+
+        let cluster = |cell_size: f32| -> std::collections::HashMap<(i64, i64, i64), Vec<usize>> {
+            let mut clusters = std::collections::HashMap::<(i64, i64, i64), Vec<usize>>::new();
+            for (vertex_id, vertex) in self.vertices.iter().enumerate() {
+                let cell = (
+                    ((vertex.point.x - min.x) / cell_size) as i64,
+                    ((vertex.point.y - min.y) / cell_size) as i64,
+                    ((vertex.point.z - min.z) / cell_size) as i64,
+                );
+                clusters.entry(cell).or_default().push(vertex_id);
+            }
+            clusters
+        };
+
+        // Cluster count scales with the surface area over the cell area, so refine the cell size a few times
+        let total_area: f32 = self.faces.iter().map(|face| face.area).sum();
+        let mut cell_size = (total_area / target_vertex_count as f32).sqrt();
+        let mut clusters = cluster(cell_size);
+        for _ in 0..8 {
+            let ratio = clusters.len() as f32 / target_vertex_count as f32;
+            if (ratio - 1.0).abs() < 0.1 {
+                break;
+            }
+            cell_size *= ratio.sqrt();
+            clusters = cluster(cell_size);
+        }
+
+        // Pick a representative vertex for each cluster
+        let mut vertex_map = vec![0; self.vertices.len()];
+        let mut representatives = Vec::<usize>::with_capacity(clusters.len());
+        let mut cells: Vec<_> = clusters.into_iter().collect();
+        cells.sort_by_key(|(cell, _)| *cell);
+        for (cluster_id, (_, vertex_ids)) in cells.iter().enumerate() {
+            let mut mean = GeoVector::zero();
+            for vertex_id in vertex_ids.iter() {
+                mean += self.vertices[*vertex_id].point.into();
+                vertex_map[*vertex_id] = cluster_id;
+            }
+            let mean: Point = (mean / vertex_ids.len() as f32).into();
+            let representative = vertex_ids.iter()
+                .min_by(|a, b| self.vertices[**a].point.distance(&mean).total_cmp(&self.vertices[**b].point.distance(&mean)))
+                .copied()
+                .unwrap_or(vertex_ids[0]);
+            representatives.push(representative);
+        }
+
+        // Remap the triangles, dropping collapsed, duplicate, and non-manifold ones
+        let mut triangles = Vec::<[usize; 3]>::new();
+        let mut seen_triangles = std::collections::HashSet::<[usize; 3]>::new();
+        let mut edge_use = std::collections::HashMap::<(usize, usize), usize>::new();
+        for face in self.faces.iter() {
+            let triangle = face.vertices.map(|v| vertex_map[v]);
+            if triangle[0] == triangle[1] || triangle[1] == triangle[2] || triangle[2] == triangle[0] {
+                continue;
+            }
+            let mut sorted = triangle;
+            sorted.sort();
+            let edges = [(sorted[0], sorted[1]), (sorted[1], sorted[2]), (sorted[0], sorted[2])];
+            if seen_triangles.contains(&sorted) || edges.iter().any(|e| edge_use.get(e).copied().unwrap_or(0) >= 2) {
+                continue;
+            }
+            seen_triangles.insert(sorted);
+            for edge in edges.iter() {
+                *edge_use.entry(*edge).or_insert(0) += 1;
+            }
+            triangles.push(triangle);
+        }
+
+        // Compact to the vertices still in use
+        let mut used_map = vec![None as Option<usize>; representatives.len()];
+        let mut points = Vec::<Point>::new();
+        for triangle in triangles.iter_mut() {
+            for v in triangle.iter_mut() {
+                *v = *used_map[*v].get_or_insert_with(|| {
+                    points.push(self.vertices[representatives[*v]].point);
+                    points.len() - 1
+                });
+            }
+        }
+
+        Surface::from_triangles(points, triangles)
+    }
+
     pub fn get_boundary_vertex_indices(&self) -> Vec<usize> {
         let mut boundary_vertex_indices = Vec::new();
 
@@ -30,6 +451,92 @@ impl Surface {
         boundary_vertex_indices
     }
 
+    /// Chain the boundary edges (edges with a single adjacent face) into closed loops of vertex indices.
+    /// Where several boundary loops touch at a vertex, the walk takes the first unused edge, so pinched loops may be merged.
+    pub fn boundary_loops(&self) -> Vec<Vec<usize>> {
+        let mut boundary_neighbors = vec![Vec::<usize>::new(); self.vertices.len()];
+        for edge in self.edges.iter() {
+            if edge.adj_faces.iter().any(|f| f.is_none()) {
+                boundary_neighbors[edge.vertices[0]].push(edge.vertices[1]);
+                boundary_neighbors[edge.vertices[1]].push(edge.vertices[0]);
+            }
+        }
+
+        let mut loops = Vec::new();
+        for start_id in 0..self.vertices.len() {
+            while let Some(mut next_id) = boundary_neighbors[start_id].pop() {
+                // Walk from the start along unused boundary edges until back at the start (or stuck on an open chain)
+                let mut boundary_loop = vec![start_id];
+                let mut vertex_id = start_id;
+                loop {
+                    boundary_neighbors[next_id].retain(|neighbor| *neighbor != vertex_id);
+                    if next_id == start_id {
+                        break;
+                    }
+                    boundary_loop.push(next_id);
+                    vertex_id = next_id;
+                    next_id = match boundary_neighbors[vertex_id].pop() {
+                        Some(next_id) => next_id,
+                        None => break,
+                    };
+                }
+                loops.push(boundary_loop);
+            }
+        }
+        loops
+    }
+
+    /// Split the vertices into connected components (vertices linked by edges).
+    /// Each component lists its vertex indices in ascending order,
+    /// and the components are ordered by their lowest vertex index.
+    pub fn connected_components(&self) -> Vec<Vec<usize>> {
+        let mut component_of = vec![None; self.vertices.len()];
+        let mut components = Vec::<Vec<usize>>::new();
+        for start_id in 0..self.vertices.len() {
+            if component_of[start_id].is_some() {
+                continue;
+            }
+
+            // Flood the component out from the lowest unvisited vertex
+            let component_id = components.len();
+            component_of[start_id] = Some(component_id);
+            let mut component = vec![start_id];
+            let mut frontier = vec![start_id];
+            while let Some(vertex_id) = frontier.pop() {
+                for edge_id in self.vertices[vertex_id].adj_edges.iter() {
+                    for next_id in self.edges[*edge_id].vertices.iter() {
+                        if component_of[*next_id].is_none() {
+                            component_of[*next_id] = Some(component_id);
+                            component.push(*next_id);
+                            frontier.push(*next_id);
+                        }
+                    }
+                }
+            }
+            component.sort();
+            components.push(component);
+        }
+        components
+    }
+
+    /// Get the part of the surface made of the faces with all their vertices in `vertex_indices`,
+    /// e.g. a connected component (see `connected_components`).
+    /// Vertices are renumbered in the order given.
+    pub fn sub_surface(&self, vertex_indices: &[usize]) -> Self {
+        let mut vertex_map = vec![None; self.vertices.len()];
+        for (new_id, vertex_id) in vertex_indices.iter().enumerate() {
+            vertex_map[*vertex_id] = Some(new_id);
+        }
+        let points = vertex_indices.iter().map(|vertex_id| self.vertices[*vertex_id].point).collect();
+        let triangles = self.faces.iter()
+            .filter_map(|face| {
+                let [a, b, c] = face.vertices.map(|vertex_id| vertex_map[vertex_id]);
+                Some([a?, b?, c?])
+            })
+            .collect();
+        Surface::from_triangles(points, triangles)
+    }
+
     /// Trim the surface by a plane.
     /// Returns the new surface and the indices of the vertices on the cut boundary.
     pub fn trim_by_plane(&self, plane: &Plane, flatten_cut: bool) -> (Self, Vec<usize>) {
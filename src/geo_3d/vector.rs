@@ -7,7 +7,7 @@ use std::ops::{
 use std::fmt;
 use serde::{Serialize, Deserialize};
 
-use crate::geo_3d::{Angle, Point};
+use crate::geo_3d::{Angle, Point, Rotation};
 
 /// A vector in 3D space.
 /// Used for the normal vector of a point.
@@ -66,6 +66,11 @@ impl GeoVector {
         self.norm_sq().sqrt()
     }
 
+    /// Check if two vectors differ by a vector of magnitude `tol` or less.
+    pub fn approx_eq(&self, other: &GeoVector, tol: f32) -> bool {
+        (*self - *other).norm() <= tol
+    }
+
     /// Get the angle between two vectors.
     pub fn angle_to(&self, other: &GeoVector) -> Angle {
         let dot = self.dot(other);
@@ -96,13 +101,9 @@ impl GeoVector {
         *self - self.proj_onto(other)
     }
 
-    /// Rotate around another vector by an angle.
+    /// Rotate around another vector by an angle (see `Rotation` to compose several rotations).
     pub fn rotate_around(&self, axis: &GeoVector, angle: Angle) -> GeoVector {
-        let c = angle.cos();
-        let s = angle.sin();
-        let cross = axis.cross(&self);
-
-        *self * c + cross * s + *axis * axis.dot(&self) * (1.0 - c)
+        Rotation::from_axis_angle(axis, angle).apply(self)
     }
 
     /// Reflect a vector across a normal vector.
@@ -131,6 +132,11 @@ impl GeoVector {
         self.x.is_nan() || self.y.is_nan() || self.z.is_nan()
     }
 
+    /// Check if all of the components are finite (not NaN or infinite).
+    pub fn is_finite(&self) -> bool {
+        self.x.is_finite() && self.y.is_finite() && self.z.is_finite()
+    }
+
     /// Elementwise power of the vector.
     pub fn el_powf(&self, power: f32) -> GeoVector {
         GeoVector{
@@ -1,5 +1,8 @@
 use std::io::Write;
 pub mod stl;
+pub mod msh;
+pub mod vtk;
+pub mod bundle;
 
 #[derive(Debug)]
 pub enum IoErrorType {
@@ -74,6 +77,14 @@ pub fn read_to_string(path: &str) -> IoResult<String> {
     }
 }
 
+/// Read the raw bytes of a file with verbose errors
+pub fn read_to_bytes(path: &str) -> IoResult<Vec<u8>> {
+    match std::fs::read(path){
+        Ok(buffer) => Ok(buffer),
+        Err(error) => Err(IoError{file: Some(path.to_string()), cause: IoErrorType::File(error)}),
+    }
+}
+
 /// Write string to file with verbose errors.
 pub fn write_to_file(path: &str, buffer: &str) -> IoResult<()> {
     let mut f = create(path)?;
@@ -85,6 +96,46 @@ pub fn write_to_file(path: &str, buffer: &str) -> IoResult<()> {
     }
 }
 
+/// Resolve a relative path against a base directory, if one is given.
+/// Absolute paths (and all paths without a base directory) are returned unchanged.
+pub fn resolve_path(path: &str, base_dir: Option<&std::path::Path>) -> String {
+    match base_dir {
+        Some(base_dir) if std::path::Path::new(path).is_relative() => base_dir.join(path).to_string_lossy().to_string(),
+        _ => path.to_string(),
+    }
+}
+
+/// Temporary directory, removed along with its contents when dropped.
+#[derive(Debug)]
+pub struct TempDir {
+    path: std::path::PathBuf,
+}
+impl TempDir {
+    /// Create a fresh, empty directory in the system temp directory.
+    /// The name starts with `prefix` and is unique to this process and call, so concurrent runs (and tests) don't collide.
+    pub fn new(prefix: &str) -> IoResult<Self> {
+        static COUNT: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+        let count = COUNT.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        let path = std::env::temp_dir().join(format!("{}_{}_{}", prefix, std::process::id(), count));
+        let file_err = |error: std::io::Error| IoError{file: Some(path.to_string_lossy().to_string()), cause: IoErrorType::File(error)};
+        if path.exists() {
+            std::fs::remove_dir_all(&path).map_err(file_err)?;
+        }
+        std::fs::create_dir_all(&path).map_err(file_err)?;
+        Ok(TempDir{path})
+    }
+
+    /// Get the directory path.
+    pub fn path(&self) -> &std::path::Path {
+        &self.path
+    }
+}
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.path);
+    }
+}
+
 /// Read in cfg files from the supported filetypes.
 pub fn load_deser_from<T>(path: &str) -> IoResult<T> 
 where T: serde::de::DeserializeOwned
@@ -119,6 +170,50 @@ where T: serde::de::DeserializeOwned
     }
 }
 
+/// Serialization wrapper that rounds every float to a fixed number of decimal places.
+/// Full f32 precision leaves long float tails (e.g. `12.300000190734863`), which makes saved files noisy to read and diff.
+pub struct Rounded<'a, T: serde::Serialize> {
+    pub value: &'a T,
+    pub decimals: usize,
+}
+impl<T: serde::Serialize> serde::Serialize for Rounded<'_, T> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut value = serde_json::to_value(self.value).map_err(serde::ser::Error::custom)?;
+        round_floats(&mut value, self.decimals);
+        value.serialize(serializer)
+    }
+}
+
+/// Quote a CSV field if it holds a separator, quote or line break, doubling any quotes.
+pub fn escape_csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// Escape line breaks so user text stays inside a single-line comment.
+pub fn escape_comment(text: &str) -> String {
+    text.replace('\r', "\\r").replace('\n', "\\n")
+}
+
+/// Round every float in a JSON value to `decimals` places, in place.
+fn round_floats(value: &mut serde_json::Value, decimals: usize) {
+    match value {
+        serde_json::Value::Number(number) if number.is_f64() => {
+            let scale = 10.0_f64.powi(decimals as i32);
+            let rounded = (number.as_f64().unwrap() * scale).round() / scale;
+            if let Some(rounded) = serde_json::Number::from_f64(rounded) {
+                *number = rounded;
+            }
+        },
+        serde_json::Value::Array(values) => values.iter_mut().for_each(|value| round_floats(value, decimals)),
+        serde_json::Value::Object(map) => map.values_mut().for_each(|value| round_floats(value, decimals)),
+        _ => {},
+    }
+}
+
 /// Dump a struct to a file with the supported filetypes.
 pub fn save_ser_to(path: &str, cfg: &impl serde::Serialize) -> IoResult<()> {
     match path.split('.').last(){
@@ -150,3 +245,22 @@ pub fn save_ser_to(path: &str, cfg: &impl serde::Serialize) -> IoResult<()> {
         },
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn csv_fields_are_quoted_only_when_needed() {
+        assert_eq!(escape_csv_field("row_a"), "row_a");
+        assert_eq!(escape_csv_field("row a, left"), "\"row a, left\"");
+        assert_eq!(escape_csv_field("the \"top\" row"), "\"the \"\"top\"\" row\"");
+        assert_eq!(escape_csv_field("row\na"), "\"row\na\"");
+    }
+
+    #[test]
+    fn comments_stay_on_one_line() {
+        assert_eq!(escape_comment("row_a"), "row_a");
+        assert_eq!(escape_comment("row\r\na"), "row\\r\\na");
+    }
+}
@@ -0,0 +1,70 @@
+use crate::io;
+use std::path::{Path, PathBuf};
+
+/// Supported config file extensions inside a bundle.
+const CFG_EXTENSIONS: [&str; 4] = ["json", "toml", "yaml", "yml"];
+
+/// Leading bytes of a zip archive (local file header signature).
+const ZIP_MAGIC: [u8; 4] = [0x50, 0x4b, 0x03, 0x04];
+
+/// Extracted `.comrade` bundle.
+/// A bundle is a tar or zip archive holding the surface mesh, the stage configs, and any other inputs (e.g. a static layout).
+/// Stage configs are found by name at the top level of the archive (`layout.yaml`, `mesh.json`, `sim.toml`, `match.yml`, ...).
+/// Relative input paths in bundled configs are resolved against the extraction directory.
+#[derive(Debug)]
+pub struct Bundle {
+    /// Directory the bundle was extracted to, removed when the bundle is dropped.
+    pub dir: io::TempDir,
+    pub layout_cfg: Option<String>,
+    pub mesh_cfg: Option<String>,
+    pub sim_cfg: Option<String>,
+    pub matching_cfg: Option<String>,
+}
+
+/// Extract a `.comrade` bundle (tar or zip archive) to a fresh temporary directory and find the stage configs inside.
+/// The archive type is detected from its contents. Keep the returned `Bundle` alive while its files are in use.
+/// Returns an `IoResult` with the `Bundle` or an `Err`.
+pub fn extract_bundle(path: &str) -> io::IoResult<Bundle> {
+    let bundle_err = |msg: String| -> io::IoError {
+        io::IoError{file: Some(path.to_string()), cause: io::IoErrorType::StringOnly(msg)}
+    };
+
+    if !path.ends_with(".comrade") {
+        return Err(bundle_err("Bundle file must end with .comrade".to_string()));
+    }
+
+    // Extract to a fresh directory named after the bundle
+    let stem = Path::new(path).file_stem().and_then(|s| s.to_str()).unwrap_or("bundle");
+    let temp_dir = io::TempDir::new(&format!("comrade_bundle_{}", stem))?;
+    let dir = temp_dir.path();
+    let mut magic = [0u8; 4];
+    let is_zip = std::io::Read::read_exact(&mut io::open(path)?, &mut magic).is_ok() && magic == ZIP_MAGIC;
+    if is_zip {
+        let mut archive = zip::ZipArchive::new(io::open(path)?).map_err(|error| bundle_err(format!("Failed to read zip bundle: {}", error)))?;
+        archive.extract(dir).map_err(|error| bundle_err(format!("Failed to extract bundle: {}", error)))?;
+    } else {
+        let mut archive = tar::Archive::new(io::open(path)?);
+        archive.unpack(dir).map_err(|error| bundle_err(format!("Failed to extract bundle: {}", error)))?;
+    }
+
+    // Find the stage configs by name
+    let find_cfg = |name: &str| -> io::IoResult<Option<String>> {
+        let found: Vec<PathBuf> = CFG_EXTENSIONS.iter()
+            .map(|extension| dir.join(format!("{}.{}", name, extension)))
+            .filter(|cfg_path| cfg_path.is_file())
+            .collect();
+        match found.len() {
+            0 => Ok(None),
+            1 => Ok(Some(found[0].to_string_lossy().to_string())),
+            _ => Err(bundle_err(format!("Bundle has more than one {} config: {:?}", name, found))),
+        }
+    };
+
+    Ok(Bundle{
+        layout_cfg: find_cfg("layout")?,
+        mesh_cfg: find_cfg("mesh")?,
+        sim_cfg: find_cfg("sim")?,
+        matching_cfg: find_cfg("match")?,
+        dir: temp_dir,
+    })
+}
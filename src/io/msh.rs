@@ -0,0 +1,301 @@
+use crate::io;
+use crate::geo_3d::{
+    Point,
+    Surface,
+};
+
+/// GMSH element type id for 3-node triangles.
+const MSH_TRIANGLE: usize = 2;
+
+/// Load a GMSH `.msh` file (ASCII, format 2.2 or 4.1) from the input path.
+/// Only the nodes and 3-node triangle elements are used -- other elements (lines, points, volumes) are ignored.
+/// Nodes that aren't part of any triangle are dropped.
+/// Returns an `IoResult` with the `Surface` or an `Err` (including for binary files).
+pub fn load_msh(filename: &str) -> io::IoResult<Surface> {
+    let contents = io::read_to_string(filename)?;
+    let parse_err = |msg: &str| -> io::IoError {
+        io::IoError{file: Some(filename.to_string()), cause: io::IoErrorType::StringOnly(format!("MSH parse error: {}", msg))}
+    };
+
+    // Split into sections by their $Name ... $EndName markers
+    let section = |name: &str| -> Option<Vec<&str>> {
+        let start_marker = format!("${}", name);
+        let end_marker = format!("$End{}", name);
+        let mut lines = contents.lines().map(|l| l.trim());
+        lines.find(|l| *l == start_marker)?;
+        Some(lines.take_while(|l| *l != end_marker).filter(|l| !l.is_empty()).collect())
+    };
+
+    // Check the format
+    let format = section("MeshFormat").ok_or_else(|| parse_err("Missing $MeshFormat section"))?;
+    let header: Vec<&str> = format.first().ok_or_else(|| parse_err("Empty $MeshFormat section"))?.split_whitespace().collect();
+    if header.len() < 2 {
+        return Err(parse_err("Malformed $MeshFormat header"));
+    }
+    if header[1] != "0" {
+        return Err(parse_err("Binary MSH files are not supported, re-export from GMSH as ASCII"));
+    }
+    let version = header[0];
+
+    let nodes = section("Nodes").ok_or_else(|| parse_err("Missing $Nodes section"))?;
+    let elements = section("Elements").ok_or_else(|| parse_err("Missing $Elements section"))?;
+
+    let (node_tags, node_points, triangles) = if version.starts_with("2.") {
+        let (node_tags, node_points) = parse_nodes_v2(&nodes).map_err(|e| parse_err(&e))?;
+        (node_tags, node_points, parse_elements_v2(&elements).map_err(|e| parse_err(&e))?)
+    } else if version.starts_with("4.") {
+        let (node_tags, node_points) = parse_nodes_v4(&nodes).map_err(|e| parse_err(&e))?;
+        (node_tags, node_points, parse_elements_v4(&elements).map_err(|e| parse_err(&e))?)
+    } else {
+        return Err(parse_err(&format!("Unsupported MSH version {} (supported: 2.2, 4.1)", version)));
+    };
+
+    // Keep only the nodes used by triangles, re-indexed in order of appearance
+    let mut tag_to_node = std::collections::HashMap::<usize, usize>::new();
+    for (node_id, tag) in node_tags.iter().enumerate() {
+        tag_to_node.insert(*tag, node_id);
+    }
+    let mut node_to_vertex = vec![None as Option<usize>; node_points.len()];
+    let mut points = Vec::<Point>::new();
+    let mut faces = Vec::<[usize; 3]>::with_capacity(triangles.len());
+    for triangle in triangles.iter() {
+        let mut face = [0; 3];
+        for (i, tag) in triangle.iter().enumerate() {
+            let node_id = *tag_to_node.get(tag).ok_or_else(|| parse_err(&format!("Triangle references missing node {}", tag)))?;
+            face[i] = *node_to_vertex[node_id].get_or_insert_with(|| {
+                points.push(node_points[node_id]);
+                points.len() - 1
+            });
+        }
+        faces.push(face);
+    }
+
+    if faces.is_empty() {
+        return Err(parse_err("No triangle elements found"));
+    }
+
+    Ok(Surface::from_triangles(points, faces))
+}
+
+/// Parse a whitespace-separated list of numbers from a line.
+fn parse_line<T: std::str::FromStr>(line: &str) -> Result<Vec<T>, String> {
+    line.split_whitespace()
+        .map(|s| s.parse::<T>().map_err(|_| format!("Could not parse \"{}\" in line \"{}\"", s, line)))
+        .collect()
+}
+
+/// Parse a single count from the start of a line.
+fn parse_count(line: Option<&&str>) -> Result<usize, String> {
+    let line = line.ok_or("Unexpected end of section")?;
+    parse_line::<usize>(line)?.first().copied().ok_or_else(|| format!("Expected a count, found \"{}\"", line))
+}
+
+/// Parse the $Nodes section of an MSH 2.2 file: a count, then `tag x y z` per line.
+fn parse_nodes_v2(lines: &[&str]) -> Result<(Vec<usize>, Vec<Point>), String> {
+    let count = parse_count(lines.first())?;
+    let mut tags = Vec::with_capacity(count);
+    let mut points = Vec::with_capacity(count);
+    for line in lines.iter().skip(1).take(count) {
+        let (tag, coordinates) = line.trim().split_once(char::is_whitespace).unwrap_or((line, ""));
+        let values = parse_line::<f32>(coordinates)?;
+        if values.len() < 3 {
+            return Err(format!("Malformed node line \"{}\"", line));
+        }
+        tags.push(parse_count(Some(&tag))?);
+        points.push(Point::new(values[0], values[1], values[2]));
+    }
+    if tags.len() != count {
+        return Err(format!("Expected {} nodes, found {}", count, tags.len()));
+    }
+    Ok((tags, points))
+}
+
+/// Parse the triangles in the $Elements section of an MSH 2.2 file:
+/// a count, then `tag type tag_count tags... nodes...` per line.
+fn parse_elements_v2(lines: &[&str]) -> Result<Vec<[usize; 3]>, String> {
+    let count = parse_count(lines.first())?;
+    let mut triangles = Vec::new();
+    for line in lines.iter().skip(1).take(count) {
+        let values = parse_line::<usize>(line)?;
+        if values.len() < 3 {
+            return Err(format!("Malformed element line \"{}\"", line));
+        }
+        if values[1] == MSH_TRIANGLE {
+            if 3 + values[2] > values.len() {
+                return Err(format!("Malformed triangle element \"{}\"", line));
+            }
+            let nodes = &values[3 + values[2]..];
+            if nodes.len() != 3 {
+                return Err(format!("Malformed triangle element \"{}\"", line));
+            }
+            triangles.push([nodes[0], nodes[1], nodes[2]]);
+        }
+    }
+    Ok(triangles)
+}
+
+/// Parse the $Nodes section of an MSH 4.1 file.
+/// Nodes are in entity blocks: `dim tag parametric count`, then `count` tags, then `count` coordinate lines.
+fn parse_nodes_v4(lines: &[&str]) -> Result<(Vec<usize>, Vec<Point>), String> {
+    let block_count = parse_count(lines.first())?;
+    let mut tags = Vec::new();
+    let mut points = Vec::new();
+    let mut line_id = 1;
+    for _ in 0..block_count {
+        let block_header = parse_line::<usize>(lines.get(line_id).ok_or("Unexpected end of $Nodes")?)?;
+        if block_header.len() < 4 {
+            return Err("Malformed node block header".to_string());
+        }
+        let count = block_header[3];
+        line_id += 1;
+        for line in lines.iter().skip(line_id).take(count) {
+            tags.push(parse_count(Some(line))?);
+        }
+        for line in lines.iter().skip(line_id + count).take(count) {
+            let values = parse_line::<f32>(line)?;
+            if values.len() < 3 {
+                return Err(format!("Malformed node coordinates \"{}\"", line));
+            }
+            points.push(Point::new(values[0], values[1], values[2]));
+        }
+        line_id += 2 * count;
+    }
+    if tags.len() != points.len() {
+        return Err(format!("Found {} node tags but {} node coordinates", tags.len(), points.len()));
+    }
+    Ok((tags, points))
+}
+
+/// Parse the triangles in the $Elements section of an MSH 4.1 file.
+/// Elements are in entity blocks: `dim tag type count`, then `tag nodes...` per line.
+fn parse_elements_v4(lines: &[&str]) -> Result<Vec<[usize; 3]>, String> {
+    let block_count = parse_count(lines.first())?;
+    let mut triangles = Vec::new();
+    let mut line_id = 1;
+    for _ in 0..block_count {
+        let block_header = parse_line::<usize>(lines.get(line_id).ok_or("Unexpected end of $Elements")?)?;
+        if block_header.len() < 4 {
+            return Err("Malformed element block header".to_string());
+        }
+        let (element_type, count) = (block_header[2], block_header[3]);
+        line_id += 1;
+        if element_type == MSH_TRIANGLE {
+            for line in lines.iter().skip(line_id).take(count) {
+                let values = parse_line::<usize>(line)?;
+                if values.len() != 4 {
+                    return Err(format!("Malformed triangle element \"{}\"", line));
+                }
+                triangles.push([values[1], values[2], values[3]]);
+            }
+        }
+        line_id += count;
+    }
+    Ok(triangles)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Unit square split into two triangles, with a stray line element and an unused node
+    const SQUARE_V2: &str = "\
+$MeshFormat
+2.2 0 8
+$EndMeshFormat
+$Nodes
+5
+1 0 0 0
+2 1 0 0
+3 1 1 0
+4 0 1 0
+5 5 5 5
+$EndNodes
+$Elements
+3
+1 1 2 0 1 1 2
+2 2 2 0 1 1 2 3
+3 2 2 0 1 1 3 4
+$EndElements
+";
+
+    const SQUARE_V4: &str = "\
+$MeshFormat
+4.1 0 8
+$EndMeshFormat
+$Nodes
+2 5 1 5
+0 1 0 1
+5
+5 5 5
+2 1 0 4
+1
+2
+3
+4
+0 0 0
+1 0 0
+1 1 0
+0 1 0
+$EndNodes
+$Elements
+2 3 1 3
+1 1 1 1
+1 1 2
+2 1 2 2
+2 1 2 3
+3 1 3 4
+$EndElements
+";
+
+    fn load_from_str(name: &str, contents: &str) -> io::IoResult<Surface> {
+        let test_dir = crate::test_fixtures::temp_dir("msh_test");
+        let path = test_dir.path().join(name);
+        let path = path.to_string_lossy();
+        io::write_to_file(&path, contents)?;
+        load_msh(&path)
+    }
+
+    #[test]
+    fn load_ascii_msh_v2() {
+        let surface = load_from_str("square_v2.msh", SQUARE_V2).unwrap();
+        assert_eq!(surface.vertices.len(), 4);
+        assert_eq!(surface.faces.len(), 2);
+        assert_eq!(surface.edges.len(), 5);
+        assert_eq!(surface.get_boundary_vertex_indices().len(), 4);
+    }
+
+    #[test]
+    fn load_ascii_msh_v4() {
+        let surface = load_from_str("square_v4.msh", SQUARE_V4).unwrap();
+        assert_eq!(surface.vertices.len(), 4);
+        assert_eq!(surface.faces.len(), 2);
+        assert!(surface.vertices.iter().all(|v| (v.normal.z - 1.0).abs() < 1e-6));
+    }
+
+    #[test]
+    fn msh_v2_node_tags_are_exact() {
+        // Tags past f32 precision (2^24 + 1) still match their elements
+        let large_tags = SQUARE_V2.replace("\n1 0 0 0", "\n16777217 0 0 0")
+            .replace("1 1 2 3\n", "1 16777217 2 3\n").replace("1 1 3 4\n", "1 16777217 3 4\n");
+        let surface = load_from_str("square_large_tags.msh", &large_tags).unwrap();
+        assert_eq!(surface.faces.len(), 2);
+        assert_eq!(surface.vertices.len(), 4);
+
+        let fractional_tag = SQUARE_V2.replace("\n1 0 0 0", "\n1.5 0 0 0");
+        assert!(load_from_str("square_fractional_tag.msh", &fractional_tag).is_err());
+    }
+
+    #[test]
+    fn msh_v2_truncated_triangle_is_an_error() {
+        // Tag count runs past the end of the line
+        let truncated = SQUARE_V2.replace("2 2 2 0 1 1 2 3", "2 2 9 0 1");
+        let error = load_from_str("square_truncated.msh", &truncated).unwrap_err().to_string();
+        assert!(error.contains("Malformed triangle element"), "{}", error);
+    }
+
+    #[test]
+    fn binary_msh_is_an_error() {
+        let binary = SQUARE_V2.replace("2.2 0 8", "2.2 1 8");
+        assert!(load_from_str("square_binary.msh", &binary).is_err());
+    }
+}
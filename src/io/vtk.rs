@@ -0,0 +1,149 @@
+use crate::io;
+use crate::geo_3d::{Point, GeoVector, Surface};
+
+/// Save a surface with a per-vertex scalar field as a legacy ASCII VTK polydata file.
+/// `values` must have one entry per surface vertex.
+/// Returns an `IoResult` with `()` or an `Err`.
+pub fn save_point_scalars(filename: &str, surface: &Surface, field_name: &str, values: &[f32]) -> io::IoResult<()> {
+    let values: Vec<String> = values.iter().map(|value| value.to_string()).collect();
+    save_point_field(filename, surface, field_name, "float", &values)
+}
+
+/// Save a surface with a per-vertex integer field (e.g. coil ids) as a legacy ASCII VTK polydata file.
+/// `values` must have one entry per surface vertex.
+/// Returns an `IoResult` with `()` or an `Err`.
+pub fn save_point_ints(filename: &str, surface: &Surface, field_name: &str, values: &[i32]) -> io::IoResult<()> {
+    let values: Vec<String> = values.iter().map(|value| value.to_string()).collect();
+    save_point_field(filename, surface, field_name, "int", &values)
+}
+
+/// Save a scalar field sampled on a regular grid as a legacy ASCII VTK structured points volume.
+/// `values` must have one entry per grid point, x fastest, starting at `origin`.
+/// Returns an `IoResult` with `()` or an `Err`.
+pub fn save_grid_scalars(filename: &str, origin: Point, spacing: GeoVector, dimensions: [usize; 3], field_name: &str, values: &[f32]) -> io::IoResult<()> {
+    let point_count = dimensions.iter().product::<usize>();
+    if values.len() != point_count {
+        return Err(io::IoError{
+            file: Some(filename.to_string()),
+            cause: io::IoErrorType::StringOnly(format!("VTK volume has {} values for {} grid points", values.len(), point_count)),
+        });
+    }
+
+    let mut buffer = String::new();
+    buffer.push_str("# vtk DataFile Version 3.0\n");
+    buffer.push_str(&format!("comrade {}\n", field_name));
+    buffer.push_str("ASCII\n");
+    buffer.push_str("DATASET STRUCTURED_POINTS\n");
+    buffer.push_str(&format!("DIMENSIONS {} {} {}\n", dimensions[0], dimensions[1], dimensions[2]));
+    buffer.push_str(&format!("ORIGIN {} {} {}\n", origin.x, origin.y, origin.z));
+    buffer.push_str(&format!("SPACING {} {} {}\n", spacing.x, spacing.y, spacing.z));
+
+    buffer.push_str(&format!("POINT_DATA {}\n", point_count));
+    buffer.push_str(&format!("SCALARS {} float 1\n", field_name));
+    buffer.push_str("LOOKUP_TABLE default\n");
+    for value in values.iter() {
+        buffer.push_str(&format!("{}\n", value));
+    }
+
+    io::write_to_file(filename, &buffer)
+}
+
+/// Save a closed polyline (e.g. an outline on the surface) as a legacy ASCII VTK polydata file.
+/// Returns an `IoResult` with `()` or an `Err`.
+pub fn save_closed_polyline(filename: &str, points: &[Point]) -> io::IoResult<()> {
+    let mut buffer = String::new();
+    buffer.push_str("# vtk DataFile Version 3.0\n");
+    buffer.push_str("comrade polyline\n");
+    buffer.push_str("ASCII\n");
+    buffer.push_str("DATASET POLYDATA\n");
+
+    buffer.push_str(&format!("POINTS {} float\n", points.len()));
+    for point in points.iter() {
+        buffer.push_str(&format!("{} {} {}\n", point.x, point.y, point.z));
+    }
+
+    // Repeat the first point to close the line
+    if !points.is_empty() {
+        let indices: Vec<String> = (0..points.len()).chain(std::iter::once(0)).map(|id| id.to_string()).collect();
+        buffer.push_str(&format!("LINES 1 {}\n", indices.len() + 1));
+        buffer.push_str(&format!("{} {}\n", indices.len(), indices.join(" ")));
+    }
+
+    io::write_to_file(filename, &buffer)
+}
+
+/// Write the surface and a formatted per-vertex field of the given VTK data type.
+fn save_point_field(filename: &str, surface: &Surface, field_name: &str, data_type: &str, values: &[String]) -> io::IoResult<()> {
+    if values.len() != surface.vertices.len() {
+        return Err(io::IoError{
+            file: Some(filename.to_string()),
+            cause: io::IoErrorType::StringOnly(format!("VTK scalar field has {} values for {} vertices", values.len(), surface.vertices.len())),
+        });
+    }
+
+    let mut buffer = String::new();
+    buffer.push_str("# vtk DataFile Version 3.0\n");
+    buffer.push_str(&format!("comrade {}\n", field_name));
+    buffer.push_str("ASCII\n");
+    buffer.push_str("DATASET POLYDATA\n");
+
+    buffer.push_str(&format!("POINTS {} float\n", surface.vertices.len()));
+    for vertex in surface.vertices.iter() {
+        buffer.push_str(&format!("{} {} {}\n", vertex.point.x, vertex.point.y, vertex.point.z));
+    }
+
+    buffer.push_str(&format!("POLYGONS {} {}\n", surface.faces.len(), 4 * surface.faces.len()));
+    for face in surface.faces.iter() {
+        buffer.push_str(&format!("3 {} {} {}\n", face.vertices[0], face.vertices[1], face.vertices[2]));
+    }
+
+    buffer.push_str(&format!("POINT_DATA {}\n", values.len()));
+    buffer.push_str(&format!("SCALARS {} {} 1\n", field_name, data_type));
+    buffer.push_str("LOOKUP_TABLE default\n");
+    for value in values.iter() {
+        buffer.push_str(&format!("{}\n", value));
+    }
+
+    io::write_to_file(filename, &buffer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::temp_dir;
+
+    #[test]
+    fn scalar_field_matches_surface() {
+        let surface = Surface::square_grid(1.0, 2);
+        let values: Vec<f32> = (0..surface.vertices.len()).map(|v| v as f32).collect();
+        let test_dir = temp_dir("vtk_test");
+        let path = test_dir.path().join("vtk.vtk").to_string_lossy().to_string();
+
+        save_point_scalars(&path, &surface, "index", &values).unwrap();
+        let contents = std::fs::read_to_string(&path).unwrap();
+        assert!(contents.contains("POINTS 9 float"));
+        assert!(contents.contains("POLYGONS 8 32"));
+        assert!(contents.contains("POINT_DATA 9\nSCALARS index float 1"));
+
+        assert!(save_point_scalars(&path, &surface, "index", &values[1..]).is_err());
+
+        let ids: Vec<i32> = (0..surface.vertices.len()).map(|v| v as i32 - 1).collect();
+        save_point_ints(&path, &surface, "coil_id", &ids).unwrap();
+        let contents = std::fs::read_to_string(&path).unwrap();
+        assert!(contents.contains("SCALARS coil_id int 1\nLOOKUP_TABLE default\n-1\n0\n"));
+    }
+
+    #[test]
+    fn grid_volume_has_one_value_per_point() {
+        let test_dir = temp_dir("vtk_volume_test");
+        let path = test_dir.path().join("vtk_volume.vtk").to_string_lossy().to_string();
+        let values: Vec<f32> = (0..24).map(|v| v as f32).collect();
+
+        save_grid_scalars(&path, Point::new(0.5, 0.5, 0.5), GeoVector::new(1.0, 1.0, 2.0), [2, 3, 4], "b1", &values).unwrap();
+        let contents = std::fs::read_to_string(&path).unwrap();
+        assert!(contents.contains("DATASET STRUCTURED_POINTS\nDIMENSIONS 2 3 4\nORIGIN 0.5 0.5 0.5\nSPACING 1 1 2\n"));
+        assert!(contents.contains("POINT_DATA 24\nSCALARS b1 float 1"));
+
+        assert!(save_grid_scalars(&path, Point::zero(), GeoVector::new(1.0, 1.0, 1.0), [2, 3, 3], "b1", &values).is_err());
+    }
+}
@@ -1,13 +1,20 @@
 mod proc_errors;
 mod cfg;
 mod methods;
+mod manifest;
+mod capacitors;
+mod diff;
+mod statistics;
 
 use serde::{Serialize, Deserialize};
 
 use std::f32::consts::PI;
 const MU0: f32 = 1.256637062; // mu0 in nH/mm
+const COPPER_CONDUCTIVITY: f32 = 5.8e7; // S/m
 
 use crate::geo_3d::*;
+use crate::progress::{self, ProgressEvent};
+use crate::timing;
 
 // Re-export errors
 pub use proc_errors::{
@@ -18,10 +25,52 @@ pub use proc_errors::{
 // Re-export cfg handling
 pub use cfg::LayoutTarget;
 
+// Re-export the reproducibility manifest
+pub use manifest::{
+    Manifest,
+    build_manifest,
+    save_manifest,
+};
+
+// Re-export the capacitor summary
+pub use capacitors::{
+    CapacitorSummaryArgs,
+    CapacitorSummary,
+    CoilCapacitors,
+    CapacitorPosition,
+    coil_capacitors,
+    resonant_capacitance_pf,
+    build_capacitor_summary,
+    save_capacitor_summary,
+};
+
 // Re-export layout methods
 pub use methods::{
     MethodEnum,
+    LayoutMethod,
     LayoutMethodTrait,
+    MethodFactory,
+    MethodRegistry,
+    method_registry,
+    load_surface,
+};
+pub use methods::adam_circles::{CircleArgs, CircleLabel};
+#[cfg(any(test, feature = "bench"))]
+pub(crate) use methods::adam_circles::Method as AdamCirclesMethod;
+
+// Re-export layout comparison
+pub use diff::{
+    LayoutDiff,
+    CoilDiff,
+    diff_layouts,
+};
+
+// Re-export method-independent statistics
+pub use statistics::{
+    LayoutStatistics,
+    CoilStatistics,
+    StatisticsOptions,
+    statistics,
 };
 
 /// Layout struct.
@@ -37,6 +86,391 @@ impl Layout {
     pub fn new() -> Self{
         Layout{coils: Vec::new()}
     }
+
+    /// Get the maximum magnitude and RMS of the coupling factor over close pairs of coils,
+    /// i.e. pairs closer than `close_cutoff` times the sum of their average radii.
+    /// Returns `None` if there are no close pairs.
+    pub fn close_coupling_stats(&self, close_cutoff: f32) -> Option<(f32, f32)> {
+        let mut max_k: f32 = 0.0;
+        let mut sum_sq = 0.0;
+        let mut close_pairs = 0;
+        for (coil_id, coil) in self.coils.iter().enumerate() {
+            for other_coil in self.coils.iter().skip(coil_id + 1) {
+                let distance_scale = coil.average_radius() + other_coil.average_radius();
+                if (coil.center - other_coil.center).norm() / distance_scale < close_cutoff {
+                    let k = coil.coupling_factor(other_coil, 1.0);
+                    max_k = max_k.max(k.abs());
+                    sum_sq += k * k;
+                    close_pairs += 1;
+                }
+            }
+        }
+        if close_pairs == 0 {
+            return None;
+        }
+        Some((max_k, (sum_sq / close_pairs as f32).sqrt()))
+    }
+
+    /// Get the coils with a minimum bend radius below a limit.
+    /// Returns (coil index, minimum bend radius) pairs.
+    pub fn coils_below_bend_radius(&self, limit: f32) -> Vec<(usize, f32)> {
+        self.coils.iter().enumerate()
+            .map(|(coil_id, coil)| (coil_id, coil.min_bend_radius()))
+            .filter(|(_, bend_radius)| *bend_radius < limit)
+            .collect()
+    }
+
+    /// Get the coils lifting off the surface by more than a limit (see `Coil::max_liftoff`).
+    /// Returns (coil index, maximum lift-off) pairs.
+    pub fn coils_above_liftoff(&self, surface: &Surface, limit: f32) -> Vec<(usize, f32)> {
+        self.coils.iter().enumerate()
+            .map(|(coil_id, coil)| (coil_id, coil.max_liftoff(surface)))
+            .filter(|(_, liftoff)| *liftoff > limit)
+            .collect()
+    }
+
+    /// Scale the whole layout about the origin (e.g. for unit changes).
+    /// Coil points and centers are multiplied by `factor`, and wire radii too if `scale_wire_radius` is set.
+    /// Inductances scale with length, so any stored results must be recomputed.
+    pub fn scale(&mut self, factor: f32, scale_wire_radius: bool) {
+        for coil in self.coils.iter_mut() {
+            coil.scale(factor, scale_wire_radius);
+        }
+        crate::warnings::warn(&format!("Layout scaled by {} -- inductances scale linearly with length, recompute any inductance or coupling results", factor));
+    }
+
+    /// Count how many coils cover each surface vertex.
+    /// A vertex is covered if the coil loop encloses it (viewed along the coil normal)
+    /// and it is no farther from the coil center than the farthest coil vertex, so the far side of a closed surface isn't counted.
+    pub fn vertex_coverage(&self, surface: &Surface) -> Vec<usize> {
+        self.covering_coils(surface).iter().map(|coil_ids| coil_ids.len()).collect()
+    }
+
+    /// Get the coil that owns each surface vertex, by the same coverage test as `vertex_coverage`.
+    /// Vertices under several coils go to the coil with the nearest center, and uncovered vertices get -1.
+    pub fn vertex_ownership(&self, surface: &Surface) -> Vec<i32> {
+        self.covering_coils(surface).iter().zip(surface.vertices.iter()).map(|(coil_ids, vertex)| {
+            coil_ids.iter()
+                .min_by(|a, b| vertex.point.distance(&self.coils[**a].center).partial_cmp(&vertex.point.distance(&self.coils[**b].center)).unwrap())
+                .map_or(-1, |coil_id| *coil_id as i32)
+        }).collect()
+    }
+
+    /// Find the holes in the array: connected patches of surface vertices no coil covers (see `vertex_coverage`).
+    /// Each gap lists its vertex indices in ascending order, and the gaps are sorted by area (see `Surface::vertex_areas`), largest first.
+    pub fn coverage_gaps(&self, surface: &Surface) -> Vec<Vec<usize>> {
+        let uncovered: Vec<usize> = self.vertex_coverage(surface).iter().enumerate()
+            .filter(|(_, count)| **count == 0)
+            .map(|(vertex_id, _)| vertex_id)
+            .collect();
+        let vertex_areas = surface.vertex_areas();
+
+        // Components of the uncovered part, mapped back to the surface vertex indices
+        let mut gaps: Vec<(f32, Vec<usize>)> = surface.sub_surface(&uncovered).connected_components().iter().map(|component| {
+            let gap: Vec<usize> = component.iter().map(|sub_id| uncovered[*sub_id]).collect();
+            (gap.iter().map(|vertex_id| vertex_areas[*vertex_id]).sum(), gap)
+        }).collect();
+        gaps.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap());
+        gaps.into_iter().map(|(_, gap)| gap).collect()
+    }
+
+    /// Get the outline of the array footprint on the surface, e.g. for enclosure design:
+    /// the outer boundary of the covered vertices (see `vertex_coverage`), as a closed polyline.
+    /// Holes in the coverage are left out, and if the coverage is split, the longest boundary wins.
+    /// Returns an empty outline if no vertex is covered.
+    pub fn footprint_outline(&self, surface: &Surface) -> Vec<Point> {
+        let covered: Vec<usize> = self.vertex_coverage(surface).iter().enumerate()
+            .filter(|(_, count)| **count > 0)
+            .map(|(vertex_id, _)| vertex_id)
+            .collect();
+        let covered_surface = surface.sub_surface(&covered);
+
+        let loop_length = |boundary_loop: &Vec<usize>| -> f32 {
+            boundary_loop.iter().zip(boundary_loop.iter().cycle().skip(1))
+                .map(|(a, b)| covered_surface.vertices[*a].point.distance(&covered_surface.vertices[*b].point))
+                .sum()
+        };
+        covered_surface.boundary_loops().iter()
+            .max_by(|a, b| loop_length(a).partial_cmp(&loop_length(b)).unwrap())
+            .map_or(Vec::new(), |outline| outline.iter().map(|vertex_id| covered_surface.vertices[*vertex_id].point).collect())
+    }
+
+    /// Get the indices of the coils covering each surface vertex (see `vertex_coverage`).
+    fn covering_coils(&self, surface: &Surface) -> Vec<Vec<usize>> {
+        let extents: Vec<f32> = self.coils.iter()
+            .map(|coil| coil.vertices.iter().map(|v| v.point.distance(&coil.center)).fold(0.0, f32::max))
+            .collect();
+        surface.vertices.iter().map(|vertex| {
+            self.coils.iter().zip(extents.iter()).enumerate()
+                .filter(|(_, (coil, extent))| vertex.point.distance(&coil.center) <= **extent && coil.encloses_point(&vertex.point))
+                .map(|(coil_id, _)| coil_id)
+                .collect()
+        }).collect()
+    }
+
+    /// Reverse coils so they all share the winding sense of the first coil.
+    /// Breaks and ports are remapped to the reversed vertex order, so they stay on the same points.
+    /// Returns the indices of the reversed coils.
+    pub fn normalize_winding(&mut self) -> Vec<usize> {
+        let sense = match self.coils.first() {
+            Some(coil) => coil.winding_sense(),
+            None => return Vec::new(),
+        };
+        let mut reversed = Vec::new();
+        for (coil_id, coil) in self.coils.iter_mut().enumerate() {
+            if coil.winding_sense() == sense {
+                continue;
+            }
+            coil.reverse(false);
+            reversed.push(coil_id);
+        }
+        reversed
+    }
+
+    /// Find coil pairs whose coupling factor sign contradicts the expected sign.
+    /// Close pairs are those closer than `close_cutoff` times the sum of their average radii, as in `close_coupling_stats`.
+    /// Returns (coil index, other coil index, coupling factor) for each violating pair.
+    pub fn coupling_sign_violations(&self, check: &CouplingSignCheck, close_cutoff: f32) -> Vec<(usize, usize, f32)> {
+        let expected_pairs: Vec<(usize, usize, f32)> = match check {
+            CouplingSignCheck::AdjacentNegative => {
+                let mut pairs = Vec::new();
+                for (coil_id, coil) in self.coils.iter().enumerate() {
+                    for (other_id, other_coil) in self.coils.iter().enumerate().skip(coil_id + 1) {
+                        let distance_scale = coil.average_radius() + other_coil.average_radius();
+                        if (coil.center - other_coil.center).norm() / distance_scale < close_cutoff {
+                            pairs.push((coil_id, other_id, -1.0));
+                        }
+                    }
+                }
+                pairs
+            },
+            CouplingSignCheck::Pairs(pairs) => pairs.iter()
+                .filter(|(coil_id, other_id, _)| *coil_id < self.coils.len() && *other_id < self.coils.len())
+                .map(|(coil_id, other_id, sign)| (*coil_id, *other_id, *sign as f32))
+                .collect(),
+        };
+
+        expected_pairs.into_iter().filter_map(|(coil_id, other_id, sign)| {
+            let k = self.coils[coil_id].coupling_factor(&self.coils[other_id], 1.0);
+            if k * sign < 0.0 {
+                Some((coil_id, other_id, k))
+            } else {
+                None
+            }
+        }).collect()
+    }
+
+    /// Find the tightest wire-to-wire gap for every coil pair, using `Coil::closest_approach`.
+    /// The gap is the centerline distance less both wire radii, so it's comparable to the layout clearance.
+    /// Returns (coil index, other coil index, gap) for each pair.
+    pub fn min_spacing_report(&self) -> Vec<(usize, usize, f32)> {
+        let mut report = Vec::new();
+        for (coil_id, coil) in self.coils.iter().enumerate() {
+            for (other_id, other_coil) in self.coils.iter().enumerate().skip(coil_id + 1) {
+                let (distance, _, _) = coil.closest_approach(other_coil);
+                report.push((coil_id, other_id, distance - coil.wire_radius - other_coil.wire_radius));
+            }
+        }
+        report
+    }
+
+    /// Find the first NaN or infinite value in the layout, scanning coils in order
+    /// (center, normal and wire radius, then each vertex's point and normals).
+    /// Returns a description of its location, or `None` if every value is finite.
+    pub fn find_non_finite(&self) -> Option<String> {
+        for (coil_id, coil) in self.coils.iter().enumerate() {
+            let coil_fields = [
+                ("center", GeoVector::from(coil.center)),
+                ("normal", coil.normal),
+                ("wire_radius", GeoVector::new(coil.wire_radius, 0.0, 0.0)),
+            ];
+            if let Some((field, value)) = coil_fields.iter().find(|(_, value)| !value.is_finite()) {
+                return Some(format!("Coil {}: {} is not finite ({})", coil_id, field, value));
+            }
+            for (vertex_id, vertex) in coil.vertices.iter().enumerate() {
+                let vertex_fields = [
+                    ("point", GeoVector::from(vertex.point)),
+                    ("surface_normal", vertex.surface_normal),
+                    ("wire_radius_normal", vertex.wire_radius_normal),
+                ];
+                if let Some((field, value)) = vertex_fields.iter().find(|(_, value)| !value.is_finite()) {
+                    return Some(format!("Coil {}, vertex {}: {} is not finite ({})", coil_id, vertex_id, field, value));
+                }
+            }
+        }
+        None
+    }
+
+    /// Calculate the full inductance matrix of the layout, in nH.
+    /// Self-inductances are on the diagonal, mutual inductances off it.
+    pub fn inductance_matrix(&self) -> Vec<Vec<f32>> {
+        let mut matrix = vec![vec![0.0; self.coils.len()]; self.coils.len()];
+        for (coil_id, coil) in self.coils.iter().enumerate() {
+            matrix[coil_id][coil_id] = coil.self_inductance(1.0);
+            for (other_id, other_coil) in self.coils.iter().enumerate().skip(coil_id + 1) {
+                let m = coil.mutual_inductance(other_coil, 1.0);
+                matrix[coil_id][other_id] = m;
+                matrix[other_id][coil_id] = m;
+            }
+        }
+        matrix
+    }
+
+    /// Calculate the condition number of the coupling (L + M) reactance matrix at the Larmor frequency, as a decoupling quality metric.
+    /// Lower is better: 1.0 means fully decoupled coils, and a tightly-coupled array is close to singular.
+    /// The reactance scales every entry equally with frequency, so the condition number is the same as the inductance matrix's.
+    /// Returns 1.0 for an empty layout.
+    pub fn coupling_condition_number(&self, larmor_mhz: f32) -> f32 {
+        if self.coils.is_empty() {
+            return 1.0;
+        }
+
+        // Reactance matrix in ohms (nH * MHz * 1e-3)
+        let omega = 2.0 * PI as f64 * larmor_mhz as f64;
+        let reactance: Vec<Vec<f64>> = self.inductance_matrix().iter()
+            .map(|row| row.iter().map(|l| omega * *l as f64 * 1.0e-3).collect())
+            .collect();
+
+        let eigenvalues = symmetric_eigenvalues(reactance);
+        let max = eigenvalues.iter().fold(0.0, |max: f64, e| max.max(e.abs()));
+        let min = eigenvalues.iter().fold(f64::INFINITY, |min: f64, e| min.min(e.abs()));
+        if min == 0.0 {
+            f32::INFINITY
+        } else {
+            (max / min) as f32
+        }
+    }
+}
+
+/// Eigenvalues of a symmetric matrix, by cyclic Jacobi rotations.
+/// Fine for the small matrices of a coil array.
+fn symmetric_eigenvalues(matrix: Vec<Vec<f64>>) -> Vec<f64> {
+    let mut a = matrix;
+    let n = a.len();
+    for _ in 0..100 {
+        let off_diagonal: f64 = (0..n).flat_map(|i| (0..n).filter(move |j| *j != i).map(move |j| (i, j)))
+            .map(|(i, j)| a[i][j] * a[i][j])
+            .sum();
+        let diagonal: f64 = (0..n).map(|i| a[i][i] * a[i][i]).sum();
+        if off_diagonal <= 1e-24 * diagonal {
+            break;
+        }
+
+        for p in 0..n {
+            for q in (p + 1)..n {
+                if a[p][q] == 0.0 {
+                    continue;
+                }
+                // Rotation angle that zeroes a[p][q]
+                let theta = (a[q][q] - a[p][p]) / (2.0 * a[p][q]);
+                let t = theta.signum() / (theta.abs() + (theta * theta + 1.0).sqrt());
+                let c = 1.0 / (t * t + 1.0).sqrt();
+                let s = t * c;
+                for row in a.iter_mut() {
+                    let (akp, akq) = (row[p], row[q]);
+                    row[p] = c * akp - s * akq;
+                    row[q] = s * akp + c * akq;
+                }
+                let (row_p, row_q) = (a[p].clone(), a[q].clone());
+                a[p] = row_p.iter().zip(row_q.iter()).map(|(apk, aqk)| c * apk - s * aqk).collect();
+                a[q] = row_p.iter().zip(row_q.iter()).map(|(apk, aqk)| s * apk + c * aqk).collect();
+            }
+        }
+    }
+    (0..n).map(|i| a[i][i]).collect()
+}
+
+/// Winding sense of a coil, viewed from the tip of its normal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sense {
+    Clockwise,
+    CounterClockwise,
+}
+
+/// Length unit of coil coordinates.
+/// The inductance calculations work in millimeters, so coils in other units are converted first
+/// (see `Coil::self_inductance_in` and `load_layout_in`).
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[derive(Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum LengthUnit {
+    #[default]
+    Millimeters,
+    Centimeters,
+    Meters,
+}
+impl LengthUnit {
+    /// Get the length of one unit in millimeters.
+    pub fn in_millimeters(&self) -> f32 {
+        match self {
+            LengthUnit::Millimeters => 1.0,
+            LengthUnit::Centimeters => 10.0,
+            LengthUnit::Meters => 1000.0,
+        }
+    }
+}
+
+/// Units for inductance results.
+/// The inductance calculations return nH (with mu0 in nH/mm), so other units are converted from that.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InductanceUnits {
+    #[default]
+    Nanohenries,
+    Henries,
+}
+impl InductanceUnits {
+    /// Convert an inductance in nH to these units.
+    pub fn from_nanohenries(&self, inductance_nh: f32) -> f32 {
+        match self {
+            InductanceUnits::Nanohenries => inductance_nh,
+            InductanceUnits::Henries => inductance_nh * 1.0e-9,
+        }
+    }
+
+    /// Get the unit symbol, for output.
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            InductanceUnits::Nanohenries => "nH",
+            InductanceUnits::Henries => "H",
+        }
+    }
+}
+
+/// A bend in a coil's wire, for wire-bending fabrication.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Bend {
+    /// Arc length along the wire from vertex 0 to the bend, in mm.
+    pub arc_length: f32,
+    /// Total bend angle, in degrees.
+    pub angle: f32,
+    /// Bend direction, viewed from the tip of the coil normal.
+    pub direction: Sense,
+}
+
+/// Minimum turning angle at a vertex (degrees) for it to count as part of a bend in `Coil::bend_sequence`.
+pub const BEND_ANGLE_THRESHOLD: f32 = 10.0;
+
+/// Number of consecutive vertices per bounding sphere in `Coil::closest_approach`.
+const APPROACH_CHUNK: usize = 8;
+
+/// Default distance (mm) below which consecutive coil vertices are merged at the end of the layout (see `Coil::dedup_vertices`).
+pub const DEDUP_TOLERANCE: f32 = 1.0e-3;
+
+/// Fraction of the way each vertex moves toward its neighbors' midpoint per smoothing iteration (see `Coil::smooth`).
+pub const SMOOTH_STRENGTH: f32 = 0.5;
+
+/// Expected coupling signs between coil pairs, for catching winding mistakes.
+#[derive(Debug, Clone, PartialEq)]
+#[derive(Serialize, Deserialize)]
+pub enum CouplingSignCheck {
+    /// Close (adjacent) pairs should couple negatively, as for counter-wound neighbor decoupling.
+    #[serde(rename = "adjacent_negative")]
+    AdjacentNegative,
+    /// Expected signs for listed pairs, as (coil index, other coil index, sign) with a sign of 1 or -1.
+    #[serde(rename = "pairs")]
+    Pairs(Vec<(usize, usize, i8)>),
 }
 
 /// A coil.
@@ -51,6 +485,15 @@ pub struct Coil {
     pub vertices: Vec<CoilVertex>,
     pub port: Option<usize>,
     pub breaks: Vec<usize>,
+    /// Optional user label (e.g. a row or rung name), carried through to the outputs.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    /// Optional user group number, carried through to the outputs.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group: Option<u32>,
+    /// Vertex ranges (start, end, inclusive, wrapping around the coil) offset into mousehole overlaps.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub overlap_segments: Vec<(usize, usize)>,
 }
 impl Coil {
     /// Create a new coil.
@@ -85,7 +528,163 @@ impl Coil {
             });
         }
 
-        Ok(Coil{center, normal, wire_radius, vertices: coil_vertices, port: None, breaks: Vec::new()})
+        Ok(Coil{center, normal, wire_radius, vertices: coil_vertices, port: None, breaks: Vec::new(), label: None, group: None, overlap_segments: Vec::new()})
+    }
+
+    /// Smooth the coil path with Laplacian smoothing along the loop, to round off kinks (e.g. at mousehole entries and exits).
+    /// Each iteration moves every vertex `strength` (0 to 1) of the way toward its neighbors' midpoint, then back onto the surface.
+    /// Heights off the surface along the vertex normal are smoothed the same way and kept,
+    /// except inside the overlap segments, where they hold so the crossing clearance survives.
+    /// The port, breaks and their neighbors stay fixed, as do the vertex normals.
+    /// Like any Laplacian smoothing, many iterations shrink the loop slightly.
+    pub fn smooth(&mut self, surface: &Surface, iterations: usize, strength: f32) {
+        let count = self.vertices.len();
+        let strength = strength.clamp(0.0, 1.0);
+        if count < 3 || iterations == 0 || strength == 0.0 {
+            return;
+        }
+
+        let mut fixed = vec![false; count];
+        for anchor in self.port.iter().chain(self.breaks.iter()) {
+            for offset in [count - 1, 0, 1] {
+                fixed[(anchor + offset) % count] = true;
+            }
+        }
+
+        // Split each vertex into its foot on the surface and its height above it
+        let mut feet = Vec::<Point>::with_capacity(count);
+        let mut heights = Vec::<f32>::with_capacity(count);
+        for vertex in self.vertices.iter() {
+            let offset = &vertex.point - surface;
+            feet.push(vertex.point - offset);
+            heights.push(offset.dot(&vertex.surface_normal));
+        }
+
+        let in_overlap: Vec<bool> = (0..count).map(|vertex_id| self.in_overlap(vertex_id)).collect();
+
+        for _ in 0..iterations {
+            let (prev_feet, prev_heights) = (feet.clone(), heights.clone());
+            for vertex_id in (0..count).filter(|vertex_id| !fixed[*vertex_id]) {
+                let (prev_id, next_id) = ((vertex_id + count - 1) % count, (vertex_id + 1) % count);
+                let midpoint = prev_feet[prev_id] + (prev_feet[next_id] - prev_feet[prev_id]) * 0.5;
+                let moved = prev_feet[vertex_id] + (midpoint - prev_feet[vertex_id]) * strength;
+                feet[vertex_id] = moved - (&moved - surface);
+                if in_overlap[vertex_id] {
+                    continue;
+                }
+                let mid_height = 0.5 * (prev_heights[prev_id] + prev_heights[next_id]);
+                heights[vertex_id] = prev_heights[vertex_id] + (mid_height - prev_heights[vertex_id]) * strength;
+            }
+        }
+
+        for (vertex_id, vertex) in self.vertices.iter_mut().enumerate() {
+            if !fixed[vertex_id] {
+                vertex.point = feet[vertex_id] + vertex.surface_normal * heights[vertex_id];
+            }
+        }
+    }
+
+    /// Scale the coil about the origin.
+    /// Points and the center are multiplied by `factor`, and the wire radius too if `scale_wire_radius` is set.
+    /// Normals are unchanged, and break/port indices stay valid.
+    pub fn scale(&mut self, factor: f32, scale_wire_radius: bool) {
+        self.center = Point::from(GeoVector::from(self.center) * factor);
+        for vertex in self.vertices.iter_mut() {
+            vertex.point = Point::from(GeoVector::from(vertex.point) * factor);
+        }
+        if scale_wire_radius {
+            self.wire_radius *= factor;
+        }
+    }
+
+    /// Reverse the winding direction of the coil.
+    /// Port, break and overlap segment indices are remapped to the reversed vertex order, so they stay on the same points.
+    /// The normal is flipped too if `flip_normal` is set.
+    pub fn reverse(&mut self, flip_normal: bool) {
+        let count = self.vertices.len();
+        self.vertices.reverse();
+        self.port = self.port.map(|id| count - 1 - id);
+        self.breaks = self.breaks.iter().rev().map(|id| count - 1 - id).collect();
+        self.overlap_segments = self.overlap_segments.iter().map(|(start, end)| (count - 1 - end, count - 1 - start)).collect();
+        if flip_normal {
+            self.normal = -self.normal;
+        }
+    }
+
+    /// Remove consecutive vertices (including across the wrap) within `tol` of the last vertex kept.
+    /// Zero-length segments break the inductance calculation and meshing.
+    /// Port, break and overlap segment indices are remapped to the kept vertex at the same point.
+    /// Coils are never reduced below 3 vertices.
+    /// Returns the number of vertices removed.
+    pub fn dedup_vertices(&mut self, tol: f32) -> usize {
+        let count = self.vertices.len();
+        let mut kept: Vec<usize> = Vec::with_capacity(count);
+        let mut new_ids = vec![0; count];
+        for (vertex_id, vertex) in self.vertices.iter().enumerate() {
+            match kept.last() {
+                Some(last_id) if vertex.point.distance(&self.vertices[*last_id].point) <= tol => {},
+                _ => kept.push(vertex_id),
+            }
+            new_ids[vertex_id] = kept.len() - 1;
+        }
+        // Trailing vertices on top of the first one fold into it
+        while kept.len() > 1 && self.vertices[*kept.last().unwrap()].point.distance(&self.vertices[0].point) <= tol {
+            let last_id = kept.pop().unwrap();
+            for new_id in new_ids[last_id..].iter_mut() {
+                *new_id = 0;
+            }
+        }
+        if kept.len() == count || kept.len() < 3 {
+            return 0;
+        }
+
+        self.vertices = kept.iter().map(|vertex_id| self.vertices[*vertex_id].clone()).collect();
+        self.port = self.port.map(|id| new_ids[id]);
+        // Breaks on trailing vertices fold into 0, so re-sort before merging repeats
+        self.breaks = self.breaks.iter().map(|id| new_ids[*id]).collect();
+        self.breaks.sort_unstable();
+        self.breaks.dedup();
+        self.overlap_segments = self.overlap_segments.iter().map(|(start, end)| (new_ids[*start], new_ids[*end])).collect();
+        count - kept.len()
+    }
+
+    /// Get a copy of the coil with each vertex-to-vertex span split into `subdivisions` spans.
+    /// The new points follow a Catmull-Rom spline through the vertices, so a coarsely sampled curve gets rounder
+    /// rather than just gaining points along its straight edges. Normals are interpolated linearly.
+    /// Breaks, the port and overlap segments are remapped onto the same (original) vertices.
+    /// A `subdivisions` of 0 or 1 returns an unchanged copy.
+    /// Subdivision is for reporting and meshing only (the mesh tuning inductance and the sim fields):
+    /// the layout methods optimize the coupling on the coils as sampled by the surface intersection.
+    pub fn subdivided(&self, subdivisions: usize) -> Coil {
+        if subdivisions <= 1 || self.vertices.len() < 3 {
+            return self.clone();
+        }
+        let n = self.vertices.len();
+        let point_at = |id: usize| GeoVector::from(self.vertices[id % n].point);
+
+        let mut coil = self.clone();
+        coil.vertices = Vec::with_capacity(n * subdivisions);
+        for (vertex_id, vertex) in self.vertices.iter().enumerate() {
+            let next_vertex = &self.vertices[(vertex_id + 1) % n];
+            let (p0, p1, p2, p3) = (point_at(vertex_id + n - 1), point_at(vertex_id), point_at(vertex_id + 1), point_at(vertex_id + 2));
+            for step in 0..subdivisions {
+                let t = step as f32 / subdivisions as f32;
+                let point = (p1 * 2.0
+                    + (p2 - p0) * t
+                    + (p0 * 2.0 - p1 * 5.0 + p2 * 4.0 - p3) * (t * t)
+                    + (p1 * 3.0 - p0 - p2 * 3.0 + p3) * (t * t * t)) * 0.5;
+                let lerp = |a: GeoVector, b: GeoVector| (a * (1.0 - t) + b * t).normalize();
+                coil.vertices.push(CoilVertex{
+                    point: Point::from(point),
+                    surface_normal: lerp(vertex.surface_normal, next_vertex.surface_normal),
+                    wire_radius_normal: lerp(vertex.wire_radius_normal, next_vertex.wire_radius_normal),
+                });
+            }
+        }
+        coil.port = self.port.map(|port| port * subdivisions);
+        coil.breaks = self.breaks.iter().map(|break_id| break_id * subdivisions).collect();
+        coil.overlap_segments = self.overlap_segments.iter().map(|(start, end)| (start * subdivisions, end * subdivisions)).collect();
+        coil
     }
 
     /// Calculate the wire length of the coil, in mm
@@ -97,6 +696,122 @@ impl Coil {
         length
     }
 
+    /// Estimate the series resistance of the copper wire at the Larmor frequency, in ohms.
+    /// Uses the skin depth at that frequency, or the full wire cross section if the wire is thinner than the skin depth.
+    pub fn series_resistance(&self, larmor_mhz: f32) -> f32 {
+        let wire_radius_m = self.wire_radius * 1.0e-3;
+        let skin_depth_m = (2.0 / (2.0 * PI * larmor_mhz * 1.0e6 * MU0 * 1.0e-6 * COPPER_CONDUCTIVITY)).sqrt();
+        let area_m2 = if skin_depth_m < wire_radius_m {
+            2.0 * PI * wire_radius_m * skin_depth_m
+        } else {
+            PI * wire_radius_m * wire_radius_m
+        };
+        self.wire_length() * 1.0e-3 / (COPPER_CONDUCTIVITY * area_m2)
+    }
+
+    /// Calculate the volume of wire in the coil, in mm^3
+    pub fn wire_volume(&self) -> f32 {
+        PI * self.wire_radius * self.wire_radius * self.wire_length()
+    }
+
+    /// Calculate the minimum bend radius of the coil, in mm.
+    /// Uses the circumradius of each vertex and its neighbors (infinite for straight runs).
+    pub fn min_bend_radius(&self) -> f32 {
+        let count = self.vertices.len();
+        let mut min_radius = f32::INFINITY;
+        for id in 0..count {
+            let prev = self.vertices[(id + count - 1) % count].point;
+            let point = self.vertices[id].point;
+            let next = self.vertices[(id + 1) % count].point;
+
+            // R = abc / 4A, with the area from the cross product
+            let double_area = (point - prev).cross(&(next - prev)).norm();
+            if double_area <= 0.0 {
+                continue;
+            }
+            let radius = prev.distance(&point) * point.distance(&next) * next.distance(&prev) / (2.0 * double_area);
+            min_radius = min_radius.min(radius);
+        }
+        min_radius
+    }
+
+    /// Calculate the largest distance of any vertex from the surface, in mm.
+    /// Coils start on the surface, so this is how far the overlap offsets push the wire off the former (on either side).
+    pub fn max_liftoff(&self, surface: &Surface) -> f32 {
+        self.vertices.iter().fold(0.0, |max: f32, vertex| max.max((&vertex.point - surface).norm()))
+    }
+
+    /// Get the significant bends along the wire, starting from vertex 0.
+    /// Each vertex turning the wire by more than `min_angle` degrees is part of a bend,
+    /// and consecutive bending vertices turning the same way are merged into one bend,
+    /// placed at the angle-weighted mean arc length with the total angle.
+    /// Gentle, continuous curvature (e.g. a finely sampled circle) has no significant bends.
+    pub fn bend_sequence(&self, min_angle: f32) -> Vec<Bend> {
+        let count = self.vertices.len();
+        if count < 3 {
+            return Vec::new();
+        }
+
+        // Arc length to each vertex and the signed turning angle there (positive counterclockwise about the normal)
+        let mut arc_lengths = vec![0.0; count];
+        for id in 1..count {
+            arc_lengths[id] = arc_lengths[id - 1] + self.vertices[id].point.distance(&self.vertices[id - 1].point);
+        }
+        let turns: Vec<f32> = (0..count).map(|id| {
+            let prev = self.vertices[(id + count - 1) % count].point;
+            let point = self.vertices[id].point;
+            let next = self.vertices[(id + 1) % count].point;
+            let (incoming, outgoing) = (point - prev, next - point);
+            let angle = incoming.angle_to(&outgoing).to_degrees();
+            if incoming.cross(&outgoing).dot(&self.normal) < 0.0 {-angle} else {angle}
+        }).collect();
+        let bending = |id: usize| turns[id].abs() > min_angle;
+
+        // Start from a straight vertex, so a bend isn't split by the wraparound
+        let start = match (0..count).find(|id| !bending(*id)) {
+            Some(start) => start,
+            None => return Vec::new(),
+        };
+
+        // Vertices before the start are reached after wrapping around, so their arc lengths carry on past the perimeter
+        let perimeter = arc_lengths[count - 1] + self.vertices[count - 1].point.distance(&self.vertices[0].point);
+        let unwrapped_arc_length = |id: usize| if id < start {arc_lengths[id] + perimeter} else {arc_lengths[id]};
+
+        let mut bends = Vec::new();
+        let mut run: Vec<usize> = Vec::new();
+        let mut close_run = |run: &mut Vec<usize>| {
+            if run.is_empty() {
+                return;
+            }
+            let angle: f32 = run.iter().map(|id| turns[*id]).sum();
+            let weight: f32 = run.iter().map(|id| turns[*id].abs()).sum();
+            let arc_length = run.iter().map(|id| unwrapped_arc_length(*id) * turns[*id].abs()).sum::<f32>() / weight;
+            bends.push(Bend{
+                arc_length: arc_length % perimeter,
+                angle: angle.abs(),
+                direction: if angle < 0.0 {Sense::Clockwise} else {Sense::CounterClockwise},
+            });
+            run.clear();
+        };
+        for offset in 0..count {
+            let id = (start + offset) % count;
+            let same_way = match run.last() {
+                Some(last) => turns[*last].signum() == turns[id].signum(),
+                None => true,
+            };
+            if !bending(id) || !same_way {
+                close_run(&mut run);
+            }
+            if bending(id) {
+                run.push(id);
+            }
+        }
+        close_run(&mut run);
+
+        bends.sort_by(|a, b| a.arc_length.partial_cmp(&b.arc_length).unwrap());
+        bends
+    }
+
     /// Calculate the average radius of the coil, in mm
     pub fn average_radius(&self) -> f32 {
         let mut radius = 0.0;
@@ -106,7 +821,144 @@ impl Coil {
         radius / (self.vertices.len() as f32)
     }
 
+    /// Get the winding sense of the coil about its normal, from the signed area of the loop.
+    pub fn winding_sense(&self) -> Sense {
+        let mut signed_area = 0.0;
+        for (id, vertex) in self.vertices.iter().enumerate() {
+            let a = vertex.point - self.center;
+            let b = self.vertices[(id + 1) % self.vertices.len()].point - self.center;
+            signed_area += a.cross(&b).dot(&self.normal);
+        }
+        if signed_area < 0.0 { Sense::Clockwise } else { Sense::CounterClockwise }
+    }
+
+    /// Check if the coil winds clockwise about its normal.
+    pub fn is_clockwise(&self) -> bool {
+        self.winding_sense() == Sense::Clockwise
+    }
+
+    /// Check if a vertex lies inside one of the coil's mousehole overlap segments.
+    pub fn in_overlap(&self, vertex_id: usize) -> bool {
+        self.overlap_segments.iter().any(|(start, end)| {
+            if start <= end {
+                vertex_id >= *start && vertex_id <= *end
+            } else {
+                vertex_id >= *start || vertex_id <= *end
+            }
+        })
+    }
+
+    /// Check if a point is inside the coil loop, viewed along the coil normal.
+    /// Uses the winding number of the loop around the point.
+    pub fn encloses_point(&self, point: &Point) -> bool {
+        let normal = self.normal.normalize();
+        let mut winding = 0.0;
+        for (id, vertex) in self.vertices.iter().enumerate() {
+            let a = (vertex.point - *point).rej_onto(&normal);
+            let b = (self.vertices[(id + 1) % self.vertices.len()].point - *point).rej_onto(&normal);
+            winding += a.cross(&b).dot(&normal).atan2(a.dot(&b));
+        }
+        winding.abs() > PI
+    }
+
+    /// Check if another coil is nested entirely inside this one (every vertex enclosed), viewed along this coil's normal.
+    pub fn contains(&self, other: &Coil) -> bool {
+        !other.vertices.is_empty() && other.vertices.iter().all(|vertex| self.encloses_point(&vertex.point))
+    }
+
+    /// Re-place the breaks at equal arc-length intervals around the coil, starting from the port.
+    /// Use after the coil length changes (e.g. overlap offsets), when breaks placed by angle are no longer evenly spaced along the wire.
+    /// `count` includes the port, as in `add_even_breaks_by_angle`. The port is kept (vertex 0 if unset),
+    /// and each break takes the unused vertex nearest its target arc length.
+    /// A `count` of 0 leaves an uninterrupted loop, with no port or breaks.
+    /// Returns the vertex indices used (port first), or an error if the coil has fewer vertices than breaks.
+    pub fn redistribute_breaks_by_length(&mut self, count: usize) -> ProcResult<Vec<usize>> {
+        if count == 0 {
+            self.port = None;
+            self.breaks.clear();
+            return Ok(Vec::new());
+        }
+        let vertex_count = self.vertices.len();
+        if vertex_count < count {
+            return err_str(&format!("Not enough points ({}) for that many breaks ({}) -- breaks must be on distinct points", vertex_count, count));
+        }
+        let port = self.port.unwrap_or(0);
+
+        // Arc length from the port to each vertex, going around in vertex order
+        let mut arc_lengths = vec![0.0; vertex_count];
+        for step in 1..vertex_count {
+            let (prev_id, vertex_id) = ((port + step - 1) % vertex_count, (port + step) % vertex_count);
+            arc_lengths[vertex_id] = arc_lengths[prev_id] + (self.vertices[vertex_id].point - self.vertices[prev_id].point).norm();
+        }
+        let total_length = self.wire_length();
+
+        let mut used = vec![false; vertex_count];
+        used[port] = true;
+        let mut placed = vec![port];
+        for break_id in 1..count {
+            let target = total_length * break_id as f32 / count as f32;
+            let vertex_id = (0..vertex_count)
+                .filter(|vertex_id| !used[*vertex_id])
+                .min_by(|a, b| (arc_lengths[*a] - target).abs().partial_cmp(&(arc_lengths[*b] - target).abs()).unwrap())
+                .unwrap();
+            used[vertex_id] = true;
+            placed.push(vertex_id);
+        }
+
+        self.port = Some(port);
+        self.breaks = placed[1..].to_vec();
+        Ok(placed)
+    }
+
+    /// Get bounding spheres over runs of consecutive vertices, for pruning distance queries.
+    /// Returns (first vertex index, sphere center, sphere radius) for each run.
+    fn bounding_chunks(&self) -> Vec<(usize, Point, f32)> {
+        self.vertices.chunks(APPROACH_CHUNK).enumerate().map(|(chunk_id, chunk)| {
+            let sum = chunk.iter().fold(GeoVector::zero(), |sum, vertex| sum + GeoVector::from(vertex.point));
+            let center = Point::from(sum / chunk.len() as f32);
+            let radius = chunk.iter().fold(0.0, |max: f32, vertex| max.max((vertex.point - center).norm()));
+            (chunk_id * APPROACH_CHUNK, center, radius)
+        }).collect()
+    }
+
+    /// Find the closest approach between the wire centerlines of two coils.
+    /// Vertex runs are bounded by spheres, and run pairs that can't beat the best distance so far are skipped.
+    /// Returns (distance, vertex index on this coil, vertex index on the other coil).
+    /// Returns an infinite distance if either coil has no vertices.
+    pub fn closest_approach(&self, other: &Coil) -> (f32, usize, usize) {
+        let mut best = (f32::INFINITY, 0, 0);
+
+        // Check the run pairs nearest first, so the pruning bound tightens quickly
+        let other_chunks = other.bounding_chunks();
+        let mut chunk_pairs = Vec::new();
+        for (start, center, radius) in self.bounding_chunks() {
+            for (other_start, other_center, other_radius) in other_chunks.iter() {
+                let lower_bound = (center - *other_center).norm() - radius - other_radius;
+                chunk_pairs.push((lower_bound, start, *other_start));
+            }
+        }
+        chunk_pairs.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+
+        for (lower_bound, start, other_start) in chunk_pairs {
+            if lower_bound >= best.0 {
+                break;
+            }
+            let end = (start + APPROACH_CHUNK).min(self.vertices.len());
+            let other_end = (other_start + APPROACH_CHUNK).min(other.vertices.len());
+            for vertex_id in start..end {
+                for other_vertex_id in other_start..other_end {
+                    let distance = (self.vertices[vertex_id].point - other.vertices[other_vertex_id].point).norm();
+                    if distance < best.0 {
+                        best = (distance, vertex_id, other_vertex_id);
+                    }
+                }
+            }
+        }
+        best
+    }
+
     /// Calculate the self-inductance of the coil, in nH.
+    /// Assumes the coordinates (and `dl`) are in mm -- see `self_inductance_in` for other units.
     pub fn self_inductance(&self, dl:f32) -> f32 {
         // TODO: This may depend on frequency, so it may need to be updated.
         const CORRECTION_SCALE : f32 = 0.0012;
@@ -222,11 +1074,43 @@ impl Coil {
     }
 
     /// Wrapper to calculate the mutual inductance between two coils, in nH.
+    /// Assumes the coordinates (and `dl`) are in mm -- see `mutual_inductance_in` for other units.
     pub fn mutual_inductance(&self, other: &Coil, dl: f32) -> f32 {
         let (m, _, _, _, _) = self.mutual_inductance_info(other, dl, true, false, false);
         m.unwrap()
     }
 
+    /// Calculate the self-inductance of the coil in the given units, for coordinates (and `dl`) in `length_unit`.
+    /// The coil is converted to mm first, since the calculation's near-field threshold is in mm.
+    pub fn self_inductance_in(&self, dl: f32, length_unit: LengthUnit, units: InductanceUnits) -> f32 {
+        let mm_per_unit = length_unit.in_millimeters();
+        let mut coil_mm = self.clone();
+        coil_mm.scale(mm_per_unit, true);
+        units.from_nanohenries(coil_mm.self_inductance(dl * mm_per_unit))
+    }
+
+    /// Calculate the mutual inductance between two coils in the given units, for coordinates (and `dl`) in `length_unit`.
+    pub fn mutual_inductance_in(&self, other: &Coil, dl: f32, length_unit: LengthUnit, units: InductanceUnits) -> f32 {
+        let mm_per_unit = length_unit.in_millimeters();
+        let mut coil_mm = self.clone();
+        let mut other_mm = other.clone();
+        coil_mm.scale(mm_per_unit, true);
+        other_mm.scale(mm_per_unit, true);
+        units.from_nanohenries(coil_mm.mutual_inductance(&other_mm, dl * mm_per_unit))
+    }
+
+    /// Calculate the self-inductance of the coil, in nH, after splitting each span into `segment_subdivisions` (see `subdivided`).
+    /// A `segment_subdivisions` of 1 gives the same value as `self_inductance`.
+    pub fn self_inductance_subdivided(&self, dl: f32, segment_subdivisions: usize) -> f32 {
+        self.subdivided(segment_subdivisions).self_inductance(dl)
+    }
+
+    /// Calculate the mutual inductance between two coils, in nH, after splitting each span of both into `segment_subdivisions`.
+    /// A `segment_subdivisions` of 1 gives the same value as `mutual_inductance`.
+    pub fn mutual_inductance_subdivided(&self, other: &Coil, dl: f32, segment_subdivisions: usize) -> f32 {
+        self.subdivided(segment_subdivisions).mutual_inductance(&other.subdivided(segment_subdivisions), dl)
+    }
+
     /// Wrapper to calculate the mutual inductance between two coils, as well as the gradient wrt only the radius
     pub fn mutual_inductance_dradius(&self, other: &Coil, dl: f32) -> (f32, f32) {
         let (m, _, _, _, dr) = self.mutual_inductance_info(other, dl, true, false, true);
@@ -239,10 +1123,93 @@ impl Coil {
         (m.unwrap(), dx.unwrap(), dy.unwrap(), dz.unwrap(), dr.unwrap())
     }
 
+    /// Get the vector area of the coil, in mm^2: half the sum of r x dr around the wire, from its centroid.
+    /// It points along the winding by the right-hand rule, and its norm is the area of a planar loop.
+    pub fn vector_area(&self) -> GeoVector {
+        let centroid = self.wire_centroid();
+        let vertex_count = self.vertices.len();
+        (0..vertex_count).fold(GeoVector::zero(), |sum, vertex_id| {
+            let a = GeoVector::from(self.vertices[vertex_id].point) - centroid;
+            let b = GeoVector::from(self.vertices[(vertex_id + 1) % vertex_count].point) - centroid;
+            sum + a.cross(&b)
+        }) * 0.5
+    }
+
+    /// Calculate the mutual inductance between two coils with the magnetic dipole approximation, in nH.
+    /// Each coil is a dipole at its center, with a moment of its vector area per amp (see `vector_area`).
+    /// Only accurate for coils far apart compared to their size -- see `mutual_inductance_with_cutoff`.
+    pub fn dipole_mutual_inductance(&self, other: &Coil) -> f32 {
+        let offset = other.center - self.center;
+        let distance = offset.norm();
+        if distance == 0.0 {
+            return f32::INFINITY;
+        }
+        let direction = offset / distance;
+        let (area, other_area) = (self.vector_area(), other.vector_area());
+        MU0 / (4.0 * PI) * (3.0 * area.dot(&direction) * other_area.dot(&direction) - area.dot(&other_area)) / distance.powi(3)
+    }
+
+    /// Whether two coils are far enough apart for the dipole approximation:
+    /// the distance between their centers is at least `dipole_cutoff` times the sum of their average radii,
+    /// measured like the closeness of a pair (see `layout::statistics`).
+    pub fn beyond_dipole_cutoff(&self, other: &Coil, dipole_cutoff: f32) -> bool {
+        let distance = (other.center - self.center).norm();
+        distance >= dipole_cutoff * (self.average_radius() + other.average_radius())
+    }
+
+    /// Calculate the mutual inductance between two coils, in nH, with the dipole approximation beyond the `dipole_cutoff`
+    /// (see `beyond_dipole_cutoff`) and the full Neumann integral otherwise (or always, without a cutoff).
+    pub fn mutual_inductance_with_cutoff(&self, other: &Coil, dl: f32, dipole_cutoff: Option<f32>) -> f32 {
+        match dipole_cutoff {
+            Some(dipole_cutoff) if self.beyond_dipole_cutoff(other, dipole_cutoff) => self.dipole_mutual_inductance(other),
+            _ => self.mutual_inductance(other, dl),
+        }
+    }
+
+    /// Get the centroid of the wire vertices (as a vector from the origin).
+    fn wire_centroid(&self) -> GeoVector {
+        self.vertices.iter().fold(GeoVector::zero(), |sum, vertex| sum + GeoVector::from(vertex.point)) / self.vertices.len() as f32
+    }
+
     /// Calculate the coupling factor between two coils.
     pub fn coupling_factor(&self, other: &Coil, dl: f32) -> f32 {
         self.mutual_inductance(other, dl) / (self.self_inductance(dl) * other.self_inductance(dl)).sqrt()
     }
+
+    /// Get the image of the coil in a conductive shield plane.
+    /// Vertices are reflected across the plane and the winding is reversed, so the image carries the opposite current.
+    pub fn image_across(&self, shield_plane: &Plane) -> Coil {
+        let mut image = self.clone();
+        image.center = self.center.reflect_across(shield_plane);
+        image.normal = self.normal.reflect_across(&shield_plane.get_normal());
+        for vertex in image.vertices.iter_mut() {
+            vertex.point = vertex.point.reflect_across(shield_plane);
+            vertex.surface_normal = vertex.surface_normal.reflect_across(&shield_plane.get_normal());
+            vertex.wire_radius_normal = vertex.wire_radius_normal.reflect_across(&shield_plane.get_normal());
+        }
+        image.reverse(false);
+        image.port = None;
+        image.breaks.clear();
+        image
+    }
+
+    /// Calculate the self-inductance of the coil over a conductive shield plane, in nH.
+    /// Includes the (negative) mutual inductance with the coil's own image.
+    pub fn shielded_self_inductance(&self, shield_plane: &Plane, dl: f32) -> f32 {
+        self.self_inductance(dl) + self.mutual_inductance(&self.image_across(shield_plane), dl)
+    }
+
+    /// Calculate the mutual inductance between two coils over a conductive shield plane, in nH.
+    /// Includes the mutual inductance with the other coil's image.
+    pub fn shielded_mutual_inductance(&self, other: &Coil, shield_plane: &Plane, dl: f32) -> f32 {
+        self.mutual_inductance(other, dl) + self.mutual_inductance(&other.image_across(shield_plane), dl)
+    }
+
+    /// Calculate the coupling factor between two coils over a conductive shield plane.
+    pub fn shielded_coupling_factor(&self, other: &Coil, shield_plane: &Plane, dl: f32) -> f32 {
+        self.shielded_mutual_inductance(other, shield_plane, dl)
+            / (self.shielded_self_inductance(shield_plane, dl) * other.shielded_self_inductance(shield_plane, dl)).sqrt()
+    }
 }
 
 /// A point on a coil (includes adjacency and surface vectors).
@@ -254,29 +1221,1023 @@ pub struct CoilVertex {
     pub wire_radius_normal: GeoVector,
 }
 
-/// Run the layout process.
+/// Run the layout process, loading the surface from the target's input path.
 /// Returns a `ProcResult` with the `Layout` or an `Err`.
 pub fn do_layout(layout_target: &LayoutTarget) -> ProcResult<Layout> {
+    let surface = layout_target.method.load_surface(&layout_target.input_path)?;
+    do_layout_on(layout_target, &surface)
+}
+
+/// Run the layout process on an already loaded surface (e.g. one shared with the later stages).
+/// With a verbose method, the time spent in each phase is printed at the end (see `timing::report`).
+/// Returns a `ProcResult` with the `Layout` or an `Err`.
+pub fn do_layout_on(layout_target: &LayoutTarget, surface: &Surface) -> ProcResult<Layout> {
+    let verbose = layout_target.method.is_verbose();
+    timing::start(verbose);
+    let layout = lay_out_and_clean(layout_target, surface);
+    timing::stop();
+    if verbose && layout.is_ok() {
+        println!();
+        print!("{}", timing::report());
+    }
+    layout
+}
+
+/// Run the layout method, then the cleanup passes on its coils.
+fn lay_out_and_clean(layout_target: &LayoutTarget, surface: &Surface) -> ProcResult<Layout> {
     // Extract the layout method
     let layout_method = &layout_target.method;
-
-    // Load the input
-    let surface = layout_method.load_surface(&layout_target.input_path)?;
+    if let Some(max_liftoff) = layout_target.max_liftoff {
+        if max_liftoff.is_nan() || max_liftoff < 0.0 {
+            return err_str(&format!("Lift-off warning threshold must be non-negative, got {}", max_liftoff));
+        }
+    }
 
     // Run the layout method
     println!("Running layout method: {}...", layout_method.get_method_display_name());
     println!();
-    layout_method.do_layout(&surface)
+    progress::emit(ProgressEvent::StageStarted{stage: "layout"});
+    let mut layout = {
+        let _timer = timing::phase("Layout method");
+        layout_method.do_layout(surface)?
+    };
+    let _timer = timing::phase("Cleanup");
+
+    // Keep the winding consistent across the array, if asked (reflections and merges can flip it)
+    if layout_target.normalize_winding {
+        let reversed = layout.normalize_winding();
+        if !reversed.is_empty() {
+            println!("Reversed the winding of coils {:?} to match coil 0", reversed);
+        }
+    }
+
+    // Round off kinks left by overlap offsets
+    if layout_target.smooth_iterations > 0 {
+        for coil in layout.coils.iter_mut() {
+            coil.smooth(surface, layout_target.smooth_iterations, SMOOTH_STRENGTH);
+        }
+    }
+
+    // Drop zero-length segments left by cleaning and overlap offsets
+    if layout_target.dedup_vertices {
+        for (coil_id, coil) in layout.coils.iter_mut().enumerate() {
+            let removed = coil.dedup_vertices(layout_target.dedup_tolerance);
+            if removed > 0 {
+                println!("Removed {} duplicate vertices from coil {}", removed, coil_id);
+            }
+        }
+    }
+
+    // Flag coils bulging off the surface, after all the processing
+    if let Some(max_liftoff) = layout_target.max_liftoff {
+        for (coil_id, liftoff) in layout.coils_above_liftoff(surface, max_liftoff) {
+            crate::warnings::warn_grouped("Coil lifts off the surface",
+                &format!("Coil {} lifts off the surface by {:.2}mm (limit {:.2}mm)", coil_id, liftoff, max_liftoff)
+            );
+        }
+    }
+    progress::emit(ProgressEvent::Finished{stage: "layout"});
+    Ok(layout)
+}
+
+/// Sweep two circular coils apart on a surface and record their coupling factor at each separation (k vs. d curve).
+/// `circle_b` sets the direction of the sweep from `circle_a`, and `distances` are center-to-center separations in mm.
+/// Each separation is a single ADAM circles layout pass (including overlaps) with default parameters.
+/// Returns `(d_rel, k)` pairs, where `d_rel` is the separation relative to the sum of the radii.
+pub fn coupling_vs_distance(
+    surface: &Surface,
+    circle_a: CircleArgs,
+    circle_b: CircleArgs,
+    distances: &[f32],
+) -> ProcResult<Vec<(f32, f32)>> {
+    let method = methods::adam_circles::Method{
+        symmetry_plane: None,
+        layout_in_path: None,
+        circles: vec![circle_a, circle_b],
+        iterations: 0,
+        final_cfg_output: None,
+        ..Default::default()
+    };
+    method.coupling_vs_distance(surface, circle_a, circle_b, distances)
 }
 
 pub fn save_layout(layout: &Layout, output_path: &str) -> ProcResult<()> {
+    save_layout_with_precision(layout, output_path, None)
+}
+
+/// Save a layout with its floats rounded to `precision` decimal places (full precision if `None`).
+/// Returns a `ProcResult` with `()`, or an `Err` if the layout has non-finite values or can't be written.
+pub fn save_layout_with_precision(layout: &Layout, output_path: &str, precision: Option<usize>) -> ProcResult<()> {
     assert!(output_path.ends_with(".json"), "Output path must end with .json -- cfg file loader should check this!");
-    crate::io::save_ser_to(output_path, layout)?;
+    // Refuse to write a layout that couldn't be reloaded or meshed
+    if let Some(location) = layout.find_non_finite() {
+        return err_str(&format!("Not saving layout to {}: {}", output_path, location));
+    }
+    match precision {
+        Some(decimals) => crate::io::save_ser_to(output_path, &crate::io::Rounded{value: layout, decimals})?,
+        None => crate::io::save_ser_to(output_path, layout)?,
+    }
     Ok(())
 }
 
+/// Export a coil's bend sequence (see `Coil::bend_sequence`) as a CSV of `arc_length, bend_angle, direction` for a wire bender.
+/// Arc lengths are in mm from vertex 0, angles in degrees, and directions are `cw` or `ccw` viewed from the tip of the coil normal.
+/// Returns a `ProcResult` with the bends or an `Err`.
+pub fn export_bend_sequence(coil: &Coil, output_path: &str) -> ProcResult<Vec<Bend>> {
+    let bends = coil.bend_sequence(BEND_ANGLE_THRESHOLD);
+    let mut buffer = String::from("arc_length, bend_angle, direction\n");
+    for bend in bends.iter() {
+        let direction = match bend.direction {
+            Sense::Clockwise => "cw",
+            Sense::CounterClockwise => "ccw",
+        };
+        buffer.push_str(&format!("{:.3}, {:.2}, {}\n", bend.arc_length, bend.angle, direction));
+    }
+    crate::io::write_to_file(output_path, &buffer)?;
+    Ok(bends)
+}
+
+/// Port impedance (ohms) terminating each coil in the SPICE netlist (see `export_spice_netlist`).
+const SPICE_PORT_OHM: f32 = 50.0;
+
+/// Export the layout as a SPICE netlist, with a `.subckt` per coil and `K` statements between close coils.
+/// Each subcircuit holds the coil's series resistance (`Coil::series_resistance`), a tuning capacitor per break
+/// (the last one across the port), and a placeholder series matching capacitor to the `rf` pin.
+/// The tuning capacitors are the ones of the capacitor summary (see `coil_capacitors`), resonating the self-inductance at `larmor_mhz`;
+/// the matching capacitor is only a starting value, with the reactance of the port impedance.
+/// The coil inductors sit at the top level, since SPICE can't portably couple inductors inside different subcircuits,
+/// every coil's port returns to node 0, and each `rf` pin is terminated in the port impedance so no node floats.
+/// Coils are close if their centers are nearer than `close_cutoff` times the sum of their average radii.
+/// Returns a `ProcResult` with the netlist text or an `Err`.
+pub fn export_spice_netlist(layout: &Layout, output_path: &str, larmor_mhz: f32, close_cutoff: f32) -> ProcResult<String> {
+    if larmor_mhz <= 0.0 {
+        return err_str(&format!("Larmor frequency must be positive, got {}", larmor_mhz));
+    }
+    // Tuning capacitors from the capacitor summary, starting at the port
+    let capacitor_args = CapacitorSummaryArgs{larmor_mhz, ..Default::default()};
+    let capacitors: Vec<CoilCapacitors> = layout.coils.iter().map(|coil| coil_capacitors(coil, &capacitor_args)).collect();
+    let inductances: Vec<f32> = capacitors.iter().map(|coil_capacitors| coil_capacitors.self_inductance_nh).collect();
+    let match_pf = 1.0e12 / (2.0 * PI * larmor_mhz * 1.0e6 * SPICE_PORT_OHM);
+
+    let mut buffer = format!("* COMRADE array netlist: {} coils tuned to {} MHz\n", layout.coils.len(), larmor_mhz);
+    buffer.push_str("* Matching capacitors are placeholders -- replace them with the matching results\n");
+
+    for (coil_id, coil) in layout.coils.iter().enumerate() {
+        // Without a port or breaks, one capacitor resonates the whole loop
+        let mut tuning_pf: Vec<f32> = capacitors[coil_id].capacitors.iter().map(|capacitor| capacitor.capacitance_pf).collect();
+        if tuning_pf.is_empty() {
+            tuning_pf.push(resonant_capacitance_pf(inductances[coil_id], larmor_mhz));
+        }
+        let capacitor_count = tuning_pf.len();
+        buffer.push_str(&format!("\n* Coil {}{}: L = {:.4} nH, {} tuning capacitors\n",
+            coil_id, coil.label.as_ref().map_or(String::new(), |label| format!(" ({})", crate::io::escape_comment(label))), inductances[coil_id], capacitor_count
+        ));
+        buffer.push_str(&format!(".subckt coil_{} rf gnd l_out\n", coil_id));
+        buffer.push_str(&format!("R1 l_out n1 {:.6}\n", coil.series_resistance(larmor_mhz)));
+        for (capacitor_id, capacitance_pf) in tuning_pf.iter().enumerate().skip(1) {
+            buffer.push_str(&format!("C{} n{} n{} {:.4}p\n", capacitor_id, capacitor_id, capacitor_id + 1, capacitance_pf));
+        }
+        buffer.push_str(&format!("Ctune n{} gnd {:.4}p\n", capacitor_count, tuning_pf[0]));
+        buffer.push_str(&format!("Cmatch n{} rf {:.4}p\n", capacitor_count, match_pf));
+        buffer.push_str(&format!(".ends coil_{}\n", coil_id));
+    }
+
+    buffer.push_str("\n* Coil instances, inductors and port terminations\n");
+    for (coil_id, inductance) in inductances.iter().enumerate() {
+        buffer.push_str(&format!("X{} rf_{} 0 l_out_{} coil_{}\n", coil_id, coil_id, coil_id, coil_id));
+        buffer.push_str(&format!("L{} 0 l_out_{} {:.4}n\n", coil_id, coil_id, inductance));
+        buffer.push_str(&format!("Rport{} rf_{} 0 {:.1}\n", coil_id, coil_id, SPICE_PORT_OHM));
+    }
+
+    buffer.push_str("\n* Coupling between close coils\n");
+    for (coil_id, coil) in layout.coils.iter().enumerate() {
+        for (other_id, other_coil) in layout.coils.iter().enumerate().skip(coil_id + 1) {
+            let distance_scale = coil.average_radius() + other_coil.average_radius();
+            if (coil.center - other_coil.center).norm() / distance_scale < close_cutoff {
+                let k = coil.mutual_inductance(other_coil, 1.0) / (inductances[coil_id] * inductances[other_id]).sqrt();
+                buffer.push_str(&format!("K{}_{} L{} L{} {:.6}\n", coil_id, other_id, coil_id, other_id, k));
+            }
+        }
+    }
+    buffer.push_str("\n.end\n");
+
+    crate::io::write_to_file(output_path, &buffer)?;
+    Ok(buffer)
+}
+
 pub fn load_layout(input_path: &str) -> ProcResult<Layout> {
     assert!(input_path.ends_with(".json"), "Input path must end with .json -- cfg file loader should check this!");
     let layout: Layout = crate::io::load_deser_from(input_path)?;
     Ok(layout)
 }
+
+/// Load a layout with coordinates in `length_unit`, converted to mm for the inductance calculations.
+pub fn load_layout_in(input_path: &str, length_unit: LengthUnit) -> ProcResult<Layout> {
+    let mut layout = load_layout(input_path)?;
+    if length_unit != LengthUnit::Millimeters {
+        for coil in layout.coils.iter_mut() {
+            coil.scale(length_unit.in_millimeters(), true);
+        }
+    }
+    Ok(layout)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::{circle_coil, flat_adam, flat_surface, layout_target, temp_dir};
+
+    #[test]
+    fn shield_plane_alters_coupling() {
+        let coil_a = circle_coil(Point::new(0.0, 0.0, 0.0), 10.0);
+        let coil_b = circle_coil(Point::new(22.0, 0.0, 0.0), 10.0);
+        let near_shield = Plane::from_normal_and_offset(GeoVector::zhat(), -5.0);
+        let far_shield = Plane::from_normal_and_offset(GeoVector::zhat(), -1000.0);
+
+        // The image is counter-wound, so it lowers the self-inductance
+        let image = coil_a.image_across(&near_shield);
+        assert!((image.center.z + 10.0).abs() < 1e-4);
+        assert!(coil_a.mutual_inductance(&image, 1.0) < 0.0);
+        assert!(coil_a.shielded_self_inductance(&near_shield, 1.0) < coil_a.self_inductance(1.0));
+
+        // A nearby shield changes the coupling, a distant one doesn't
+        let k = coil_a.coupling_factor(&coil_b, 1.0);
+        let k_near = coil_a.shielded_coupling_factor(&coil_b, &near_shield, 1.0);
+        let k_far = coil_a.shielded_coupling_factor(&coil_b, &far_shield, 1.0);
+        assert!((k_near - k).abs() > 0.1 * k.abs(), "Shielded coupling {k_near} vs. unshielded {k}");
+        assert!((k_far - k).abs() < 0.01 * k.abs());
+    }
+
+    #[test]
+    fn dipole_approximation_matches_far_pairs_only_beyond_cutoff() {
+        let coil = circle_coil(Point::zero(), 10.0);
+        assert!((coil.vector_area().z - PI * 100.0).abs() < 0.01 * PI * 100.0, "Vector area {}", coil.vector_area());
+
+        // Far apart, side by side (negative coupling) and on axis (positive), the dipoles agree with the full integral
+        for far_center in [Point::new(150.0, 0.0, 0.0), Point::new(0.0, 0.0, 150.0)] {
+            let far_coil = circle_coil(far_center, 10.0);
+            let exact = coil.mutual_inductance(&far_coil, 1.0);
+            let dipole = coil.dipole_mutual_inductance(&far_coil);
+            assert!((dipole - exact).abs() < 0.02 * exact.abs(), "Dipole {} nH, exact {} nH", dipole, exact);
+        }
+
+        // The cutoff picks the method by distance over the sum of the radii (here 20 mm)
+        let near_coil = circle_coil(Point::new(30.0, 0.0, 0.0), 10.0);
+        let far_coil = circle_coil(Point::new(150.0, 0.0, 0.0), 10.0);
+        assert!(!coil.beyond_dipole_cutoff(&near_coil, 5.0) && coil.beyond_dipole_cutoff(&far_coil, 5.0));
+        assert_eq!(coil.mutual_inductance_with_cutoff(&near_coil, 1.0, Some(5.0)), coil.mutual_inductance(&near_coil, 1.0));
+        assert_eq!(coil.mutual_inductance_with_cutoff(&far_coil, 1.0, Some(5.0)), coil.dipole_mutual_inductance(&far_coil));
+        assert_eq!(coil.mutual_inductance_with_cutoff(&far_coil, 1.0, None), coil.mutual_inductance(&far_coil, 1.0));
+        assert!((coil.dipole_mutual_inductance(&near_coil) - coil.mutual_inductance(&near_coil, 1.0)).abs() > 0.05 * coil.mutual_inductance(&near_coil, 1.0).abs());
+
+        // The statistics pass uses it for the far pairs, with the usual close cutoff
+        let layout_in = Layout{coils: vec![coil.clone(), near_coil.clone(), far_coil.clone()]};
+        let options = StatisticsOptions{dipole_cutoff: Some(5.0), all_pairs: true, ..Default::default()};
+        let stats = statistics(&layout_in, 0.95, &options);
+        assert!(stats.close_pairs.is_empty());
+        let far_m = |other_id: usize| stats.far_pairs.iter().find(|(coil_id, id, _, _)| (*coil_id, *id) == (0, other_id)).unwrap().2;
+        assert_eq!(far_m(1), coil.mutual_inductance(&near_coil, 1.0));
+        assert_eq!(far_m(2), coil.dipole_mutual_inductance(&far_coil));
+
+        // A cutoff of 0 would treat coincident coils as dipoles
+        assert!(options.check().is_ok());
+        for dipole_cutoff in [0.0, -1.0, f32::NAN] {
+            assert!(StatisticsOptions{dipole_cutoff: Some(dipole_cutoff), ..Default::default()}.check().is_err());
+        }
+    }
+
+    #[test]
+    fn verbose_layout_reports_phase_timing() {
+        let surface = Surface::square_grid(40.0, 40);
+        let layout_target = |verbose: bool| layout_target(MethodEnum::AdamCircles(methods::adam_circles::Method{
+            iterations: 2,
+            verbose,
+            ..flat_adam(vec![
+                CircleArgs{center: Point::new(-6.0, 0.0, 0.0), coil_radius: 10.0, ..CircleArgs::default()},
+                CircleArgs{center: Point::new(6.0, 0.0, 0.0), coil_radius: 10.0, ..CircleArgs::default()},
+            ])
+        }));
+
+        do_layout_on(&layout_target(true), &surface).unwrap();
+        let phases: Vec<String> = timing::phases().into_iter().map(|(phase, _, _)| phase).collect();
+        for phase in ["Layout method", "Laying out coils", "Overlap resolution", "Coupling gradients", "Statistics", "Cleanup"] {
+            assert!(phases.iter().any(|recorded| recorded == phase), "Missing phase {} in {:?}", phase, phases);
+            assert!(timing::report().contains(&format!("- {}: ", phase)));
+        }
+
+        // Quiet runs record nothing
+        do_layout_on(&layout_target(false), &surface).unwrap();
+        assert!(timing::phases().is_empty());
+        assert!(timing::report().is_empty());
+    }
+
+    #[test]
+    fn layout_warns_about_coils_above_the_liftoff_threshold() {
+        let surface = Surface::square_grid(40.0, 40);
+        let layout_target = |max_liftoff: Option<f32>| LayoutTarget{
+            max_liftoff,
+            ..layout_target(MethodEnum::AdamCircles(methods::adam_circles::Method{
+                iterations: 0,
+                ..flat_adam(vec![
+                    CircleArgs{center: Point::new(-6.0, 0.0, 0.0), coil_radius: 10.0, ..CircleArgs::default()},
+                    CircleArgs{center: Point::new(6.0, 0.0, 0.0), coil_radius: 10.0, ..CircleArgs::default()},
+                ])
+            }))
+        };
+        let liftoff_warnings = || crate::warnings::summary().into_iter()
+            .find(|(group, _)| group == "Coil lifts off the surface")
+            .map_or(0, |(_, count)| count);
+
+        // The overlap mousehole lifts the crossing coil off the surface
+        crate::warnings::clear();
+        let layout_out = do_layout_on(&layout_target(Some(0.1)), &surface).unwrap();
+        assert_eq!(layout_out.coils_above_liftoff(&surface, 0.1).len(), 1);
+        assert_eq!(liftoff_warnings(), 1);
+
+        // Nothing above a generous threshold, and no check without one
+        crate::warnings::clear();
+        do_layout_on(&layout_target(Some(100.0)), &surface).unwrap();
+        do_layout_on(&layout_target(None), &surface).unwrap();
+        assert_eq!(liftoff_warnings(), 0);
+
+        assert!(do_layout_on(&layout_target(Some(-1.0)), &surface).is_err());
+        crate::warnings::clear();
+    }
+
+    #[test]
+    fn sharp_mousehole_has_small_bend_radius() {
+        let circle_points = |notch: bool| -> Vec<Point> {
+            (0..64).map(|i| {
+                let theta = 2.0 * PI * i as f32 / 64.0;
+                let lift = if notch && i == 16 {1.0} else {0.0};
+                Point::new(10.0 * theta.cos(), 10.0 * theta.sin(), lift)
+            }).collect()
+        };
+        let normals = vec![GeoVector::zhat(); 64];
+        let smooth = Coil::new(Point::zero(), GeoVector::zhat(), circle_points(false), 0.645, normals.clone()).unwrap();
+        let notched = Coil::new(Point::zero(), GeoVector::zhat(), circle_points(true), 0.645, normals).unwrap();
+
+        assert!((smooth.min_bend_radius() - 10.0).abs() < 0.1);
+        assert!(notched.min_bend_radius() < 1.5, "Notched coil bend radius {}", notched.min_bend_radius());
+
+        let layout_out = Layout{coils: vec![smooth, notched]};
+        let flagged = layout_out.coils_below_bend_radius(2.0);
+        assert_eq!(flagged.len(), 1);
+        assert_eq!(flagged[0].0, 1);
+    }
+
+    #[test]
+    fn overlap_offset_shows_up_as_liftoff() {
+        let surface = Surface::square_grid(40.0, 20);
+        let flat = circle_coil(Point::zero(), 15.0);
+        assert!(flat.max_liftoff(&surface) < 1e-4);
+
+        // Dip a stretch of the wire under the surface, as an overlap offset does
+        let mut offset = flat.clone();
+        for vertex in offset.vertices[10..20].iter_mut() {
+            vertex.point -= vertex.surface_normal * 2.5;
+        }
+        offset.overlap_segments.push((10, 19));
+        assert!((offset.max_liftoff(&surface) - 2.5).abs() < 1e-3, "Lift-off {}", offset.max_liftoff(&surface));
+
+        let layout_out = Layout{coils: vec![flat, offset]};
+        assert_eq!(layout_out.coils_above_liftoff(&surface, 1.0).iter().map(|(coil_id, _)| *coil_id).collect::<Vec<_>>(), vec![1]);
+        let stats = statistics(&layout_out, 0.95, &StatisticsOptions{surface: Some(&surface), ..Default::default()});
+        assert!((stats.coils[1].max_liftoff.unwrap() - 2.5).abs() < 1e-3);
+        assert!(statistics(&layout_out, 0.95, &StatisticsOptions::default()).coils[1].max_liftoff.is_none());
+    }
+
+    #[test]
+    fn smoothing_rounds_off_a_kink_on_the_surface() {
+        let surface = Surface::square_grid(40.0, 20);
+        let points: Vec<Point> = (0..64).map(|i| {
+            let theta = 2.0 * PI * i as f32 / 64.0;
+            let radius = if (14..=18).contains(&i) {18.0} else {15.0};
+            Point::new(radius * theta.cos(), radius * theta.sin(), 0.0)
+        }).collect();
+        let mut coil = Coil::new(Point::zero(), GeoVector::zhat(), points, 0.645, vec![GeoVector::zhat(); 64]).unwrap();
+        coil.port = Some(0);
+        coil.breaks = vec![32];
+
+        // Sum of the changes in turning angle from vertex to vertex
+        let curvature_variation = |coil: &Coil| {
+            let count = coil.vertices.len();
+            let turns: Vec<f32> = (0..count).map(|id| {
+                let prev = coil.vertices[(id + count - 1) % count].point;
+                let next = coil.vertices[(id + 1) % count].point;
+                let angle = (coil.vertices[id].point - prev).angle_to(&(next - coil.vertices[id].point));
+                if (coil.vertices[id].point - prev).cross(&(next - coil.vertices[id].point)).z < 0.0 {-angle} else {angle}
+            }).collect();
+            (0..count).map(|id| (turns[(id + 1) % count] - turns[id]).abs()).sum::<f32>()
+        };
+
+        let mut smoothed = coil.clone();
+        smoothed.smooth(&surface, 10, SMOOTH_STRENGTH);
+        assert!(curvature_variation(&smoothed) < 0.5 * curvature_variation(&coil),
+            "Curvature variation {} after smoothing, {} before", curvature_variation(&smoothed), curvature_variation(&coil));
+        for vertex in smoothed.vertices.iter() {
+            assert!((&vertex.point - &surface).norm() < 1e-4, "Vertex {:?} left the surface", vertex.point);
+        }
+
+        // The port, breaks and their neighbors don't move
+        for vertex_id in [63, 0, 1, 31, 32, 33] {
+            assert!(smoothed.vertices[vertex_id].point.approx_eq(&coil.vertices[vertex_id].point, 0.0));
+        }
+    }
+
+    #[test]
+    fn smoothing_keeps_the_crossing_clearance() {
+        let surface = Surface::square_grid(40.0, 40);
+        let layout_target = |smooth_iterations: usize| LayoutTarget{
+            smooth_iterations,
+            ..layout_target(MethodEnum::AdamCircles(methods::adam_circles::Method{
+                iterations: 0,
+                ..flat_adam(vec![
+                    CircleArgs{center: Point::new(-6.0, 0.0, 0.0), coil_radius: 10.0, ..CircleArgs::default()},
+                    CircleArgs{center: Point::new(6.0, 0.0, 0.0), coil_radius: 10.0, ..CircleArgs::default()},
+                ])
+            }))
+        };
+
+        // Closest approach of the two wires, at the crossings
+        let clearance = |layout_out: &Layout| layout_out.coils[0].vertices.iter()
+            .flat_map(|vertex| layout_out.coils[1].vertices.iter().map(move |other| vertex.point.distance(&other.point)))
+            .fold(f32::INFINITY, f32::min);
+
+        let rough = do_layout_on(&layout_target(0), &surface).unwrap();
+        let smoothed = do_layout_on(&layout_target(20), &surface).unwrap();
+        assert!(clearance(&smoothed) >= 0.99 * clearance(&rough),
+            "Crossing clearance {} after smoothing, {} before", clearance(&smoothed), clearance(&rough));
+    }
+
+    #[test]
+    fn coupling_decreases_with_separation_on_flat_surface() {
+        let surface = flat_surface();
+        let circle_a = CircleArgs{
+            center: Point::new(-10.0, 0.0, 0.0),
+            coil_radius: 5.0,
+            ..CircleArgs::default()
+        };
+        let circle_b = CircleArgs{
+            center: Point::new(0.0, 0.0, 0.0),
+            ..circle_a
+        };
+
+        let distances = [11.0, 13.0, 16.0, 20.0, 25.0, 30.0];
+        let coupling_curve = coupling_vs_distance(&surface, circle_a, circle_b, &distances).unwrap();
+
+        assert_eq!(coupling_curve.len(), distances.len());
+        for pair in coupling_curve.windows(2) {
+            let ((d_rel, k), (next_d_rel, next_k)) = (pair[0], pair[1]);
+            assert!(next_d_rel > d_rel);
+            assert!(next_k.abs() < k.abs(), "|k| increased from {k} at d_rel {d_rel} to {next_k} at d_rel {next_d_rel}");
+        }
+    }
+
+    #[test]
+    fn mirrored_coil_is_reversed_to_match_source() {
+        let mut source = circle_coil(Point::new(20.0, 0.0, 0.0), 10.0);
+        source.port = Some(0);
+        source.breaks = vec![16, 32, 48];
+        assert_eq!(source.winding_sense(), Sense::CounterClockwise);
+
+        // Mirroring across x = 0 keeps the vertex order, so the winding flips
+        let mirror_plane = Plane::from_normal_and_offset(GeoVector::xhat(), 0.0);
+        let mut mirrored = source.clone();
+        mirrored.center = source.center.reflect_across(&mirror_plane);
+        for vertex in mirrored.vertices.iter_mut() {
+            vertex.point = vertex.point.reflect_across(&mirror_plane);
+        }
+        assert!(mirrored.is_clockwise());
+        let port_point = mirrored.vertices[0].point;
+        let break_points: Vec<Point> = mirrored.breaks.iter().map(|id| mirrored.vertices[*id].point).collect();
+
+        let mut layout_out = Layout{coils: vec![source, mirrored]};
+        assert_eq!(layout_out.normalize_winding(), vec![1]);
+
+        let mirrored = &layout_out.coils[1];
+        assert_eq!(mirrored.winding_sense(), layout_out.coils[0].winding_sense());
+        assert_eq!(mirrored.port, Some(63));
+        assert_eq!(mirrored.breaks, vec![15, 31, 47]);
+        assert_eq!(mirrored.vertices[mirrored.port.unwrap()].point.distance(&port_point), 0.0);
+        for (break_id, point) in mirrored.breaks.iter().rev().zip(break_points.iter()) {
+            assert_eq!(mirrored.vertices[*break_id].point.distance(point), 0.0);
+        }
+
+        // Already consistent, so nothing else changes
+        assert!(layout_out.normalize_winding().is_empty());
+    }
+
+    #[test]
+    fn reversing_twice_restores_the_coil() {
+        let points: Vec<Point> = (0..32).map(|i| {
+            let theta = 2.0 * PI * i as f32 / 32.0;
+            Point::new(10.0 * theta.cos(), 10.0 * theta.sin(), 0.0)
+        }).collect();
+        let mut original = Coil::new(Point::zero(), GeoVector::zhat(), points, 0.645, vec![GeoVector::zhat(); 32]).unwrap();
+        original.port = Some(3);
+        original.breaks = vec![3, 11, 19, 27];
+        original.overlap_segments = vec![(5, 9)];
+
+        let mut coil = original.clone();
+        coil.reverse(true);
+        assert_eq!(coil.port, Some(28));
+        assert_eq!(coil.breaks, vec![4, 12, 20, 28]);
+        assert_eq!(coil.overlap_segments, vec![(22, 26)]);
+        assert_eq!(coil.vertices[coil.port.unwrap()].point.distance(&original.vertices[3].point), 0.0);
+        assert_eq!(coil.normal.dot(&original.normal), -1.0);
+
+        coil.reverse(true);
+        assert_eq!(coil.port, original.port);
+        assert_eq!(coil.breaks, original.breaks);
+        assert_eq!(coil.overlap_segments, original.overlap_segments);
+        assert_eq!(coil.normal.dot(&original.normal), 1.0);
+        for (vertex, original_vertex) in coil.vertices.iter().zip(original.vertices.iter()) {
+            assert_eq!(vertex.point.distance(&original_vertex.point), 0.0);
+        }
+    }
+
+    #[test]
+    fn duplicate_vertices_are_removed() {
+        let points: Vec<Point> = (0..16).map(|i| {
+            let theta = 2.0 * PI * i as f32 / 16.0;
+            Point::new(10.0 * theta.cos(), 10.0 * theta.sin(), 0.0)
+        }).collect();
+        let mut original = Coil::new(Point::zero(), GeoVector::zhat(), points, 0.645, vec![GeoVector::zhat(); 16]).unwrap();
+        original.port = Some(0);
+        original.breaks = vec![0, 4, 8, 12];
+        original.overlap_segments = vec![(5, 7)];
+
+        // Duplicate vertex 4 (twice), vertex 8, and vertex 0 at the end of the loop, then mark the duplicates
+        let mut coil = original.clone();
+        let duplicate = |id: usize, offset: f32| {
+            let mut vertex = original.vertices[id].clone();
+            vertex.point += GeoVector::new(offset, 0.0, 0.0);
+            vertex
+        };
+        coil.vertices.push(duplicate(0, 1e-5));
+        coil.vertices.insert(9, duplicate(8, 0.0));
+        coil.vertices.insert(5, duplicate(4, 1e-5));
+        coil.vertices.insert(5, duplicate(4, -1e-5));
+        coil.breaks = vec![0, 6, 11, 15];
+        coil.overlap_segments = vec![(7, 9)];
+        assert_eq!(coil.vertices.len(), 20);
+
+        assert_eq!(coil.dedup_vertices(DEDUP_TOLERANCE), 4);
+        assert_eq!(coil.vertices.len(), 16);
+        assert_eq!(coil.port, original.port);
+        assert_eq!(coil.breaks, original.breaks);
+        assert_eq!(coil.overlap_segments, original.overlap_segments);
+        for (vertex, original_vertex) in coil.vertices.iter().zip(original.vertices.iter()) {
+            assert_eq!(vertex.point.distance(&original_vertex.point), 0.0);
+        }
+        assert!(coil.self_inductance(1.0).is_finite());
+        assert_eq!(coil.dedup_vertices(DEDUP_TOLERANCE), 0);
+    }
+
+    #[test]
+    fn dedup_keeps_breaks_sorted_when_a_trailing_break_folds_into_0() {
+        let points: Vec<Point> = (0..16).map(|i| {
+            let theta = 2.0 * PI * i as f32 / 16.0;
+            Point::new(10.0 * theta.cos(), 10.0 * theta.sin(), 0.0)
+        }).collect();
+        let mut coil = Coil::new(Point::zero(), GeoVector::zhat(), points, 0.645, vec![GeoVector::zhat(); 16]).unwrap();
+        let mut trailing = coil.vertices[0].clone();
+        trailing.point += GeoVector::new(1e-5, 0.0, 0.0);
+        coil.vertices.push(trailing);
+
+        let mut folded = coil.clone();
+        folded.breaks = vec![4, 8, 16];
+        assert_eq!(folded.dedup_vertices(DEDUP_TOLERANCE), 1);
+        assert_eq!(folded.breaks, vec![0, 4, 8]);
+
+        // A break already at 0 merges with the folded one
+        let mut merged = coil.clone();
+        merged.breaks = vec![0, 8, 16];
+        assert_eq!(merged.dedup_vertices(DEDUP_TOLERANCE), 1);
+        assert_eq!(merged.breaks, vec![0, 8]);
+    }
+
+    #[test]
+    fn flipped_winding_is_flagged_as_sign_violation() {
+
+        // Side by side, co-wound coplanar coils couple negatively
+        let mut layout_out = Layout{coils: vec![
+            circle_coil(Point::new(0.0, 0.0, 0.0), 10.0),
+            circle_coil(Point::new(22.0, 0.0, 0.0), 10.0),
+            circle_coil(Point::new(100.0, 0.0, 0.0), 10.0),
+        ]};
+        assert!(layout_out.coupling_sign_violations(&CouplingSignCheck::AdjacentNegative, 1.5).is_empty());
+
+        // Winding the second coil the other way flips the sign of its coupling
+        layout_out.coils[1].vertices.reverse();
+        let violations = layout_out.coupling_sign_violations(&CouplingSignCheck::AdjacentNegative, 1.5);
+        assert_eq!(violations.len(), 1);
+        assert_eq!((violations[0].0, violations[0].1), (0, 1));
+        assert!(violations[0].2 > 0.0);
+
+        // An explicit sign map agrees
+        let expected = CouplingSignCheck::Pairs(vec![(0, 1, -1), (0, 2, -1)]);
+        let violations = layout_out.coupling_sign_violations(&expected, 1.5);
+        assert_eq!(violations.len(), 1);
+        assert_eq!((violations[0].0, violations[0].1), (0, 1));
+    }
+
+    #[test]
+    fn overlap_count_matches_coils_over_shared_region() {
+        let surface = Surface::square_grid(30.0, 60);
+
+        // Three coils share the region around the origin, a fourth is off on its own
+        let layout_out = Layout{coils: vec![
+            circle_coil(Point::new(-4.0, 0.0, 0.0), 10.0),
+            circle_coil(Point::new(4.0, 0.0, 0.0), 10.0),
+            circle_coil(Point::new(0.0, 5.0, 0.0), 10.0),
+            circle_coil(Point::new(0.0, -19.0, 0.0), 10.0),
+        ]};
+        let coverage = layout_out.vertex_coverage(&surface);
+
+        assert_eq!(coverage.iter().copied().max(), Some(3));
+        let origin_id = Point::zero().nearest_point_idx(&surface);
+        assert_eq!(coverage[origin_id], 3);
+        let lone_id = Point::new(0.0, -25.0, 0.0).nearest_point_idx(&surface);
+        assert_eq!(coverage[lone_id], 1);
+        let corner_id = Point::new(29.0, 29.0, 0.0).nearest_point_idx(&surface);
+        assert_eq!(coverage[corner_id], 0);
+
+        // Ownership agrees with the coverage: single-coil vertices get that coil, uncovered ones get -1
+        let ownership = layout_out.vertex_ownership(&surface);
+        assert_eq!(ownership[lone_id], 3);
+        assert_eq!(ownership[corner_id], -1);
+        let shared_id = Point::new(0.0, 8.0, 0.0).nearest_point_idx(&surface);
+        assert_eq!(coverage[shared_id], 3);
+        assert_eq!(ownership[shared_id], 2);
+        for (vertex_id, count) in coverage.iter().enumerate() {
+            assert_eq!(ownership[vertex_id] >= 0, *count > 0);
+        }
+        let left_id = Point::new(-13.0, 0.0, 0.0).nearest_point_idx(&surface);
+        assert_eq!(coverage[left_id], 1);
+        assert_eq!(ownership[left_id], 0);
+    }
+
+    #[test]
+    fn coverage_gaps_are_reported_largest_first() {
+        // 1 mm grid, with one coil over the left side and one over the lower right,
+        // leaving a 14.5 mm square hole in the upper right and a 2.5 mm strip along the bottom left
+        let surface = Surface::square_grid(20.0, 40);
+        let rectangle_coil = |min: Point, max: Point| -> Coil {
+            let corners = [min, Point::new(max.x, min.y, 0.0), max, Point::new(min.x, max.y, 0.0)];
+            let points = (0..4).flat_map(|side| {
+                let (start, end) = (corners[side], corners[(side + 1) % 4]);
+                (0..16).map(move |i| start + (end - start) * (i as f32 / 16.0))
+            }).collect();
+            Coil::new(min + (max - min) * 0.5, GeoVector::zhat(), points, 0.645, vec![GeoVector::zhat(); 64]).unwrap()
+        };
+        let layout_out = Layout{coils: vec![
+            rectangle_coil(Point::new(-20.5, -17.5, 0.0), Point::new(5.5, 20.5, 0.0)),
+            rectangle_coil(Point::new(5.5, -20.5, 0.0), Point::new(20.5, 5.5, 0.0)),
+        ]};
+
+        let gaps = layout_out.coverage_gaps(&surface);
+        assert_eq!(gaps.len(), 2);
+        let vertex_areas = surface.vertex_areas();
+        let gap_area = |gap: &Vec<usize>| gap.iter().map(|vertex_id| vertex_areas[*vertex_id]).sum::<f32>();
+        assert!((gap_area(&gaps[0]) - 14.5 * 14.5).abs() < 0.05 * 14.5 * 14.5, "Largest gap area {}", gap_area(&gaps[0]));
+        assert!((gap_area(&gaps[1]) - 25.5 * 2.5).abs() < 0.1 * 25.5 * 2.5, "Second gap area {}", gap_area(&gaps[1]));
+        assert!(gaps[0].contains(&Point::new(15.0, 15.0, 0.0).nearest_point_idx(&surface)));
+        assert!(gaps[1].contains(&Point::new(-10.0, -19.0, 0.0).nearest_point_idx(&surface)));
+
+        // Full coverage leaves no gaps
+        let covered = Layout{coils: vec![rectangle_coil(Point::new(-20.5, -20.5, 0.0), Point::new(20.5, 20.5, 0.0))]};
+        assert!(covered.coverage_gaps(&surface).is_empty());
+    }
+
+    #[test]
+    fn footprint_outline_encloses_adjacent_coils() {
+        // Two overlapping 11 mm squares side by side on a 1 mm grid
+        let surface = Surface::square_grid(20.0, 40);
+        let rectangle_coil = |min: Point, max: Point| -> Coil {
+            let corners = [min, Point::new(max.x, min.y, 0.0), max, Point::new(min.x, max.y, 0.0)];
+            let points = (0..4).flat_map(|side| {
+                let (start, end) = (corners[side], corners[(side + 1) % 4]);
+                (0..16).map(move |i| start + (end - start) * (i as f32 / 16.0))
+            }).collect();
+            Coil::new(min + (max - min) * 0.5, GeoVector::zhat(), points, 0.645, vec![GeoVector::zhat(); 64]).unwrap()
+        };
+        let layout_out = Layout{coils: vec![
+            rectangle_coil(Point::new(-10.5, -5.5, 0.0), Point::new(0.5, 5.5, 0.0)),
+            rectangle_coil(Point::new(-0.5, -5.5, 0.0), Point::new(10.5, 5.5, 0.0)),
+        ]};
+
+        let outline = layout_out.footprint_outline(&surface);
+        assert!(outline.len() >= 4);
+        assert!(outline.iter().all(|point| point.x.abs() <= 10.0 && point.y.abs() <= 5.0), "Outline leaves the covered region");
+        let (min_x, max_x) = outline.iter().fold((f32::MAX, f32::MIN), |(min, max), point| (min.min(point.x), max.max(point.x)));
+        assert!(min_x < -9.5 && max_x > 9.5, "Outline spans x from {} to {}", min_x, max_x);
+
+        // Treat the outline as a loop to check what it encloses
+        let outline_loop = Coil::new(Point::zero(), GeoVector::zhat(), outline.clone(), 0.645, vec![GeoVector::zhat(); outline.len()]).unwrap();
+        for coil in layout_out.coils.iter() {
+            assert!(outline_loop.encloses_point(&coil.center));
+        }
+        for exterior in [Point::new(15.0, 0.0, 0.0), Point::new(0.0, 10.0, 0.0), Point::new(-15.0, -15.0, 0.0)] {
+            assert!(!outline_loop.encloses_point(&exterior), "Outline encloses {:.1}", exterior);
+        }
+
+        assert!(Layout{coils: Vec::new()}.footprint_outline(&surface).is_empty());
+    }
+
+    #[test]
+    fn square_coil_has_four_right_angle_bends() {
+        // 20 mm square, sampled every 1 mm, with each corner cut by a short chamfer (two 45 degree turns)
+        let corners = [Point::new(-10.0, -10.0, 0.0), Point::new(10.0, -10.0, 0.0), Point::new(10.0, 10.0, 0.0), Point::new(-10.0, 10.0, 0.0)];
+        let mut points = Vec::new();
+        for corner_id in 0..4 {
+            let (corner, next) = (corners[corner_id], corners[(corner_id + 1) % 4]);
+            let side = (next - corner) / 20.0;
+            let next_side = (corners[(corner_id + 2) % 4] - next) / 20.0;
+            for step in 1..20 {
+                points.push(corner + side * step as f32);
+            }
+            points.push(next - side * 0.5);
+            points.push(next + next_side * 0.5);
+        }
+        let coil = Coil::new(Point::zero(), GeoVector::zhat(), points, 0.645, vec![GeoVector::zhat(); 84]).unwrap();
+
+        let test_dir = temp_dir("bend_test");
+        let output_path = test_dir.path().join("bend.csv").to_string_lossy().to_string();
+        let bends = export_bend_sequence(&coil, &output_path).unwrap();
+        assert_eq!(bends.len(), 4, "Bends: {:?}", bends);
+        for (bend_id, bend) in bends.iter().enumerate() {
+            assert!((bend.angle - 90.0).abs() < 5.0, "Bend {} angle {}", bend_id, bend.angle);
+            assert_eq!(bend.direction, Sense::CounterClockwise);
+        }
+        for pair in bends.windows(2) {
+            assert!((pair[1].arc_length - pair[0].arc_length - 20.0).abs() < 1.0);
+        }
+
+        let csv = std::fs::read_to_string(&output_path).unwrap();
+        assert_eq!(csv.lines().count(), 5);
+        assert!(csv.lines().nth(1).unwrap().ends_with("ccw"));
+
+        // Starting the wire between the two halves of a chamfer puts that corner's bend at the end, not halfway round
+        let mut straddling_points = coil.vertices.iter().map(|vertex| vertex.point).collect::<Vec<Point>>();
+        straddling_points.rotate_left(83);
+        let straddling = Coil::new(Point::zero(), GeoVector::zhat(), straddling_points, 0.645, vec![GeoVector::zhat(); 84]).unwrap();
+        let perimeter = straddling.wire_length();
+        let bends = straddling.bend_sequence(BEND_ANGLE_THRESHOLD);
+        assert_eq!(bends.len(), 4, "Bends: {:?}", bends);
+        for pair in bends.windows(2) {
+            assert!((pair[1].arc_length - pair[0].arc_length - 20.0).abs() < 1.0, "Bends: {:?}", bends);
+        }
+        assert!(bends[3].arc_length > perimeter - 1.0 && bends[3].arc_length < perimeter, "Bends: {:?}", bends);
+    }
+
+    #[test]
+    fn subdivided_self_inductance_converges() {
+        let circle_coil = |vertex_count: usize| -> Coil {
+            let points = (0..vertex_count).map(|i| {
+                let theta = 2.0 * PI * i as f32 / vertex_count as f32;
+                Point::new(10.0 * theta.cos(), 10.0 * theta.sin(), 0.0)
+            }).collect();
+            Coil::new(Point::zero(), GeoVector::zhat(), points, 0.645, vec![GeoVector::zhat(); vertex_count]).unwrap()
+        };
+        // Much finer spans than this hit the near-field cutoff unevenly, so 128 vertices is the reference
+        let reference = circle_coil(128).self_inductance(0.25);
+        let coarse = circle_coil(8);
+        assert_eq!(coarse.self_inductance_subdivided(0.25, 1), coarse.self_inductance(0.25));
+
+        let errors: Vec<f32> = [1, 2, 4, 8, 16].iter()
+            .map(|subdivisions| (coarse.self_inductance_subdivided(0.25, *subdivisions) - reference).abs() / reference)
+            .collect();
+        for pair in errors.windows(2) {
+            assert!(pair[1] < pair[0], "Relative errors {:?} should shrink with subdivision", errors);
+        }
+        assert!(errors[4] < 0.01, "Relative errors {:?}", errors);
+
+        let mut ported = coarse.clone();
+        ported.port = Some(3);
+        ported.breaks = vec![3, 7];
+        let subdivided = ported.subdivided(4);
+        assert_eq!(subdivided.vertices.len(), 32);
+        assert_eq!((subdivided.port, subdivided.breaks.clone()), (Some(12), vec![12, 28]));
+        assert!(subdivided.vertices[12].point.approx_eq(&coarse.vertices[3].point, 1e-5));
+    }
+
+    #[test]
+    fn save_refuses_nan_vertex() {
+        let points = (0..16).map(|i| {
+            let theta = 2.0 * PI * i as f32 / 16.0;
+            Point::new(10.0 * theta.cos(), 10.0 * theta.sin(), 0.0)
+        }).collect();
+        let coil = Coil::new(Point::zero(), GeoVector::zhat(), points, 0.645, vec![GeoVector::zhat(); 16]).unwrap();
+        let mut layout = Layout{coils: vec![coil.clone(), coil]};
+        let test_dir = temp_dir("nan_layout_test");
+        let output_path = test_dir.path().join("nan_layout.json").to_string_lossy().to_string();
+        let _ = std::fs::remove_file(&output_path);
+        assert!(layout.find_non_finite().is_none());
+
+        layout.coils[1].vertices[5].point.y = f32::NAN;
+        let error = save_layout(&layout, &output_path).unwrap_err().to_string();
+        assert!(error.contains("Coil 1, vertex 5: point is not finite"), "{}", error);
+        assert!(!std::path::Path::new(&output_path).exists());
+
+        layout.coils[1].vertices[5].point.y = 0.0;
+        layout.coils[0].normal.z = f32::INFINITY;
+        assert_eq!(layout.find_non_finite().unwrap(), format!("Coil 0: normal is not finite ({})", layout.coils[0].normal));
+    }
+
+    #[test]
+    fn rounded_layout_reloads_without_float_tails() {
+        let points = (0..16).map(|i| {
+            let theta = 2.0 * PI * i as f32 / 16.0 + 0.1;
+            Point::new(10.0 * theta.cos(), 10.0 * theta.sin(), 1.0 / 3.0)
+        }).collect();
+        let coil = Coil::new(Point::new(0.1, 0.2, 1.0 / 3.0), GeoVector::zhat(), points, 0.645, vec![GeoVector::zhat(); 16]).unwrap();
+        let layout = Layout{coils: vec![coil]};
+        let test_dir = temp_dir("rounded_layout_test");
+        let output_path = test_dir.path().join("rounded_layout.json").to_string_lossy().to_string();
+
+        save_layout_with_precision(&layout, &output_path, Some(3)).unwrap();
+        let contents = std::fs::read_to_string(&output_path).unwrap();
+        let longest_decimals = contents.split(|c: char| !(c.is_ascii_digit() || c == '.'))
+            .filter_map(|number| number.split_once('.').map(|(_, decimals)| decimals.len()))
+            .max().unwrap();
+        assert!(longest_decimals <= 3, "Found a float with {} decimals in:\n{}", longest_decimals, contents);
+
+        let reloaded = load_layout(&output_path).unwrap();
+        std::fs::remove_file(&output_path).unwrap();
+        assert!(reloaded.coils[0].center.distance(&layout.coils[0].center) <= 1e-3);
+        for (vertex, original) in reloaded.coils[0].vertices.iter().zip(layout.coils[0].vertices.iter()) {
+            assert!(vertex.point.distance(&original.point) <= 1e-3);
+        }
+    }
+
+    #[test]
+    fn spice_netlist_parses_and_couples_close_pairs() {
+        let mut layout = Layout::new();
+        for coil_id in 0..3 {
+            layout.coils.push(circle_coil(Point::new(15.0 * coil_id as f32, 0.0, 0.0), 10.0));
+        }
+        layout.coils[1].breaks = vec![0, 16, 32, 48];
+
+        let test_dir = temp_dir("spice_test");
+        let output_path = test_dir.path().join("spice.cir").to_string_lossy().to_string();
+        let netlist = export_spice_netlist(&layout, &output_path, 127.73, 0.95).unwrap();
+        assert_eq!(std::fs::read_to_string(&output_path).unwrap(), netlist);
+
+        // Lexical check: comments, dot commands, or elements with a name, nodes, and a numeric value
+        let parse_value = |value: &str| value.trim_end_matches(['n', 'p']).parse::<f32>();
+        for line in netlist.lines().filter(|line| !line.is_empty() && !line.starts_with('*')) {
+            let tokens: Vec<&str> = line.split_whitespace().collect();
+            match line.chars().next().unwrap() {
+                '.' => assert!([".subckt", ".ends", ".end"].contains(&tokens[0]), "{}", line),
+                'R' | 'C' | 'L' => {
+                    assert_eq!(tokens.len(), 4, "{}", line);
+                    assert!(parse_value(tokens[3]).unwrap() > 0.0, "{}", line);
+                },
+                'K' => {
+                    assert_eq!(tokens.len(), 4, "{}", line);
+                    assert!(parse_value(tokens[3]).unwrap().abs() < 1.0, "{}", line);
+                },
+                'X' => assert_eq!(tokens.len(), 5, "{}", line),
+                _ => panic!("Unexpected netlist line: {}", line),
+            }
+        }
+        assert_eq!(netlist.matches(".subckt").count(), 3);
+        assert_eq!(netlist.matches(".ends").count(), 3);
+        assert!(netlist.trim_end().ends_with(".end"));
+        assert_eq!(netlist.lines().filter(|line| line.starts_with("C") && !line.starts_with("Cmatch")).count(), 1 + 4 + 1);
+
+        // Every rf pin is terminated, and the matching placeholder isn't a copy of a tuning capacitor
+        for coil_id in 0..3 {
+            assert!(netlist.lines().any(|line| line.starts_with(&format!("Rport{} rf_{} 0 ", coil_id, coil_id))), "Coil {} port floats", coil_id);
+        }
+        let value_of = |prefix: &str| netlist.lines().find(|line| line.starts_with(prefix)).unwrap().split_whitespace().nth(3).unwrap().to_string();
+        assert_ne!(value_of("Cmatch"), value_of("Ctune"));
+
+        // Neighbors are coupled, the far pair isn't
+        let k_lines: Vec<&str> = netlist.lines().filter(|line| line.starts_with('K')).collect();
+        assert_eq!(k_lines.len(), 2, "{:?}", k_lines);
+        assert!(k_lines[0].starts_with("K0_1 L0 L1") && k_lines[1].starts_with("K1_2 L1 L2"));
+    }
+
+    #[test]
+    fn decoupled_array_has_lower_condition_number() {
+        let row = |spacing: f32| -> Layout {
+            let mut layout = Layout::new();
+            for coil_id in 0..3 {
+                layout.coils.push(circle_coil(Point::new(spacing * coil_id as f32, 0.0, 0.0), 10.0));
+            }
+            layout
+        };
+
+        // Symmetric 2x2 check of the eigenvalue routine
+        let eigenvalues = symmetric_eigenvalues(vec![vec![2.0, 1.0], vec![1.0, 2.0]]);
+        let (min, max) = (eigenvalues[0].min(eigenvalues[1]), eigenvalues[0].max(eigenvalues[1]));
+        assert!((min - 1.0).abs() < 1e-9 && (max - 3.0).abs() < 1e-9, "Eigenvalues {:?}", eigenvalues);
+
+        // Overlapped near critical overlap vs. nearly concentric
+        let decoupled = row(15.0).coupling_condition_number(127.73);
+        let tight = row(3.0).coupling_condition_number(127.73);
+        assert!(decoupled >= 1.0);
+        assert!(decoupled < tight, "Decoupled array condition number {} vs. tightly-coupled {}", decoupled, tight);
+        assert!((row(15.0).coupling_condition_number(63.87) / decoupled - 1.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn closest_approach_matches_brute_force() {
+        let wobbly_coil = |center: Point, radius: f32, wobble: f32, count: usize| -> Coil {
+            let points = (0..count).map(|i| {
+                let theta = 2.0 * PI * i as f32 / count as f32;
+                let r = radius + wobble * (5.0 * theta).sin();
+                center + GeoVector::new(r * theta.cos(), r * theta.sin(), wobble * (3.0 * theta).cos())
+            }).collect();
+            Coil::new(center, GeoVector::zhat(), points, 0.645, vec![GeoVector::zhat(); count]).unwrap()
+        };
+
+        for (offset, other_count) in [(22.0, 61), (15.0, 100), (40.0, 13)] {
+            let coil = wobbly_coil(Point::zero(), 10.0, 1.5, 90);
+            let other_coil = wobbly_coil(Point::new(offset, 3.0, 1.0), 9.0, 0.8, other_count);
+
+            let mut brute_force = (f32::INFINITY, 0, 0);
+            for (vertex_id, vertex) in coil.vertices.iter().enumerate() {
+                for (other_vertex_id, other_vertex) in other_coil.vertices.iter().enumerate() {
+                    let distance = (vertex.point - other_vertex.point).norm();
+                    if distance < brute_force.0 {
+                        brute_force = (distance, vertex_id, other_vertex_id);
+                    }
+                }
+            }
+            assert_eq!(coil.closest_approach(&other_coil), brute_force);
+        }
+    }
+
+    #[test]
+    fn loop_inductance_in_nanohenries_and_henries() {
+        let circle_coil = |radius: f32, wire_radius: f32| -> Coil {
+            let points = (0..64).map(|i| {
+                let theta = 2.0 * PI * i as f32 / 64.0;
+                Point::new(radius * theta.cos(), radius * theta.sin(), 0.0)
+            }).collect();
+            Coil::new(Point::zero(), GeoVector::zhat(), points, wire_radius, vec![GeoVector::zhat(); 64]).unwrap()
+        };
+
+        // Thin circular loop: L = mu0 R (ln(8R/a) - 2), about 35 nH for R = 10 mm and a = 0.645 mm
+        // The discretized calculation lands within 20% of that
+        let coil_mm = circle_coil(10.0, 0.645);
+        let expected_h = 4.0e-7 * PI * 0.01 * ((8.0 * 10.0 / 0.645_f32).ln() - 2.0);
+        let inductance_nh = coil_mm.self_inductance_in(1.0, LengthUnit::Millimeters, InductanceUnits::Nanohenries);
+        let inductance_h = coil_mm.self_inductance_in(1.0, LengthUnit::Millimeters, InductanceUnits::Henries);
+        assert_eq!(inductance_nh, coil_mm.self_inductance(1.0));
+        assert!((inductance_h / expected_h - 1.0).abs() < 0.2, "Inductance {} H, expected {} H", inductance_h, expected_h);
+        assert!((inductance_h * 1.0e9 / inductance_nh - 1.0).abs() < 1e-5);
+
+        // The same loop in meters gives the same inductance
+        let coil_m = circle_coil(0.01, 0.000645);
+        let inductance_m_h = coil_m.self_inductance_in(0.001, LengthUnit::Meters, InductanceUnits::Henries);
+        assert!((inductance_m_h / inductance_h - 1.0).abs() < 1e-3, "{} H in meters vs. {} H in mm", inductance_m_h, inductance_h);
+        assert_eq!(InductanceUnits::Henries.symbol(), "H");
+    }
+
+    #[test]
+    fn layout_in_meters_loads_as_millimeters() {
+        let coil_mm = circle_coil(Point::new(5.0, 0.0, 0.0), 10.0);
+        let mut coil_m = coil_mm.clone();
+        coil_m.scale(1.0e-3, true);
+
+        let test_dir = temp_dir("length_unit_test");
+        let layout_path = test_dir.path().join("layout.json").to_string_lossy().to_string();
+        save_layout(&Layout{coils: vec![coil_m]}, &layout_path).unwrap();
+        let layout_in = load_layout_in(&layout_path, LengthUnit::Meters).unwrap();
+
+        let coil = &layout_in.coils[0];
+        assert!(coil.center.approx_eq(&coil_mm.center, 1e-3));
+        assert!((coil.wire_radius - coil_mm.wire_radius).abs() < 1e-5);
+        let (inductance, expected) = (coil.self_inductance(1.0), coil_mm.self_inductance(1.0));
+        assert!((inductance / expected - 1.0).abs() < 1e-3, "{} nH loaded from meters vs. {} nH in mm", inductance, expected);
+    }
+
+    #[test]
+    fn scaling_doubles_length_and_inductance() {
+        let coil = circle_coil(Point::new(5.0, 0.0, 0.0), 10.0);
+
+        let mut layout_out = Layout{coils: vec![coil.clone()]};
+        layout_out.scale(2.0, true);
+        let scaled = &layout_out.coils[0];
+        assert!((scaled.wire_length() - 2.0 * coil.wire_length()).abs() < 1e-3);
+        assert!((scaled.center.x - 10.0).abs() < 1e-6);
+        assert_eq!(scaled.wire_radius, 2.0 * coil.wire_radius);
+
+        // With the wire scaled too, the whole geometry scales and so does the inductance
+        // (up to the fixed near-point distance threshold in the mutual inductance)
+        let inductance = coil.self_inductance(1.0);
+        let scaled_inductance = scaled.self_inductance(2.0);
+        assert!((scaled_inductance / inductance - 2.0).abs() < 0.1, "Inductance ratio {}", scaled_inductance / inductance);
+
+        // A relatively thinner wire has more inductance per length
+        let mut thin = coil.clone();
+        thin.scale(2.0, false);
+        assert_eq!(thin.wire_radius, coil.wire_radius);
+        assert!(thin.self_inductance(2.0) > scaled_inductance);
+    }
+}
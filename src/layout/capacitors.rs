@@ -0,0 +1,228 @@
+use serde::{Serialize, Deserialize};
+
+use std::f32::consts::PI;
+
+use crate::layout;
+
+const SPEED_OF_LIGHT: f32 = 2.997_924_6e11; // mm/s
+
+/// Arguments for the capacitor summary written next to the layout output.
+#[derive(Debug, Clone)]
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CapacitorSummaryArgs {
+    /// Larmor frequency the capacitors resonate the coils at, in MHz.
+    #[serde(default = "CapacitorSummaryArgs::default_larmor_mhz")]
+    pub larmor_mhz: f32,
+    /// Longest allowed wire segment between capacitors, as a fraction of the free-space wavelength.
+    #[serde(default = "CapacitorSummaryArgs::default_max_electrical_length", alias = "max_wavelengths")]
+    pub max_electrical_length: f32,
+}
+impl CapacitorSummaryArgs {
+    pub fn default_larmor_mhz() -> f32 {
+        127.73
+    }
+    pub fn default_max_electrical_length() -> f32 {
+        0.1
+    }
+}
+impl Default for CapacitorSummaryArgs {
+    fn default() -> Self {
+        CapacitorSummaryArgs{
+            larmor_mhz: Self::default_larmor_mhz(),
+            max_electrical_length: Self::default_max_electrical_length(),
+        }
+    }
+}
+
+/// A capacitor at the port or a break of a coil.
+#[derive(Debug, Clone)]
+#[derive(Serialize)]
+pub struct CapacitorPosition {
+    /// Coil vertex the capacitor sits on.
+    pub vertex: usize,
+    /// Arc length along the wire from the port (or vertex 0 without a port), in mm.
+    pub arc_length: f32,
+    /// Whether this is the port.
+    pub is_port: bool,
+    /// Length of the wire segment from this capacitor to the next one, in mm.
+    pub segment_length: f32,
+    /// Capacitance that resonates the segment's share of the self-inductance at the Larmor frequency, in pF.
+    pub capacitance_pf: f32,
+    /// Reactance of the capacitor at the Larmor frequency, in ohms.
+    pub reactance_ohm: f32,
+}
+
+/// Capacitor positions and expected values for one coil.
+#[derive(Debug, Clone)]
+#[derive(Serialize)]
+pub struct CoilCapacitors {
+    /// Optional user label of the coil.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    /// Wire length, in mm.
+    pub wire_length: f32,
+    /// Self-inductance, in nH.
+    pub self_inductance_nh: f32,
+    /// Capacitors in order along the wire, starting from the port.
+    pub capacitors: Vec<CapacitorPosition>,
+    /// Fewest evenly spaced capacitors that keep every segment below the target electrical length.
+    pub min_capacitor_count: usize,
+    /// Whether every segment is below the target electrical length.
+    pub meets_target: bool,
+}
+
+/// Capacitor summary of a layout, written next to the layout output.
+#[derive(Debug, Clone)]
+#[derive(Serialize)]
+pub struct CapacitorSummary {
+    /// Larmor frequency, in MHz.
+    pub larmor_mhz: f32,
+    /// Longest allowed segment, in mm.
+    pub max_segment_length: f32,
+    /// Per-coil capacitors, indexed like the layout coils.
+    pub coils: Vec<CoilCapacitors>,
+}
+
+/// Work out the capacitor positions and values of a coil.
+/// The port and breaks split the wire into segments, and each capacitor resonates the segment after it,
+/// taking a share of the self-inductance proportional to the segment length.
+/// More capacitors each cancel a smaller reactance, so the voltage across each one drops (while the capacitance rises).
+pub fn coil_capacitors(coil: &layout::Coil, args: &CapacitorSummaryArgs) -> CoilCapacitors {
+    let vertex_count = coil.vertices.len();
+    let start = coil.port.unwrap_or(0);
+    let mut arc_lengths = vec![0.0; vertex_count];
+    for step in 1..vertex_count {
+        let (prev_id, vertex_id) = ((start + step - 1) % vertex_count, (start + step) % vertex_count);
+        arc_lengths[vertex_id] = arc_lengths[prev_id] + (coil.vertices[vertex_id].point - coil.vertices[prev_id].point).norm();
+    }
+    let wire_length = coil.wire_length();
+    let self_inductance_nh = coil.self_inductance(1.0);
+    let omega = 2.0 * PI * args.larmor_mhz * 1.0e6;
+    let max_segment_length = max_segment_length(args);
+
+    let mut vertices: Vec<usize> = coil.port.into_iter().chain(coil.breaks.iter().copied()).collect();
+    vertices.sort_by(|a, b| arc_lengths[*a].total_cmp(&arc_lengths[*b]));
+    vertices.dedup();
+
+    let capacitors: Vec<CapacitorPosition> = vertices.iter().enumerate().map(|(id, vertex)| {
+        let next_arc_length = vertices.get(id + 1).map_or(wire_length + arc_lengths[vertices[0]], |next| arc_lengths[*next]);
+        let segment_length = next_arc_length - arc_lengths[*vertex];
+        let segment_inductance_nh = self_inductance_nh * segment_length / wire_length;
+        CapacitorPosition{
+            vertex: *vertex,
+            arc_length: arc_lengths[*vertex],
+            is_port: coil.port == Some(*vertex),
+            segment_length,
+            capacitance_pf: resonant_capacitance_pf(segment_inductance_nh, args.larmor_mhz),
+            reactance_ohm: omega * segment_inductance_nh * 1.0e-9,
+        }
+    }).collect();
+
+    // Without capacitors, the whole loop is one segment
+    let longest_segment = capacitors.iter().fold(0.0, |max: f32, capacitor| max.max(capacitor.segment_length));
+    let longest_segment = if capacitors.is_empty() { wire_length } else { longest_segment };
+    CoilCapacitors{
+        label: coil.label.clone(),
+        wire_length,
+        self_inductance_nh,
+        capacitors,
+        min_capacitor_count: (wire_length / max_segment_length).ceil() as usize,
+        meets_target: longest_segment <= max_segment_length,
+    }
+}
+
+/// Capacitance that resonates an inductance (nH) at the Larmor frequency (MHz), in pF.
+pub fn resonant_capacitance_pf(inductance_nh: f32, larmor_mhz: f32) -> f32 {
+    let omega = 2.0 * PI * larmor_mhz * 1.0e6;
+    1.0e21 / (omega * omega * inductance_nh)
+}
+
+/// Longest allowed segment for the summary arguments, in mm.
+fn max_segment_length(args: &CapacitorSummaryArgs) -> f32 {
+    args.max_electrical_length * SPEED_OF_LIGHT / (args.larmor_mhz * 1.0e6)
+}
+
+/// Build the capacitor summary for a layout.
+/// Returns a `ProcResult` with the summary, or an `Err` if the arguments aren't positive.
+pub fn build_capacitor_summary(layout_out: &layout::Layout, args: &CapacitorSummaryArgs) -> layout::ProcResult<CapacitorSummary> {
+    if args.larmor_mhz <= 0.0 {
+        return layout::err_str(&format!("Larmor frequency must be positive, got {}", args.larmor_mhz));
+    }
+    if args.max_electrical_length <= 0.0 {
+        return layout::err_str(&format!("Maximum electrical length must be positive, got {}", args.max_electrical_length));
+    }
+    Ok(CapacitorSummary{
+        larmor_mhz: args.larmor_mhz,
+        max_segment_length: max_segment_length(args),
+        coils: layout_out.coils.iter().map(|coil| coil_capacitors(coil, args)).collect(),
+    })
+}
+
+/// Write the capacitor summary as `<layout stem>_capacitors.json` in the same directory as the layout output.
+pub fn save_capacitor_summary(layout_target: &layout::LayoutTarget, layout_out: &layout::Layout) -> layout::ProcResult<()> {
+    let (output_path, args) = match (layout_target.output_path.as_ref(), layout_target.capacitor_summary.as_ref()) {
+        (Some(output_path), Some(args)) => (output_path, args),
+        _ => panic!("BUG: Writing a capacitor summary, but missing output path or arguments! Should've been checked!"),
+    };
+    let output_path = std::path::Path::new(output_path);
+    let stem = output_path.file_stem().map_or("layout".into(), |stem| stem.to_string_lossy());
+    let summary_path = output_path.with_file_name(format!("{}_capacitors.json", stem));
+    let summary_path = summary_path.to_string_lossy();
+
+    println!("Saving capacitor summary to {}...", summary_path);
+    let summary = build_capacitor_summary(layout_out, args)?;
+    for (coil_id, coil) in summary.coils.iter().enumerate() {
+        if !coil.meets_target {
+            println!("Coil {} has a segment over {:.1} mm -- needs at least {} capacitors", coil_id, summary.max_segment_length, coil.min_capacitor_count);
+        }
+    }
+    crate::io::save_ser_to(&summary_path, &summary)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geo_3d::*;
+
+    fn circle_coil(breaks: usize) -> layout::Coil {
+        let points = (0..60).map(|i| {
+            let theta = 2.0 * PI * i as f32 / 60.0;
+            Point::new(40.0 * theta.cos(), 40.0 * theta.sin(), 0.0)
+        }).collect();
+        let mut coil = layout::Coil::new(Point::zero(), GeoVector::zhat(), points, 0.645, vec![GeoVector::zhat(); 60]).unwrap();
+        coil.redistribute_breaks_by_length(breaks).unwrap();
+        coil
+    }
+
+    #[test]
+    fn more_breaks_mean_smaller_capacitor_reactance() {
+        let args = CapacitorSummaryArgs::default();
+        let few = coil_capacitors(&circle_coil(3), &args);
+        let many = coil_capacitors(&circle_coil(6), &args);
+        assert_eq!(few.capacitors.len(), 3);
+        assert_eq!(many.capacitors.len(), 6);
+        assert!(few.capacitors[0].is_port && few.capacitors[0].arc_length == 0.0);
+        assert!(few.capacitors.iter().all(|capacitor| (capacitor.segment_length - few.wire_length / 3.0).abs() < 1.0));
+
+        let max_reactance = |coil: &CoilCapacitors| coil.capacitors.iter().fold(0.0, |max: f32, capacitor| max.max(capacitor.reactance_ohm));
+        assert!(max_reactance(&many) < max_reactance(&few), "{} ohm with 6 capacitors, {} ohm with 3", max_reactance(&many), max_reactance(&few));
+
+        // Either way, the capacitors in series resonate the whole coil
+        let omega = 2.0 * PI * args.larmor_mhz * 1.0e6;
+        let expected_pf = 1.0e21 / (omega * omega * few.self_inductance_nh);
+        for summary in [few, many] {
+            let series_pf = 1.0 / summary.capacitors.iter().map(|capacitor| 1.0 / capacitor.capacitance_pf).sum::<f32>();
+            assert!((series_pf - expected_pf).abs() < 1e-3 * expected_pf, "Series {} pF, expected {} pF", series_pf, expected_pf);
+        }
+    }
+
+    #[test]
+    fn uninterrupted_loop_has_no_capacitors() {
+        let summary = coil_capacitors(&circle_coil(0), &CapacitorSummaryArgs{max_electrical_length: 0.01, ..Default::default()});
+        assert!(summary.capacitors.is_empty());
+        assert!(!summary.meets_target);
+        assert!(summary.min_capacitor_count >= 2);
+    }
+}
@@ -3,7 +3,6 @@ use crate::{
     io,
     layout,
 };
-use layout::LayoutMethodTrait;
 use serde::{Serialize, Deserialize};
 
 /// Layout target struct. Includes the layout method, method arguments, and general i/o arguments.
@@ -23,14 +22,65 @@ pub struct LayoutTarget {
     #[serde(default, rename = "force_save")]
     pub save: bool,
 
-    /// Layout method.
-    pub method: layout::MethodEnum,
+    /// Write a reproducibility manifest (manifest.json) next to the layout output.
+    #[serde(default)]
+    pub manifest: bool,
+
+    /// Write a summary of the capacitor positions and expected values (`<layout stem>_capacitors.json`) next to the layout output.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub capacitor_summary: Option<layout::CapacitorSummaryArgs>,
+
+    /// Decimal places to round the floats in the saved layout to (full precision if unset).
+    #[serde(default, skip_serializing_if = "Option::is_none", alias = "precision")]
+    pub output_precision: Option<usize>,
+
+    /// Laplacian smoothing iterations applied to every coil path after the layout method (see `Coil::smooth`), 0 to skip.
+    #[serde(default, alias = "smooth")]
+    pub smooth_iterations: usize,
+
+    /// Reverse coils wound against coil 0 after the layout method, so the array winds consistently (see `Layout::normalize_winding`).
+    #[serde(default)]
+    pub normalize_winding: bool,
+
+    /// Merge near-coincident consecutive coil vertices after the layout method (see `Coil::dedup_vertices`).
+    #[serde(default = "LayoutTarget::default_dedup_vertices")]
+    pub dedup_vertices: bool,
+
+    /// Distance (mm) below which consecutive coil vertices are merged, if `dedup_vertices` is set.
+    #[serde(default = "LayoutTarget::default_dedup_tolerance")]
+    pub dedup_tolerance: f32,
+
+    /// Warn about coils lifting off the surface by more than this, in mm (see `Layout::coils_above_liftoff`). Unchecked if unset.
+    #[serde(default, skip_serializing_if = "Option::is_none", alias = "liftoff_warning")]
+    pub max_liftoff: Option<f32>,
+
+    /// Layout method, built through the method registry (see `layout::method_registry`).
+    pub method: layout::LayoutMethod,
 }
 impl LayoutTarget {
+    pub fn default_dedup_vertices() -> bool {
+        true
+    }
+    pub fn default_dedup_tolerance() -> f32 {
+        layout::DEDUP_TOLERANCE
+    }
+
     /// Construct a layout target from a config file.
-    pub fn from_cfg_file(cfg_file: &str, is_last: bool) -> args::ProcResult<Self> {
+    /// If `input_dir` is given (e.g. an extracted bundle), relative input paths are resolved against it.
+    /// If `surface_path` is given (e.g. from the command line), it replaces the configured input path.
+    pub fn from_cfg_file(cfg_file: &str, input_dir: Option<&std::path::Path>, surface_path: Option<&str>, is_last: bool) -> args::ProcResult<Self> {
+        // The method is built by its config key through the registry (see `layout::LayoutMethod`)
         let mut layout_target: LayoutTarget = io::load_deser_from(cfg_file)?;
 
+        // Resolve the input paths
+        if let Some(input_dir) = input_dir {
+            layout_target.input_path = io::resolve_path(&layout_target.input_path, Some(input_dir));
+            layout_target.method.resolve_input_paths(input_dir);
+        }
+        if let Some(surface_path) = surface_path {
+            layout_target.input_path = surface_path.to_string();
+        }
+
         // Check that the input path is a supported filetype
         let mut supported = false;
         for filetype in layout_target.method.get_input_filetypes() {
@@ -43,26 +93,80 @@ impl LayoutTarget {
             args::err_str("Input file type not supported by layout method")?;
         }
 
-        // Check the output path
-        if layout_target.save && layout_target.output_path.is_none() {
-            args::err_str("Layout output path not specified, but force_save was set")?;
-        }
-
         layout_target.save |= is_last;
 
+        // Check the output path (only a given one is created up front, to check it can be written)
         if layout_target.save {
             if let Some(output_path) = layout_target.output_path.as_ref() {
-                if !output_path.ends_with(".json")
+                let extension = layout_target.method.get_output_extension();
+                if !output_path.ends_with(&format!(".{}", extension))
                 {
-                    args::err_str("Layout output path must end with .json")?;
+                    args::err_str(&format!("Layout output path must end with .{}", extension))?;
                 }
                 let _ = crate::io::create(output_path)?;
             }
-            else {
-                args::err_str("Layout output path not specified, but saving is required at the last stage")?;
-            }
+        }
+
+        // Default the output path next to the config file if the layout, manifest, or capacitor summary will be written without one.
+        // Configs with an input directory (e.g. an extracted bundle) may sit in a temporary directory, so they default to the working directory.
+        if layout_target.output_path.is_none() && (layout_target.save || layout_target.manifest || layout_target.capacitor_summary.is_some()) {
+            let cfg_dir = match input_dir {
+                Some(_) => std::path::Path::new(""),
+                None => std::path::Path::new(cfg_file).parent().unwrap_or(std::path::Path::new("")),
+            };
+            let output_path = layout_target.default_output_path(cfg_dir);
+            println!("Layout output path not specified, defaulting to {}", output_path);
+            layout_target.output_path = Some(output_path);
         }
 
         Ok(layout_target)
     }
+
+    /// Save the layout target as it will run, with every default filled in, so it can be inspected or run again.
+    /// The format follows the extension of `path` (see `io::save_ser_to`).
+    pub fn save_effective_config(&self, path: &str) -> io::IoResult<()> {
+        println!("Saving effective layout config to {}...", path);
+        io::save_ser_to(path, self)
+    }
+
+    /// Get the default output path for the layout file: `layout` in `cfg_dir`, with the method's output extension.
+    pub fn default_output_path(&self, cfg_dir: &std::path::Path) -> String {
+        cfg_dir.join(format!("layout.{}", self.method.get_output_extension())).to_string_lossy().to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::temp_dir;
+
+    #[test]
+    fn default_output_path_uses_the_method_extension() {
+        let test_dir = temp_dir("layout_cfg_test");
+        let cfg_dir = test_dir.path();
+        let cfg_file = cfg_dir.join("layout.yaml").to_string_lossy().to_string();
+
+        // Not the last stage, so nothing is created, but the manifest still needs an output path
+        std::fs::write(&cfg_file, "input_path: plane.stl\nmanifest: true\nmethod:\n  name: adam_circles\n  args:\n    circles: [{center: {x: 0.0, y: 0.0, z: 0.0}}]\n").unwrap();
+        let layout_target = LayoutTarget::from_cfg_file(&cfg_file, None, None, false).unwrap();
+        let expected = cfg_dir.join(format!("layout.{}", layout_target.method.get_output_extension())).to_string_lossy().to_string();
+        assert_eq!(layout_target.output_path.as_deref(), Some(expected.as_str()));
+        assert_eq!(layout_target.default_output_path(cfg_dir), expected);
+
+        // Saving at the last stage defaults next to the config too, without creating the file before the run
+        let _ = std::fs::remove_file(&expected);
+        let layout_target = LayoutTarget::from_cfg_file(&cfg_file, None, None, true).unwrap();
+        assert_eq!(layout_target.output_path.as_deref(), Some(expected.as_str()));
+        assert!(!std::path::Path::new(&expected).exists());
+
+        // Without anything to write, the output path stays unset
+        std::fs::write(&cfg_file, "input_path: plane.stl\nmethod:\n  name: adam_circles\n  args:\n    circles: [{center: {x: 0.0, y: 0.0, z: 0.0}}]\n").unwrap();
+        assert!(LayoutTarget::from_cfg_file(&cfg_file, None, None, false).unwrap().output_path.is_none());
+
+        // An output path with another extension is rejected when saving
+        let output_path = cfg_dir.join("layout.txt").to_string_lossy().to_string();
+        std::fs::write(&cfg_file, format!("input_path: plane.stl\noutput_path: {}\nmethod:\n  name: adam_circles\n  args:\n    circles: [{{center: {{x: 0.0, y: 0.0, z: 0.0}}}}]\n", output_path)).unwrap();
+        let error = LayoutTarget::from_cfg_file(&cfg_file, None, None, true).unwrap_err().to_string();
+        assert!(error.contains("must end with .json"), "Unexpected error: {}", error);
+    }
 }
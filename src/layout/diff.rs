@@ -0,0 +1,112 @@
+use serde::Serialize;
+
+use crate::layout;
+use crate::geo_3d::*;
+
+/// Differences between two layouts, coil by coil (matched by index).
+#[derive(Debug)]
+#[derive(Serialize)]
+pub struct LayoutDiff {
+    /// Per-coil differences (second layout minus first), for the coils in both layouts.
+    pub coils: Vec<CoilDiff>,
+    /// Number of coils in the first layout.
+    pub coil_count_a: usize,
+    /// Number of coils in the second layout.
+    pub coil_count_b: usize,
+    /// Maximum coupling factor magnitude over close pairs, in each layout.
+    pub max_coupling: (Option<f32>, Option<f32>),
+    /// RMS coupling factor over close pairs, in each layout.
+    pub rms_coupling: (Option<f32>, Option<f32>),
+}
+impl LayoutDiff {
+    /// Check if the two layouts have different coil counts (coils appeared or disappeared).
+    pub fn count_changed(&self) -> bool {
+        self.coil_count_a != self.coil_count_b
+    }
+
+    /// Change in maximum close coupling (second minus first), if both layouts have close pairs.
+    pub fn max_coupling_delta(&self) -> Option<f32> {
+        Some(self.max_coupling.1? - self.max_coupling.0?)
+    }
+
+    /// Change in RMS close coupling (second minus first), if both layouts have close pairs.
+    pub fn rms_coupling_delta(&self) -> Option<f32> {
+        Some(self.rms_coupling.1? - self.rms_coupling.0?)
+    }
+}
+
+/// Differences for a single coil (second layout minus first).
+#[derive(Debug)]
+#[derive(Serialize)]
+pub struct CoilDiff {
+    pub coil_id: usize,
+    /// Change in the coil center, in mm.
+    pub center_delta: GeoVector,
+    /// Change in the average radius, in mm.
+    pub radius_delta: f32,
+    /// Change in the wire length, in mm.
+    pub wire_length_delta: f32,
+}
+
+/// Compare two layouts. Coils are matched by index, so extra coils in either layout are only counted.
+/// Coupling is summarized over pairs closer than `close_cutoff` times the sum of their average radii.
+pub fn diff_layouts(layout_a: &layout::Layout, layout_b: &layout::Layout, close_cutoff: f32) -> LayoutDiff {
+    let coils = layout_a.coils.iter().zip(layout_b.coils.iter()).enumerate()
+        .map(|(coil_id, (coil_a, coil_b))| CoilDiff{
+            coil_id,
+            center_delta: coil_b.center - coil_a.center,
+            radius_delta: coil_b.average_radius() - coil_a.average_radius(),
+            wire_length_delta: coil_b.wire_length() - coil_a.wire_length(),
+        })
+        .collect();
+
+    let (max_a, rms_a) = layout_a.close_coupling_stats(close_cutoff).unzip();
+    let (max_b, rms_b) = layout_b.close_coupling_stats(close_cutoff).unzip();
+
+    LayoutDiff{
+        coils,
+        coil_count_a: layout_a.coils.len(),
+        coil_count_b: layout_b.coils.len(),
+        max_coupling: (max_a, max_b),
+        rms_coupling: (rms_a, rms_b),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn circle_layout(centers: &[Point], radius: f32) -> layout::Layout {
+        layout::Layout{coils: centers.iter().map(|c| crate::test_fixtures::circle_coil(*c, radius)).collect()}
+    }
+
+    #[test]
+    fn diff_reports_translation() {
+        let centers = [Point::new(0.0, 0.0, 0.0), Point::new(8.0, 0.0, 0.0), Point::new(0.0, 8.0, 0.0)];
+        let translation = GeoVector::new(1.5, -2.0, 0.5);
+        let layout_a = circle_layout(&centers, 5.0);
+        let layout_b = circle_layout(&centers.map(|c| c + translation), 5.0);
+
+        let diff = diff_layouts(&layout_a, &layout_b, 0.95);
+        assert!(!diff.count_changed());
+        assert_eq!(diff.coils.len(), 3);
+        for coil_diff in diff.coils.iter() {
+            assert!(coil_diff.center_delta.approx_eq(&translation, 1e-4), "Coil {} moved by {}", coil_diff.coil_id, coil_diff.center_delta);
+            assert!(coil_diff.radius_delta.abs() < 1e-4);
+            assert!(coil_diff.wire_length_delta.abs() < 1e-3);
+        }
+        assert!(diff.rms_coupling_delta().unwrap().abs() < 1e-4);
+        assert!(diff.max_coupling_delta().unwrap().abs() < 1e-4);
+    }
+
+    #[test]
+    fn diff_flags_count_change() {
+        let layout_a = circle_layout(&[Point::new(0.0, 0.0, 0.0), Point::new(8.0, 0.0, 0.0)], 5.0);
+        let layout_b = circle_layout(&[Point::new(0.0, 0.0, 0.0)], 5.0);
+
+        let diff = diff_layouts(&layout_a, &layout_b, 0.95);
+        assert!(diff.count_changed());
+        assert_eq!(diff.coils.len(), 1);
+        assert!(diff.max_coupling.1.is_none());
+    }
+}
@@ -0,0 +1,105 @@
+use serde::Serialize;
+
+use crate::layout;
+
+/// Reproducibility manifest, written next to the layout output.
+/// Ties a layout back to the crate version, config, and input surface that produced it.
+#[derive(Debug)]
+#[derive(Serialize)]
+pub struct Manifest {
+    /// Crate version that produced the layout.
+    pub comrade_version: String,
+    /// Seconds since the UNIX epoch when the crate was built (set by the build script).
+    pub build_unix_time: u64,
+    /// Input surface filepath.
+    pub input_path: String,
+    /// FNV-1a (64 bit) hash of the input surface file contents, as hex.
+    pub input_hash: String,
+    /// Resolved layout method config (including defaults).
+    pub config: serde_json::Value,
+    /// Number of coils in the layout.
+    pub coil_count: usize,
+    /// RMS coupling factor over close coil pairs (center distance below 0.95 of the summed radii).
+    pub rms_coupling: Option<f32>,
+}
+
+/// Build the manifest for a layout target and its output.
+pub fn build_manifest(layout_target: &layout::LayoutTarget, layout_out: &layout::Layout) -> layout::ProcResult<Manifest> {
+    let input_bytes = crate::io::read_to_bytes(&layout_target.input_path)?;
+    let config = match serde_json::to_value(&layout_target.method) {
+        Ok(config) => config,
+        Err(error) => return layout::err_str(&format!("Failed to serialize the layout method for the manifest: {}", error)),
+    };
+    let build_unix_time = env!("COMRADE_BUILD_UNIX_TIME").parse().unwrap_or(0);
+
+    Ok(Manifest{
+        comrade_version: env!("CARGO_PKG_VERSION").to_string(),
+        build_unix_time,
+        input_path: layout_target.input_path.clone(),
+        input_hash: format!("{:016x}", fnv1a_64(&input_bytes)),
+        config,
+        coil_count: layout_out.coils.len(),
+        rms_coupling: layout_out.close_coupling_stats(0.95).map(|(_, rms)| rms),
+    })
+}
+
+/// Write the manifest as `manifest.json` in the same directory as the layout output.
+pub fn save_manifest(layout_target: &layout::LayoutTarget, layout_out: &layout::Layout) -> layout::ProcResult<()> {
+    let output_path = match layout_target.output_path.as_ref() {
+        Some(output_path) => output_path,
+        None => panic!("BUG: Writing a manifest, but missing output path! Should've been checked!"),
+    };
+    let manifest_path = std::path::Path::new(output_path).with_file_name("manifest.json");
+    let manifest_path = manifest_path.to_string_lossy();
+
+    println!("Saving manifest to {}...", manifest_path);
+    let manifest = build_manifest(layout_target, layout_out)?;
+    crate::io::save_ser_to(&manifest_path, &manifest)?;
+    Ok(())
+}
+
+/// FNV-1a 64 bit hash, stable across builds and platforms.
+fn fnv1a_64(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in bytes.iter() {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::temp_dir;
+    use crate::geo_3d::*;
+    use crate::layout::methods::adam_circles;
+
+    #[test]
+    fn manifest_records_config_and_surface_hash() {
+        let test_dir = temp_dir("manifest_test");
+        let input_path = test_dir.path().join("manifest.stl");
+        let input_path = input_path.to_string_lossy().to_string();
+        crate::io::stl::save_stl_from_surface(&Surface::square_grid(10.0, 4), &input_path).unwrap();
+
+        let circles = vec![adam_circles::CircleArgs::default(); 3];
+        let layout_target = layout::LayoutTarget{
+            input_path: input_path.clone(),
+            manifest: true,
+            ..crate::test_fixtures::layout_target(layout::MethodEnum::AdamCircles(adam_circles::Method{
+                circles,
+                ..Default::default()
+            }))
+        };
+
+        let manifest = build_manifest(&layout_target, &layout::Layout::new()).unwrap();
+        assert_eq!(manifest.config["args"]["circles"].as_array().map(|c| c.len()), Some(3));
+        assert!(!manifest.input_hash.is_empty());
+        assert_eq!(manifest.input_path, input_path);
+        assert!(manifest.rms_coupling.is_none());
+
+        // The build time is fixed at compile time, not when the manifest is written
+        assert!(manifest.build_unix_time > 0);
+        assert_eq!(build_manifest(&layout_target, &layout::Layout::new()).unwrap().build_unix_time, manifest.build_unix_time);
+    }
+}
@@ -5,12 +5,16 @@
  * New methods need:
  * - A struct implementing `LayoutMethodTrait`
  * - An enum variant containing that struct in `MethodEnum`
+ *   (this also registers it by config key in `method_registry`,
+ *   which builds the `LayoutMethod` of each layout target)
  * 
  */
 
 use enum_dispatch::enum_dispatch;
 use serde::{Serialize, Deserialize};
-use strum::EnumIter;
+use serde::ser::SerializeMap;
+use strum::{EnumIter, IntoEnumIterator};
+use std::collections::BTreeMap;
 
 use crate::layout;
 
@@ -28,7 +32,7 @@ pub mod helper;
 mod gradient_circles;
 mod alternating_circles;
 mod k_means_isometric;
-mod adam_circles;
+pub(crate) mod adam_circles;
 
 /// Layout methods enum.
 /// To add a new method:
@@ -57,6 +61,160 @@ pub enum MethodEnum {
     #[serde(rename = "k_means_isometric")]
     KMeansIsometric(k_means_isometric::Method),
 }
+impl MethodEnum {
+    /// Get the config key of the layout method (the `name` in config files).
+    pub fn get_config_key(&self) -> String {
+        match serde_json::to_value(self).ok()
+            .and_then(|value| value.get("name").and_then(|name| name.as_str()).map(|name| name.to_string())) {
+            Some(key) => key,
+            None => panic!("BUG: Layout method has no config name"),
+        }
+    }
+
+    /// Get a one-line description of the layout method, for listing.
+    pub fn get_method_description(&self) -> &'static str {
+        match self {
+            MethodEnum::AdamCircles(_) => "Circles optimized for low coupling with the Adam algorithm, with optional symmetry",
+            MethodEnum::GradientCircles(_) => "Circles optimized for low coupling with gradient descent, with optional symmetry",
+            MethodEnum::AlternatingCircles(_) => "Circles optimized by alternating radius and position steps, with optional symmetry",
+            MethodEnum::KMeansIsometric(_) => "Circles seeded by k-means clustering of the surface, then optimized with Adam",
+        }
+    }
+}
+
+/// Factory for a layout method, built from the method's `args` config value.
+pub type MethodFactory = Box<dyn Fn(&serde_json::Value) -> layout::ProcResult<Box<dyn LayoutMethodTrait>>>;
+
+/// Registry of layout methods by config key (e.g. `"adam_circles"`).
+/// Built-in methods are registered from `MethodEnum` by `method_registry`,
+/// so adding a variant there is enough to make a method resolvable by name.
+pub struct MethodRegistry {
+    factories: BTreeMap<String, MethodFactory>,
+}
+impl MethodRegistry {
+    /// Create an empty registry.
+    pub fn new() -> Self {
+        MethodRegistry{factories: BTreeMap::new()}
+    }
+
+    /// Register a method factory under a config key, replacing any existing entry.
+    pub fn register(&mut self, key: &str, factory: MethodFactory) {
+        self.factories.insert(key.to_string(), factory);
+    }
+
+    /// Check if a config key is registered.
+    pub fn contains(&self, key: &str) -> bool {
+        self.factories.contains_key(key)
+    }
+
+    /// Get the registered config keys, in sorted order.
+    pub fn keys(&self) -> Vec<&str> {
+        self.factories.keys().map(|key| key.as_str()).collect()
+    }
+
+    /// Build the method registered under `key` from its `args` config value.
+    /// Returns a `ProcResult` with the method or an `Err` if the key is unknown or the arguments are invalid.
+    pub fn build(&self, key: &str, args: &serde_json::Value) -> layout::ProcResult<Box<dyn LayoutMethodTrait>> {
+        match self.factories.get(key) {
+            Some(factory) => factory(args),
+            None => layout::err_str(&format!("Unknown layout method '{}'. Registered methods: {:?}", key, self.keys())),
+        }
+    }
+}
+impl Default for MethodRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Load a surface from an STL or GMSH (ASCII) MSH file, by extension.
+pub fn load_surface(input_path: &str) -> layout::ProcResult<crate::geo_3d::Surface> {
+    if input_path.ends_with(".msh") {
+        println!("Loading MSH file: {}", input_path);
+        return Ok(crate::geo_3d::Surface::from_msh(input_path)?);
+    }
+    println!("Loading STL file: {}", input_path);
+    Ok(crate::io::stl::load_stl(input_path)?)
+}
+
+/// Get the layout method registry, with every `MethodEnum` variant registered under its config key.
+pub fn method_registry() -> MethodRegistry {
+    let mut registry = MethodRegistry::new();
+    for method in MethodEnum::iter() {
+        let key = method.get_config_key();
+        let factory_key = key.clone();
+        registry.register(&key, Box::new(move |args: &serde_json::Value| {
+            let tagged = serde_json::json!({"name": factory_key, "args": args});
+            match serde_json::from_value::<MethodEnum>(tagged) {
+                Ok(method) => Ok(Box::new(method) as Box<dyn LayoutMethodTrait>),
+                Err(error) => layout::err_str(&format!("Invalid arguments for layout method '{}': {}", factory_key, error)),
+            }
+        }));
+    }
+    registry
+}
+
+/// Layout method of a target, built through the method registry from its config key and arguments.
+/// Dereferences to the method, and serializes back to the `name`/`args` form of the config with every default filled in.
+pub struct LayoutMethod {
+    key: String,
+    method: Box<dyn LayoutMethodTrait>,
+}
+impl LayoutMethod {
+    /// Build the method registered under `key` in the registry from its `args` config value.
+    /// Returns a `ProcResult` with the method or an `Err` if the key is unknown or the arguments are invalid.
+    pub fn build(registry: &MethodRegistry, key: &str, args: &serde_json::Value) -> layout::ProcResult<Self> {
+        Ok(LayoutMethod{key: key.to_string(), method: registry.build(key, args)?})
+    }
+
+    /// Get the config key the method was built from.
+    pub fn get_config_key(&self) -> &str {
+        &self.key
+    }
+}
+impl From<MethodEnum> for LayoutMethod {
+    fn from(method: MethodEnum) -> Self {
+        LayoutMethod{key: method.get_config_key(), method: Box::new(method)}
+    }
+}
+impl std::ops::Deref for LayoutMethod {
+    type Target = dyn LayoutMethodTrait;
+    fn deref(&self) -> &Self::Target {
+        self.method.as_ref()
+    }
+}
+impl std::ops::DerefMut for LayoutMethod {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.method.as_mut()
+    }
+}
+impl std::fmt::Debug for LayoutMethod {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let args = serde_json::to_value(self.method.effective_args()).unwrap_or_default();
+        f.debug_struct("LayoutMethod").field("name", &self.key).field("args", &args).finish()
+    }
+}
+impl Serialize for LayoutMethod {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(2))?;
+        map.serialize_entry("name", &self.key)?;
+        map.serialize_entry("args", self.method.effective_args())?;
+        map.end()
+    }
+}
+impl<'de> Deserialize<'de> for LayoutMethod {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(deny_unknown_fields)]
+        struct TaggedMethod {
+            name: String,
+            #[serde(default)]
+            args: serde_json::Value,
+        }
+        let tagged = TaggedMethod::deserialize(deserializer)?;
+        LayoutMethod::build(&method_registry(), &tagged.name, &tagged.args).map_err(serde::de::Error::custom)
+    }
+}
 
 //
 // ------------------------------------------------------------
@@ -78,21 +236,127 @@ pub trait LayoutMethodTrait {
     fn get_method_display_name(&self) -> &'static str;
 
     /// Get a vector of viable input filetypes for the layout method.
-    /// Defaults to STL.
+    /// Defaults to STL and GMSH (ASCII) MSH.
     fn get_input_filetypes(&self) -> Vec<&'static str> {
-        vec!["stl"]
+        vec!["stl", "msh"]
+    }
+
+    /// Get the output file extension for the layout method.
+    /// Defaults to JSON, the format `layout::save_layout` writes.
+    fn get_output_extension(&self) -> &'static str {
+        "json"
+    }
+
+    /// Get the method arguments for serializing (the `args` of the method config), with every default filled in.
+    fn effective_args(&self) -> &dyn erased_serde::Serialize;
+
+    /// Whether the method was asked for verbose output, which also prints the timing breakdown of a run.
+    /// Defaults to false.
+    fn is_verbose(&self) -> bool {
+        false
     }
 
     /// Load the layout input file. 
-    /// Default implementation is for STL files.
+    /// Default implementation is for STL and GMSH (ASCII) MSH files (see `load_surface`).
     fn load_surface(&self, input_path: &str) -> layout::ProcResult<crate::geo_3d::Surface> {
-        println!("Loading STL file: {}", input_path);
-        Ok(crate::io::stl::load_stl(input_path)?)
+        load_surface(input_path)
     }
     
+    /// Resolve relative input paths in the method arguments against a base directory (e.g. an extracted bundle).
+    /// Default implementation does nothing, for methods without extra input files.
+    fn resolve_input_paths(&mut self, _base_dir: &std::path::Path) {}
+
     /// Run the layout process with the given arguments.
     /// Uses the `layout` module.
     /// Takes a loaded `Surface`.
     /// Returns a `ProcResult` with the `layout::Layout` or an `Err`.
     fn do_layout(&self, surface: &crate::geo_3d::Surface) -> layout::ProcResult<layout::Layout>;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct DummyMethod;
+    impl LayoutMethodTrait for DummyMethod {
+        fn get_method_display_name(&self) -> &'static str {
+            "Dummy"
+        }
+
+        fn effective_args(&self) -> &dyn erased_serde::Serialize {
+            &()
+        }
+
+        fn do_layout(&self, _surface: &crate::geo_3d::Surface) -> layout::ProcResult<layout::Layout> {
+            Ok(layout::Layout::new())
+        }
+    }
+
+    #[test]
+    fn registered_dummy_method_resolves_by_key() {
+        let mut registry = method_registry();
+        assert!(registry.contains("adam_circles"));
+        assert!(!registry.contains("dummy"));
+        assert!(registry.build("dummy", &serde_json::json!({})).is_err());
+
+        registry.register("dummy", Box::new(|_args: &serde_json::Value| {
+            Ok(Box::new(DummyMethod) as Box<dyn LayoutMethodTrait>)
+        }));
+        let method = registry.build("dummy", &serde_json::json!({})).unwrap();
+        assert_eq!(method.get_method_display_name(), "Dummy");
+        assert!(registry.keys().contains(&"dummy"));
+    }
+
+    #[test]
+    fn layout_method_is_built_through_the_registry() {
+        let method: LayoutMethod = serde_json::from_value(serde_json::json!({"name": "adam_circles", "args": {"iterations": 2, "circles": []}})).unwrap();
+        assert_eq!(method.get_config_key(), "adam_circles");
+        assert!(!method.is_verbose());
+
+        // It serializes back with the defaults filled in
+        let cfg_value = serde_json::to_value(&method).unwrap();
+        assert_eq!(cfg_value["name"], "adam_circles");
+        assert_eq!(cfg_value["args"]["iterations"], 2);
+        assert!(cfg_value["args"]["close_cutoff"].is_number());
+
+        let error = serde_json::from_value::<LayoutMethod>(serde_json::json!({"name": "dummy", "args": {}})).unwrap_err().to_string();
+        assert!(error.contains("Unknown layout method 'dummy'"), "Unexpected error: {}", error);
+    }
+
+    #[test]
+    fn adam_only_options_are_rejected_by_other_methods() {
+        // Method args with one circle, each with the extra fields merged in
+        let args_with = |method_args: serde_json::Value, circle_args: serde_json::Value| {
+            let mut circle = serde_json::json!({"center": {"x": 0.0, "y": 0.0, "z": 0.0}, "radius": 5.0});
+            circle.as_object_mut().unwrap().extend(circle_args.as_object().unwrap().clone());
+            let mut args = serde_json::json!({"circles": [circle]});
+            args.as_object_mut().unwrap().extend(method_args.as_object().unwrap().clone());
+            args
+        };
+        // Method options fail as unknown fields, circle options by name
+        let adam_only = [
+            (serde_json::json!({}), serde_json::json!({"aspect_ratio": 2.0}), "`aspect_ratio` is only supported by the adam_circles"),
+            (serde_json::json!({}), serde_json::json!({"rotation_angle": 30.0}), "`rotation_angle` is only supported by the adam_circles"),
+            (serde_json::json!({}), serde_json::json!({"crossing_priority": 1}), "`crossing_priority` is only supported by the adam_circles"),
+            (serde_json::json!({"curvature_threshold": 0.05}), serde_json::json!({}), "unknown field"),
+            (serde_json::json!({"trajectory_output": "trajectory.csv"}), serde_json::json!({}), "unknown field"),
+        ];
+        for name in ["gradient_circles", "alternating_circles"] {
+            let args = args_with(serde_json::json!({}), serde_json::json!({}));
+            assert!(serde_json::from_value::<LayoutMethod>(serde_json::json!({"name": name, "args": args})).is_ok());
+
+            // Circle options that do not change the layout are still ignored
+            let args = args_with(serde_json::json!({}), serde_json::json!({"label": "L1", "group": 0, "weight": 2.0}));
+            assert!(serde_json::from_value::<LayoutMethod>(serde_json::json!({"name": name, "args": args})).is_ok());
+
+            for (method_args, circle_args, expected) in adam_only.iter() {
+                let args = args_with(method_args.clone(), circle_args.clone());
+                let error = match serde_json::from_value::<LayoutMethod>(serde_json::json!({"name": name, "args": args})) {
+                    Ok(_) => panic!("{} accepted the ADAM-only options {} {}", name, method_args, circle_args),
+                    Err(error) => error.to_string(),
+                };
+                assert!(error.contains(expected), "Unexpected error: {}", error);
+            }
+        }
+    }
+}
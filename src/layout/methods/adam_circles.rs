@@ -7,46 +7,107 @@
 
 use crate::layout;
 use crate::geo_3d::*;
+use crate::warnings;
+use crate::timing;
+use crate::progress::{self, ProgressEvent};
 use layout::methods;
 use methods::helper::{
-    sphere_intersect,
-    clean_coil_by_angle,
+    StaticLayoutPaths,
+    circle_coil,
+    ProjectionMode,
+    RadiusMode,
+    CenterFreedomMode,
+    DistanceMetric,
+    PreShift,
+    NestingPolicy,
+    BreakSpacing,
+    OneSidedPolicy,
+    is_one_sided,
+    mirror_complete,
+    nested_pairs,
+    SeededRng,
     merge_segments,
     add_even_breaks_by_angle,
-    closest_point,
+    even_break_port,
+    zero_angle_reference,
+    closest_point_by,
+    Intersections,
+    bounding_radii,
+    sym_pair_center,
+    stretch_coil,
+    SymmetryPlane,
+    check_coil_count,
+    check_sym_groups,
+    check_wire_params,
+    check_circle_count,
+    rms_coupling,
+    check_epsilon,
 };
 
 use serde::{Serialize, Deserialize};
 use itertools::concat;
 
+/// Relative slack on the clearance when flagging tight wire spacing.
+/// The overlap offset reaches the full clearance at the crossing itself, which falls between wire vertices.
+const SPACING_TOLERANCE: f32 = 0.01;
+
 /// ADAM Circles method struct.
 /// This struct contains all the parameters for the ADAM Circles layout method.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 #[derive(Serialize, Deserialize)]
 #[serde(deny_unknown_fields)]
 pub struct Method {
     // Optional symmetry plane
     #[serde(default = "Method::default_symmetry_plane", alias = "plane")]
-    pub symmetry_plane: Option<Plane>,
+    pub symmetry_plane: Option<SymmetryPlane>,
     #[serde(default = "Method::default_layout_in_path", rename = "layout_in", alias = "static_layout")]
-    pub layout_in_path: Option<String>,
+    pub layout_in_path: Option<StaticLayoutPaths>,
+    // Optional conductive shield plane (adds counter-wound image coils to the coupling)
+    #[serde(default = "Method::default_shield_plane", alias = "shield")]
+    pub shield_plane: Option<Plane>,
 
     // Circle intersection parameters
     pub circles: Vec<CircleArgs>,
+    #[serde(default = "Method::default_radius_mode")]
+    pub radius_mode: RadiusMode,
+    #[serde(default = "Method::default_max_coils")]
+    pub max_coils: Option<usize>,
+    #[serde(default = "Method::default_skip_failed_coils", alias = "skip_failed")]
+    pub skip_failed_coils: bool,
     #[serde(default = "Method::default_epsilon")]
     pub epsilon: f32,
     #[serde(default = "Method::default_pre_shift")]
-    pub pre_shift: bool,
+    pub pre_shift: PreShift,
+    #[serde(default = "Method::default_min_vertices")]
+    pub min_vertices: usize,
+    #[serde(default = "Method::default_projection_mode", alias = "projection")]
+    pub projection_mode: ProjectionMode,
+    #[serde(default = "Method::default_curvature_threshold", alias = "max_curvature")]
+    pub curvature_threshold: Option<f32>,
 
     // Overlap handling parameters
+    #[serde(default = "Method::default_resolve_overlaps")]
+    pub resolve_overlaps: bool,
     #[serde(default = "Method::default_clearance")]
     pub clearance: f32,
+    #[serde(default = "Method::default_min_bend_radius")]
+    pub min_bend_radius: Option<f32>,
+    #[serde(default = "Method::default_max_stack_offset", alias = "max_stack")]
+    pub max_stack_offset: Option<f32>,
     #[serde(default = "Method::default_wire_radius")]
     pub wire_radius: f32,
     #[serde(default = "Method::default_zero_angle_vector")]
     pub zero_angle_vector: GeoVector,
     #[serde(default = "Method::default_backup_zero_angle_vector")]
     pub backup_zero_angle_vector: GeoVector,
+    #[serde(default = "Method::default_port_keepout_radius", alias = "port_keepout")]
+    pub port_keepout_radius: f32,
+    #[serde(default = "Method::default_nesting_policy", alias = "nesting")]
+    pub nesting_policy: NestingPolicy,
+    #[serde(default = "Method::default_break_spacing")]
+    pub break_spacing: BreakSpacing,
+    #[serde(default = "Method::default_one_sided_policy", alias = "one_sided")]
+    pub one_sided_policy: OneSidedPolicy,
 
     // Iteration parameters
     #[serde(default = "Method::default_iterations")]
@@ -63,8 +124,32 @@ pub struct Method {
     pub radius_freedom: f32,
     #[serde(default = "Method::default_center_freedom")]
     pub center_freedom: f32,
+    #[serde(default = "Method::default_center_freedom_mode")]
+    pub center_freedom_mode: CenterFreedomMode,
+    #[serde(default = "Method::default_boundary_metric")]
+    pub boundary_metric: DistanceMetric,
+    #[serde(default = "Method::default_allowed_centers", alias = "mounting_points")]
+    pub allowed_centers: Option<Vec<Point>>,
     #[serde(default = "Method::default_close_cutoff")]
     pub close_cutoff: f32,
+    #[serde(default = "Method::default_coupling_floor")]
+    pub coupling_floor: f32,
+    #[serde(default = "Method::default_lock_aspect", alias = "lock_on_plane")]
+    pub lock_aspect: bool,
+
+    // Random restart parameters
+    #[serde(default = "Method::default_restarts")]
+    pub restarts: usize,
+    #[serde(default = "Method::default_jitter")]
+    pub jitter: f32,
+    #[serde(default = "Method::default_seed")]
+    pub seed: u64,
+
+    // Coarse-to-fine parameters
+    #[serde(default = "Method::default_coarse_stages")]
+    pub coarse_stages: Vec<usize>,
+    #[serde(default = "Method::default_polish_iterations")]
+    pub polish_iterations: Option<usize>,
 
     // Verbosity
     #[serde(default = "Method::default_verbose")]
@@ -73,35 +158,77 @@ pub struct Method {
     pub warn_on_shift: bool,
     #[serde(default = "Method::default_statistics_level", alias = "statistics")]
     pub statistics_level: u32,
+    #[serde(default = "Method::default_larmor_mhz")]
+    pub larmor_mhz: f32,
+    #[serde(default = "Method::default_coupling_sign_check", alias = "expected_coupling_signs")]
+    pub coupling_sign_check: Option<layout::CouplingSignCheck>,
 
     // Save final cfg output
     #[serde(default = "Method::default_final_cfg_output")]
     pub final_cfg_output: Option<String>,
+
+    // Save the center and radius of each coil at every iteration (CSV), from the restart that's kept
+    #[serde(default = "Method::default_trajectory_output", alias = "trajectory")]
+    pub trajectory_output: Option<String>,
 }
 impl Method {
-    pub fn example_symmetry_plane() -> Option<Plane> {
-        Some(Plane::from_normal_and_offset(GeoVector::xhat(), 0.0))
+    pub fn example_symmetry_plane() -> Option<SymmetryPlane> {
+        Some(SymmetryPlane::Fixed(Plane::from_normal_and_offset(GeoVector::xhat(), 0.0)))
     }
-    pub fn default_symmetry_plane() -> Option<Plane> {
+    pub fn default_symmetry_plane() -> Option<SymmetryPlane> {
         None
     }
-    pub fn example_layout_in_path() -> Option<String> {
-        Some("PATH/TO/INITIAL/CFG.json".to_string())
+    pub fn example_layout_in_path() -> Option<StaticLayoutPaths> {
+        Some(StaticLayoutPaths::single("PATH/TO/INITIAL/CFG.json"))
+    }
+    pub fn default_layout_in_path() -> Option<StaticLayoutPaths> {
+        None
     }
-    pub fn default_layout_in_path() -> Option<String> {
+
+    pub fn default_shield_plane() -> Option<Plane> {
         None
     }
 
+    pub fn default_skip_failed_coils() -> bool {
+        false
+    }
+    pub fn default_max_coils() -> Option<usize> {
+        None
+    }
+    pub fn default_radius_mode() -> RadiusMode {
+        RadiusMode::Absolute
+    }
     pub fn default_epsilon() -> f32 {
         1.5
     }
-    pub fn default_pre_shift() -> bool {
-        true
+    pub fn default_pre_shift() -> PreShift {
+        PreShift::default()
+    }
+    pub fn default_min_vertices() -> usize {
+        0
+    }
+    pub fn default_projection_mode() -> ProjectionMode {
+        ProjectionMode::SphereIntersect
+    }
+    pub fn default_curvature_threshold() -> Option<f32> {
+        None
     }
 
+    pub fn default_resolve_overlaps() -> bool {
+        true
+    }
     pub fn default_clearance() -> f32 {
         1.29
     }
+    pub fn default_min_bend_radius() -> Option<f32> {
+        None
+    }
+    pub fn example_min_bend_radius() -> Option<f32> {
+        Some(1.0)
+    }
+    pub fn default_max_stack_offset() -> Option<f32> {
+        None
+    }
     pub fn default_wire_radius() -> f32 {
         0.645
     }
@@ -112,6 +239,23 @@ impl Method {
         GeoVector::yhat()
     }
 
+    pub fn default_port_keepout_radius() -> f32 {
+        0.0
+    }
+    pub fn example_port_keepout_radius() -> f32 {
+        5.0
+    }
+
+    pub fn default_nesting_policy() -> NestingPolicy {
+        NestingPolicy::default()
+    }
+    pub fn default_break_spacing() -> BreakSpacing {
+        BreakSpacing::default()
+    }
+    pub fn default_one_sided_policy() -> OneSidedPolicy {
+        OneSidedPolicy::default()
+    }
+
     pub fn default_iterations() -> usize {
         0
     }
@@ -130,12 +274,48 @@ impl Method {
     pub fn default_center_freedom() -> f32 {
         0.5
     }
+    pub fn default_boundary_metric() -> DistanceMetric {
+        DistanceMetric::default()
+    }
+    pub fn default_center_freedom_mode() -> CenterFreedomMode {
+        CenterFreedomMode::default()
+    }
+    pub fn default_allowed_centers() -> Option<Vec<Point>> {
+        None
+    }
     pub fn default_radius_freedom() -> f32 {
         0.15
     }
     pub fn default_close_cutoff() -> f32 {
         0.95
     }
+    pub fn default_coupling_floor() -> f32 {
+        0.0
+    }
+    pub fn default_lock_aspect() -> bool {
+        false
+    }
+    pub fn default_restarts() -> usize {
+        1
+    }
+    pub fn default_jitter() -> f32 {
+        0.0
+    }
+    pub fn example_jitter() -> f32 {
+        2.0
+    }
+    pub fn default_seed() -> u64 {
+        0
+    }
+    pub fn default_coarse_stages() -> Vec<usize> {
+        Vec::new()
+    }
+    pub fn example_coarse_stages() -> Vec<usize> {
+        vec![1000, 4000]
+    }
+    pub fn default_polish_iterations() -> Option<usize> {
+        None
+    }
     pub fn default_radius_reg() -> f32 {
         0.1
     }
@@ -149,6 +329,15 @@ impl Method {
     pub fn default_statistics_level() -> u32 {
         0
     }
+    pub fn default_larmor_mhz() -> f32 {
+        127.73
+    }
+    pub fn default_coupling_sign_check() -> Option<layout::CouplingSignCheck> {
+        None
+    }
+    pub fn example_coupling_sign_check() -> Option<layout::CouplingSignCheck> {
+        Some(layout::CouplingSignCheck::AdjacentNegative)
+    }
 
     pub fn example_final_cfg_output() -> Option<String> {
         Some("PATH/TO/FINAL/CFG.[json|yaml|toml]".to_string())
@@ -156,46 +345,135 @@ impl Method {
     pub fn default_final_cfg_output() -> Option<String> {
         None
     }
+    pub fn default_trajectory_output() -> Option<String> {
+        None
+    }
 }
 impl Default for Method{
     fn default() -> Self {
         Method{
             symmetry_plane: Self::example_symmetry_plane(),
             layout_in_path: Self::example_layout_in_path(),
+            shield_plane: Self::default_shield_plane(),
 
             circles: vec![CircleArgs::default(); 2],
+            radius_mode: Self::default_radius_mode(),
+            max_coils: Self::default_max_coils(),
+            skip_failed_coils: Self::default_skip_failed_coils(),
             epsilon: Self::default_epsilon(),
             pre_shift: Self::default_pre_shift(),
+            min_vertices: Self::default_min_vertices(),
+            projection_mode: Self::default_projection_mode(),
+            curvature_threshold: Self::default_curvature_threshold(),
 
+            resolve_overlaps: Self::default_resolve_overlaps(),
             clearance: Self::default_clearance(),
+            min_bend_radius: Self::example_min_bend_radius(),
+            max_stack_offset: Self::default_max_stack_offset(),
             wire_radius: Self::default_wire_radius(),
             zero_angle_vector: Self::default_zero_angle_vector(),
             backup_zero_angle_vector: Self::default_backup_zero_angle_vector(),
+            port_keepout_radius: Self::example_port_keepout_radius(),
+            nesting_policy: Self::default_nesting_policy(),
+            break_spacing: Self::default_break_spacing(),
+            one_sided_policy: Self::default_one_sided_policy(),
 
             iterations: Self::example_iterations(),
             step_size: Self::default_step_size(),
             first_moment_decay: Self::default_first_moment_decay(),
             second_moment_decay: Self::default_second_moment_decay(),
             center_freedom: Self::default_center_freedom(),
+            center_freedom_mode: Self::default_center_freedom_mode(),
+            boundary_metric: Self::default_boundary_metric(),
+            allowed_centers: Self::default_allowed_centers(),
             radius_freedom: Self::default_radius_freedom(),
             close_cutoff: Self::default_close_cutoff(),
+            coupling_floor: Self::default_coupling_floor(),
+            lock_aspect: Self::default_lock_aspect(),
             radius_reg: Self::default_radius_reg(),
 
+            restarts: Self::default_restarts(),
+            jitter: Self::example_jitter(),
+            seed: Self::default_seed(),
+
+            coarse_stages: Self::example_coarse_stages(),
+            polish_iterations: Self::default_polish_iterations(),
+
             verbose: Self::default_verbose(),
             warn_on_shift: Self::default_warn_on_shift(),
             statistics_level: Self::default_statistics_level(),
+            larmor_mhz: Self::default_larmor_mhz(),
+            coupling_sign_check: Self::example_coupling_sign_check(),
 
             final_cfg_output: Self::example_final_cfg_output(),
+            trajectory_output: Self::default_trajectory_output(),
+        }
+    }
+}
+
+/// Longest circle label, in bytes (see `CircleLabel`).
+const CIRCLE_LABEL_CAPACITY: usize = 32;
+
+/// User label of a circle (e.g. a row or rung name), up to 32 bytes.
+/// Stored inline so `CircleArgs` stays `Copy`; the coils carry it on as a `String`.
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[derive(Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct CircleLabel {
+    bytes: [u8; CIRCLE_LABEL_CAPACITY],
+    len: u8,
+}
+impl CircleLabel {
+    /// Get the label text.
+    pub fn as_str(&self) -> &str {
+        std::str::from_utf8(&self.bytes[..self.len as usize]).expect("BUG: Circle label isn't valid UTF-8!")
+    }
+}
+impl TryFrom<&str> for CircleLabel {
+    type Error = String;
+
+    fn try_from(label: &str) -> Result<Self, Self::Error> {
+        if label.len() > CIRCLE_LABEL_CAPACITY {
+            return Err(format!("Circle label '{}' is longer than {} bytes", label, CIRCLE_LABEL_CAPACITY));
         }
+        let mut bytes = [0; CIRCLE_LABEL_CAPACITY];
+        bytes[..label.len()].copy_from_slice(label.as_bytes());
+        Ok(CircleLabel{bytes, len: label.len() as u8})
+    }
+}
+impl TryFrom<String> for CircleLabel {
+    type Error = String;
+
+    fn try_from(label: String) -> Result<Self, Self::Error> {
+        CircleLabel::try_from(label.as_str())
+    }
+}
+impl From<CircleLabel> for String {
+    fn from(label: CircleLabel) -> Self {
+        label.as_str().to_string()
+    }
+}
+impl std::fmt::Debug for CircleLabel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self.as_str())
     }
 }
 
 /// Single element arguments
+/// The radius can also be given as a `diameter` (but not both).
+/// A `component` keeps the circle on that connected component of the surface (see `Surface::connected_components`),
+/// for surfaces with several disconnected shells. Components are found again on each coarse stage surface,
+/// and aren't supported with a symmetry plane.
+/// A `zero_angle_vector` overrides the method's for this coil only, to point its port (break 0) in a chosen direction.
+/// A `radius_reg` overrides the method's for this coil only; a high value effectively pins the coil's radius.
+/// A `weight` scales this coil's coupling gradients (a pair by the product of both weights),
+/// so raising it prioritizes decoupling this coil over the others. The reported RMS coupling, and the best
+/// iteration and restart picked by it, are unweighted.
 #[derive(Debug, Clone, Copy)]
 #[derive(Serialize, Deserialize)]
+#[serde(try_from = "CircleArgsInput")]
 pub struct CircleArgs {
     pub center: Point,
-    #[serde(default = "CircleArgs::default_coil_radius", alias = "radius")]
     pub coil_radius: f32,
     #[serde(default = "CircleArgs::default_break_count", alias = "breaks")]
     pub break_count: usize,
@@ -203,8 +481,89 @@ pub struct CircleArgs {
     pub break_angle_offset: f32,
     #[serde(default = "CircleArgs::default_on_symmetry_plane", alias = "on_sym")]
     pub on_symmetry_plane: bool,
+    #[serde(default = "CircleArgs::default_aspect_ratio", alias = "aspect")]
+    pub aspect_ratio: f32,
+    #[serde(default = "CircleArgs::default_rotation_angle", alias = "rotation")]
+    pub rotation_angle: f32,
+    #[serde(default = "CircleArgs::default_crossing_priority", alias = "priority")]
+    pub crossing_priority: i32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<CircleLabel>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub component: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub zero_angle_vector: Option<GeoVector>,
+    #[serde(default, skip_serializing_if = "Option::is_none", alias = "radius_regularization")]
+    pub radius_reg: Option<f32>,
+    #[serde(default = "CircleArgs::default_weight")]
+    pub weight: f32,
 }
-impl CircleArgs {
+/// Deserialization form of `CircleArgs`, with the radius optionally given as a diameter
+#[derive(Deserialize)]
+struct CircleArgsInput {
+    center: Point,
+    #[serde(alias = "radius")]
+    coil_radius: Option<f32>,
+    diameter: Option<f32>,
+    #[serde(default = "CircleArgs::default_break_count", alias = "breaks")]
+    break_count: usize,
+    #[serde(default = "CircleArgs::default_break_angle_offset", alias = "angle")]
+    break_angle_offset: f32,
+    #[serde(default = "CircleArgs::default_on_symmetry_plane", alias = "on_sym")]
+    on_symmetry_plane: bool,
+    #[serde(default = "CircleArgs::default_aspect_ratio", alias = "aspect")]
+    aspect_ratio: f32,
+    #[serde(default = "CircleArgs::default_rotation_angle", alias = "rotation")]
+    rotation_angle: f32,
+    #[serde(default = "CircleArgs::default_crossing_priority", alias = "priority")]
+    crossing_priority: i32,
+    #[serde(default)]
+    label: Option<CircleLabel>,
+    #[serde(default)]
+    group: Option<u32>,
+    #[serde(default)]
+    component: Option<usize>,
+    #[serde(default)]
+    zero_angle_vector: Option<GeoVector>,
+    #[serde(default, alias = "radius_regularization")]
+    radius_reg: Option<f32>,
+    #[serde(default = "CircleArgs::default_weight")]
+    weight: f32,
+}
+impl TryFrom<CircleArgsInput> for CircleArgs {
+    type Error = String;
+
+    fn try_from(input: CircleArgsInput) -> Result<Self, Self::Error> {
+        let coil_radius = match (input.coil_radius, input.diameter) {
+            (Some(_), Some(_)) => return Err("Circle has both a radius and a diameter, only one may be given".to_string()),
+            (Some(coil_radius), None) => coil_radius,
+            (None, Some(diameter)) => diameter / 2.0,
+            (None, None) => CircleArgs::default_coil_radius(),
+        };
+        if input.weight.is_nan() || input.weight < 0.0 {
+            return Err(format!("Circle weight must be non-negative, got {}", input.weight));
+        }
+        Ok(CircleArgs{
+            center: input.center,
+            coil_radius,
+            break_count: input.break_count,
+            break_angle_offset: input.break_angle_offset,
+            on_symmetry_plane: input.on_symmetry_plane,
+            aspect_ratio: input.aspect_ratio,
+            rotation_angle: input.rotation_angle,
+            crossing_priority: input.crossing_priority,
+            label: input.label,
+            group: input.group,
+            component: input.component,
+            zero_angle_vector: input.zero_angle_vector,
+            radius_reg: input.radius_reg,
+            weight: input.weight,
+        })
+    }
+}
+impl Default for CircleArgs {
     fn default() -> Self {
         CircleArgs{
             coil_radius: Self::default_coil_radius(),
@@ -212,8 +571,19 @@ impl CircleArgs {
             break_count: Self::default_break_count(),
             break_angle_offset: Self::default_break_angle_offset(),
             on_symmetry_plane: Self::default_on_symmetry_plane(),
+            aspect_ratio: Self::default_aspect_ratio(),
+            rotation_angle: Self::default_rotation_angle(),
+            crossing_priority: Self::default_crossing_priority(),
+            label: None,
+            group: None,
+            component: None,
+            zero_angle_vector: None,
+            radius_reg: None,
+            weight: Self::default_weight(),
         }
     }
+}
+impl CircleArgs {
     pub fn default_coil_radius() -> f32 {
         5.0
     }
@@ -229,6 +599,25 @@ impl CircleArgs {
     pub fn default_on_symmetry_plane() -> bool {
         false
     }
+    pub fn default_aspect_ratio() -> f32 {
+        1.0
+    }
+    pub fn default_rotation_angle() -> f32 {
+        0.0
+    }
+    pub fn default_crossing_priority() -> i32 {
+        0
+    }
+    pub fn default_weight() -> f32 {
+        1.0
+    }
+}
+
+/// Check whether the first circle's wire crosses over the second's at their intersections.
+/// Higher `crossing_priority` crosses over, and ties go to the lower coil id.
+fn crosses_over(circles: &[CircleArgs], coil_id: usize, other_id: usize) -> bool {
+    let rank = |id: usize| (-circles[id].crossing_priority, id);
+    rank(coil_id) < rank(other_id)
 }
 
 /// ADAM Circles moment struct.
@@ -281,47 +670,361 @@ impl methods::LayoutMethodTrait for Method {
         "ADAM Circles (Optional Symmetry)"
     }
 
+    /// Whether the method was asked for verbose output.
+    fn is_verbose(&self) -> bool {
+        self.verbose
+    }
+
+    fn effective_args(&self) -> &dyn erased_serde::Serialize {
+        self
+    }
+
+    /// Resolve the static layout paths against a base directory.
+    fn resolve_input_paths(&mut self, base_dir: &std::path::Path) {
+        if let Some(layout_in_path) = self.layout_in_path.as_mut() {
+            layout_in_path.resolve(base_dir);
+        }
+    }
+
     fn do_layout(&self, surface: &Surface) -> layout::ProcResult<layout::Layout> {
 
+        // Convert fractional radii to absolute ones on this surface
+        if self.radius_mode != RadiusMode::Absolute {
+            let radius_scale = self.radius_mode.radius_scale(surface);
+            let absolute_method = Method{
+                circles: self.circles.iter().map(|circle| CircleArgs{coil_radius: circle.coil_radius * radius_scale, ..*circle}).collect(),
+                radius_mode: RadiusMode::Absolute,
+                ..self.clone()
+            };
+            return absolute_method.do_layout(surface);
+        }
+
+        // Drop the circles that fail on their own, lay out the rest, and report the failures at the end
+        if self.skip_failed_coils {
+            let mut failures = Vec::<(usize, String)>::new();
+            let circles: Vec<CircleArgs> = self.circles.iter().enumerate().filter_map(|(circle_id, circle)| {
+                match self.check_circle_lays_out(surface, circle) {
+                    Ok(()) => Some(*circle),
+                    Err(error) => {
+                        failures.push((circle_id, error.to_string()));
+                        None
+                    },
+                }
+            }).collect();
+            let remaining_method = Method{
+                circles,
+                skip_failed_coils: false,
+                ..self.clone()
+            };
+            let layout_out = remaining_method.do_layout(surface);
+            for (circle_id, error) in failures.iter() {
+                warnings::warn_grouped("Circle failed to lay out and was skipped", &format!("Circle {} failed to lay out and was skipped: {}", circle_id, error));
+            }
+            if !failures.is_empty() {
+                println!("Skipped {} of {} circles that failed to lay out", failures.len(), self.circles.len());
+            }
+            return layout_out;
+        }
+
+        check_circle_count(self.circles.len())?;
+        let coil_radii: Vec<f32> = self.circles.iter().map(|circle| circle.coil_radius).collect();
+        check_wire_params(self.wire_radius, self.clearance, &coil_radii)?;
+        if let Some(max_stack_offset) = self.max_stack_offset {
+            let spacing = self.clearance + 2.0 * self.wire_radius;
+            if max_stack_offset < spacing {
+                return layout::err_str(&format!("Max stack offset ({}) is below one wire spacing (clearance + wire diameter = {})", max_stack_offset, spacing));
+            }
+        }
+
+        // Fit the symmetry plane to the surface if it's set to auto
+        if let Some(SymmetryPlane::Auto) = self.symmetry_plane {
+            let fitted_method = Method{
+                symmetry_plane: Some(SymmetryPlane::Fixed(SymmetryPlane::Auto.resolve(surface)?)),
+                ..self.clone()
+            };
+            return fitted_method.do_layout(surface);
+        }
+        check_epsilon(surface, self.epsilon, self.verbose || self.statistics_level > 0);
+        if self.symmetry_plane().is_some() && self.circles.iter().any(|circle| circle.component.is_some()) {
+            return layout::err_str("Circle components are not supported with a symmetry plane");
+        }
+        if let Some(allowed_centers) = self.allowed_centers.as_ref() {
+            if self.symmetry_plane().is_some() {
+                return layout::err_str("Allowed centers are not supported with a symmetry plane");
+            }
+            if allowed_centers.len() < self.circles.len() {
+                return layout::err_str(&format!("Only {} allowed centers for {} circles -- each coil needs its own", allowed_centers.len(), self.circles.len()));
+            }
+        }
+
+        // Shrink coils in high-curvature regions, then handle coils nested inside other coils
+        let circles = self.shrink_for_curvature(surface, &self.circles);
+        let circles = self.resolve_nesting(surface, &circles)?;
+
+        // Drop the worst-placed circles if there are more than the cap
+        let mut circles = self.select_circles(surface, &circles)?;
+
+        // Number the iterations across the coarse stages, the polish and its restarts
+        let polish_iterations = if self.coarse_stages.is_empty() {
+            self.iterations
+        } else {
+            self.polish_iterations.unwrap_or(self.iterations / 4)
+        };
+        progress::start_iterations(self.coarse_stages.len() * self.iterations + self.restarts.max(1) * polish_iterations);
+
+        // Optimize on decimated surfaces first, seeding each stage with the circles from the last
+        for (stage_id, target_vertex_count) in self.coarse_stages.iter().enumerate() {
+            let coarse_surface = surface.decimate(*target_vertex_count);
+            println!("Coarse stage {}/{}: {} vertices...", stage_id + 1, self.coarse_stages.len(), coarse_surface.vertices.len());
+
+            // Widen the intersection tolerance so the coarse mesh still gives connected coils
+            let stage_method = Method{
+                circles,
+                epsilon: self.epsilon.max(0.75 * coarse_surface.mean_edge_length()),
+                coarse_stages: Vec::new(),
+                statistics_level: 0,
+                final_cfg_output: None,
+                nesting_policy: NestingPolicy::Ignore,
+                ..self.clone()
+            };
+            circles = stage_method.optimize(&coarse_surface)?.seed_circles;
+            println!();
+        }
+
+        // Polish on the full resolution surface
+        if !self.coarse_stages.is_empty() {
+            println!("Polishing on the full resolution surface...");
+        }
+        let final_method = Method{
+            circles,
+            iterations: polish_iterations,
+            coarse_stages: Vec::new(),
+            ..self.clone()
+        };
+        let (best_result, _) = final_method.optimize_with_restarts(surface)?;
+        let (mut best_layout_out, new_circles) = (best_result.layout, best_result.circles);
+
+        // Add breaks
+        println!("Adding breaks...");
+        for (coil_id, coil) in best_layout_out.coils.iter_mut().enumerate() {
+            let break_count = new_circles[coil_id].break_count;
+            let break_angle_offset_rad = new_circles[coil_id].break_angle_offset * std::f32::consts::PI / 180.0;
+            let zero_angle_vector = self.coil_zero_angle_vector(&new_circles[coil_id], coil.normal);
+
+            add_even_breaks_by_angle(coil, break_count, break_angle_offset_rad, zero_angle_vector)?;
+            if self.break_spacing == BreakSpacing::ByLength {
+                coil.redistribute_breaks_by_length(break_count)?;
+            }
+        }
+
+        // Flag unmanufacturable bends
+        if let Some(min_bend_radius) = self.min_bend_radius {
+            for (coil_id, bend_radius) in best_layout_out.coils_below_bend_radius(min_bend_radius) {
+                warnings::warn_grouped(&format!("Coil {} below minimum bend radius", coil_id), &format!("Coil {} has a bend radius of {:.2}, below the minimum of {:.2}", coil_id, bend_radius, min_bend_radius));
+            }
+        }
+        
+        Ok(best_layout_out)
+    }
+}
+
+/// Result of a single ADAM optimization run.
+struct OptimizeResult {
+    /// Best layout (without breaks)
+    layout: layout::Layout,
+    /// Final circles in layout order (including symmetric copies)
+    circles: Vec<CircleArgs>,
+    /// Final circles in config order (to seed another run)
+    seed_circles: Vec<CircleArgs>,
+    /// Best RMS coupling over the run
+    best_rms: f32,
+    /// Coil centers and radii at every iteration, as CSV (see `trajectory_output`)
+    trajectory: String,
+}
+
+/// Surfaces the circles are placed on: the whole surface,
+/// or for circles with a `component`, just that connected component (see `Surface::connected_components`).
+/// Keeps the projection and boundary handling of those circles within their own shell.
+struct CircleSurfaces<'a> {
+    surface: &'a Surface,
+    /// Surface and boundary points of each component in use
+    components: std::collections::HashMap<usize, (Surface, Vec<Point>)>,
+}
+impl<'a> CircleSurfaces<'a> {
+    /// Split out the components the circles are constrained to.
+    /// Returns a `ProcResult` with the surfaces, or an `Err` if a component doesn't exist.
+    fn new(surface: &'a Surface, circles: &[CircleArgs]) -> layout::ProcResult<Self> {
+        let mut components = std::collections::HashMap::new();
+        if circles.iter().any(|circle| circle.component.is_some()) {
+            let vertex_groups = surface.connected_components();
+            for (circle_id, circle) in circles.iter().enumerate() {
+                let Some(component) = circle.component else { continue };
+                if components.contains_key(&component) {
+                    continue;
+                }
+                let Some(vertex_group) = vertex_groups.get(component) else {
+                    return layout::err_str(&format!("Circle {} is constrained to component {}, but the surface only has {} component(s)",
+                        circle_id, component, vertex_groups.len()
+                    ));
+                };
+                let component_surface = surface.sub_surface(vertex_group);
+                let component_boundary = boundary_points(&component_surface);
+                components.insert(component, (component_surface, component_boundary));
+            }
+        }
+        Ok(CircleSurfaces{surface, components})
+    }
+
+    /// Get the surface a circle is placed on.
+    fn get(&self, circle: &CircleArgs) -> &Surface {
+        match circle.component {
+            Some(component) => &self.components[&component].0,
+            None => self.surface,
+        }
+    }
+
+    /// Get the boundary points of the surface a circle is placed on, given those of the whole surface.
+    fn boundary_points<'b>(&'b self, circle: &CircleArgs, surface_boundary: &'b Vec<Point>) -> &'b Vec<Point> {
+        match circle.component {
+            Some(component) => &self.components[&component].1,
+            None => surface_boundary,
+        }
+    }
+}
+
+/// Get the points of the boundary vertices of a surface.
+fn boundary_points(surface: &Surface) -> Vec<Point> {
+    surface.get_boundary_vertex_indices().iter().map(|v| surface.vertices[*v].point).collect()
+}
+
+impl Method {
+
+    /// Get the symmetry plane, if fixed.
+    /// An `auto` plane is fitted at the start of `do_layout`, so it counts as no plane before then.
+    fn symmetry_plane(&self) -> Option<Plane> {
+        self.symmetry_plane.and_then(|symmetry_plane| symmetry_plane.fixed())
+    }
+
+    /// Run the optimization `restarts` times and keep the run with the lowest RMS coupling.
+    /// The first run starts from the configured centers, later runs perturb them tangent to the surface
+    /// by up to `jitter`, using a RNG seeded with `seed` for reproducibility.
+    /// Returns the best result and the best RMS of each run.
+    fn optimize_with_restarts(&self, surface: &Surface) -> layout::ProcResult<(OptimizeResult, Vec<f32>)> {
+        let mut rng = SeededRng::new(self.seed);
+        let mut best_result: Option<OptimizeResult> = None;
+        let mut run_rms = Vec::<f32>::new();
+        let restarts = self.restarts.max(1);
+        for restart in 0..restarts {
+            let result = if restart == 0 {
+                self.optimize(surface)?
+            } else {
+                println!("Restart {}/{}...", restart + 1, restarts);
+                let restart_method = Method{
+                    circles: self.jitter_circles(surface, &mut rng)?,
+                    final_cfg_output: None,
+                    nesting_policy: NestingPolicy::Ignore,
+                    ..self.clone()
+                };
+                restart_method.optimize(surface)?
+            };
+            run_rms.push(result.best_rms);
+            let improved = match &best_result {
+                Some(best) => result.best_rms < best.best_rms,
+                None => true,
+            };
+            if improved {
+                best_result = Some(result);
+            }
+        }
+        if restarts > 1 {
+            println!("Keeping the best of {} restarts", restarts);
+        }
+        let best_result = best_result.expect("BUG: No optimization runs!");
+
+        // Only the kept run's trajectory is written
+        if let Some(trajectory_output) = self.trajectory_output.as_ref() {
+            println!("Writing coil trajectories to {}...", trajectory_output);
+            crate::io::write_to_file(trajectory_output, &best_result.trajectory)?;
+        }
+        Ok((best_result, run_rms))
+    }
+
+    /// Perturb the circle centers by up to `jitter`, tangent to the surface, then snap them back onto it.
+    fn jitter_circles(&self, surface: &Surface, rng: &mut SeededRng) -> layout::ProcResult<Vec<CircleArgs>> {
+        let circle_surfaces = CircleSurfaces::new(surface, &self.circles)?;
+        Ok(self.circles.iter().map(|circle| {
+            let surface = circle_surfaces.get(circle);
+            let normal = surface.vertices[circle.center.nearest_point_idx(surface)].normal;
+            let offset = (rng.unit_ball_vector() * self.jitter).rej_onto(&normal);
+            let center = circle.center + offset;
+            CircleArgs{center: center - (&center - surface), ..*circle}
+        }).collect())
+    }
+
+    /// Snap each circle center to the nearest allowed center not already taken by a lower circle, then onto the surface.
+    /// Does nothing without `allowed_centers`.
+    fn snap_to_allowed_centers(&self, circles: &mut [CircleArgs], circle_surfaces: &CircleSurfaces) -> layout::ProcResult<()> {
+        let Some(allowed_centers) = self.allowed_centers.as_ref() else {
+            return Ok(());
+        };
+        let mut taken = vec![false; allowed_centers.len()];
+        for circle in circles.iter_mut() {
+            let Some(center_id) = (0..allowed_centers.len())
+                .filter(|center_id| !taken[*center_id])
+                .min_by(|a, b| circle.center.distance(&allowed_centers[*a]).partial_cmp(&circle.center.distance(&allowed_centers[*b])).unwrap())
+            else {
+                return layout::err_str("Ran out of allowed centers -- each coil needs its own");
+            };
+            taken[center_id] = true;
+            let center = allowed_centers[center_id];
+            let surface = circle_surfaces.get(circle);
+            circle.center = center - (&center - surface);
+        }
+        Ok(())
+    }
+
+    /// Run the ADAM optimization on a surface.
+    fn optimize(&self, surface: &Surface) -> layout::ProcResult<OptimizeResult> {
+
         // Initialize potential symmetrical circles
         let mut sym_circles = Vec::<CircleArgs>::new();
         let mut pos_circles = Vec::<CircleArgs>::new();
         let mut neg_circles = Vec::<CircleArgs>::new();
 
-        // Load the static layout if provided
+        // Load the static layout(s) if provided
         let static_layout = if let Some(layout_in_path) = &self.layout_in_path {
             println!("Loading initial layout...");
-            Some(crate::io::load_deser_from::<layout::Layout>(layout_in_path)?)
+            Some(layout_in_path.load()?)
         } else {
             None
         };
 
         // Collect and clone the circles, with extra effort for symmetry
-        let original_circles = if let Some(symmetry_plane) = &self.symmetry_plane {
+        let original_circles = if let Some(symmetry_plane) = &self.symmetry_plane() {
             // Separate the coils by their symmetry
             for (circle_num, circle) in self.circles.iter().enumerate() {
                 if circle.on_symmetry_plane {
                     // Make sure the circle is on the symmetry plane
-                    let mut circle = circle.clone();
-                    if symmetry_plane.distance_to_point(&circle.center).abs() > self.epsilon {
-                        println!("WARNING: Circle {} more than epsilon ({}) from symmetry plane, moving to symmetry plane", circle_num, self.epsilon);
+                    let mut circle = *circle;
+                    if !symmetry_plane.contains_point(&circle.center, self.epsilon) {
+                        warnings::warn(&format!("Circle {} more than epsilon ({}) from symmetry plane, moving to symmetry plane", circle_num, self.epsilon));
                     }
                     circle.center = symmetry_plane.project_point(&circle.center);
                     sym_circles.push(circle);
                 } else {
                     // Make sure the circle is on the right side of the symmetry plane
-                    let mut circle = circle.clone();
+                    let mut circle = *circle;
                     if symmetry_plane.distance_to_point(&circle.center) < 0.0 {
-                        println!("WARNING: Circle {} on wrong side of symmetry plane, flipping", circle_num);
+                        warnings::warn(&format!("Circle {} on wrong side of symmetry plane, flipping", circle_num));
                         circle.center = circle.center.reflect_across(&symmetry_plane);
                     }
-                    if symmetry_plane.distance_to_point(&circle.center).abs() < self.epsilon {
-                        println!("WARNING: Circle {} close to symmetry plane, may cause issues", circle_num);
+                    if symmetry_plane.contains_point(&circle.center, self.epsilon) {
+                        warnings::warn(&format!("Circle {} close to symmetry plane, may cause issues", circle_num));
                     }
                     pos_circles.push(circle);
 
                     // Add the flipped circle
-                    let mut neg_circle = circle.clone();
+                    let mut neg_circle = circle;
                     neg_circle.center = neg_circle.center.reflect_across(&symmetry_plane);
                     neg_circles.push(neg_circle);
                 }
@@ -336,9 +1039,9 @@ impl methods::LayoutMethodTrait for Method {
 
         let mut new_circles = original_circles.clone();
 
-        // Store boundary points
-        let boundary_points: Vec<Point> = surface.get_boundary_vertex_indices().iter()
-            .map(|v| surface.vertices[*v].point).collect();
+        // Store boundary points, and the components the circles are constrained to
+        let boundary_points = boundary_points(surface);
+        let circle_surfaces = CircleSurfaces::new(surface, &new_circles)?;
 
         // Store if the coils are on the boundary
         let mut on_boundary = vec![false; new_circles.len()];
@@ -348,56 +1051,76 @@ impl methods::LayoutMethodTrait for Method {
         let neg_offset = pos_offset + pos_circles.len();
         let total_coil_count = new_circles.len();
         for (coil_id, circle) in new_circles.iter_mut().enumerate() {
-            let mut boundary_point = *closest_point(&circle.center, &boundary_points);
-            let vec_to_boundary = circle.center - boundary_point;
+            let (surface, boundary_points) = (circle_surfaces.get(circle), circle_surfaces.boundary_points(circle, &boundary_points));
+            let surface_normal = surface.vertices[circle.center.nearest_point_idx(surface)].normal;
+            let boundary_distance = |a: &Point, b: &Point| self.boundary_metric.distance(a, b, &surface_normal);
+            let Some(mut boundary_point) = closest_point_by(&circle.center, boundary_points, boundary_distance).copied() else {
+                continue;
+            };
+            let vec_to_boundary = -self.boundary_metric.vector(&circle.center, &boundary_point, &surface_normal);
             let distance_to_boundary = vec_to_boundary.norm();
             if distance_to_boundary < circle.coil_radius {
                 let original_center = circle.center;
                 circle.center = boundary_point + vec_to_boundary.normalize() * circle.coil_radius;
                 circle.center = circle.center - (&circle.center - surface);
-                boundary_point = *closest_point(&circle.center, &boundary_points);
-                circle.coil_radius = (circle.center - boundary_point).norm();
-                if let Some(symmetry_plane) = &self.symmetry_plane {
+                boundary_point = *closest_point_by(&circle.center, boundary_points, boundary_distance).unwrap_or(&boundary_point);
+                circle.coil_radius = boundary_distance(&circle.center, &boundary_point);
+                if let Some(symmetry_plane) = &self.symmetry_plane() {
+                    // Shift the whole pair, so the partner stays the exact reflection
+                    let reflect = |circle: &CircleArgs, partner: &mut CircleArgs| {
+                        partner.center = circle.center.reflect_across(symmetry_plane);
+                        partner.coil_radius = circle.coil_radius;
+                    };
                     match coil_id {
                         n if (0..pos_offset).contains(&n) => {
-                            sym_circles[coil_id].center = circle.center;
+                            sym_circles[coil_id] = *circle;
                             if self.warn_on_shift {
-                                println!("WARNING: Coil {} too close to boundary, center shifted by |{:.2}| to {:.2} and radius shrunk to {:.2}",
+                                warnings::warn_grouped(&format!("Coil {} shifted to boundary", coil_id), &format!("Coil {} too close to boundary, center shifted by |{:.2}| to {:.2} and radius shrunk to {:.2}",
                                     coil_id, (original_center - circle.center).norm(), circle.center, circle.coil_radius
-                                );
+                                ));
                             }
                         },
                         n if (pos_offset..neg_offset).contains(&n) => {
-                            pos_circles[coil_id - pos_offset].center = circle.center;
-                            neg_circles[coil_id - pos_offset].center = circle.center.reflect_across(&symmetry_plane);
+                            let pair_id = coil_id - pos_offset;
+                            pos_circles[pair_id] = *circle;
+                            reflect(circle, &mut neg_circles[pair_id]);
                             if self.warn_on_shift {
-                                println!("WARNING: Coil {} too close to boundary, center shifted by |{:.2}| to {:.2} and radius shrunk to {:.2}",
+                                warnings::warn_grouped(&format!("Coil {} shifted to boundary", coil_id), &format!("Coil {} too close to boundary, center shifted by |{:.2}| to {:.2} and radius shrunk to {:.2}",
                                     coil_id, (original_center - circle.center).norm(), circle.center, circle.coil_radius
-                                );
+                                ));
                             }
                         },
                         n if (neg_offset..total_coil_count).contains(&n) => {
-                            neg_circles[coil_id - neg_offset].center = circle.center;
-                            pos_circles[coil_id - neg_offset].center = circle.center.reflect_across(&symmetry_plane);
+                            let pair_id = coil_id - neg_offset;
+                            neg_circles[pair_id] = *circle;
+                            reflect(circle, &mut pos_circles[pair_id]);
                             if self.warn_on_shift {
-                                println!("WARNING: Coil {} (reflection of coil {}) too close to boundary, center shifted by |{:.2}| to {:.2} and radius shrunk to {:.2}",
-                                    coil_id, coil_id - neg_offset, (original_center - circle.center).norm(), circle.center, circle.coil_radius
-                                );
+                                warnings::warn_grouped(&format!("Coil {} shifted to boundary", coil_id), &format!("Coil {} (reflection of coil {}) too close to boundary, center shifted by |{:.2}| to {:.2} and radius shrunk to {:.2}",
+                                    coil_id, pos_offset + pair_id, (original_center - circle.center).norm(), circle.center, circle.coil_radius
+                                ));
                             }
                         },
                         _ => unreachable!(),
                     }
                 } else {
                     if self.warn_on_shift {
-                        println!("WARNING: Coil {} too close to boundary, center shifted by |{:.2}| to {:.2} and radius shrunk to {:.2}",
+                        warnings::warn_grouped(&format!("Coil {} shifted to boundary", coil_id), &format!("Coil {} too close to boundary, center shifted by |{:.2}| to {:.2} and radius shrunk to {:.2}",
                             coil_id, (original_center - circle.center).norm(), circle.center, circle.coil_radius
-                        );
+                        ));
                     }
                 }
                 on_boundary[coil_id] = true;
             }
         }
 
+        // Keep the flat list in step with the groups the symmetric layout runs on (shifts also moved the partners)
+        if self.symmetry_plane().is_some() {
+            new_circles = concat(vec![sym_circles.clone(), pos_circles.clone(), neg_circles.clone()]);
+        }
+
+        // Fix the centers to the allowed points
+        self.snap_to_allowed_centers(&mut new_circles, &circle_surfaces)?;
+
         // Get initial close coils
         let mut close_coils = 0;
         for (coil_id, coil) in new_circles.iter().enumerate() {
@@ -427,7 +1150,7 @@ impl methods::LayoutMethodTrait for Method {
         }
             
         // Run a single pass
-        let mut layout_out = if let Some(symmetry_plane) = &self.symmetry_plane {
+        let mut layout_out = if let Some(symmetry_plane) = &self.symmetry_plane() {
             self.lay_out_coils_sym(
                 surface,
                 symmetry_plane,
@@ -436,8 +1159,9 @@ impl methods::LayoutMethodTrait for Method {
                 &neg_circles,
                 false)?
         } else {
-            self.lay_out_coils(surface, &new_circles, false)?
+            self.lay_out_coils_on(&circle_surfaces, &new_circles, false)?
         };
+        self.warn_nesting(&layout_out);
 
         // Store the previous and best layouts
         let mut prev_layout_out = layout_out.clone();
@@ -448,11 +1172,12 @@ impl methods::LayoutMethodTrait for Method {
         let mut objective;
         let mut best_rms = std::f32::INFINITY;
         let mut moments = vec![Moment::new(); new_circles.len()];
+        let mut trajectory = String::from("iteration, coil_id, x, y, z, radius\n");
         for i in 0..self.iterations {
             println!();
             println!("Iteration {}/{}...", (i + 1), self.iterations);
 
-            if let Some(symmetry_plane) = &self.symmetry_plane {
+            if let Some(symmetry_plane) = &self.symmetry_plane() {
                 // Update circles symmetrically
                 // Objective is calculated from the PREVIOUS layout, not the updated one 
                 // (the one that goes in to the function, not the one that comes out of it)
@@ -463,12 +1188,12 @@ impl methods::LayoutMethodTrait for Method {
                     &original_circles,
                     &layout_out,
                     &static_layout,
-                    surface,
+                    &circle_surfaces,
                     symmetry_plane,
                     &boundary_points,
                     &mut on_boundary,
                     &mut moments
-                );
+                )?;
                 layout_out = self.lay_out_coils_sym(
                     surface,
                     symmetry_plane,
@@ -485,25 +1210,35 @@ impl methods::LayoutMethodTrait for Method {
                     &original_circles,
                     &layout_out,
                     &static_layout,
-                    surface,
+                    &circle_surfaces,
                     &boundary_points,
                     &mut on_boundary,
-                    &mut moments
-                );
-                layout_out = self.lay_out_coils(surface, &new_circles, false)?;
+                    &mut moments,
+                    None,
+                )?;
+                self.snap_to_allowed_centers(&mut new_circles, &circle_surfaces)?;
+                layout_out = self.lay_out_coils_on(&circle_surfaces, &new_circles, false)?;
+            }
+
+            // Record the updated centers and radii
+            for (coil_id, circle) in new_circles.iter().enumerate() {
+                trajectory.push_str(&format!("{}, {}, {:.4}, {:.4}, {:.4}, {:.4}\n",
+                    i + 1, coil_id, circle.center.x, circle.center.y, circle.center.z, circle.coil_radius
+                ));
             }
 
             // Store the best layout
-            if (objective / new_close_coils as f32).sqrt() < best_rms {
+            if rms_coupling(objective, new_close_coils) < best_rms {
                 best_layout_out = prev_layout_out.clone();
-                best_rms = (objective / new_close_coils as f32).sqrt();
+                best_rms = rms_coupling(objective, new_close_coils);
             }
             prev_layout_out = layout_out.clone();
 
             // Print statistics
-            println!("Starting RMS Coupling: {:.2}", (objective / new_close_coils as f32).sqrt());
+            println!("Starting RMS Coupling: {:.2}", rms_coupling(objective, new_close_coils));
+            progress::iteration(rms_coupling(objective, new_close_coils));
             if close_coils != new_close_coils {
-                println!("WARNING: Number of close coils changed! ({} -> {})", close_coils, new_close_coils);
+                warnings::warn_grouped("Number of close coils changed", &format!("Number of close coils changed! ({} -> {})", close_coils, new_close_coils));
             }
             close_coils = new_close_coils;
         }
@@ -514,16 +1249,15 @@ impl methods::LayoutMethodTrait for Method {
         let static_mutual_inductances;
         (objective, close_coils, self_inductances, mutual_inductances, static_mutual_inductances) = 
             self.get_statistics(
-                &new_circles,
                 &layout_out,
                 &static_layout,
                 self.statistics_level > 1,
                 self.statistics_level > 2
             );
-        println!("Final RMS Coupling: {:.2}", (objective / close_coils as f32).sqrt());
-        if (objective / close_coils as f32).sqrt() < best_rms {
+        println!("Final RMS Coupling: {:.2}", rms_coupling(objective, close_coils));
+        if rms_coupling(objective, close_coils) < best_rms {
             best_layout_out = layout_out.clone();
-            best_rms = (objective / close_coils as f32).sqrt();
+            best_rms = rms_coupling(objective, close_coils);
         }
         println!("Best RMS Coupling: {:.2}", best_rms.sqrt());
         println!();
@@ -532,7 +1266,9 @@ impl methods::LayoutMethodTrait for Method {
         if self.statistics_level > 0 {
             println!("Final Coils:");
             for (coil_id, coil) in best_layout_out.coils.iter().enumerate() {
-                println!("Coil {}: Radius [{:.2}], Center [{:.2}], Length [{:.2}]", coil_id, new_circles[coil_id].coil_radius, coil.center, coil.wire_length());
+                println!("Coil {}: Radius [{:.2}], Center [{:.2}], Length [{:.2}], Min Bend [{:.2}]",
+                    coil_id, new_circles[coil_id].coil_radius, coil.center, coil.wire_length(), coil.min_bend_radius()
+                );
             }
             println!();
             
@@ -559,6 +1295,46 @@ impl methods::LayoutMethodTrait for Method {
                 }
                 println!();
             }
+
+            println!("Coupling matrix condition number: {:.2} (lower is better)", best_layout_out.coupling_condition_number(self.larmor_mhz));
+            println!();
+        }
+
+        // Check the coupling signs against the expected winding
+        if let Some(coupling_sign_check) = self.coupling_sign_check.as_ref() {
+            let violations = best_layout_out.coupling_sign_violations(coupling_sign_check, self.close_cutoff);
+            if violations.is_empty() {
+                println!("Coupling signs match the expected winding");
+            }
+            for (coil_id, other_id, k) in violations.iter() {
+                warnings::warn_grouped(
+                    "Coupling sign contradicts expected winding",
+                    &format!("Coil {} with Coil {}: coupling {:.3} has the wrong sign -- check the winding", coil_id, other_id, k),
+                );
+            }
+            println!();
+        }
+
+        // Check that the overlap resolution kept the wires at least the clearance apart
+        // Unresolved overlaps cross on purpose, so only flag the spacing if they were resolved
+        let spacing_report = if self.statistics_level > 0 || self.resolve_overlaps {
+            best_layout_out.min_spacing_report()
+        } else {
+            Vec::new()
+        };
+        if self.statistics_level > 0 {
+            if let Some((coil_id, other_id, gap)) = spacing_report.iter().min_by(|a, b| a.2.total_cmp(&b.2)) {
+                println!("Minimum wire spacing: {:.3} (Coil {} with Coil {})", gap, coil_id, other_id);
+                println!();
+            }
+        }
+        if self.resolve_overlaps {
+            for (coil_id, other_id, gap) in spacing_report.iter().filter(|(_, _, gap)| *gap < (1.0 - SPACING_TOLERANCE) * self.clearance) {
+                warnings::warn_grouped(
+                    "Wire spacing below clearance",
+                    &format!("Coil {} with Coil {}: gap {:.3} is below the clearance {}", coil_id, other_id, gap, self.clearance),
+                );
+            }
         }
 
         if let Some(final_cfg_output) = self.final_cfg_output.as_ref() {
@@ -566,26 +1342,35 @@ impl methods::LayoutMethodTrait for Method {
             crate::io::save_ser_to(final_cfg_output, &new_circles)?;
         }
 
-        // Add breaks
-        println!("Adding breaks...");
-        for (coil_id, coil) in best_layout_out.coils.iter_mut().enumerate() {
-            let break_count = new_circles[coil_id].break_count;
-            let break_angle_offset_rad = new_circles[coil_id].break_angle_offset * std::f32::consts::PI / 180.0;
-            let zero_angle_vector = {
-                if coil.normal.normalize().dot(&self.zero_angle_vector.normalize()) < 0.95 {
-                    self.zero_angle_vector
+        // Map the circles back to the config order
+        let seed_circles = if self.symmetry_plane().is_some() {
+            let (mut sym_id, mut pos_id) = (0, sym_circles.len());
+            self.circles.iter().map(|circle| {
+                if circle.on_symmetry_plane {
+                    sym_id += 1;
+                    new_circles[sym_id - 1]
                 } else {
-                    self.backup_zero_angle_vector
+                    pos_id += 1;
+                    new_circles[pos_id - 1]
                 }
-            }.normalize();
+            }).collect()
+        } else {
+            new_circles.clone()
+        };
 
-            add_even_breaks_by_angle(coil, break_count, break_angle_offset_rad, zero_angle_vector)?;
-        }
-        
-        Ok(best_layout_out)
+        Ok(OptimizeResult{
+            layout: best_layout_out,
+            circles: new_circles,
+            seed_circles,
+            best_rms,
+            trajectory,
+        })
     }
 }
 
+/// Symmetric, positive, and negative circles; the objective function; and the number of close coils
+type SymCircleUpdate = (Vec<CircleArgs>, Vec<CircleArgs>, Vec<CircleArgs>, f32, usize);
+
 impl Method {
 
     /// Do a single pass of spherical intersection on the circles
@@ -595,6 +1380,16 @@ impl Method {
         circles: &Vec::<CircleArgs>,
         verbose: bool
     ) -> layout::ProcResult<layout::Layout> {
+        self.lay_out_coils_on(&CircleSurfaces::new(surface, circles)?, circles, verbose)
+    }
+
+    /// Do a single pass of spherical intersection on the circles, with the surfaces they're placed on already split out
+    fn lay_out_coils_on(
+        &self,
+        circle_surfaces: &CircleSurfaces,
+        circles: &Vec::<CircleArgs>,
+        verbose: bool
+    ) -> layout::ProcResult<layout::Layout> {
 
         let mut layout_out = layout::Layout::new();
 
@@ -606,38 +1401,82 @@ impl Method {
             
             // Grab arguments from the circle arguments
             let coil_radius = circle_args.coil_radius;
+            let surface = circle_surfaces.get(circle_args);
             
             // Snap the center to the surface
             let vec_to_surface = &circle_args.center - surface;
             let center = circle_args.center - vec_to_surface;
 
-            // Create the circle through surface intersection with sphere
-            let (cid, points, point_normals) = sphere_intersect(surface, center, coil_radius, self.epsilon);
-            let coil_normal = surface.vertices[cid].normal;
-
-            let coil = clean_coil_by_angle(
+            // Create the circle on the surface
+            let mut coil = circle_coil(
+                surface,
                 center,
-                coil_normal,
-                coil_radius, 
+                coil_radius,
                 self.wire_radius,
-                points,
-                point_normals,
+                self.epsilon,
                 self.pre_shift,
-                false
+                self.min_vertices,
+                self.projection_mode
             )?;
 
+            // Stretch elliptical elements along their major axis
+            if circle_args.aspect_ratio != 1.0 {
+                let major_axis = self.major_axis(circle_args, coil.normal);
+                stretch_coil(&mut coil, surface, major_axis, circle_args.aspect_ratio);
+            }
+
             layout_out.coils.push(coil);
+
+            progress::emit(ProgressEvent::CoilLaidOut{id: layout_out.coils.len() - 1});
         }
 
+        // Keep the wires off the ports
+        let surfaces: Vec<&Surface> = circles.iter().map(|circle| circle_surfaces.get(circle)).collect();
+        self.place_ports(&mut layout_out, circles, &surfaces);
+
+        // Carry the user tags over to the coils
+        tag_coils(&mut layout_out, circles);
+
         // Do overlaps
         self.mousehole_overlap(&mut layout_out, circles);
 
         Ok(layout_out)
     }
 
-    /// Do a single pass of symmetric coil intersection
-    fn lay_out_coils_sym(
-        &self, 
+    /// Sweep two circles apart, laying them out at each separation and recording their coupling factor.
+    /// `circle_b` only sets the direction of the sweep from `circle_a`; `distances` are center-to-center separations.
+    /// Returns (relative distance, coupling factor) pairs, with distances relative to the sum of the radii.
+    pub fn coupling_vs_distance(
+        &self,
+        surface: &Surface,
+        circle_a: CircleArgs,
+        circle_b: CircleArgs,
+        distances: &[f32],
+    ) -> layout::ProcResult<Vec<(f32, f32)>> {
+        let direction = circle_b.center - circle_a.center;
+        if direction.norm() <= 0.0 {
+            return layout::err_str("Coupling sweep circles must have different centers to set the sweep direction");
+        }
+        let direction = direction.normalize();
+
+        let mut coupling_curve = Vec::<(f32, f32)>::with_capacity(distances.len());
+        for distance in distances.iter() {
+            let mut swept_circle = circle_b;
+            swept_circle.center = circle_a.center + direction * *distance;
+
+            let layout_out = self.lay_out_coils(surface, &vec![circle_a, swept_circle], false)?;
+            let (coil_a, coil_b) = (&layout_out.coils[0], &layout_out.coils[1]);
+
+            let d_rel = (coil_a.center - coil_b.center).norm() / (circle_a.coil_radius + swept_circle.coil_radius);
+            coupling_curve.push((d_rel, coil_a.coupling_factor(coil_b, 1.0)));
+        }
+
+        Ok(coupling_curve)
+    }
+
+    /// Do a single pass of symmetric coil intersection
+    fn lay_out_coils_sym(
+        &self, 
         surface: &Surface, 
         symmetry_plane: &Plane,
         sym_circles: &Vec::<CircleArgs>, 
@@ -649,33 +1488,50 @@ impl Method {
         let mut layout_out = layout::Layout::new();
 
         // Create the coils for the on-symmetry circles
-        for (_, circle_args) in sym_circles.iter().enumerate() {
+        for (coil_id, circle_args) in sym_circles.iter().enumerate() {
             
             // Grab arguments from the circle arguments
             let coil_radius = circle_args.coil_radius;
             let center = circle_args.center;
 
-            // Create the circle through surface intersection with sphere
-            let (cid, points, point_normals) =
-                sphere_intersect(surface, center, coil_radius, self.epsilon);
-            let coil_normal = surface.vertices[cid].normal.normalize();
-
-            if verbose { println!("Uncleaned point count: {}", points.len()) };
-
-            let coil = clean_coil_by_angle(
+            // Create the circle on the surface
+            let mut coil = circle_coil(
+                surface,
                 center,
-                coil_normal,
                 coil_radius,
                 self.wire_radius,
-                points,
-                point_normals,
+                self.epsilon,
                 self.pre_shift,
-                false
+                self.min_vertices,
+                self.projection_mode
             )?;
+
+            // Handle a wire that only covers one side of the plane (e.g. a surface trimmed at the plane)
+            if is_one_sided(&coil, symmetry_plane, self.epsilon) {
+                match self.one_sided_policy {
+                    OneSidedPolicy::Warn => {
+                        warnings::warn_grouped(&format!("Coil {} is one-sided on the symmetry plane", coil_id), &format!("Coil {} only covers one side of the symmetry plane -- the surface may be trimmed at the plane", coil_id));
+                    },
+                    OneSidedPolicy::Mirror => {
+                        coil = mirror_complete(&coil, symmetry_plane, self.epsilon)?;
+                    },
+                    OneSidedPolicy::Error => {
+                        return layout::err_str(&format!("Coil {} only covers one side of the symmetry plane -- the surface may be trimmed at the plane", coil_id));
+                    },
+                }
+            }
+
+            // Stretch elliptical elements along their major axis
+            if circle_args.aspect_ratio != 1.0 {
+                let major_axis = self.major_axis(circle_args, coil.normal);
+                stretch_coil(&mut coil, surface, major_axis, circle_args.aspect_ratio);
+            }
     
             if verbose { println!("Cleaned point count: {}", coil.vertices.len()) };
     
             layout_out.coils.push(coil);
+    
+            progress::emit(ProgressEvent::CoilLaidOut{id: layout_out.coils.len() - 1});
         }
 
         // Create the coils for the positive circles
@@ -685,27 +1541,29 @@ impl Method {
             let coil_radius = circle_args.coil_radius;
             let center = circle_args.center;
 
-            // Create the circle through surface intersection with sphere
-            let (cid, points, point_normals) =
-                sphere_intersect(surface, center, coil_radius, self.epsilon);
-            let coil_normal = surface.vertices[cid].normal.normalize();
-
-            if verbose { println!("Uncleaned point count: {}", points.len()) };
-
-            let coil = clean_coil_by_angle(
+            // Create the circle on the surface
+            let mut coil = circle_coil(
+                surface,
                 center,
-                coil_normal,
                 coil_radius,
                 self.wire_radius,
-                points,
-                point_normals,
+                self.epsilon,
                 self.pre_shift,
-                false
+                self.min_vertices,
+                self.projection_mode
             )?;
+
+            // Stretch elliptical elements along their major axis
+            if circle_args.aspect_ratio != 1.0 {
+                let major_axis = self.major_axis(circle_args, coil.normal);
+                stretch_coil(&mut coil, surface, major_axis, circle_args.aspect_ratio);
+            }
     
             if verbose { println!("Cleaned point count: {}", coil.vertices.len()) };
     
             layout_out.coils.push(coil);
+    
+            progress::emit(ProgressEvent::CoilLaidOut{id: layout_out.coils.len() - 1});
         }
 
         // Create the coils for the flipped circles
@@ -719,30 +1577,37 @@ impl Method {
                 coil.vertices.iter().map(|vertex| vertex.surface_normal.reflect_across(&symmetry_plane.get_normal())).rev().collect()
             )?;
             layout_out.coils.push(neg_coil);
+            progress::emit(ProgressEvent::CoilLaidOut{id: layout_out.coils.len() - 1});
         }
 
-        // Do overlaps
+        // Keep the wires off the ports, carry the user tags over to the coils, then do overlaps
         let circles = concat(vec![sym_circles.clone(), pos_circles.clone(), neg_circles.clone()]);
+        self.place_ports(&mut layout_out, &circles, &vec![surface; circles.len()]);
+        tag_coils(&mut layout_out, &circles);
         self.mousehole_overlap(&mut layout_out, &circles);
 
         Ok(layout_out)
     }
 
     /// Update the circle parameters
+    /// If `locked_plane` is given as `(plane, count)`, the first `count` circles are on the plane,
+    /// and their centers only step within it (see `lock_aspect`).
     fn update_circles(
         &self, 
         circles: &Vec::<CircleArgs>,
         original_circles: &Vec::<CircleArgs>,
         layout_out: &layout::Layout,
         static_layout: &Option<layout::Layout>,
-        surface: &Surface,
+        circle_surfaces: &CircleSurfaces,
         boundary_points: &Vec::<Point>,
         on_boundary: &mut Vec::<bool>,
-        moments: &mut Vec::<Moment>
-    ) -> (Vec<CircleArgs>, f32, usize) {
+        moments: &mut Vec::<Moment>,
+        locked_plane: Option<(&Plane, usize)>,
+    ) -> layout::ProcResult<(Vec<CircleArgs>, f32, usize)> {
+        let _timer = timing::phase("Coupling gradients");
 
         let mut new_circles = circles.clone();
-        assert!(new_circles.len() == layout_out.coils.len());
+        check_coil_count(new_circles.len(), layout_out.coils.len())?;
 
         // Initialize objective function and number of close coils
         let mut objective = 0.0;
@@ -766,62 +1631,90 @@ impl Method {
             rel_radial_err[coil_id] = (circle.coil_radius - original_radius) / original_radius;
             min_radii[coil_id] = original_radius * (1.0 - self.radius_freedom);
             max_radii[coil_id] = original_radius * (1.0 + self.radius_freedom);
-            self_inductances[coil_id] = layout_out.coils[coil_id].self_inductance(1.0);
+            self_inductances[coil_id] = self.self_inductance(&layout_out.coils[coil_id]);
         }
 
+        // Shield images, counter-wound
+        let images = self.shield_images(layout_out);
+
+        // Closeness goes by the average radii as laid out, which offsets and curvature pull away from the nominal
+        let effective_radii: Vec<f32> = layout_out.coils.iter().map(|coil| coil.average_radius()).collect();
+
+        // Pairs already decoupled below the floor stop pushing on each other (but still count in the objective)
+        let above_floor = |m: f32, self_inductance: f32, other_self_inductance: f32| {
+            m.abs() / (self_inductance * other_self_inductance).sqrt() >= self.coupling_floor
+        };
+
+        // Port keepout discs, treated as obstacles for the other coils' wires
+        let port_points = self.port_points(layout_out);
+
         // Calculate the updates for each coil
         let mut center_grads = vec![Vec::<GeoVector>::new(); layout_out.coils.len()];
         let mut radial_grads = vec![0.0; layout_out.coils.len()];
         for (coil_id, coil) in layout_out.coils.iter().enumerate() {
 
-            // Initialize with the regularization term for radius
-            radial_grads[coil_id] += -2.0 * self.radius_reg * rel_radial_err[coil_id];
+            // Initialize with the regularization term for radius, per coil if given
+            // The gradients are descended, so this pulls the radius back toward the original
+            let radius_reg = circles[coil_id].radius_reg.unwrap_or(self.radius_reg);
+            radial_grads[coil_id] += 2.0 * radius_reg * rel_radial_err[coil_id];
+            let weight = circles[coil_id].weight;
 
             // Get the parameters that will shift, and their original values
             let mut center = coil.center;
             let original_center = original_circles[coil_id].center;
             let mut radius = circles[coil_id].coil_radius;
             let original_radius = original_circles[coil_id].coil_radius;
+            let surface = circle_surfaces.get(&circles[coil_id]);
+            let boundary_points = circle_surfaces.boundary_points(&circles[coil_id], boundary_points);
 
             // Check all coils of a higher id than the current coil
             for (other_id, other_coil) in layout_out.coils.iter().enumerate() {
                 if other_id != coil_id {
 
                     // Establish vectors and distances
-                    let other_radius = circles[other_id].coil_radius;
                     let vec_from_other = center - other_coil.center;
 
                     // Apply coupling forces from nearby coils
-                    if vec_from_other.norm() / (radius + other_radius) < self.close_cutoff {
+                    if vec_from_other.norm() / (effective_radii[coil_id] + effective_radii[other_id]) < self.close_cutoff {
 
                         // Track close coils and add to objective function
                         if other_id > coil_id {
 
-                            // Get coupling and gradient wrt center and radius
-                            let (m, dx, dy, dz, dr) = coil.mutual_inductance_full(other_coil, 1.0);
+                            // Get coupling (including through the other coil's shield image) and gradient wrt both centers and radius
+                            let (m, grad, other_grad, dr) = self.pair_mutual_inductance_full(coil, other_coil, images[other_id].as_ref());
 
-                            // Track the objective function and close coils
+                            // Track the objective function and close coils, unweighted so it stays the reported coupling
                             close_coils += 1;
                             objective += m * m * 1.0e6 / (self_inductances[coil_id] * self_inductances[other_id]);
+                            if !above_floor(m, self_inductances[coil_id], self_inductances[other_id]) {
+                                continue;
+                            }
 
-                            // Track the gradients relative to the center location
+                            // Track the gradients relative to the center location, weighted by both coils
                             // dk^2/dx = 2k * dk/dx = 2(m/sqrt(L1L2)) * dm/dx / sqrt(L1L2) = 2m * dm/dx / L1L2
-                            let adjustment = 2.0 * m * GeoVector::new(dx, dy, dz)
-                            / (self_inductances[coil_id] * self_inductances[other_id]);
+                            let pair_weight = weight * circles[other_id].weight;
+                            let scale = 2.0 * pair_weight * m / (self_inductances[coil_id] * self_inductances[other_id]);
 
-                            // Add the force to the coil
-                            center_grads[coil_id].push(adjustment);
-                            center_grads[other_id].push(-adjustment);
+                            // Add the force to both coils
+                            center_grads[coil_id].push(scale * grad);
+                            center_grads[other_id].push(scale * other_grad);
 
                             // Track the gradient relative to the radius
-                            radial_grads[coil_id] += 2.0 * m * dr / (self_inductances[coil_id] * self_inductances[other_id]);
+                            radial_grads[coil_id] += 2.0 * pair_weight * m * dr / (self_inductances[coil_id] * self_inductances[other_id]);
                         } else {
 
                             // Just get the gradient wrt radius
-                            let (m, dr) = coil.mutual_inductance_dradius(other_coil, 1.0);
+                            let (mut m, mut dr) = coil.mutual_inductance_dradius(other_coil, 1.0);
+                            if let Some(image) = images[other_id].as_ref() {
+                                let (mi, dri) = coil.mutual_inductance_dradius(image, 1.0);
+                                (m, dr) = (m + mi, dr + dri);
+                            }
 
                             // Track the gradient relative to the radius
-                            radial_grads[coil_id] += 2.0 * m * dr / (self_inductances[coil_id] * self_inductances[other_id]);
+                            if above_floor(m, self_inductances[coil_id], self_inductances[other_id]) {
+                                let pair_weight = weight * circles[other_id].weight;
+                                radial_grads[coil_id] += 2.0 * pair_weight * m * dr / (self_inductances[coil_id] * self_inductances[other_id]);
+                            }
                         }
                     }
                 }
@@ -835,7 +1728,7 @@ impl Method {
                     // Calculate proximity exactly to allow for non-spherical static coils
                     for vertex in static_coil.vertices.iter() {
                         let vec_from_static = center - vertex.point;
-                        if vec_from_static.norm() / radius < self.close_cutoff {
+                        if vec_from_static.norm() / effective_radii[coil_id] < self.close_cutoff {
                             close = true;
                             break;
                         }
@@ -855,16 +1748,19 @@ impl Method {
                         // Track the objective function and close coils
                         close_coils += 1;
                         objective += m * m * 1.0e6 / (self_inductances[coil_id] * static_self_inductances[static_id].unwrap());
+                        if !above_floor(m, self_inductances[coil_id], static_self_inductances[static_id].unwrap()) {
+                            continue;
+                        }
 
                         // Track the gradients relative to the center location
                         // dk^2/dx = 2k * dk/dx = 2(m/sqrt(L1L2)) * dm/dx / sqrt(L1L2) = 2m * dm/dx / L1L2
                         center_grads[coil_id].push(
-                            2.0 * m * GeoVector::new(dx, dy, dz)
+                            2.0 * weight * m * GeoVector::new(dx, dy, dz)
                             / (self_inductances[coil_id] * static_self_inductances[static_id].unwrap())
                         );
 
                         // Track the gradient relative to the radius
-                        radial_grads[coil_id] += 2.0 * m * dr / (self_inductances[coil_id] * static_self_inductances[static_id].unwrap());
+                        radial_grads[coil_id] += 2.0 * weight * m * dr / (self_inductances[coil_id] * static_self_inductances[static_id].unwrap());
                     }
                 }
             }
@@ -875,16 +1771,26 @@ impl Method {
                 prox_center = prox_center + grad.rej_onto(&coil.normal);
             }
 
+            // Locked on-plane coils only move within the plane
+            let lock_normal = match locked_plane {
+                Some((plane, count)) if coil_id < count => Some(plane.get_normal()),
+                _ => None,
+            };
+            if let Some(lock_normal) = lock_normal.as_ref() {
+                prox_center = prox_center.rej_onto(lock_normal);
+            }
+
             // Check and update center boundary condition
             // If on the boundary, add a normal force keeping the coil from crossing the boundary
             if on_boundary[coil_id] {
-                let boundary_point = closest_point(&center, boundary_points);
-                let flat_vec_to_boundary = (center - *boundary_point).rej_onto(&coil.normal).normalize();
-                let boundary_component = prox_center.proj_onto(&flat_vec_to_boundary);
-                if boundary_component.norm() >= 0.0 {
-                    prox_center = prox_center - boundary_component;
-                } else {
-                    on_boundary[coil_id] = false;
+                if let Some(boundary_point) = closest_point_by(&center, boundary_points, |a, b| self.boundary_metric.distance(a, b, &coil.normal)) {
+                    let flat_vec_to_boundary = (center - *boundary_point).rej_onto(&coil.normal).normalize();
+                    let boundary_component = prox_center.proj_onto(&flat_vec_to_boundary);
+                    if boundary_component.norm() >= 0.0 {
+                        prox_center = prox_center - boundary_component;
+                    } else {
+                        on_boundary[coil_id] = false;
+                    }
                 }
             }
 
@@ -893,12 +1799,16 @@ impl Method {
             
             // Update the center
             let mut center_step = -self.step_size * moments[coil_id].get_center_step();
-            let center_bound = self.center_freedom * original_radius;
+            let center_bound = self.center_freedom_mode.center_bound(self.center_freedom, original_radius);
             let total_delta = center + (center_step.rej_onto(&coil.normal)) - original_center;
             if total_delta.norm() > center_bound {
                 center_step += total_delta.normalize() * (center_bound - total_delta.norm());
             }
-            center = center + center_step.rej_onto(&coil.normal);
+            let mut center_step = center_step.rej_onto(&coil.normal);
+            if let Some(lock_normal) = lock_normal.as_ref() {
+                center_step = center_step.rej_onto(lock_normal);
+            }
+            center += center_step;
 
             // Update the radius
             radius += -self.step_size * moments[coil_id].get_radius_step();
@@ -906,24 +1816,47 @@ impl Method {
             else if radius > max_radii[coil_id] {radius = max_radii[coil_id];}
 
             // If center is too close to the boundary, move it away. Iterate 10 times and then shrink the radius
-            let boundary_point = closest_point(&center, boundary_points);
-            for i in 0..10 {
-                let vec_to_boundary = center - *boundary_point;
-                let distance_to_boundary = vec_to_boundary.norm();
-                if distance_to_boundary < radius {
-                    on_boundary[coil_id] = true;
-                    if i < 9 {center = *boundary_point + vec_to_boundary.normalize() * radius;}
-                    else {radius = distance_to_boundary;}
+            if let Some(boundary_point) = closest_point_by(&center, boundary_points, |a, b| self.boundary_metric.distance(a, b, &coil.normal)) {
+                for i in 0..10 {
+                    let mut vec_to_boundary = -self.boundary_metric.vector(&center, boundary_point, &coil.normal);
+                    if let Some(lock_normal) = lock_normal.as_ref() {
+                        vec_to_boundary = vec_to_boundary.rej_onto(lock_normal);
+                    }
+                    let distance_to_boundary = vec_to_boundary.norm();
+                    if distance_to_boundary < radius {
+                        on_boundary[coil_id] = true;
+                        if i == 0 && self.warn_on_shift {
+                            // Recorded every iteration, so only print the details when verbose
+                            let group = format!("Coil {} shifted to boundary", coil_id);
+                            if self.verbose {
+                                warnings::warn_grouped(&group, &format!("Coil {} pushed off the boundary at {:.2}", coil_id, center));
+                            } else {
+                                warnings::record(&group);
+                            }
+                        }
+                        if i < 9 {center += vec_to_boundary.normalize() * (radius - distance_to_boundary);}
+                        else {radius = distance_to_boundary;}
+                    }
                 }
             }
 
-            // Project the center onto the surface
-            new_circles[coil_id].center = center - (&center - surface);
+            // Keep the wire off the other coils' port keepouts
+            let keepout_center = self.avoid_port_keepouts(coil_id, center, radius, coil.normal, &port_points);
+            center = match lock_normal.as_ref() {
+                Some(lock_normal) => center + (keepout_center - center).rej_onto(lock_normal),
+                None => keepout_center,
+            };
+
+            // Project the center onto the surface (along the plane for locked coils)
+            new_circles[coil_id].center = match lock_normal.as_ref() {
+                Some(lock_normal) => center - (&center - surface).rej_onto(lock_normal),
+                None => center - (&center - surface),
+            };
             new_circles[coil_id].coil_radius = radius;
         }
 
         // Return the updated circles
-        (new_circles, objective, close_coils)
+        Ok((new_circles, objective, close_coils))
     }
     
     /// Update the parameters of the circles with symmetry
@@ -936,26 +1869,31 @@ impl Method {
         original_circles: &Vec::<CircleArgs>,
         layout_out: &layout::Layout,
         static_layout: &Option<layout::Layout>,
-        surface: &Surface,
+        circle_surfaces: &CircleSurfaces,
         symmetry_plane: &Plane,
         boundary_points: &Vec::<Point>,
         on_boundary: &mut Vec::<bool>,
         moments: &mut Vec::<Moment>
-    ) -> (Vec<CircleArgs>, Vec<CircleArgs>, Vec<CircleArgs>, f32, usize) {
+    ) -> layout::ProcResult<SymCircleUpdate> {
+
+        check_sym_groups(sym_circles.len(), pos_circles.len(), neg_circles.len(), layout_out.coils.len())?;
+        let surface = circle_surfaces.surface;
 
         let old_circles = concat(vec![sym_circles.clone(), pos_circles.clone(), neg_circles.clone()]);
 
-        // Update the circles
+        // Update the circles, with a reduced-DOF step for the on-plane circles if locked
+        let locked_plane = if self.lock_aspect {Some((symmetry_plane, sym_circles.len()))} else {None};
         let (new_circles, objective, close_coils) = self.update_circles(
             &old_circles,
             original_circles,
             layout_out,
             static_layout,
-            surface,
+            circle_surfaces,
             boundary_points,
             on_boundary,
-            moments
-        );
+            moments,
+            locked_plane,
+        )?;
 
         // Split the circles back into their respective groups
         let mut new_sym_circles = Vec::<CircleArgs>::new();
@@ -972,37 +1910,56 @@ impl Method {
         }
 
         // Project the symmetric circles onto the symmetry plane, then again onto the surface
-        for circle in new_sym_circles.iter_mut() {
-            circle.center = symmetry_plane.project_point(&circle.center);
-            circle.center = circle.center - (&circle.center - surface).rej_onto(&symmetry_plane.get_normal());
+        // Locked circles never left the plane
+        if !self.lock_aspect {
+            for circle in new_sym_circles.iter_mut() {
+                circle.center = symmetry_plane.project_point(&circle.center);
+                circle.center = circle.center - (&circle.center - surface).rej_onto(&symmetry_plane.get_normal());
+            }
         }
 
-        // Average the positive and negative circles (flipped) to keep them symmetric
-        for (pos_circle, neg_circle) in new_pos_circles.iter_mut().zip(new_neg_circles.iter_mut()) {
-            pos_circle.center = ((GeoVector::from(pos_circle.center) + GeoVector::from(neg_circle.center.reflect_across(&symmetry_plane))) / 2.0).into();
+        // Average the positive and negative circles (flipped) to keep them symmetric,
+        // weighted towards whichever side is pinned at the boundary (see `sym_pair_center`)
+        let pos_offset = sym_circles.len();
+        let neg_offset = sym_circles.len() + pos_circles.len();
+        for (pair_id, (pos_circle, neg_circle)) in new_pos_circles.iter_mut().zip(new_neg_circles.iter_mut()).enumerate() {
+            pos_circle.center = sym_pair_center(
+                pos_circle.center,
+                neg_circle.center,
+                symmetry_plane,
+                on_boundary[pos_offset + pair_id],
+                on_boundary[neg_offset + pair_id],
+            );
             neg_circle.center = pos_circle.center.reflect_across(&symmetry_plane);
         }
 
         // Return the updated circles
-        (new_sym_circles, new_pos_circles, new_neg_circles, objective, close_coils)
+        Ok((new_sym_circles, new_pos_circles, new_neg_circles, objective, close_coils))
     }
 
     /// Get the final statistics
     /// Returns: Objective function, number of close coils, self-inductances, mutual inductances, and static mutual inductances
     fn get_statistics(
         &self,
-        circles: &Vec::<CircleArgs>,
         layout_out: &layout::Layout,
         static_layout: &Option<layout::Layout>,
         calc_all_nonstatic: bool,
         calc_all_static: bool
     ) -> (f32, usize, Vec<f32>, Vec<Vec<(usize, f32)>>, Vec<Vec<(usize, f32)>>) {
+        let _timer = timing::phase("Statistics");
 
-        // Initialize objective function and number of close coils
-        let mut objective = 0.0;
-        let mut close_coils = 0;
+        // Coupling between the laid out coils, from the method-independent statistics
+        let stats = layout::statistics(layout_out, self.close_cutoff, &layout::StatisticsOptions{
+            shield_plane: self.shield_plane.as_ref(),
+            all_pairs: calc_all_nonstatic,
+            ..Default::default()
+        });
 
-        let mut self_inductances = vec![0.0; layout_out.coils.len()];
+        // Objective function and number of close coils
+        let mut objective = stats.close_pairs.iter().map(|(_, _, _, k)| k * k * 1.0e6).sum::<f32>();
+        let mut close_coils = stats.close_pairs.len();
+
+        let self_inductances: Vec<f32> = stats.coils.iter().map(|coil| coil.self_inductance).collect();
         let mut static_self_inductances: Vec::<Option<f32>> = if let Some(static_layout) = static_layout.as_ref() {
             vec![None; static_layout.coils.len()]
         } else {
@@ -1012,39 +1969,19 @@ impl Method {
         let mut mutual_inductances: Vec<Vec<(usize, f32)>> = vec![vec![]; layout_out.coils.len()];
         let mut static_mutual_inductances: Vec<Vec<(usize, f32)>> = vec![vec![]; layout_out.coils.len()];
 
-        // Calculate all the self inductances
-        for (coil_id, coil) in layout_out.coils.iter().enumerate() {
-            self_inductances[coil_id] = coil.self_inductance(1.0);
+        // Track the mutual inductances in order of the other coil
+        for (coil_id, other_id, m, _) in stats.close_pairs.iter().chain(stats.far_pairs.iter()) {
+            mutual_inductances[*coil_id].push((*other_id, *m));
+        }
+        for mutual_ind_vec in mutual_inductances.iter_mut() {
+            mutual_ind_vec.sort_by_key(|(other_id, _)| *other_id);
         }
 
-        // Calculate the objective contribution for each coil
-        for (coil_id, coil) in layout_out.coils.iter().enumerate() {
-
-            // Check all coils of a higher id than the current coil
-            for (other_id, other_coil) in layout_out.coils.iter().enumerate() {
-                if other_id > coil_id {
-
-                    // Establish vectors and distances
-                    let close = (coil.center - other_coil.center).norm() 
-                        / (circles[coil_id].coil_radius + circles[other_id].coil_radius) < self.close_cutoff;
-
-                    // Count objective from close coils
-                    if calc_all_nonstatic || close {
-
-                        // Get coupling
-                        let m = coil.mutual_inductance(other_coil, 1.0);
+        // Closeness to static coils goes by the radii as laid out (see `update_circles`)
+        let effective_radii: Vec<f32> = stats.coils.iter().map(|coil| coil.radius).collect();
 
-                        // Track the objective function and close coils
-                        if close {
-                            close_coils += 1;
-                            objective += m * m * 1.0e6 / (self_inductances[coil_id] * self_inductances[other_id]);
-                        }
-
-                        // Track the mutual inductance
-                        mutual_inductances[coil_id].push((other_id, m));
-                    }
-                }
-            }
+        // Calculate the static objective contribution for each coil
+        for (coil_id, coil) in layout_out.coils.iter().enumerate() {
 
             // Check all static coils
             if let Some(static_layout) = static_layout.as_ref() {
@@ -1054,7 +1991,7 @@ impl Method {
                     // Calculate proximity exactly to allow for non-spherical static coils
                     for vertex in static_coil.vertices.iter() {
                         let vec_from_static = coil.center - vertex.point;
-                        if vec_from_static.norm() / circles[coil_id].coil_radius < self.close_cutoff {
+                        if vec_from_static.norm() / effective_radii[coil_id] < self.close_cutoff {
                             close = true;
                             break;
                         }
@@ -1088,9 +2025,308 @@ impl Method {
         (objective, close_coils, self_inductances, mutual_inductances, static_mutual_inductances)
     }
 
+    /// Get the major axis of an elliptical element, in the plane of the coil.
+    /// The zero-angle vector (as used for breaks), rotated by the element's rotation angle.
+    fn major_axis(&self, circle: &CircleArgs, coil_normal: GeoVector) -> GeoVector {
+        let coil_normal = coil_normal.normalize();
+        let zero_angle_vector = self.coil_zero_angle_vector(circle, coil_normal).rej_onto(&coil_normal).normalize();
+        zero_angle_vector.rotate_around(&coil_normal, circle.rotation_angle * std::f32::consts::PI / 180.0)
+    }
+
+    /// Get the zero-angle vector for a coil, falling back to the backup if the primary is too close to the coil normal,
+    /// and to an axis perpendicular to the normal if both are (see `zero_angle_reference`).
+    /// The primary is the circle's own `zero_angle_vector` if it has one, otherwise the method's.
+    fn coil_zero_angle_vector(&self, circle: &CircleArgs, coil_normal: GeoVector) -> GeoVector {
+        let zero_angle_vector = circle.zero_angle_vector.unwrap_or(self.zero_angle_vector);
+        zero_angle_reference(&coil_normal, &[zero_angle_vector, self.backup_zero_angle_vector])
+    }
+
+    /// Get the self inductance of a coil, including its own shield image if there is a shield plane
+    fn self_inductance(&self, coil: &layout::Coil) -> f32 {
+        match self.shield_plane.as_ref() {
+            Some(shield_plane) => coil.shielded_self_inductance(shield_plane, 1.0),
+            None => coil.self_inductance(1.0),
+        }
+    }
+
+    /// Get the mutual inductance between two coils, including through the other coil's shield `image` if given,
+    /// with its gradient wrt the center of each coil and wrt the radius of the first.
+    /// Returns (M [nH], dM/d(center) [nH/mm], dM/d(other center) [nH/mm], dM/dr [nH/mm]).
+    /// The image moves with the other coil mirrored across the shield, so its part of the other coil's gradient is reflected, not negated.
+    fn pair_mutual_inductance_full(&self, coil: &layout::Coil, other_coil: &layout::Coil, image: Option<&layout::Coil>) -> (f32, GeoVector, GeoVector, f32) {
+        let (m, dx, dy, dz, dr) = coil.mutual_inductance_full(other_coil, 1.0);
+        let (grad, other_grad) = (GeoVector::new(dx, dy, dz), -GeoVector::new(dx, dy, dz));
+        match (image, self.shield_plane.as_ref()) {
+            (Some(image), Some(shield_plane)) => {
+                let (mi, dxi, dyi, dzi, dri) = coil.mutual_inductance_full(image, 1.0);
+                let image_grad = GeoVector::new(dxi, dyi, dzi);
+                (m + mi, grad + image_grad, other_grad - image_grad.reflect_across(&shield_plane.get_normal()), dr + dri)
+            },
+            _ => (m, grad, other_grad, dr),
+        }
+    }
+
+    /// Get the shield image of each coil, if there is a shield plane
+    fn shield_images(&self, layout_out: &layout::Layout) -> Vec<Option<layout::Coil>> {
+        layout_out.coils.iter()
+            .map(|coil| self.shield_plane.as_ref().map(|shield_plane| coil.image_across(shield_plane)))
+            .collect()
+    }
+
+    /// Shrink coils where the surface is too curved for them to stay close to planar.
+    /// The local curvature is the mean absolute mean curvature of the surface vertices within the coil radius (or at the nearest vertex).
+    /// Where it exceeds `curvature_threshold`, the radius is scaled by the threshold over the local curvature.
+    /// Returns the (possibly shrunk) circles.
+    fn shrink_for_curvature(&self, surface: &Surface, circles: &[CircleArgs]) -> Vec<CircleArgs> {
+        let mut circles = circles.to_vec();
+        let curvature_threshold = match self.curvature_threshold {
+            Some(curvature_threshold) if !surface.vertices.is_empty() => curvature_threshold,
+            _ => return circles,
+        };
+        let curvature = surface.vertex_curvature();
+
+        for (circle_id, circle) in circles.iter_mut().enumerate() {
+            let nearby: Vec<f32> = surface.vertices.iter().zip(curvature.iter())
+                .filter(|(vertex, _)| vertex.point.distance(&circle.center) <= circle.coil_radius)
+                .map(|(_, (mean, _))| mean.abs())
+                .collect();
+            let local_curvature = if nearby.is_empty() {
+                curvature[circle.center.nearest_point_idx(surface)].0.abs()
+            } else {
+                nearby.iter().sum::<f32>() / nearby.len() as f32
+            };
+
+            if local_curvature > curvature_threshold {
+                let radius = circle.coil_radius * curvature_threshold / local_curvature;
+                warnings::warn(&format!("Coil {} shrunk from radius {:.2} to {:.2} for local curvature {:.3} (threshold {:.3})",
+                    circle_id, circle.coil_radius, radius, local_curvature, curvature_threshold
+                ));
+                circle.coil_radius = radius;
+            }
+        }
+        circles
+    }
+
+    /// Move coils nested entirely inside another coil out onto the outer wire, if the nesting policy is `Offset`.
+    /// This needs its own layout pass before optimizing, the other policies don't change the circles (see `warn_nesting`).
+    /// Returns the (possibly offset) circles.
+    fn resolve_nesting(&self, surface: &Surface, circles: &[CircleArgs]) -> layout::ProcResult<Vec<CircleArgs>> {
+        let mut circles = circles.to_vec();
+        if self.nesting_policy != NestingPolicy::Offset {
+            return Ok(circles);
+        }
+
+        let layout_out = self.lay_out_coils(surface, &circles, false)?;
+        let circle_surfaces = CircleSurfaces::new(surface, &circles)?;
+        for (outer_id, inner_id) in nested_pairs(&layout_out) {
+            let (outer, inner) = (&layout_out.coils[outer_id], &layout_out.coils[inner_id]);
+
+            // Move the inner center out to the outer wire, away from the outer center (or along the zero-angle vector if concentric)
+            let mut direction = (inner.center - outer.center).rej_onto(&outer.normal);
+            if direction.norm() < self.epsilon {
+                direction = self.coil_zero_angle_vector(&circles[outer_id], outer.normal).rej_onto(&outer.normal);
+            }
+            if let (Some(symmetry_plane), true) = (self.symmetry_plane(), circles[inner_id].on_symmetry_plane) {
+                direction = direction.rej_onto(&symmetry_plane.get_normal());
+            }
+            let center = outer.center + direction.normalize() * outer.average_radius();
+            let center = center - (&center - circle_surfaces.get(&circles[inner_id]));
+            warnings::warn_grouped(&format!("Coil {} is nested inside coil {}", inner_id, outer_id), &format!("Coil {} is nested inside coil {}, offsetting its center from {:.2} to {:.2}",
+                inner_id, outer_id, circles[inner_id].center, center
+            ));
+            circles[inner_id].center = center;
+        }
+        Ok(circles)
+    }
+
+    /// Report coils nested entirely inside another coil, if the nesting policy is `Warn`.
+    /// Checks the first layout pass of the optimization, so it needs no extra pass.
+    fn warn_nesting(&self, layout_out: &layout::Layout) {
+        if self.nesting_policy != NestingPolicy::Warn {
+            return;
+        }
+        for (outer_id, inner_id) in nested_pairs(layout_out) {
+            warnings::warn(&format!("Coil {} is nested inside coil {}", inner_id, outer_id));
+        }
+    }
+
+    /// Check that a circle lays out on its own, with a valid wire radius (see `skip_failed_coils`).
+    /// Returns a `ProcResult` with `()`, or the `Err` from laying it out.
+    fn check_circle_lays_out(&self, surface: &Surface, circle: &CircleArgs) -> layout::ProcResult<()> {
+        if self.wire_radius >= circle.coil_radius {
+            return layout::err_str(&format!("Wire radius ({}) must be smaller than the coil radius ({})", self.wire_radius, circle.coil_radius));
+        }
+        let single_method = Method{resolve_overlaps: false, ..self.clone()};
+        single_method.lay_out_coils(surface, &vec![*circle], false)?;
+        Ok(())
+    }
+
+    /// Keep at most `max_coils` circles, picked greedily for coverage and low coupling.
+    /// Each pick is the circle with the largest fraction of newly covered surface vertices (see `Layout::vertex_coverage`),
+    /// scaled by one minus its largest coupling factor magnitude to the circles already kept.
+    /// Kept circles stay in config order, and the dropped circles are reported.
+    /// With a symmetry plane, the cap counts configured circles, not their mirrored copies.
+    fn select_circles(&self, surface: &Surface, circles: &[CircleArgs]) -> layout::ProcResult<Vec<CircleArgs>> {
+        let max_coils = match self.max_coils {
+            Some(max_coils) if max_coils < circles.len() => max_coils,
+            _ => return Ok(circles.to_vec()),
+        };
+        if max_coils == 0 {
+            return layout::err_str("max_coils must be at least 1");
+        }
+
+        let layout_out = self.lay_out_coils(surface, &circles.to_vec(), false)?;
+        let coverage: Vec<Vec<bool>> = layout_out.coils.iter().map(|coil| {
+            layout::Layout{coils: vec![coil.clone()]}.vertex_coverage(surface).iter().map(|count| *count > 0).collect()
+        }).collect();
+        let coupling: Vec<Vec<f32>> = layout_out.coils.iter().map(|coil| {
+            layout_out.coils.iter().map(|other_coil| coil.coupling_factor(other_coil, 1.0).abs()).collect()
+        }).collect();
+
+        let vertex_count = surface.vertices.len().max(1) as f32;
+        let mut covered = vec![false; surface.vertices.len()];
+        let mut kept = Vec::<usize>::with_capacity(max_coils);
+        while kept.len() < max_coils {
+            // Score each remaining circle, falling back to the lowest coupling once everything is covered
+            let (best_id, _) = (0..circles.len())
+                .filter(|circle_id| !kept.contains(circle_id))
+                .map(|circle_id| {
+                    let new_coverage = coverage[circle_id].iter().zip(covered.iter())
+                        .filter(|(covers, already)| **covers && !**already)
+                        .count() as f32 / vertex_count;
+                    let max_coupling = kept.iter().map(|kept_id| coupling[circle_id][*kept_id]).fold(0.0, f32::max);
+                    (circle_id, (new_coverage * (1.0 - max_coupling).max(0.0), -max_coupling))
+                })
+                .max_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap())
+                .expect("BUG: No circles left to keep!");
+
+            for (already, covers) in covered.iter_mut().zip(coverage[best_id].iter()) {
+                *already |= *covers;
+            }
+            kept.push(best_id);
+        }
+        kept.sort();
+
+        let dropped: Vec<usize> = (0..circles.len()).filter(|circle_id| !kept.contains(circle_id)).collect();
+        println!("Keeping {} of {} circles (max_coils), dropped circles {:?}", max_coils, circles.len(), dropped);
+        println!("Coverage of the kept circles: {:.1}%", 100.0 * covered.iter().filter(|covers| **covers).count() as f32 / vertex_count);
+        println!();
+        Ok(kept.iter().map(|circle_id| circles[*circle_id]).collect())
+    }
+
+    /// Get the port point of each coil, as placed by `place_ports` on this layout pass.
+    fn port_points(&self, layout_out: &layout::Layout) -> Vec<Option<Point>> {
+        layout_out.coils.iter().map(|coil| coil.port.map(|port| coil.vertices[port].point)).collect()
+    }
+
+    /// Keep the wires off the port keepout discs, if `port_keepout_radius` is set.
+    /// Each coil's port is placed where it will land when the breaks are added (once per layout pass, see `even_break_port`),
+    /// then the other coils' wires that pass within `port_keepout_radius` of it are bent around the edge of the disc
+    /// and snapped back to their surface. A wire that encloses the port goes around the outside of the disc, any other wire the inside.
+    fn place_ports(&self, layout_out: &mut layout::Layout, circles: &[CircleArgs], surfaces: &[&Surface]) {
+        if self.port_keepout_radius <= 0.0 {
+            return;
+        }
+        let _timer = timing::phase("Port keepouts");
+
+        // Place the ports
+        for (coil, circle) in layout_out.coils.iter_mut().zip(circles.iter()) {
+            let break_angle_offset_rad = circle.break_angle_offset * std::f32::consts::PI / 180.0;
+            let zero_angle_vector = self.coil_zero_angle_vector(circle, coil.normal);
+            coil.port = even_break_port(coil, circle.break_count, break_angle_offset_rad, zero_angle_vector);
+        }
+
+        // Bend the other wires around each port
+        let port_points = self.port_points(layout_out);
+        let port_normals: Vec<GeoVector> = layout_out.coils.iter().map(|coil| coil.normal).collect();
+        for (port_id, port_point) in port_points.iter().enumerate() {
+            let Some(port_point) = port_point else { continue };
+            let port_normal = port_normals[port_id];
+            for (coil_id, coil) in layout_out.coils.iter_mut().enumerate() {
+                if coil_id == port_id {
+                    continue;
+                }
+
+                // Push along the line from this coil's center through the port, outward if the port is inside the coil
+                let flat_vec_to_port = (*port_point - coil.center).rej_onto(&port_normal);
+                let outward = flat_vec_to_port.normalize();
+                if outward.has_nan() {
+                    continue;
+                }
+                let port_inside = coil.vertices.iter()
+                    .map(|v| (v.point - coil.center).rej_onto(&port_normal).norm())
+                    .fold(0.0, f32::max) > flat_vec_to_port.norm();
+                let side = if port_inside { 1.0 } else { -1.0 };
+
+                for vertex in coil.vertices.iter_mut() {
+                    let flat_vec_from_port = (vertex.point - *port_point).rej_onto(&port_normal);
+                    if flat_vec_from_port.norm() >= self.port_keepout_radius {
+                        continue;
+                    }
+                    let along = flat_vec_from_port.dot(&outward);
+                    let across = flat_vec_from_port - outward * along;
+                    let clear_along = side * (self.port_keepout_radius.powi(2) - across.norm().powi(2)).sqrt();
+                    let point = vertex.point + outward * (clear_along - along);
+                    vertex.point = point - (&point - surfaces[coil_id]);
+                }
+            }
+        }
+    }
+
+    /// Push a coil off the port keepout discs of the other coils, like the boundary.
+    /// If the wire (at `radius` from `center`) passes within `port_keepout_radius` of another coil's port,
+    /// the center is moved along the surface so the wire clears the disc on whichever side it's already on.
+    fn avoid_port_keepouts(&self, coil_id: usize, center: Point, radius: f32, coil_normal: GeoVector, port_points: &[Option<Point>]) -> Point {
+        let mut center = center;
+        for (other_id, port_point) in port_points.iter().enumerate() {
+            let port_point = match port_point {
+                Some(port_point) if other_id != coil_id => *port_point,
+                _ => continue,
+            };
+            let flat_vec_from_port = (center - port_point).rej_onto(&coil_normal);
+            let distance_to_port = flat_vec_from_port.norm();
+            if (distance_to_port - radius).abs() >= self.port_keepout_radius || distance_to_port <= 0.0 {
+                continue;
+            }
+            let clear_distance = if distance_to_port >= radius || radius <= self.port_keepout_radius {
+                radius + self.port_keepout_radius
+            } else {
+                radius - self.port_keepout_radius
+            };
+            center += flat_vec_from_port.normalize() * (clear_distance - distance_to_port);
+        }
+        center
+    }
+
+    /// Get the radius of an element in the direction of a point (the coil radius for circles)
+    fn radius_toward(&self, circle: &CircleArgs, coil_normal: GeoVector, point: Point) -> f32 {
+        if circle.aspect_ratio == 1.0 {
+            return circle.coil_radius;
+        }
+        let major_axis = self.major_axis(circle, coil_normal);
+        let minor_axis = coil_normal.normalize().cross(&major_axis);
+        let vec_to_point = point - circle.center;
+        let (x, y) = (vec_to_point.dot(&major_axis), vec_to_point.dot(&minor_axis));
+        let rho = (x * x + y * y).sqrt();
+        if rho <= 0.0 {
+            return circle.coil_radius;
+        }
+        let (a, b) = (circle.coil_radius * circle.aspect_ratio, circle.coil_radius);
+        a * b / ((b * x / rho).powi(2) + (a * y / rho).powi(2)).sqrt()
+    }
+
     /// Do overlaps between the coils
+    /// Leaves the coils as intersected if `resolve_overlaps` is off.
+    /// Each wire dips one wire spacing (`clearance` plus the wire diameter) under the coils it crosses.
+    /// If `max_stack_offset` is set, where a wire dips under several coils at once the offsets stack instead, one spacing per coil,
+    /// up to the cap (with a warning, as the wires there will be too close).
     fn mousehole_overlap(&self, layout_out: &mut layout::Layout, circles: &Vec::<CircleArgs>) {
+        if !self.resolve_overlaps {
+            return;
+        }
+        let _timer = timing::phase("Overlap resolution");
         let intersections = self.get_intersections(layout_out, 2.0, circles);
+        let coil_normals: Vec<GeoVector> = layout_out.coils.iter().map(|coil| coil.normal).collect();
         
         // Structure for managing intersecting segments
         #[derive(Clone)]
@@ -1103,6 +2339,8 @@ impl Method {
         
         // Do intersections for each coil
         for (coil_id, coil) in layout_out.coils.iter_mut().enumerate() {
+            // Number of coils each vertex dips under
+            let mut stack_levels = vec![0_usize; coil.vertices.len()];
 
             // Get the length of the coil and the distance around of each point
             let mut point_lengths = vec![0.0; coil.vertices.len()];
@@ -1129,10 +2367,10 @@ impl Method {
             };
             let mut segments = Vec::<IntersectionSegment>::new();
             
-            // Get all the intersections between a coil and a coil of higher coil id than it. 
+            // Get all the intersections between a coil and the coils it crosses over (see `crosses_over`)
             let mut any_intersections = false;
-            for other_id in coil_id+1..circles.len() {
-                let other_intersection = &intersections[coil_id][other_id];
+            for other_id in (0..circles.len()).filter(|other_id| crosses_over(circles, coil_id, *other_id)) {
+                let other_intersection = intersections.get(&(coil_id, other_id)).map_or(&[][..], |points| points.as_slice());
 
                 // Ignore loops entirely contained within other loops
                 if coil.vertices.len() - other_intersection.len() < 2 {
@@ -1141,6 +2379,9 @@ impl Method {
 
                 if other_intersection.len() > 0 {
                     any_intersections = true;
+                    for p in other_intersection.iter() {
+                        stack_levels[*p] += 1;
+                    }
                     
                     let mut start = other_intersection[0];
                     let mut end;
@@ -1191,7 +2432,7 @@ impl Method {
                     vec_to_center.norm()
                 };
                 let inside_other_coil = |p: usize| -> bool {
-                    distance_to_other_coil(p) < circles[other_id].coil_radius
+                    distance_to_other_coil(p) < self.radius_toward(&circles[other_id], coil_normals[other_id], coil.vertices[p].point)
                 };
                 for segment in segments.iter_mut() {
                     let mut p_prev = segment.start;
@@ -1313,7 +2554,28 @@ impl Method {
             // Offset the segments
             for segment in merged_segments.iter_mut() {
 
-                let c = self.clearance + 2.0 * coil.wire_radius;
+                let unwrapped_end = if segment.end < segment.start {
+                    segment.end + coil.vertices.len()
+                }
+                else {
+                    segment.end
+                };
+
+                // With a cap set, stack the offset for each coil the segment dips under at once, up to the cap
+                let stack_level = match self.max_stack_offset {
+                    Some(_) => (segment.start..=unwrapped_end).map(|p| stack_levels[p % coil.vertices.len()]).max().unwrap_or(1).max(1),
+                    None => 1,
+                };
+                let mut c = (self.clearance + 2.0 * coil.wire_radius) * stack_level as f32;
+                if let Some(max_stack_offset) = self.max_stack_offset {
+                    if c > max_stack_offset {
+                        warnings::warn_grouped("Overlap stack offset capped", &format!(
+                            "Coil {} dips under {} coils near {:.2}, capping the offset of {:.2} at {:.2} -- the region is too crowded",
+                            coil_id, stack_level, coil.vertices[segment.start].point, c, max_stack_offset
+                        ));
+                        c = max_stack_offset;
+                    }
+                }
                 // The amount to offset the wire
                 let start_tail = segment.wire_crossings[0] / segment.length;
                 let end_tail = 1.0 - segment.wire_crossings[segment.wire_crossings.len() - 1] / segment.length;
@@ -1361,15 +2623,9 @@ impl Method {
                     }
                 };
 
-                let unwrapped_end = if segment.end < segment.start {
-                    segment.end + coil.vertices.len()
-                }
-                else {
-                    segment.end
-                };
-
                 let start_anchor = (segment.start + coil.vertices.len() - 1) % coil.vertices.len();
 
+                coil.overlap_segments.push((segment.start, segment.end));
                 for p in segment.start..=unwrapped_end {
                     let pid = p % coil.vertices.len();
                     coil.vertices[pid].point = coil.vertices[pid].point - coil.vertices[pid].surface_normal * offset(point_distance(start_anchor, pid));
@@ -1408,20 +2664,24 @@ impl Method {
         adjacency
     }
 
-    /// Get a matrix of vectors of intersection points between cleaned coils
-    #[allow(dead_code)]
-    fn get_intersections(&self, intersecting_layout: &layout::Layout, clearance_scale: f32, circles: &Vec::<CircleArgs>) -> Vec<Vec<Vec<usize>>> {
-        let mut intersections: Vec<Vec<Vec<usize>>> = vec![vec![vec![]; circles.len()]; circles.len()];
+    /// Get the intersection points between cleaned coils (see `Intersections`).
+    /// Only pairs whose bounding spheres come within the tolerance of each other are checked.
+    fn get_intersections(&self, intersecting_layout: &layout::Layout, clearance_scale: f32, circles: &Vec::<CircleArgs>) -> Intersections {
+        let mut intersections = Intersections::new();
+        let bounding_radii = bounding_radii(intersecting_layout);
         for (i, coil) in intersecting_layout.coils.iter().enumerate() {
             for (j, other_coil) in intersecting_layout.coils.iter().enumerate() {
-                if i != j {
-                    for (k, vertex) in coil.vertices.iter().enumerate() {
-                        if ((vertex.point - other_coil.center).norm() - circles[j].coil_radius).abs() < 
-                            (coil.wire_radius + other_coil.wire_radius + self.clearance) * clearance_scale {
-                            
-                            intersections[i][j].push(k);
-                        }
-                    }
+                let tolerance = (coil.wire_radius + other_coil.wire_radius + self.clearance) * clearance_scale;
+                let outline_radius = circles[j].coil_radius * circles[j].aspect_ratio.max(1.0);
+                if i == j || (coil.center - other_coil.center).norm() >= bounding_radii[i] + outline_radius + tolerance {
+                    continue;
+                }
+                let points: Vec<usize> = coil.vertices.iter().enumerate()
+                    .filter(|(_, vertex)| ((vertex.point - other_coil.center).norm() - self.radius_toward(&circles[j], other_coil.normal, vertex.point)).abs() < tolerance)
+                    .map(|(k, _)| k)
+                    .collect();
+                if !points.is_empty() {
+                    intersections.insert((i, j), points);
                 }
             }
         }
@@ -1438,3 +2698,1470 @@ mod debug {
         println!("{}", s);
     }
 }
+
+/// Copy the user label and group from each circle to its coil.
+fn tag_coils(layout_out: &mut layout::Layout, circles: &[CircleArgs]) {
+    for (coil, circle) in layout_out.coils.iter_mut().zip(circles.iter()) {
+        coil.label = circle.label.map(String::from);
+        coil.group = circle.group;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::{flat_adam, flat_surface, temp_dir};
+
+    #[test]
+    fn coils_shrink_on_small_sphere() {
+        let method = Method{
+            curvature_threshold: Some(0.05),
+            ..flat_adam(Vec::new())
+        };
+        let circle_on = |radius: f32| CircleArgs{
+            center: Point::new(0.0, 0.0, radius),
+            coil_radius: 4.0,
+            ..CircleArgs::default()
+        };
+
+        let small_sphere = Surface::sphere(Point::zero(), 10.0, 48);
+        let large_sphere = Surface::sphere(Point::zero(), 50.0, 96);
+        let curvature = small_sphere.vertex_curvature();
+        let probe = Point::new(0.0, 10.0 * 0.6, 10.0 * 0.8);
+        let probe_id = (0..small_sphere.vertices.len())
+            .min_by(|a, b| small_sphere.vertices[*a].point.distance(&probe).total_cmp(&small_sphere.vertices[*b].point.distance(&probe)))
+            .unwrap();
+        let (mean, gaussian) = curvature[probe_id];
+        assert!((mean - 0.1).abs() < 0.01, "Mean curvature {mean}");
+        assert!((gaussian - 0.01).abs() < 0.002, "Gaussian curvature {gaussian}");
+
+        let small = method.shrink_for_curvature(&small_sphere, &[circle_on(10.0)]);
+        let large = method.shrink_for_curvature(&large_sphere, &[circle_on(50.0)]);
+        assert_eq!(large[0].coil_radius, 4.0);
+        assert!((small[0].coil_radius - 2.0).abs() < 0.2, "Shrunk radius {}", small[0].coil_radius);
+
+        let small_layout = method.lay_out_coils(&small_sphere, &small, false).unwrap();
+        let large_layout = method.lay_out_coils(&large_sphere, &large, false).unwrap();
+        assert!(small_layout.coils[0].average_radius() < 0.75 * large_layout.coils[0].average_radius());
+    }
+
+    #[test]
+    fn auto_symmetry_plane_fits_sphere_but_not_lopsided_mesh() {
+        let center = Point::new(3.0, -2.0, 5.0);
+        let sphere = Surface::sphere(center, 20.0, 12);
+        let plane = sphere.best_symmetry_plane().unwrap();
+        let sphere_residual = sphere.symmetry_residual(&plane);
+        assert!(plane.distance_to_point(&center).abs() < 0.2, "Plane misses the center: {}", plane);
+        assert!(sphere_residual < 0.5 * sphere.mean_edge_length(), "Sphere residual {sphere_residual}");
+
+        // Stretch one side of each axis differently and shear, so no plane fits well
+        let lopsided_points = sphere.vertices.iter().map(|vertex| {
+            let p = vertex.point - center;
+            Point::new(
+                if p.x > 0.0 {1.8 * p.x} else {p.x} + 0.3 * p.z,
+                if p.y > 0.0 {1.4 * p.y} else {p.y},
+                if p.z > 0.0 {1.2 * p.z} else {p.z} + 0.01 * p.x * p.y,
+            )
+        }).collect();
+        let lopsided = Surface::from_triangles(lopsided_points, sphere.faces.iter().map(|face| face.vertices).collect());
+        let lopsided_plane = lopsided.best_symmetry_plane().unwrap();
+        let lopsided_residual = lopsided.symmetry_residual(&lopsided_plane);
+        assert!(lopsided_residual > 5.0 * sphere_residual, "Lopsided residual {lopsided_residual} vs. sphere {sphere_residual}");
+
+        // "auto" is accepted in the config
+        let method: Method = serde_json::from_value(serde_json::json!({"symmetry_plane": "auto", "circles": []})).unwrap();
+        assert!(matches!(method.symmetry_plane, Some(SymmetryPlane::Auto)));
+        assert!(serde_json::from_value::<Method>(serde_json::json!({"symmetry_plane": "best", "circles": []})).is_err());
+    }
+
+    #[test]
+    fn aspect_ratio_stretches_one_axis() {
+        let surface = Surface::square_grid(30.0, 60);
+        let method = flat_adam(Vec::new());
+        let circle = CircleArgs{
+            center: Point::zero(),
+            coil_radius: 5.0,
+            ..CircleArgs::default()
+        };
+        let ellipse = CircleArgs{
+            aspect_ratio: 2.0,
+            ..circle
+        };
+
+        let extents = |coil: &layout::Coil| -> (f32, f32) {
+            let xs = coil.vertices.iter().map(|v| v.point.x);
+            let ys = coil.vertices.iter().map(|v| v.point.y);
+            (
+                xs.clone().fold(f32::MIN, f32::max) - xs.fold(f32::MAX, f32::min),
+                ys.clone().fold(f32::MIN, f32::max) - ys.fold(f32::MAX, f32::min),
+            )
+        };
+
+        // The flat surface normal is along the zero-angle vector, so the backup (y) sets the major axis
+        let circle_layout = method.lay_out_coils(&surface, &vec![circle], false).unwrap();
+        let ellipse_layout = method.lay_out_coils(&surface, &vec![ellipse], false).unwrap();
+        let (circle_x, circle_y) = extents(&circle_layout.coils[0]);
+        let (ellipse_x, ellipse_y) = extents(&ellipse_layout.coils[0]);
+
+        assert!((circle_x - circle_y).abs() < 0.05 * circle_x);
+        assert!((ellipse_x - circle_x).abs() < 0.05 * circle_x);
+        assert!((ellipse_y / circle_y - 2.0).abs() < 0.05, "Ellipse extents {ellipse_x} x {ellipse_y}");
+    }
+
+    #[test]
+    fn absolute_center_freedom_caps_every_coil_alike() {
+        let surface = flat_surface();
+        let boundary_points = boundary_points(&surface);
+        // Heavily overlapping coils of different sizes, with a step far past the cap
+        let circles = vec![
+            CircleArgs{center: Point::new(-5.0, 0.0, 0.0), coil_radius: 5.0, ..CircleArgs::default()},
+            CircleArgs{center: Point::new(6.0, 0.0, 0.0), coil_radius: 12.0, ..CircleArgs::default()},
+        ];
+        let center_moves = |center_freedom_mode: CenterFreedomMode| -> Vec<f32> {
+            let method = Method{
+                step_size: 5.0,
+                center_freedom: if center_freedom_mode == CenterFreedomMode::Absolute { 0.5 } else { 0.05 },
+                center_freedom_mode,
+                ..flat_adam(Vec::new())
+            };
+            let layout_out = method.lay_out_coils(&surface, &circles, false).unwrap();
+            let (new_circles, _, _) = method.update_circles(
+                &circles,
+                &circles,
+                &layout_out,
+                &None,
+                &CircleSurfaces::new(&surface, &circles).unwrap(),
+                &boundary_points,
+                &mut vec![false; circles.len()],
+                &mut vec![Moment::new(); circles.len()],
+                None,
+            ).unwrap();
+            new_circles.iter().zip(circles.iter()).map(|(new, old)| new.center.distance(&old.center)).collect()
+        };
+
+        let relative = center_moves(CenterFreedomMode::RelativeToRadius);
+        assert!((relative[0] - 0.25).abs() < 1e-3 && (relative[1] - 0.6).abs() < 1e-3, "Relative moves {:?}", relative);
+        let absolute = center_moves(CenterFreedomMode::Absolute);
+        for center_move in absolute {
+            assert!((center_move - 0.5).abs() < 1e-3, "Absolute move {}", center_move);
+        }
+    }
+
+    #[test]
+    fn sparse_intersections_match_dense() {
+        let surface = flat_surface();
+        let method = flat_adam(Vec::new());
+        let circles = vec![
+            CircleArgs{center: Point::new(-6.0, 0.0, 0.0), coil_radius: 8.0, ..CircleArgs::default()},
+            CircleArgs{center: Point::new(6.0, 0.0, 0.0), coil_radius: 6.0, aspect_ratio: 1.5, ..CircleArgs::default()},
+            CircleArgs{center: Point::new(0.0, 10.0, 0.0), coil_radius: 6.0, ..CircleArgs::default()},
+            CircleArgs{center: Point::new(25.0, 25.0, 0.0), coil_radius: 6.0, ..CircleArgs::default()},
+        ];
+        let layout_out = method.lay_out_coils(&surface, &circles, false).unwrap();
+        let intersections = method.get_intersections(&layout_out, 2.0, &circles);
+
+        for (i, coil) in layout_out.coils.iter().enumerate() {
+            for (j, other_coil) in layout_out.coils.iter().enumerate().filter(|(j, _)| *j != i) {
+                let tolerance = (coil.wire_radius + other_coil.wire_radius + method.clearance) * 2.0;
+                let dense: Vec<usize> = (0..coil.vertices.len()).filter(|k| {
+                    let point = coil.vertices[*k].point;
+                    ((point - other_coil.center).norm() - method.radius_toward(&circles[j], other_coil.normal, point)).abs() < tolerance
+                }).collect();
+                assert_eq!(intersections.get(&(i, j)).cloned().unwrap_or_default(), dense, "Coils {} and {}", i, j);
+            }
+        }
+        // Only the overlapping pairs among the first three coils are stored
+        assert!(!intersections.is_empty());
+        assert!(intersections.keys().all(|(i, j)| *i < 3 && *j < 3));
+    }
+
+    #[test]
+    fn shield_image_gradient_matches_finite_difference() {
+        use crate::test_fixtures::circle_coil;
+
+        let method = Method{
+            shield_plane: Some(Plane::from_normal_and_offset(GeoVector::zhat(), -5.0)),
+            ..flat_adam(Vec::new())
+        };
+        let coil = circle_coil(Point::zero(), 10.0);
+        let other_coil = circle_coil(Point::new(22.0, 4.0, 0.0), 10.0);
+        let shifted = |coil: &layout::Coil, step: GeoVector| {
+            let mut coil = coil.clone();
+            coil.center += step;
+            for vertex in coil.vertices.iter_mut() {
+                vertex.point += step;
+            }
+            coil
+        };
+
+        // Move the other coil, and its image with it, along each axis
+        let shielded_m = |other_coil: &layout::Coil| {
+            let image = other_coil.image_across(method.shield_plane.as_ref().unwrap());
+            method.pair_mutual_inductance_full(&coil, other_coil, Some(&image)).0
+        };
+        let image = other_coil.image_across(method.shield_plane.as_ref().unwrap());
+        let (_, _, other_grad, _) = method.pair_mutual_inductance_full(&coil, &other_coil, Some(&image));
+        let h = 0.05;
+        for axis in [GeoVector::xhat(), GeoVector::yhat(), GeoVector::zhat()] {
+            let finite_difference = (shielded_m(&shifted(&other_coil, axis * h)) - shielded_m(&shifted(&other_coil, axis * -h))) / (2.0 * h);
+            let analytic = other_grad.dot(&axis);
+            assert!((finite_difference - analytic).abs() < 0.05 * other_grad.norm(),
+                "Gradient along {axis}: analytic {analytic} vs. finite difference {finite_difference}");
+        }
+    }
+
+    #[test]
+    fn port_keepout_deflects_neighbor_wire() {
+        use methods::LayoutMethodTrait;
+
+        let surface = flat_surface();
+        let method = Method{
+            port_keepout_radius: 4.0,
+            iterations: 0,
+            ..flat_adam(Vec::new())
+        };
+
+        // The port of the first coil is along the backup zero-angle vector (y), the second coil's wire runs over it
+        let circles = vec![
+            CircleArgs{center: Point::zero(), coil_radius: 10.0, ..CircleArgs::default()},
+            CircleArgs{center: Point::new(6.0, 16.0, 0.0), coil_radius: 8.0, ..CircleArgs::default()},
+        ];
+        let min_distance_to_port = |layout_out: &layout::Layout| -> f32 {
+            let port_point = layout_out.coils[0].vertices[layout_out.coils[0].port.unwrap()].point;
+            layout_out.coils[1].vertices.iter()
+                .map(|v| (v.point - port_point).rej_onto(&GeoVector::zhat()).norm())
+                .fold(f32::MAX, f32::min)
+        };
+
+        // Without the keepout, the wire runs over the port
+        let mut free_layout_out = Method{port_keepout_radius: 0.0, ..method.clone()}.lay_out_coils(&surface, &circles, false).unwrap();
+        free_layout_out.coils[0].port = method.lay_out_coils(&surface, &circles, false).unwrap().coils[0].port;
+        assert!(min_distance_to_port(&free_layout_out) < 2.0);
+
+        // Each layout pass bends the wire around the port
+        let layout_out = method.lay_out_coils(&surface, &circles, false).unwrap();
+        let min_distance = min_distance_to_port(&layout_out);
+        assert!(min_distance > 3.99, "Wire is {min_distance} from the port, inside the keepout");
+
+        // The update pushes the coil off the port
+        let boundary_points = boundary_points(&surface);
+        let (new_circles, _, _) = method.update_circles(
+            &circles,
+            &circles,
+            &layout_out,
+            &None,
+            &CircleSurfaces::new(&surface, &circles).unwrap(),
+            &boundary_points,
+            &mut vec![false; circles.len()],
+            &mut vec![Moment::new(); circles.len()],
+            None,
+        ).unwrap();
+        let port_point = layout_out.coils[0].vertices[layout_out.coils[0].port.unwrap()].point;
+        let wire_gap = |circle: &CircleArgs| ((circle.center - port_point).norm() - circle.coil_radius).abs();
+        assert!(wire_gap(&new_circles[1]) > wire_gap(&circles[1]));
+
+        // The final layout, with breaks, keeps the wire out too, even without iterating
+        let final_layout_out = Method{circles: circles.clone(), ..method.clone()}.do_layout(&surface).unwrap();
+        let min_distance = min_distance_to_port(&final_layout_out);
+        assert!(min_distance > 3.99, "Wire is {min_distance} from the final port, inside the keepout");
+    }
+
+    #[test]
+    fn coarse_to_fine_matches_full_resolution_faster() {
+        use methods::LayoutMethodTrait;
+
+        // Upper hemisphere of a generated sphere (a closed sphere has no boundary)
+        let sphere = Surface::sphere(Point::zero(), 60.0, 64);
+        let kept: Vec<usize> = (0..sphere.vertices.len()).filter(|v| sphere.vertices[*v].point.z > 5.0).collect();
+        let mut vertex_map = vec![None; sphere.vertices.len()];
+        for (new_id, v) in kept.iter().enumerate() {
+            vertex_map[*v] = Some(new_id);
+        }
+        let triangles: Vec<[usize; 3]> = sphere.faces.iter()
+            .filter_map(|face| Some([vertex_map[face.vertices[0]]?, vertex_map[face.vertices[1]]?, vertex_map[face.vertices[2]]?]))
+            .collect();
+        let points = kept.iter().map(|v| sphere.vertices[*v].point).collect();
+        let surface = Surface::from_triangles(points, triangles);
+
+        let circles = [(0.0, 0.0), (25.0, 0.0), (-12.0, 21.0), (-12.0, -21.0)].iter()
+            .map(|(x, y): &(f32, f32)| CircleArgs{
+                center: Point::new(*x, *y, (60.0f32.powi(2) - x * x - y * y).sqrt()),
+                coil_radius: 14.0,
+                ..CircleArgs::default()
+            }).collect::<Vec<CircleArgs>>();
+        let full_method = Method{
+            circles,
+            epsilon: 2.0,
+            iterations: 12,
+            coarse_stages: Vec::new(),
+            statistics_level: 0,
+            ..flat_adam(Vec::new())
+        };
+        let coarse_method = Method{
+            coarse_stages: vec![surface.vertices.len() / 8],
+            polish_iterations: Some(2),
+            ..full_method.clone()
+        };
+        let rms = |layout_out: &layout::Layout| layout_out.close_coupling_stats(full_method.close_cutoff).unwrap().1;
+
+        // Count the iterations each run takes, from the progress events
+        let run_counting_iterations = |method: &Method| -> (layout::Layout, usize) {
+            let iterations = std::rc::Rc::new(std::cell::Cell::new(0));
+            let counter = iterations.clone();
+            progress::set_callback(move |event| if let ProgressEvent::Iteration{..} = event { counter.set(counter.get() + 1) });
+            let layout_out = method.do_layout(&surface).unwrap();
+            progress::clear_callback();
+            (layout_out, iterations.get())
+        };
+        let (full_layout, full_iterations) = run_counting_iterations(&full_method);
+        let (coarse_layout, coarse_iterations) = run_counting_iterations(&coarse_method);
+
+        let (full_rms, coarse_rms) = (rms(&full_layout), rms(&coarse_layout));
+        assert!(coarse_rms < 1.5 * full_rms + 0.01, "Coarse-to-fine RMS {coarse_rms} vs. full resolution {full_rms}");
+
+        // The coarse stage runs the full iteration count, then only the polish iterations are at full resolution,
+        // so the work (iterations times vertices) is less than the full resolution run
+        assert_eq!(full_iterations, 12);
+        assert_eq!(coarse_iterations, 12 + 2);
+        let coarse_vertex_count = surface.decimate(surface.vertices.len() / 8).vertices.len();
+        let full_work = full_iterations * surface.vertices.len();
+        let coarse_work = 12 * coarse_vertex_count + (coarse_iterations - 12) * surface.vertices.len();
+        assert!(coarse_work < full_work, "Coarse-to-fine work {coarse_work} vs. full resolution {full_work}");
+    }
+
+    #[test]
+    fn nested_coil_is_detected_and_handled() {
+        let surface = Surface::square_grid(30.0, 60);
+        let circles = vec![
+            CircleArgs{center: Point::zero(), coil_radius: 15.0, ..CircleArgs::default()},
+            CircleArgs{center: Point::new(3.0, 0.0, 0.0), coil_radius: 4.0, ..CircleArgs::default()},
+        ];
+        let method_with = |nesting_policy: NestingPolicy| Method{
+            nesting_policy,
+            ..flat_adam(Vec::new())
+        };
+
+        let layout_out = method_with(NestingPolicy::Warn).lay_out_coils(&surface, &circles, false).unwrap();
+        assert!(layout_out.coils[0].contains(&layout_out.coils[1]));
+        assert!(!layout_out.coils[1].contains(&layout_out.coils[0]));
+        assert_eq!(nested_pairs(&layout_out), vec![(0, 1)]);
+
+        // Warn and ignore leave the circles as-is
+        for nesting_policy in [NestingPolicy::Warn, NestingPolicy::Ignore] {
+            let resolved = method_with(nesting_policy).resolve_nesting(&surface, &circles).unwrap();
+            assert!(resolved[1].center.approx_eq(&circles[1].center, 1e-6));
+        }
+
+        // Offset moves the inner coil out onto the outer wire, away from the outer center
+        let offset_method = method_with(NestingPolicy::Offset);
+        let resolved = offset_method.resolve_nesting(&surface, &circles).unwrap();
+        assert!((resolved[1].center.x - 15.0).abs() < 1.0, "Inner coil moved to {}", resolved[1].center);
+        let resolved_layout = offset_method.lay_out_coils(&surface, &resolved, false).unwrap();
+        assert!(nested_pairs(&resolved_layout).is_empty());
+
+        // Warn checks the first pass of the optimization, without laying the coils out again
+        let laid_out = std::rc::Rc::new(std::cell::Cell::new(0));
+        let counter = laid_out.clone();
+        progress::set_callback(move |event| if let ProgressEvent::CoilLaidOut{..} = event { counter.set(counter.get() + 1) });
+        warnings::clear();
+        let warn_method = Method{circles: circles.clone(), iterations: 0, coarse_stages: Vec::new(), ..method_with(NestingPolicy::Warn)};
+        methods::LayoutMethodTrait::do_layout(&warn_method, &surface).unwrap();
+        progress::clear_callback();
+        assert_eq!(laid_out.get(), circles.len());
+        assert!(warnings::summary().iter().any(|(group, _)| group == "Coil 1 is nested inside coil 0"));
+    }
+
+    #[test]
+    fn restarts_are_deterministic_and_keep_the_best() {
+        let surface = Surface::square_grid(40.0, 60);
+        let test_dir = temp_dir("restart_trajectory_test");
+        let trajectory_path = test_dir.path().join("trajectory.csv").to_string_lossy().to_string();
+        let method = Method{
+            iterations: 3,
+            restarts: 4,
+            jitter: 3.0,
+            seed: 7,
+            coarse_stages: Vec::new(),
+            trajectory_output: Some(trajectory_path.clone()),
+            ..flat_adam(vec![
+                CircleArgs{center: Point::new(-8.0, 0.0, 0.0), coil_radius: 8.0, ..CircleArgs::default()},
+                CircleArgs{center: Point::new(8.0, 0.0, 0.0), coil_radius: 8.0, ..CircleArgs::default()},
+                CircleArgs{center: Point::new(0.0, 12.0, 0.0), coil_radius: 8.0, ..CircleArgs::default()},
+            ])
+        };
+
+        let (first, first_rms) = method.optimize_with_restarts(&surface).unwrap();
+        let (second, second_rms) = method.optimize_with_restarts(&surface).unwrap();
+
+        assert_eq!(first_rms.len(), 4);
+        assert_eq!(first_rms, second_rms);
+        for (a, b) in first.layout.coils.iter().zip(second.layout.coils.iter()) {
+            assert_eq!((a.center - b.center).norm(), 0.0);
+        }
+        let lowest = first_rms.iter().copied().fold(f32::INFINITY, f32::min);
+        assert_eq!(first.best_rms, lowest);
+
+        // Jitter actually moves the starting centers
+        assert!(first_rms.iter().any(|rms| *rms != first_rms[0]));
+
+        // The trajectory written is the kept restart's, not the first one's
+        let best_run = first_rms.iter().position(|rms| *rms == lowest).unwrap();
+        assert_ne!(best_run, 0);
+        assert_eq!(std::fs::read_to_string(&trajectory_path).unwrap(), first.trajectory);
+        assert_ne!(method.optimize(&surface).unwrap().trajectory, first.trajectory);
+    }
+
+    #[test]
+    fn saved_layout_statistics_reproduce_the_run_rms() {
+        // One coil is weighted, which steers the gradients but not the reported coupling
+        let surface = Surface::square_grid(40.0, 60);
+        let method = Method{
+            iterations: 3,
+            coarse_stages: Vec::new(),
+            ..flat_adam(vec![
+                CircleArgs{center: Point::new(-8.0, 0.0, 0.0), coil_radius: 8.0, weight: 5.0, ..CircleArgs::default()},
+                CircleArgs{center: Point::new(8.0, 0.0, 0.0), coil_radius: 8.0, ..CircleArgs::default()},
+                CircleArgs{center: Point::new(0.0, 12.0, 0.0), coil_radius: 8.0, ..CircleArgs::default()},
+            ])
+        };
+        let result = method.optimize(&surface).unwrap();
+
+        let test_dir = temp_dir("stats_test");
+        let layout_path = test_dir.path().join("layout.json");
+        let layout_path = layout_path.to_string_lossy();
+        layout::save_layout(&result.layout, &layout_path).unwrap();
+        let stats = layout::statistics(&layout::load_layout(&layout_path).unwrap(), method.close_cutoff, &layout::StatisticsOptions::default());
+
+        // The run reports the RMS coupling in thousandths
+        let rms = stats.rms_coupling.unwrap() * 1.0e3;
+        assert!((rms - result.best_rms).abs() < 1e-3 * result.best_rms, "Statistics RMS {}, run RMS {}", rms, result.best_rms);
+        assert_eq!(stats.coils.len(), 3);
+        assert!(!stats.close_pairs.is_empty());
+    }
+
+    #[test]
+    fn trajectory_has_a_row_per_coil_and_iteration() {
+        use methods::LayoutMethodTrait;
+
+        let test_dir = temp_dir("trajectory_test");
+        let trajectory_path = test_dir.path().join("trajectory.csv");
+        let trajectory_path = trajectory_path.to_string_lossy().to_string();
+        let method = Method{
+            iterations: 3,
+            coarse_stages: Vec::new(),
+            trajectory_output: Some(trajectory_path.clone()),
+            ..flat_adam(vec![
+                CircleArgs{center: Point::new(-6.0, 0.0, 0.0), coil_radius: 8.0, ..CircleArgs::default()},
+                CircleArgs{center: Point::new(6.0, 0.0, 0.0), coil_radius: 8.0, ..CircleArgs::default()},
+            ])
+        };
+        method.do_layout(&Surface::square_grid(40.0, 60)).unwrap();
+
+        let trajectory = std::fs::read_to_string(&trajectory_path).unwrap();
+        let mut lines = trajectory.lines();
+        assert_eq!(lines.next(), Some("iteration, coil_id, x, y, z, radius"));
+        let rows: Vec<Vec<&str>> = lines.map(|line| line.split(", ").collect()).collect();
+        assert_eq!(rows.len(), 6);
+        assert!(rows.iter().all(|row| row.len() == 6));
+        assert_eq!((rows[5][0], rows[5][1]), ("3", "1"));
+    }
+
+    #[test]
+    fn empty_config_is_rejected_and_single_coil_has_no_coupling() {
+        use methods::LayoutMethodTrait;
+
+        let surface = Surface::square_grid(40.0, 60);
+        let method = Method{
+            iterations: 3,
+            coarse_stages: Vec::new(),
+            ..flat_adam(Vec::new())
+        };
+        assert!(method.do_layout(&surface).is_err());
+
+        let single = Method{circles: vec![CircleArgs{center: Point::zero(), coil_radius: 8.0, ..CircleArgs::default()}], ..method};
+        let result = single.optimize(&surface).unwrap();
+        assert_eq!(result.best_rms, 0.0);
+        let layout_out = single.do_layout(&surface).unwrap();
+        assert_eq!(layout_out.coils.len(), 1);
+        assert!(layout_out.find_non_finite().is_none());
+        assert!(layout::statistics(&layout_out, single.close_cutoff, &layout::StatisticsOptions::default()).close_pairs.is_empty());
+    }
+
+    #[test]
+    fn repeated_boundary_shifts_are_grouped_in_summary() {
+        let surface = Surface::square_grid(20.0, 40);
+        let method = Method{
+            iterations: 5,
+            coarse_stages: Vec::new(),
+            ..flat_adam(vec![
+                CircleArgs{center: Point::new(-8.0, 0.0, 0.0), coil_radius: 8.0, ..CircleArgs::default()},
+                CircleArgs{center: Point::new(8.0, 0.0, 0.0), coil_radius: 8.0, ..CircleArgs::default()},
+                CircleArgs{center: Point::new(0.0, 13.0, 0.0), coil_radius: 8.0, ..CircleArgs::default()},
+            ])
+        };
+
+        warnings::clear();
+        method.optimize_with_restarts(&surface).unwrap();
+        let summary = warnings::summary();
+        warnings::clear();
+
+        let (_, count) = summary.iter()
+            .find(|(group, _)| group == "Coil 2 shifted to boundary")
+            .expect("Boundary shift not recorded");
+        assert!(*count > 1);
+        assert_eq!(summary.iter().filter(|(group, _)| group.starts_with("Coil 2 shifted")).count(), 1);
+    }
+
+    #[test]
+    fn circle_group_mismatch_is_an_error() {
+        let surface = Surface::square_grid(40.0, 60);
+        let symmetry_plane = Plane::from_normal_and_offset(GeoVector::xhat(), 0.0);
+        let method = Method{
+            symmetry_plane: Some(SymmetryPlane::Fixed(symmetry_plane)),
+            layout_in_path: None,
+            ..Default::default()
+        };
+        let sym_circles = vec![CircleArgs{center: Point::new(0.0, 15.0, 0.0), coil_radius: 8.0, ..CircleArgs::default()}];
+        let pos_circles = vec![CircleArgs{center: Point::new(10.0, 0.0, 0.0), coil_radius: 8.0, ..CircleArgs::default()}];
+        let neg_circles = vec![CircleArgs{center: Point::new(-10.0, 0.0, 0.0), coil_radius: 8.0, ..CircleArgs::default()}];
+        let circles = concat(vec![sym_circles.clone(), pos_circles.clone(), neg_circles.clone()]);
+        let layout_out = method.lay_out_coils(&surface, &circles, false).unwrap();
+        let boundary_points = boundary_points(&surface);
+
+        let update_sym = |sym: &Vec<CircleArgs>, pos: &Vec<CircleArgs>, neg: &Vec<CircleArgs>| {
+            method.update_circles_sym(
+                sym, pos, neg,
+                &circles,
+                &layout_out,
+                &None,
+                &CircleSurfaces::new(&surface, &circles).unwrap(),
+                &symmetry_plane,
+                &boundary_points,
+                &mut vec![false; circles.len()],
+                &mut vec![Moment::new(); circles.len()],
+            ).map(|_| ())
+        };
+
+        // A dropped reflection is reported as a positive/negative mismatch
+        let error = update_sym(&sym_circles, &pos_circles, &Vec::new()).unwrap_err().to_string();
+        assert!(error.contains("1 positive circles, but 0 negative"), "Unexpected error: {}", error);
+
+        // An extra on-plane circle is reported against the layout's coil count
+        let extra_sym = vec![sym_circles[0], sym_circles[0]];
+        let error = update_sym(&extra_sym, &pos_circles, &neg_circles).unwrap_err().to_string();
+        assert!(error.contains("2 on-plane + 1 positive + 1 negative = 4 circles, but the layout has 3 coils"), "Unexpected error: {}", error);
+
+        // Matching groups update fine
+        assert!(update_sym(&sym_circles, &pos_circles, &neg_circles).is_ok());
+    }
+
+    #[test]
+    fn locked_on_plane_coil_stays_on_plane() {
+        let surface = Surface::square_grid(40.0, 60);
+        let symmetry_plane = Plane::from_normal_and_offset(GeoVector::xhat(), 0.0);
+        let boundary_points = boundary_points(&surface);
+
+        // The neighbor is off to one side, so it pushes the on-plane coil across the plane
+        let circles = vec![
+            CircleArgs{center: Point::new(0.0, 6.0, 0.0), coil_radius: 8.0, ..CircleArgs::default()},
+            CircleArgs{center: Point::new(9.0, -4.0, 0.0), coil_radius: 8.0, ..CircleArgs::default()},
+        ];
+        let step = |method: &Method, locked: bool| -> Vec<f32> {
+            let mut circles = circles.clone();
+            let mut moments = vec![Moment::new(); circles.len()];
+            let mut offsets = Vec::new();
+            for _ in 0..3 {
+                let layout_out = method.lay_out_coils(&surface, &circles, false).unwrap();
+                (circles, _, _) = method.update_circles(
+                    &circles,
+                    &circles,
+                    &layout_out,
+                    &None,
+                    &CircleSurfaces::new(&surface, &circles).unwrap(),
+                    &boundary_points,
+                    &mut vec![false; circles.len()],
+                    &mut moments,
+                    if locked {Some((&symmetry_plane, 1))} else {None},
+                ).unwrap();
+                offsets.push(symmetry_plane.distance_to_point(&circles[0].center).abs());
+            }
+            offsets
+        };
+
+        let method = Method{
+            symmetry_plane: Some(SymmetryPlane::Fixed(symmetry_plane)),
+            layout_in_path: None,
+            lock_aspect: true,
+            ..Default::default()
+        };
+        assert!(step(&method, false).iter().any(|offset| *offset > 1e-3), "Unlocked coil should drift off the plane");
+        for offset in step(&method, true) {
+            assert!(offset < 1e-6, "Locked coil moved {} off the plane", offset);
+        }
+    }
+
+    #[test]
+    fn locked_on_plane_coil_stays_on_plane_with_symmetry() {
+        let surface = Surface::square_grid(40.0, 60);
+        let symmetry_plane = Plane::from_normal_and_offset(GeoVector::xhat(), 0.0);
+        let boundary_points = boundary_points(&surface);
+        let method = Method{
+            symmetry_plane: Some(SymmetryPlane::Fixed(symmetry_plane)),
+            layout_in_path: None,
+            lock_aspect: true,
+            ..Default::default()
+        };
+
+        let mut sym_circles = vec![CircleArgs{center: Point::new(0.0, 6.0, 0.0), coil_radius: 8.0, ..CircleArgs::default()}];
+        let mut pos_circles = vec![CircleArgs{center: Point::new(9.0, -4.0, 0.0), coil_radius: 8.0, ..CircleArgs::default()}];
+        let mut neg_circles = vec![CircleArgs{center: Point::new(-9.0, -4.0, 0.0), coil_radius: 8.0, ..CircleArgs::default()}];
+        let original_circles = concat(vec![sym_circles.clone(), pos_circles.clone(), neg_circles.clone()]);
+        let circle_surfaces = CircleSurfaces::new(&surface, &original_circles).unwrap();
+        let mut moments = vec![Moment::new(); original_circles.len()];
+        for step in 0..3 {
+            let circles = concat(vec![sym_circles.clone(), pos_circles.clone(), neg_circles.clone()]);
+            let layout_out = method.lay_out_coils_sym(&surface, &symmetry_plane, &sym_circles, &pos_circles, &neg_circles, false).unwrap();
+
+            // The on-plane circle takes the same locked step as a direct update would
+            let (direct_circles, _, _) = method.update_circles(
+                &circles,
+                &original_circles,
+                &layout_out,
+                &None,
+                &circle_surfaces,
+                &boundary_points,
+                &mut vec![false; circles.len()],
+                &mut moments.clone(),
+                Some((&symmetry_plane, sym_circles.len())),
+            ).unwrap();
+            (sym_circles, pos_circles, neg_circles, _, _) = method.update_circles_sym(
+                &sym_circles,
+                &pos_circles,
+                &neg_circles,
+                &original_circles,
+                &layout_out,
+                &None,
+                &circle_surfaces,
+                &symmetry_plane,
+                &boundary_points,
+                &mut vec![false; circles.len()],
+                &mut moments,
+            ).unwrap();
+            assert!(sym_circles[0].center.approx_eq(&direct_circles[0].center, 1e-6), "Step {} wasn't locked", step);
+
+            let offset = symmetry_plane.distance_to_point(&sym_circles[0].center).abs();
+            assert!(offset < 1e-6, "Locked coil moved {} off the plane", offset);
+            assert!(neg_circles[0].center.approx_eq(&pos_circles[0].center.reflect_across(&symmetry_plane), 1e-5));
+        }
+        assert!((sym_circles[0].center - original_circles[0].center).norm() > 1e-3, "Locked coil didn't move within the plane");
+    }
+
+    #[test]
+    fn resolved_overlap_meets_clearance() {
+        let surface = flat_surface();
+        let method = flat_adam(Vec::new());
+        let circles = vec![
+            CircleArgs{center: Point::new(-7.0, 0.0, 0.0), coil_radius: 10.0, ..CircleArgs::default()},
+            CircleArgs{center: Point::new(7.0, 0.0, 0.0), coil_radius: 10.0, ..CircleArgs::default()},
+        ];
+
+        // Unresolved, the wires cross
+        let crossing_layout = layout::Layout{coils: circles.iter()
+            .map(|circle| circle_coil(&surface, circle.center, circle.coil_radius, method.wire_radius, method.epsilon, method.pre_shift, method.min_vertices, method.projection_mode).unwrap())
+            .collect()};
+        let (_, _, crossing_gap) = crossing_layout.min_spacing_report()[0];
+        assert!(crossing_gap < 0.0, "Crossing wires have a gap of {}", crossing_gap);
+
+        let layout_out = method.lay_out_coils(&surface, &circles, false).unwrap();
+        let report = layout_out.min_spacing_report();
+        assert_eq!(report.len(), 1);
+        let (coil_id, other_id, gap) = report[0];
+        assert_eq!((coil_id, other_id), (0, 1));
+        assert!(gap >= (1.0 - SPACING_TOLERANCE) * method.clearance, "Resolved overlap has a gap of {}, below the clearance {}", gap, method.clearance);
+    }
+
+    #[test]
+    fn disabled_overlap_resolution_keeps_intersected_coils() {
+        let surface = flat_surface();
+        let method = Method{
+            resolve_overlaps: false,
+            ..flat_adam(Vec::new())
+        };
+        let circles = vec![
+            CircleArgs{center: Point::new(-7.0, 0.0, 0.0), coil_radius: 10.0, ..CircleArgs::default()},
+            CircleArgs{center: Point::new(7.0, 0.0, 0.0), coil_radius: 10.0, ..CircleArgs::default()},
+        ];
+
+        let layout_out = method.lay_out_coils(&surface, &circles, false).unwrap();
+        for (coil, circle) in layout_out.coils.iter().zip(circles.iter()) {
+            let intersected = circle_coil(&surface, circle.center, circle.coil_radius, method.wire_radius, method.epsilon, method.pre_shift, method.min_vertices, method.projection_mode).unwrap();
+            assert_eq!(coil.vertices.len(), intersected.vertices.len());
+            for (vertex, intersected_vertex) in coil.vertices.iter().zip(intersected.vertices.iter()) {
+                assert_eq!(vertex.point.distance(&intersected_vertex.point), 0.0);
+            }
+        }
+    }
+
+    #[test]
+    fn max_coils_keeps_a_well_spread_subset() {
+        use methods::LayoutMethodTrait;
+        let surface = flat_surface();
+        // Four corners of a square, plus two near-duplicates of corners
+        let centers = [(-15.0, -15.0), (15.0, -15.0), (16.0, -13.0), (-15.0, 15.0), (15.0, 15.0), (-13.0, 14.0)];
+        let circles = centers.iter()
+            .map(|(x, y)| CircleArgs{center: Point::new(*x, *y, 0.0), coil_radius: 10.0, ..CircleArgs::default()})
+            .collect();
+        let method = Method{
+            circles,
+            max_coils: Some(4),
+            iterations: 1,
+            coarse_stages: Vec::new(),
+            statistics_level: 0,
+            ..flat_adam(Vec::new())
+        };
+
+        let layout_out = method.do_layout(&surface).unwrap();
+        assert_eq!(layout_out.coils.len(), 4);
+        for (coil_id, coil) in layout_out.coils.iter().enumerate() {
+            for other_coil in layout_out.coils.iter().skip(coil_id + 1) {
+                let distance = coil.center.distance(&other_coil.center);
+                assert!(distance > 25.0, "Kept coils at {} and {} are only {} apart", coil.center, other_coil.center, distance);
+            }
+        }
+    }
+
+    #[test]
+    fn fractional_radius_scales_with_the_surface() {
+        use methods::LayoutMethodTrait;
+        let coil_radius_on = |sphere_radius: f32| {
+            let surface = crate::bench::sphere_dome(sphere_radius, 40);
+            let method = Method{
+                radius_mode: RadiusMode::FractionOfExtent,
+                epsilon: 0.1 * sphere_radius,
+                iterations: 1,
+                coarse_stages: Vec::new(),
+                statistics_level: 0,
+                ..flat_adam(vec![CircleArgs{center: Point::new(0.0, 0.0, sphere_radius), coil_radius: 0.1, ..CircleArgs::default()}])
+            };
+            method.do_layout(&surface).unwrap().coils[0].average_radius()
+        };
+
+        // The bounding box of the dome is 2R x 2R x R, so its diagonal is 3 times the sphere radius
+        let (small, large) = (coil_radius_on(20.0), coil_radius_on(40.0));
+        assert!((small - 0.3 * 20.0).abs() < 0.05 * small, "Coil radius {} on the small sphere", small);
+        assert!((large / small - 2.0).abs() < 0.05, "Coil radii {} and {} should scale with the sphere", small, large);
+    }
+
+    #[test]
+    fn progress_callback_sees_increasing_iterations() {
+        use methods::LayoutMethodTrait;
+        use crate::progress::{self, ProgressEvent};
+        use std::{cell::RefCell, rc::Rc};
+
+        let surface = flat_surface();
+        let method = Method{
+            iterations: 4,
+            coarse_stages: Vec::new(),
+            statistics_level: 0,
+            ..flat_adam(vec![
+                CircleArgs{center: Point::new(-8.0, 0.0, 0.0), coil_radius: 10.0, ..CircleArgs::default()},
+                CircleArgs{center: Point::new(8.0, 0.0, 0.0), coil_radius: 10.0, ..CircleArgs::default()},
+            ])
+        };
+
+        let events = Rc::new(RefCell::new(Vec::new()));
+        let recorder = events.clone();
+        progress::set_callback(move |event| recorder.borrow_mut().push(event));
+        method.do_layout(&surface).unwrap();
+        progress::clear_callback();
+
+        let events = events.borrow();
+        let iterations: Vec<usize> = events.iter().filter_map(|event| match event {
+            ProgressEvent::Iteration{i, total, rms} => {
+                assert_eq!(*total, 4);
+                assert!(rms.is_finite());
+                Some(*i)
+            },
+            _ => None,
+        }).collect();
+        assert_eq!(iterations, vec![1, 2, 3, 4]);
+        assert!(events.contains(&ProgressEvent::CoilLaidOut{id: 1}));
+
+        // Coarse stages and restarts keep counting up, against the total of all the passes
+        let staged_method = Method{
+            coarse_stages: vec![surface.vertices.len() / 4],
+            polish_iterations: Some(2),
+            restarts: 2,
+            ..method.clone()
+        };
+        let staged = Rc::new(RefCell::new(Vec::new()));
+        let recorder = staged.clone();
+        progress::set_callback(move |event| if let ProgressEvent::Iteration{i, total, ..} = event { recorder.borrow_mut().push((i, total)) });
+        staged_method.do_layout(&surface).unwrap();
+        progress::clear_callback();
+        assert_eq!(*staged.borrow(), (1..=8).map(|i| (i, 4 + 2 * 2)).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn closed_sphere_has_no_boundary_to_shrink_from() {
+        use methods::LayoutMethodTrait;
+        let surface = Surface::sphere(Point::zero(), 20.0, 40);
+        assert!(surface.get_boundary_vertex_indices().is_empty());
+
+        let method = Method{
+            epsilon: 2.0,
+            iterations: 2,
+            coarse_stages: Vec::new(),
+            statistics_level: 0,
+            ..flat_adam(vec![CircleArgs{center: Point::new(0.0, 0.0, 20.0), coil_radius: 8.0, ..CircleArgs::default()}])
+        };
+        let layout_out = method.do_layout(&surface).unwrap();
+        let coil_radius = layout_out.coils[0].average_radius();
+        assert!((coil_radius - 8.0).abs() < 0.5, "Coil radius {} on the closed sphere", coil_radius);
+    }
+
+    #[test]
+    fn per_coil_zero_angle_vector_moves_only_that_port() {
+        use methods::LayoutMethodTrait;
+
+        let surface = flat_surface();
+        let circles = vec![
+            CircleArgs{center: Point::new(-15.0, 0.0, 0.0), coil_radius: 8.0, ..CircleArgs::default()},
+            CircleArgs{center: Point::new(15.0, 0.0, 0.0), coil_radius: 8.0, ..CircleArgs::default()},
+        ];
+        let method = Method{
+            zero_angle_vector: GeoVector::xhat(),
+            iterations: 0,
+            coarse_stages: Vec::new(),
+            ..flat_adam(circles.clone())
+        };
+        let mut overridden = circles;
+        overridden[1].zero_angle_vector = Some(GeoVector::yhat());
+        let overridden_method = Method{circles: overridden, ..method.clone()};
+
+        let port_direction = |layout_out: &layout::Layout, coil_id: usize| {
+            let coil = &layout_out.coils[coil_id];
+            (coil.vertices[coil.port.unwrap()].point - coil.center).normalize()
+        };
+        let layout_out = method.do_layout(&surface).unwrap();
+        let overridden_out = overridden_method.do_layout(&surface).unwrap();
+        for coil_id in 0..2 {
+            assert!(port_direction(&layout_out, coil_id).dot(&GeoVector::xhat()) > 0.99);
+        }
+        assert!(port_direction(&overridden_out, 0).approx_eq(&port_direction(&layout_out, 0), 1e-5));
+        assert!(port_direction(&overridden_out, 1).dot(&GeoVector::yhat()) > 0.99, "Port direction {}", port_direction(&overridden_out, 1));
+    }
+
+    #[test]
+    fn close_pairs_follow_the_laid_out_radius() {
+        // Nominal 10 mm circles, but the second wire is dented in on the side facing the first, as by an overlap offset
+        let coil_at = |center: Point, dented: bool| {
+            let points = (0..64).map(|i| {
+                let theta = 2.0 * std::f32::consts::PI * i as f32 / 64.0;
+                let radius = if dented && theta.cos() < 0.0 { 4.0 } else { 10.0 };
+                center + GeoVector::new(radius * theta.cos(), radius * theta.sin(), 0.0)
+            }).collect();
+            layout::Coil::new(center, GeoVector::zhat(), points, 0.645, vec![GeoVector::zhat(); 64]).unwrap()
+        };
+        let method = flat_adam(vec![CircleArgs{coil_radius: 10.0, ..CircleArgs::default()}; 2]);
+        let close_coils = |distance: f32| {
+            let layout_out = layout::Layout{coils: vec![coil_at(Point::zero(), false), coil_at(Point::new(distance, 0.0, 0.0), true)]};
+            method.get_statistics(&layout_out, &None, false, false).1
+        };
+
+        let dented = coil_at(Point::zero(), true);
+        assert!((dented.average_radius() - 10.0).abs() > 2.0, "Average radius {}", dented.average_radius());
+        assert!((coil_at(Point::zero(), false).average_radius() - 10.0).abs() < 0.1);
+
+        // 18 mm is close for the nominal radii (18 / 20), but not for the dented coil as laid out
+        assert!(18.0 / 20.0 < method.close_cutoff);
+        assert_eq!(close_coils(18.0), 0);
+        assert_eq!(close_coils(15.0), 1);
+    }
+
+    /// Shallow bowl over a square grid, mirror-symmetric about x = 0 (including the triangulation)
+    fn symmetric_bowl(half_width: f32, divisions: usize) -> Surface {
+        let n = divisions;
+        let step = 2.0 * half_width / n as f32;
+        let mut points = Vec::new();
+        for j in 0..=n {
+            for i in 0..=n {
+                // Mirror each column from the positive side so the coordinates are exactly symmetric
+                let x = if 2 * i >= n { (i as f32 - n as f32 / 2.0) * step } else { -((n - i) as f32 - n as f32 / 2.0) * step };
+                let y = -half_width + j as f32 * step;
+                points.push(Point::new(x, y, 0.005 * (x * x + y * y)));
+            }
+        }
+        let id = |i: usize, j: usize| j * (n + 1) + i;
+        let mut triangles = Vec::new();
+        for j in 0..n {
+            for i in 0..n {
+                if 2 * i >= n {
+                    triangles.push([id(i, j), id(i + 1, j), id(i + 1, j + 1)]);
+                    triangles.push([id(i, j), id(i + 1, j + 1), id(i, j + 1)]);
+                } else {
+                    triangles.push([id(i, j), id(i + 1, j), id(i, j + 1)]);
+                    triangles.push([id(i + 1, j), id(i + 1, j + 1), id(i, j + 1)]);
+                }
+            }
+        }
+        Surface::from_triangles(points, triangles)
+    }
+
+    /// Largest distance from a vertex of one coil to the nearest vertex of the other
+    fn vertex_set_distance(coil: &layout::Coil, other: &layout::Coil) -> f32 {
+        coil.vertices.iter().map(|vertex| {
+            other.vertices.iter().map(|other_vertex| vertex.point.distance(&other_vertex.point)).fold(f32::INFINITY, f32::min)
+        }).fold(0.0, f32::max)
+    }
+
+    #[test]
+    fn symmetric_layout_is_mirror_consistent() {
+        let surface = symmetric_bowl(30.0, 60);
+        let plane = Plane::from_normal_and_offset(GeoVector::xhat(), 0.0);
+        let surface_point = |x: f32, y: f32| Point::new(x, y, 0.005 * (x * x + y * y));
+        let sym_circle = CircleArgs{center: surface_point(0.0, -8.0), coil_radius: 9.0, on_symmetry_plane: true, ..CircleArgs::default()};
+        let pos_circle = CircleArgs{center: surface_point(12.0, 10.0), coil_radius: 8.0, ..CircleArgs::default()};
+        let neg_circle = CircleArgs{center: surface_point(-12.0, 10.0), ..pos_circle};
+        let method = Method{
+            symmetry_plane: Some(SymmetryPlane::Fixed(plane)),
+            layout_in_path: None,
+            circles: vec![sym_circle, pos_circle],
+            final_cfg_output: None,
+            ..Default::default()
+        };
+        let layout_out = method.lay_out_coils_sym(&surface, &plane, &vec![sym_circle], &vec![pos_circle], &vec![neg_circle], false).unwrap();
+        let (sym_coil, pos_coil, neg_coil) = (&layout_out.coils[0], &layout_out.coils[1], &layout_out.coils[2]);
+
+        // The on-plane coil is its own reflection
+        let mut reflected = sym_coil.clone();
+        for vertex in reflected.vertices.iter_mut() {
+            vertex.point = vertex.point.reflect_across(&plane);
+        }
+        assert!(vertex_set_distance(sym_coil, &reflected) < 1e-3, "On-plane coil is {} from its reflection", vertex_set_distance(sym_coil, &reflected));
+        assert!(sym_coil.normal.dot(&GeoVector::xhat()).abs() < 1e-4);
+
+        // The mirrored coil matches one laid out directly at the mirrored center, wound the same way
+        let direct_method = Method{symmetry_plane: None, circles: vec![neg_circle], ..method.clone()};
+        let direct = direct_method.lay_out_coils(&surface, &vec![neg_circle], false).unwrap().coils.remove(0);
+        assert!(vertex_set_distance(neg_coil, &direct) < 1e-3 && vertex_set_distance(&direct, neg_coil) < 1e-3,
+            "Mirrored coil is {} from the direct coil", vertex_set_distance(neg_coil, &direct));
+        assert!(neg_coil.center.approx_eq(&direct.center, 1e-3));
+        assert!(neg_coil.normal.approx_eq(&direct.normal, 1e-4));
+        assert_eq!(neg_coil.winding_sense(), direct.winding_sense());
+        assert_eq!(neg_coil.winding_sense(), pos_coil.winding_sense());
+    }
+
+    #[test]
+    fn one_sided_coil_on_trimmed_surface_follows_policy() {
+        // Half of a flat sheet, trimmed at the symmetry plane, with a coil centered on the plane
+        let plane = Plane::from_normal_and_offset(GeoVector::xhat(), 0.0);
+        let (half_surface, _) = Surface::square_grid(30.0, 60).trim_by_plane(&plane, false);
+        let sym_circles = vec![CircleArgs{coil_radius: 10.0, on_symmetry_plane: true, ..CircleArgs::default()}];
+        let method = Method{
+            symmetry_plane: Some(SymmetryPlane::Fixed(plane)),
+            layout_in_path: None,
+            circles: sym_circles.clone(),
+            final_cfg_output: None,
+            ..Default::default()
+        };
+        let lay_out = |one_sided_policy: OneSidedPolicy| Method{one_sided_policy, ..method.clone()}
+            .lay_out_coils_sym(&half_surface, &plane, &sym_circles, &Vec::new(), &Vec::new(), false);
+
+        // Warn leaves the half loop as intersected
+        let half = lay_out(OneSidedPolicy::Warn).unwrap().coils.remove(0);
+        assert!(is_one_sided(&half, &plane, method.epsilon));
+
+        // Mirror completes it into a full loop across the plane
+        let mirrored = lay_out(OneSidedPolicy::Mirror).unwrap().coils.remove(0);
+        assert!(!is_one_sided(&mirrored, &plane, method.epsilon));
+        assert!(mirrored.vertices.iter().any(|vertex| vertex.point.x < -9.0));
+        assert!((mirrored.average_radius() - 10.0).abs() < 0.5, "Mirrored average radius {}", mirrored.average_radius());
+        assert!(mirrored.center.approx_eq(&Point::zero(), 1e-4));
+
+        // Error names the coil
+        let error = lay_out(OneSidedPolicy::Error).unwrap_err().to_string();
+        assert!(error.contains("Coil 0 only covers one side"), "{}", error);
+
+        // The same coil on the untrimmed surface isn't one-sided, even under Error
+        let strict = Method{one_sided_policy: OneSidedPolicy::Error, ..method.clone()};
+        assert!(strict.lay_out_coils_sym(&Surface::square_grid(30.0, 60), &plane, &sym_circles, &Vec::new(), &Vec::new(), false).is_ok());
+    }
+
+    #[test]
+    fn pairs_below_coupling_floor_stop_pushing() {
+        let surface = flat_surface();
+        let boundary_points = boundary_points(&surface);
+
+        // A nearly decoupled pair below, and a strongly coupled pair above, far enough apart not to interact
+        let circles = vec![
+            CircleArgs{center: Point::new(-6.25, -15.0, 0.0), coil_radius: 8.0, ..CircleArgs::default()},
+            CircleArgs{center: Point::new(6.25, -15.0, 0.0), coil_radius: 8.0, ..CircleArgs::default()},
+            CircleArgs{center: Point::new(-4.0, 15.0, 0.0), coil_radius: 8.0, ..CircleArgs::default()},
+            CircleArgs{center: Point::new(4.0, 15.0, 0.0), coil_radius: 8.0, ..CircleArgs::default()},
+        ];
+        let method = flat_adam(circles.clone());
+        let layout_out = method.lay_out_coils(&surface, &circles, false).unwrap();
+        let k_low = layout_out.coils[0].coupling_factor(&layout_out.coils[1], 1.0).abs();
+        let k_high = layout_out.coils[2].coupling_factor(&layout_out.coils[3], 1.0).abs();
+        assert!(k_low < k_high, "Coupling {} for the decoupled pair, {} for the overlapping pair", k_low, k_high);
+
+        let center_moves = |coupling_floor: f32| {
+            let method = Method{coupling_floor, ..method.clone()};
+            let (new_circles, objective, close_coils) = method.update_circles(
+                &circles,
+                &circles,
+                &layout_out,
+                &None,
+                &CircleSurfaces::new(&surface, &circles).unwrap(),
+                &boundary_points,
+                &mut vec![false; circles.len()],
+                &mut vec![Moment::new(); circles.len()],
+                None,
+            ).unwrap();
+            let moves: Vec<f32> = new_circles.iter().zip(circles.iter()).map(|(new, old)| new.center.distance(&old.center)).collect();
+            (moves, objective, close_coils)
+        };
+
+        // Without a floor every coil is pushed; with the floor between the pairs only the coupled pair is
+        let (free_moves, free_objective, free_close) = center_moves(0.0);
+        assert!(free_moves.iter().all(|distance| *distance > 1e-3), "Moves without a floor: {:?}", free_moves);
+        let (floored_moves, floored_objective, floored_close) = center_moves((k_low + k_high) / 2.0);
+        assert!(floored_moves[0] < 1e-4 && floored_moves[1] < 1e-4, "Moves below the floor: {:?}", floored_moves);
+        assert!(floored_moves[2] > 1e-3 && floored_moves[3] > 1e-3, "Moves above the floor: {:?}", floored_moves);
+
+        // The floored pair still counts in the objective
+        assert_eq!((floored_objective, floored_close), (free_objective, free_close));
+        assert_eq!(free_close, 2);
+    }
+
+    #[test]
+    fn centers_snap_to_allowed_points() {
+        use methods::LayoutMethodTrait;
+
+        let surface = flat_surface();
+        let allowed_centers = vec![
+            Point::new(-12.0, 0.0, 0.0),
+            Point::new(12.0, 0.0, 0.0),
+            Point::new(0.0, 14.0, 0.0),
+            Point::new(0.0, -14.0, 0.0),
+            Point::new(-1.0, 1.0, 0.0),
+        ];
+        // The first two circles start nearest the same point, so the second takes the next free one
+        let method = Method{
+            allowed_centers: Some(allowed_centers.clone()),
+            iterations: 3,
+            coarse_stages: Vec::new(),
+            ..flat_adam(vec![
+                CircleArgs{center: Point::new(-2.0, 2.0, 0.0), coil_radius: 8.0, ..CircleArgs::default()},
+                CircleArgs{center: Point::new(-1.0, 3.0, 0.0), coil_radius: 8.0, ..CircleArgs::default()},
+                CircleArgs{center: Point::new(9.0, -3.0, 0.0), coil_radius: 8.0, ..CircleArgs::default()},
+            ])
+        };
+        let layout_out = method.do_layout(&surface).unwrap();
+
+        let mut used = Vec::new();
+        for coil in layout_out.coils.iter() {
+            let center_id = allowed_centers.iter().position(|allowed_center| coil.center.approx_eq(allowed_center, 1e-4));
+            assert!(center_id.is_some(), "Coil center {} is not an allowed point", coil.center);
+            assert!(!used.contains(&center_id), "Two coils share allowed point {:?}", center_id);
+            used.push(center_id);
+        }
+
+        let too_few = Method{allowed_centers: Some(allowed_centers[..2].to_vec()), ..method};
+        assert!(too_few.do_layout(&surface).is_err());
+    }
+
+    #[test]
+    fn component_constrained_coil_stays_on_its_shell() {
+        use methods::LayoutMethodTrait;
+
+        // Two concentric domes, the inner one listed first (component 0)
+        let (inner, outer) = (crate::bench::sphere_dome(20.0, 40), crate::bench::sphere_dome(26.0, 40));
+        let offset = inner.vertices.len();
+        let points = inner.vertices.iter().chain(outer.vertices.iter()).map(|vertex| vertex.point).collect();
+        let triangles = inner.faces.iter().map(|face| face.vertices)
+            .chain(outer.faces.iter().map(|face| face.vertices.map(|vertex_id| vertex_id + offset)))
+            .collect();
+        let surface = Surface::from_triangles(points, triangles);
+        assert_eq!(surface.connected_components().len(), 2);
+
+        // The center starts closer to the outer dome
+        let layout_on = |component: Option<usize>| {
+            let method = Method{
+                epsilon: 2.0,
+                iterations: 2,
+                coarse_stages: Vec::new(),
+                statistics_level: 0,
+                ..flat_adam(vec![CircleArgs{center: Point::new(0.0, 0.0, 24.0), coil_radius: 6.0, component, ..CircleArgs::default()}])
+            };
+            method.do_layout(&surface).unwrap()
+        };
+        let shell_radii = |layout_out: &layout::Layout| -> Vec<f32> {
+            layout_out.coils[0].vertices.iter().map(|vertex| vertex.point.distance(&Point::zero())).collect()
+        };
+
+        for radius in shell_radii(&layout_on(Some(0))) {
+            assert!((radius - 20.0).abs() < 0.5, "Constrained coil has a vertex {} from the center", radius);
+        }
+        // Unconstrained, the intersection picks up both shells
+        assert!(shell_radii(&layout_on(None)).iter().any(|radius| (radius - 20.0).abs() > 0.5));
+
+        let missing_component = Method{
+            circles: vec![CircleArgs{center: Point::new(0.0, 0.0, 24.0), component: Some(2), ..CircleArgs::default()}],
+            symmetry_plane: None,
+            layout_in_path: None,
+            coarse_stages: Vec::new(),
+            ..Default::default()
+        };
+        assert!(missing_component.do_layout(&surface).is_err());
+    }
+
+    #[test]
+    fn breaks_by_length_are_even_on_lengthened_coil() {
+        let surface = flat_surface();
+        let method = flat_adam(Vec::new());
+        let circles = vec![
+            CircleArgs{center: Point::new(-7.0, 0.0, 0.0), coil_radius: 10.0, ..CircleArgs::default()},
+            CircleArgs{center: Point::new(7.0, 0.0, 0.0), coil_radius: 10.0, ..CircleArgs::default()},
+        ];
+        let mut layout_out = method.lay_out_coils(&surface, &circles, false).unwrap();
+        let coil = &mut layout_out.coils[0];
+        assert!(!coil.overlap_segments.is_empty());
+
+        add_even_breaks_by_angle(coil, 4, 0.0, GeoVector::xhat()).unwrap();
+        let port = coil.port;
+        let placed = coil.redistribute_breaks_by_length(4).unwrap();
+        assert_eq!(coil.port, port);
+        assert_eq!(placed[0], port.unwrap());
+        assert_eq!(coil.breaks, placed[1..].to_vec());
+
+        // Arc length between consecutive breaks (port first), within one wire segment of a quarter of the length
+        let vertex_count = coil.vertices.len();
+        let max_segment = (0..vertex_count)
+            .map(|v| (coil.vertices[(v + 1) % vertex_count].point - coil.vertices[v].point).norm())
+            .fold(0.0, f32::max);
+        let quarter = coil.wire_length() / 4.0;
+        for (break_id, start) in placed.iter().enumerate() {
+            let end = placed[(break_id + 1) % placed.len()];
+            let mut length = 0.0;
+            let mut v = *start;
+            while v != end {
+                length += (coil.vertices[(v + 1) % vertex_count].point - coil.vertices[v].point).norm();
+                v = (v + 1) % vertex_count;
+            }
+            assert!((length - quarter).abs() <= max_segment, "Break spacing {} vs. a quarter length {}", length, quarter);
+        }
+    }
+
+    #[test]
+    fn crossing_priority_picks_the_offset_coil() {
+        let surface = flat_surface();
+        let method = flat_adam(Vec::new());
+        let max_offset = |coil: &layout::Coil| coil.vertices.iter().fold(0.0, |max: f32, v| max.max(v.point.z.abs()));
+
+        // By default the lower id crosses over
+        let mut circles = vec![
+            CircleArgs{center: Point::new(-7.0, 0.0, 0.0), coil_radius: 10.0, ..CircleArgs::default()},
+            CircleArgs{center: Point::new(7.0, 0.0, 0.0), coil_radius: 10.0, ..CircleArgs::default()},
+        ];
+        let layout_out = method.lay_out_coils(&surface, &circles, false).unwrap();
+        assert!(!layout_out.coils[0].overlap_segments.is_empty() && layout_out.coils[1].overlap_segments.is_empty());
+        assert!(max_offset(&layout_out.coils[0]) > 0.5 && max_offset(&layout_out.coils[1]) < 1e-4);
+
+        // A higher priority on the second coil swaps them
+        circles[1].crossing_priority = 1;
+        let layout_out = method.lay_out_coils(&surface, &circles, false).unwrap();
+        assert!(layout_out.coils[0].overlap_segments.is_empty() && !layout_out.coils[1].overlap_segments.is_empty());
+        assert!(max_offset(&layout_out.coils[0]) < 1e-4 && max_offset(&layout_out.coils[1]) > 0.5);
+    }
+
+    #[test]
+    fn stacked_overlap_offset_is_capped() {
+        // Three coils whose wires all pass through the origin, so the first dips under both others there
+        let surface = flat_surface();
+        let circles: Vec<CircleArgs> = [90.0_f32, 210.0, 330.0].iter().map(|angle| {
+            let (sin, cos) = angle.to_radians().sin_cos();
+            CircleArgs{center: Point::new(10.0 * cos, 10.0 * sin, 0.0), coil_radius: 10.0, ..CircleArgs::default()}
+        }).collect();
+        let method = flat_adam(Vec::new());
+        let spacing = method.clearance + 2.0 * method.wire_radius;
+        let max_offset = |coil: &layout::Coil| coil.vertices.iter().fold(0.0, |max: f32, v| max.max(v.point.z.abs()));
+
+        // Without a cap, every wire dips a single spacing
+        warnings::clear();
+        let layout_out = method.lay_out_coils(&surface, &circles, false).unwrap();
+        assert!((max_offset(&layout_out.coils[0]) - spacing).abs() < 0.05 * spacing, "Unstacked offset {}", max_offset(&layout_out.coils[0]));
+        assert!(layout_out.coils.iter().all(|coil| max_offset(coil) < 1.05 * spacing));
+
+        // With a loose cap, the offsets stack one wire spacing per coil
+        let stacked = Method{max_stack_offset: Some(3.0 * spacing), ..method.clone()};
+        let layout_out = stacked.lay_out_coils(&surface, &circles, false).unwrap();
+        assert!(max_offset(&layout_out.coils[0]) > 1.5 * spacing, "Stacked offset {}", max_offset(&layout_out.coils[0]));
+        assert!((max_offset(&layout_out.coils[1]) - spacing).abs() < 0.05 * spacing);
+        assert!(warnings::summary().is_empty());
+
+        // Capped between one and two spacings, only the stacked coil is limited, with a warning
+        let cap = 1.2 * spacing;
+        let capped = Method{max_stack_offset: Some(cap), ..method.clone()};
+        let layout_out = capped.lay_out_coils(&surface, &circles, false).unwrap();
+        assert!(layout_out.coils.iter().all(|coil| max_offset(coil) <= cap + 1e-3));
+        assert!(max_offset(&layout_out.coils[0]) > 0.95 * cap);
+        assert!((max_offset(&layout_out.coils[1]) - spacing).abs() < 0.05 * spacing);
+        assert!(warnings::summary().iter().any(|(group, _)| group == "Overlap stack offset capped"));
+
+        // A cap below one spacing is rejected
+        let too_tight = Method{circles: circles.clone(), iterations: 0, max_stack_offset: Some(0.5 * spacing), ..method.clone()};
+        assert!(methods::LayoutMethodTrait::do_layout(&too_tight, &surface).is_err());
+    }
+
+    #[test]
+    fn failed_coil_is_skipped_and_the_rest_lay_out() {
+        use methods::LayoutMethodTrait;
+
+        // The middle circle is too small to cross any surface edge
+        let surface = Surface::square_grid(40.0, 20);
+        let method = Method{
+            iterations: 2,
+            ..flat_adam(vec![
+                CircleArgs{center: Point::new(-20.0, 0.0, 0.0), coil_radius: 10.0, ..CircleArgs::default()},
+                CircleArgs{center: Point::new(0.0, 0.0, 0.0), coil_radius: 1.0, ..CircleArgs::default()},
+                CircleArgs{center: Point::new(20.0, 0.0, 0.0), coil_radius: 10.0, ..CircleArgs::default()},
+            ])
+        };
+
+        // Fail-fast by default
+        assert!(method.do_layout(&surface).is_err());
+
+        warnings::clear();
+        let skipping = Method{skip_failed_coils: true, ..method};
+        let layout_out = skipping.do_layout(&surface).unwrap();
+        assert_eq!(layout_out.coils.len(), 2);
+        assert!(layout_out.coils[0].center.x < -10.0 && layout_out.coils[1].center.x > 10.0);
+        assert_eq!(warnings::summary().iter().find(|(group, _)| group == "Circle failed to lay out and was skipped").map(|(_, count)| *count), Some(1));
+    }
+
+    #[test]
+    fn zero_angle_vectors_along_the_normal_still_spread_the_breaks() {
+        use methods::LayoutMethodTrait;
+        use std::f32::consts::PI;
+
+        // Both configured vectors lie along the normal of the flat surface
+        let surface = Surface::square_grid(40.0, 40);
+        let method = Method{
+            zero_angle_vector: GeoVector::zhat(),
+            backup_zero_angle_vector: GeoVector::zhat(),
+            iterations: 0,
+            ..flat_adam(vec![CircleArgs{center: Point::zero(), coil_radius: 15.0, break_count: 4, ..CircleArgs::default()}])
+        };
+        let layout_out = method.do_layout(&surface).unwrap();
+        let coil = &layout_out.coils[0];
+        assert_eq!(coil.breaks.len() + 1, 4);
+
+        let mut angles: Vec<f32> = coil.port.into_iter().chain(coil.breaks.iter().copied()).map(|vertex_id| {
+            let offset = coil.vertices[vertex_id].point - coil.center;
+            offset.y.atan2(offset.x)
+        }).collect();
+        angles.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        for (angle, next_angle) in angles.iter().zip(angles.iter().cycle().skip(1)) {
+            let spacing = (next_angle - angle).rem_euclid(2.0 * PI);
+            assert!((spacing - PI / 2.0).abs() < 0.2, "Break spacing {} rad, angles {:?}", spacing, angles);
+        }
+    }
+
+    #[test]
+    fn weighted_coil_is_decoupled_first() {
+        // A row of three overlapping coils, where the middle one can't decouple from both neighbors at once
+        let surface = Surface::square_grid(60.0, 120);
+        let run = |first_weight: f32| {
+            let method = Method{
+                iterations: 30,
+                ..flat_adam(vec![
+                    CircleArgs{center: Point::new(-17.0, 0.0, 0.0), coil_radius: 12.0, weight: first_weight, ..CircleArgs::default()},
+                    CircleArgs{center: Point::new(0.0, 0.0, 0.0), coil_radius: 12.0, ..CircleArgs::default()},
+                    CircleArgs{center: Point::new(17.0, 0.0, 0.0), coil_radius: 12.0, ..CircleArgs::default()},
+                ])
+            };
+            let coils = method.optimize(&surface).unwrap().layout.coils;
+            let coupling = |a: usize, b: usize| {
+                (coils[a].mutual_inductance(&coils[b], 1.0) / (coils[a].self_inductance(1.0) * coils[b].self_inductance(1.0)).sqrt()).abs()
+            };
+            (coupling(0, 1), coupling(1, 2))
+        };
+
+        let (even_first, even_second) = run(1.0);
+        let (weighted_first, weighted_second) = run(10.0);
+        assert!(weighted_first < even_first, "Weighted pair coupling {}, unweighted {}", weighted_first, even_first);
+        assert!(weighted_second > even_second, "Other pair coupling {} with the weight, {} without", weighted_second, even_second);
+    }
+
+    #[test]
+    fn global_radius_reg_pulls_radii_back() {
+        // Two strongly overlapping coils, free to change radius, with and without the method-wide pull
+        let surface = flat_surface();
+        let method = |radius_reg: f32| Method{
+            radius_reg,
+            radius_freedom: 0.3,
+            iterations: 20,
+            ..flat_adam(vec![
+                CircleArgs{center: Point::new(-5.0, 0.0, 0.0), coil_radius: 12.0, ..CircleArgs::default()},
+                CircleArgs{center: Point::new(5.0, 0.0, 0.0), coil_radius: 12.0, ..CircleArgs::default()},
+            ])
+        };
+        let radius_change = |method: &Method| {
+            let result = method.optimize(&surface).unwrap();
+            result.seed_circles.iter().zip(method.circles.iter())
+                .map(|(new_circle, circle)| (new_circle.coil_radius - circle.coil_radius).abs())
+                .sum::<f32>()
+        };
+        let (free, held) = (radius_change(&method(0.0)), radius_change(&method(100.0)));
+        assert!(held < 0.5 * free, "Radii moved {} mm in total with the pull, {} mm without", held, free);
+    }
+
+    #[test]
+    fn per_coil_radius_reg_pins_that_radius() {
+        // Two strongly overlapping coils, with only the first one's radius held in place
+        let surface = flat_surface();
+        let method = Method{
+            radius_reg: 0.0,
+            radius_freedom: 0.3,
+            iterations: 20,
+            ..flat_adam(vec![
+                CircleArgs{center: Point::new(-5.0, 0.0, 0.0), coil_radius: 12.0, radius_reg: Some(1.0e4), ..CircleArgs::default()},
+                CircleArgs{center: Point::new(5.0, 0.0, 0.0), coil_radius: 12.0, ..CircleArgs::default()},
+            ])
+        };
+        let result = method.optimize(&surface).unwrap();
+        let radius_change = |coil_id: usize| (result.seed_circles[coil_id].coil_radius - method.circles[coil_id].coil_radius).abs();
+        assert!(radius_change(0) < 0.25 * radius_change(1), "Pinned coil moved {} mm, free coil {} mm", radius_change(0), radius_change(1));
+        assert!(radius_change(1) > 0.5, "Free coil only moved {} mm", radius_change(1));
+    }
+
+    #[test]
+    fn static_layouts_from_several_files_all_couple() {
+        let surface = flat_surface();
+        let boundary_points = boundary_points(&surface);
+        let method = flat_adam(Vec::new());
+
+        // One static coil on each side of the new circle, saved to separate files
+        let test_dir = temp_dir("static_layouts_test");
+        let static_dir = test_dir.path();
+        let static_paths: Vec<String> = [-16.0, 16.0].iter().enumerate().map(|(file_id, x)| {
+            let static_circle = CircleArgs{center: Point::new(*x, 0.0, 0.0), coil_radius: 10.0, ..CircleArgs::default()};
+            let static_layout = method.lay_out_coils(&surface, &vec![static_circle], false).unwrap();
+            let path = static_dir.join(format!("static_{}.json", file_id)).to_string_lossy().to_string();
+            layout::save_layout(&static_layout, &path).unwrap();
+            path
+        }).collect();
+
+        let paths: StaticLayoutPaths = serde_json::from_value(serde_json::json!(static_paths)).unwrap();
+        let both = paths.load().unwrap();
+        let first = StaticLayoutPaths::single(&static_paths[0]).load().unwrap();
+        assert_eq!(both.coils.len(), 2);
+        assert!(StaticLayoutPaths(vec![]).load().is_err());
+
+        // A single path is still a plain string in the config
+        let single: StaticLayoutPaths = serde_json::from_value(serde_json::json!(static_paths[0])).unwrap();
+        assert_eq!(single, StaticLayoutPaths::single(&static_paths[0]));
+        assert_eq!(serde_json::to_value(&single).unwrap(), serde_json::json!(static_paths[0]));
+
+        // The new circle is close to both static coils, so both add to the coupling
+        let circles = vec![CircleArgs{center: Point::zero(), coil_radius: 10.0, ..CircleArgs::default()}];
+        let layout_out = method.lay_out_coils(&surface, &circles, false).unwrap();
+        let coupling = |static_layout: layout::Layout| {
+            let (_, objective, close_coils) = method.update_circles(
+                &circles,
+                &circles,
+                &layout_out,
+                &Some(static_layout),
+                &CircleSurfaces::new(&surface, &circles).unwrap(),
+                &boundary_points,
+                &mut vec![false; circles.len()],
+                &mut vec![Moment::new(); circles.len()],
+                None,
+            ).unwrap();
+            (objective, close_coils)
+        };
+        let (first_objective, first_close) = coupling(first);
+        let (both_objective, both_close) = coupling(both);
+        assert_eq!((first_close, both_close), (1, 2));
+        assert!(both_objective > 1.5 * first_objective, "Objective {} with both static files, {} with the first", both_objective, first_objective);
+    }
+
+    #[test]
+    fn boundary_shift_of_a_negative_coil_moves_its_partner() {
+        // The plane sits off the middle of the square, so only the negative-side copy is too close to the boundary
+        let surface = flat_surface();
+        let plane = Plane::from_normal_and_offset(GeoVector::xhat(), -5.0);
+        let method = Method{
+            symmetry_plane: Some(SymmetryPlane::Fixed(plane)),
+            layout_in_path: None,
+            circles: vec![
+                CircleArgs{center: Point::new(-5.0, 20.0, 0.0), coil_radius: 10.0, on_symmetry_plane: true, ..CircleArgs::default()},
+                CircleArgs{center: Point::new(20.0, -10.0, 0.0), coil_radius: 12.0, ..CircleArgs::default()},
+            ],
+            iterations: 0,
+            final_cfg_output: None,
+            port_keepout_radius: 0.0,
+            ..Default::default()
+        };
+        let result = method.optimize(&surface).unwrap();
+        let (pos_circle, neg_circle) = (&result.circles[1], &result.circles[2]);
+
+        // The negative copy was shifted in from the boundary, and the positive one moved with it as its exact reflection
+        assert!(neg_circle.center.x > -29.0, "Negative coil center {:?} wasn't shifted", neg_circle.center);
+        assert!(pos_circle.center.x < 19.0, "Positive coil center {:?} didn't follow", pos_circle.center);
+        assert!(pos_circle.center.approx_eq(&neg_circle.center.reflect_across(&plane), 1e-4),
+            "Positive center {:?}, reflected negative center {:?}", pos_circle.center, neg_circle.center.reflect_across(&plane));
+        assert_eq!(pos_circle.coil_radius, neg_circle.coil_radius);
+
+        // The laid out pair matches the shifted circles too
+        let (pos_coil, neg_coil) = (&result.layout.coils[1], &result.layout.coils[2]);
+        assert!((pos_coil.average_radius() - neg_coil.average_radius()).abs() < 0.05 * neg_circle.coil_radius,
+            "Laid out radii {} and {}", pos_coil.average_radius(), neg_coil.average_radius());
+        assert!((neg_coil.average_radius() - neg_circle.coil_radius).abs() < 0.1 * neg_circle.coil_radius);
+    }
+
+    #[test]
+    fn wire_thicker_than_coil_is_rejected() {
+        use methods::LayoutMethodTrait;
+
+        let surface = Surface::square_grid(40.0, 40);
+        let method = Method{
+            wire_radius: 6.45,
+            ..flat_adam(vec![CircleArgs{center: Point::zero(), coil_radius: 5.0, ..CircleArgs::default()}])
+        };
+        let error = method.do_layout(&surface).unwrap_err().to_string();
+        assert!(error.contains("Wire radius (6.45) must be smaller than the coil radius of circle 0"), "Unexpected error: {}", error);
+
+        let method = Method{wire_radius: 0.645, clearance: -1.0, ..method};
+        let error = method.do_layout(&surface).unwrap_err().to_string();
+        assert!(error.contains("Clearance must not be negative"), "Unexpected error: {}", error);
+    }
+
+    #[test]
+    fn diameter_sets_half_the_radius() {
+        let circle: CircleArgs = serde_yaml::from_str("center: {x: 0.0, y: 0.0, z: 0.0}\ndiameter: 10").unwrap();
+        assert_eq!(circle.coil_radius, 5.0);
+
+        let circle: CircleArgs = serde_yaml::from_str("center: {x: 0.0, y: 0.0, z: 0.0}\nradius: 10").unwrap();
+        assert_eq!(circle.coil_radius, 10.0);
+
+        let both = serde_yaml::from_str::<CircleArgs>("center: {x: 0.0, y: 0.0, z: 0.0}\nradius: 5\ndiameter: 10");
+        assert!(both.is_err());
+    }
+
+    #[test]
+    fn circle_labels_round_trip_and_reject_overlong_text() {
+        let label: CircleLabel = serde_json::from_str("\"row_a\"").unwrap();
+        assert_eq!(label.as_str(), "row_a");
+        assert_eq!(serde_json::to_string(&label).unwrap(), "\"row_a\"");
+
+        let circle = CircleArgs{label: Some(label), ..CircleArgs::default()};
+        let copied = circle;
+        assert_eq!(copied.label, circle.label);
+
+        assert!(CircleLabel::try_from("a".repeat(CIRCLE_LABEL_CAPACITY).as_str()).is_ok());
+        assert!(serde_json::from_str::<CircleLabel>(&format!("\"{}\"", "a".repeat(CIRCLE_LABEL_CAPACITY + 1))).is_err());
+    }
+}
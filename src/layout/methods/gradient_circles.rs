@@ -6,13 +6,32 @@
 
 use crate::layout;
 use crate::geo_3d::*;
+use crate::warnings;
+use crate::timing;
+use crate::progress::{self, ProgressEvent};
 use layout::methods;
 use methods::helper::{
-    sphere_intersect,
-    clean_coil_by_angle,
+    deserialize_non_adam_circles,
+    StaticLayoutPaths,
+    circle_coil,
+    ProjectionMode,
+    RadiusMode,
+    SymmetryPlane,
+    CenterFreedomMode,
+    PreShift,
     merge_segments,
     add_even_breaks_by_angle,
+    zero_angle_reference,
     closest_point,
+    Intersections,
+    bounding_radii,
+    sym_pair_center,
+    check_coil_count,
+    check_sym_groups,
+    check_wire_params,
+    check_circle_count,
+    rms_coupling,
+    check_epsilon,
 };
 
 use serde::{Serialize, Deserialize};
@@ -20,24 +39,33 @@ use itertools::concat;
 
 /// Gradient Circles method struct.
 /// This struct contains all the parameters for the Gradient Circles layout method.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 #[derive(Serialize, Deserialize)]
 #[serde(deny_unknown_fields)]
 pub struct Method {
     // Optional symmetry plane
     #[serde(default = "Method::default_symmetry_plane", alias = "plane")]
-    pub symmetry_plane: Option<Plane>,
+    pub symmetry_plane: Option<SymmetryPlane>,
     #[serde(default = "Method::default_layout_in_path", rename = "layout_in", alias = "static_layout")]
-    pub layout_in_path: Option<String>,
+    pub layout_in_path: Option<StaticLayoutPaths>,
 
     // Circle intersection parameters
+    #[serde(deserialize_with = "deserialize_non_adam_circles")]
     pub circles: Vec<CircleArgs>,
+    #[serde(default = "Method::default_radius_mode")]
+    pub radius_mode: RadiusMode,
     #[serde(default = "Method::default_epsilon")]
     pub epsilon: f32,
     #[serde(default = "Method::default_pre_shift")]
-    pub pre_shift: bool,
+    pub pre_shift: PreShift,
+    #[serde(default = "Method::default_min_vertices")]
+    pub min_vertices: usize,
+    #[serde(default = "Method::default_projection_mode", alias = "projection")]
+    pub projection_mode: ProjectionMode,
 
     // Overlap handling parameters
+    #[serde(default = "Method::default_resolve_overlaps")]
+    pub resolve_overlaps: bool,
     #[serde(default = "Method::default_clearance")]
     pub clearance: f32,
     #[serde(default = "Method::default_wire_radius")]
@@ -60,6 +88,8 @@ pub struct Method {
     pub radius_freedom: f32,
     #[serde(default = "Method::default_center_freedom")]
     pub center_freedom: f32,
+    #[serde(default = "Method::default_center_freedom_mode")]
+    pub center_freedom_mode: CenterFreedomMode,
     #[serde(default = "Method::default_close_cutoff")]
     pub close_cutoff: f32,
 
@@ -76,26 +106,38 @@ pub struct Method {
     pub final_cfg_output: Option<String>,
 }
 impl Method {
-    pub fn example_symmetry_plane() -> Option<Plane> {
-        Some(Plane::from_normal_and_offset(GeoVector::xhat(), 0.0))
+    pub fn example_symmetry_plane() -> Option<SymmetryPlane> {
+        Some(SymmetryPlane::Fixed(Plane::from_normal_and_offset(GeoVector::xhat(), 0.0)))
     }
-    pub fn default_symmetry_plane() -> Option<Plane> {
+    pub fn default_symmetry_plane() -> Option<SymmetryPlane> {
         None
     }
-    pub fn example_layout_in_path() -> Option<String> {
-        Some("PATH/TO/INITIAL/CFG.json".to_string())
+    pub fn example_layout_in_path() -> Option<StaticLayoutPaths> {
+        Some(StaticLayoutPaths::single("PATH/TO/INITIAL/CFG.json"))
     }
-    pub fn default_layout_in_path() -> Option<String> {
+    pub fn default_layout_in_path() -> Option<StaticLayoutPaths> {
         None
     }
 
+    pub fn default_radius_mode() -> RadiusMode {
+        RadiusMode::Absolute
+    }
     pub fn default_epsilon() -> f32 {
         1.5
     }
-    pub fn default_pre_shift() -> bool {
-        true
+    pub fn default_pre_shift() -> PreShift {
+        PreShift::default()
+    }
+    pub fn default_min_vertices() -> usize {
+        0
+    }
+    pub fn default_projection_mode() -> ProjectionMode {
+        ProjectionMode::SphereIntersect
     }
 
+    pub fn default_resolve_overlaps() -> bool {
+        true
+    }
     pub fn default_clearance() -> f32 {
         1.29
     }
@@ -124,6 +166,9 @@ impl Method {
     pub fn default_center_freedom() -> f32 {
         0.5
     }
+    pub fn default_center_freedom_mode() -> CenterFreedomMode {
+        CenterFreedomMode::default()
+    }
     pub fn default_radius_freedom() -> f32 {
         0.15
     }
@@ -158,9 +203,13 @@ impl Default for Method{
             layout_in_path: Self::example_layout_in_path(),
 
             circles: vec![CircleArgs::default(); 2],
+            radius_mode: Self::default_radius_mode(),
             epsilon: Self::default_epsilon(),
             pre_shift: Self::default_pre_shift(),
+            min_vertices: Self::default_min_vertices(),
+            projection_mode: Self::default_projection_mode(),
 
+            resolve_overlaps: Self::default_resolve_overlaps(),
             clearance: Self::default_clearance(),
             wire_radius: Self::default_wire_radius(),
             zero_angle_vector: Self::default_zero_angle_vector(),
@@ -170,6 +219,7 @@ impl Default for Method{
             initial_step: Self::default_initial_step(),
             step_halflife: Self::default_step_halflife(),
             center_freedom: Self::default_center_freedom(),
+            center_freedom_mode: Self::default_center_freedom_mode(),
             radius_freedom: Self::default_radius_freedom(),
             close_cutoff: Self::default_close_cutoff(),
             radius_reg: Self::default_radius_reg(),
@@ -184,6 +234,9 @@ impl Default for Method{
 }
 
 /// Single element arguments
+/// A `zero_angle_vector` overrides the method's for this coil only, to point its port (break 0) in a chosen direction.
+/// A `radius_reg` overrides the method's for this coil only; a high value effectively pins the coil's radius.
+/// The ADAM-only `aspect_ratio`, `rotation_angle` and `crossing_priority` are rejected rather than ignored (see `deserialize_non_adam_circles`).
 #[derive(Debug, Clone, Copy)]
 #[derive(Serialize, Deserialize)]
 pub struct CircleArgs {
@@ -196,6 +249,10 @@ pub struct CircleArgs {
     pub break_angle_offset: f32,
     #[serde(default = "CircleArgs::default_on_symmetry_plane", alias = "on_sym")]
     pub on_symmetry_plane: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub zero_angle_vector: Option<GeoVector>,
+    #[serde(default, skip_serializing_if = "Option::is_none", alias = "radius_regularization")]
+    pub radius_reg: Option<f32>,
 }
 impl CircleArgs {
     fn default() -> Self {
@@ -205,6 +262,8 @@ impl CircleArgs {
             break_count: Self::default_break_count(),
             break_angle_offset: Self::default_break_angle_offset(),
             on_symmetry_plane: Self::default_on_symmetry_plane(),
+            zero_angle_vector: None,
+            radius_reg: None,
         }
     }
     pub fn default_coil_radius() -> f32 {
@@ -230,30 +289,72 @@ impl methods::LayoutMethodTrait for Method {
         "Gradient Circles (Optional Symmetry)"
     }
 
+    /// Whether the method was asked for verbose output.
+    fn is_verbose(&self) -> bool {
+        self.verbose
+    }
+
+    fn effective_args(&self) -> &dyn erased_serde::Serialize {
+        self
+    }
+
+    /// Resolve the static layout paths against a base directory.
+    fn resolve_input_paths(&mut self, base_dir: &std::path::Path) {
+        if let Some(layout_in_path) = self.layout_in_path.as_mut() {
+            layout_in_path.resolve(base_dir);
+        }
+    }
+
     fn do_layout(&self, surface: &Surface) -> layout::ProcResult<layout::Layout> {
 
+        // Convert fractional radii to absolute ones on this surface
+        if self.radius_mode != RadiusMode::Absolute {
+            let radius_scale = self.radius_mode.radius_scale(surface);
+            let absolute_method = Method{
+                circles: self.circles.iter().map(|circle| CircleArgs{coil_radius: circle.coil_radius * radius_scale, ..*circle}).collect(),
+                radius_mode: RadiusMode::Absolute,
+                ..self.clone()
+            };
+            return absolute_method.do_layout(surface);
+        }
+
+        // Fit the symmetry plane to the surface if it's set to auto
+        if let Some(SymmetryPlane::Auto) = self.symmetry_plane {
+            let fitted_method = Method{
+                symmetry_plane: Some(SymmetryPlane::Fixed(SymmetryPlane::Auto.resolve(surface)?)),
+                ..self.clone()
+            };
+            return fitted_method.do_layout(surface);
+        }
+
+        check_circle_count(self.circles.len())?;
+        let coil_radii: Vec<f32> = self.circles.iter().map(|circle| circle.coil_radius).collect();
+        check_wire_params(self.wire_radius, self.clearance, &coil_radii)?;
+        check_epsilon(surface, self.epsilon, self.verbose || self.statistics);
+        progress::start_iterations(self.iterations);
+
         // Initialize potential symmetrical circles
         let mut sym_circles = Vec::<CircleArgs>::new();
         let mut pos_circles = Vec::<CircleArgs>::new();
         let mut neg_circles = Vec::<CircleArgs>::new();
 
-        // Load the static layout if provided
+        // Load the static layout(s) if provided
         let static_layout = if let Some(layout_in_path) = &self.layout_in_path {
             println!("Loading initial layout...");
-            Some(crate::io::load_deser_from::<layout::Layout>(layout_in_path)?)
+            Some(layout_in_path.load()?)
         } else {
             None
         };
 
         // Collect and clone the circles, with extra effort for symmetry
-        let original_circles = if let Some(symmetry_plane) = &self.symmetry_plane {
+        let original_circles = if let Some(symmetry_plane) = &self.symmetry_plane() {
             // Separate the coils by their symmetry
             for (circle_num, circle) in self.circles.iter().enumerate() {
                 if circle.on_symmetry_plane {
                     // Make sure the circle is on the symmetry plane
                     let mut circle = circle.clone();
-                    if symmetry_plane.distance_to_point(&circle.center).abs() > self.epsilon {
-                        println!("WARNING: Circle {} more than epsilon ({}) from symmetry plane, moving to symmetry plane", circle_num, self.epsilon);
+                    if !symmetry_plane.contains_point(&circle.center, self.epsilon) {
+                        warnings::warn(&format!("Circle {} more than epsilon ({}) from symmetry plane, moving to symmetry plane", circle_num, self.epsilon));
                     }
                     circle.center = symmetry_plane.project_point(&circle.center);
                     sym_circles.push(circle);
@@ -261,11 +362,11 @@ impl methods::LayoutMethodTrait for Method {
                     // Make sure the circle is on the right side of the symmetry plane
                     let mut circle = circle.clone();
                     if symmetry_plane.distance_to_point(&circle.center) < 0.0 {
-                        println!("WARNING: Circle {} on wrong side of symmetry plane, flipping", circle_num);
+                        warnings::warn(&format!("Circle {} on wrong side of symmetry plane, flipping", circle_num));
                         circle.center = circle.center.reflect_across(&symmetry_plane);
                     }
-                    if symmetry_plane.distance_to_point(&circle.center).abs() < self.epsilon {
-                        println!("WARNING: Circle {} close to symmetry plane, may cause issues", circle_num);
+                    if symmetry_plane.contains_point(&circle.center, self.epsilon) {
+                        warnings::warn(&format!("Circle {} close to symmetry plane, may cause issues", circle_num));
                     }
                     pos_circles.push(circle);
 
@@ -294,19 +395,21 @@ impl methods::LayoutMethodTrait for Method {
         
         // Shrink initial radii to keep the coils within the boundary. Shift center if radius is too small.
         for (coil_id, circle) in new_circles.iter_mut().enumerate() {
-            let mut boundary_point = *closest_point(&circle.center, &boundary_points);
+            let Some(mut boundary_point) = closest_point(&circle.center, &boundary_points).copied() else {
+                continue;
+            };
             let vec_to_boundary = circle.center - boundary_point;
             let distance_to_boundary = vec_to_boundary.norm();
             if distance_to_boundary < circle.coil_radius {
                 let original_center = circle.center;
                 circle.center = boundary_point + vec_to_boundary.normalize() * circle.coil_radius;
                 circle.center = circle.center - (&circle.center - surface);
-                boundary_point = *closest_point(&circle.center, &boundary_points);
+                boundary_point = *closest_point(&circle.center, &boundary_points).unwrap_or(&boundary_point);
                 circle.coil_radius = (circle.center - boundary_point).norm();
                 if self.warn_on_shift {
-                    println!("WARNING: Coil {} too close to boundary, center shifted by |{:.2}| to {:.2} and radius shrunk to {:.2}",
+                    warnings::warn_grouped(&format!("Coil {} shifted to boundary", coil_id), &format!("Coil {} too close to boundary, center shifted by |{:.2}| to {:.2} and radius shrunk to {:.2}",
                         coil_id, (original_center - circle.center).norm(), circle.center, circle.coil_radius
-                    );
+                    ));
                 }
                 on_boundary[coil_id] = true;
             }
@@ -341,7 +444,7 @@ impl methods::LayoutMethodTrait for Method {
         }
             
         // Run a single pass
-        let mut layout_out = if let Some(symmetry_plane) = &self.symmetry_plane {
+        let mut layout_out = if let Some(symmetry_plane) = &self.symmetry_plane() {
             self.lay_out_coils_sym(
                 surface,
                 symmetry_plane,
@@ -353,17 +456,26 @@ impl methods::LayoutMethodTrait for Method {
             self.lay_out_coils(surface, &new_circles, false)?
         };
 
+        // Without iterations, report the single pass
+        if self.iterations == 0 {
+            let (objective, new_close_coils) = self.layout_objective(&layout_out, &new_circles, &static_layout);
+            println!("No iterations, keeping the single pass");
+            println!("Objective: {:.2}", rms_coupling(objective, new_close_coils));
+            if close_coils != new_close_coils {
+                warnings::warn_grouped("Number of close coils changed", &format!("Number of close coils changed! ({} -> {})", close_coils, new_close_coils));
+            }
+            println!();
+        }
+
         // Iterate to automatically decouple
-        let mut new_close_coils;
-        let mut objective;
-        let mut step_size = self.initial_step;
         for i in 0..self.iterations {
             println!("Iteration {}/{}...", (i + 1), self.iterations);
 
-            // Generate step size -- linear decrease currently. TODO Probably should be exponential.
-            if i > 0 { step_size *= 0.5_f32.powf(1.0 / self.step_halflife); }
+            let step_size = self.step_size(i);
+            let objective;
+            let new_close_coils;
 
-            if let Some(symmetry_plane) = &self.symmetry_plane {
+            if let Some(symmetry_plane) = &self.symmetry_plane() {
                 // Update positions
                 (sym_circles, pos_circles, neg_circles) = self.update_positions_sym(
                     &sym_circles,
@@ -377,7 +489,7 @@ impl methods::LayoutMethodTrait for Method {
                     &boundary_points,
                     &mut on_boundary,
                     step_size
-                );
+                )?;
                 layout_out = self.lay_out_coils_sym(
                     surface,
                     symmetry_plane,
@@ -398,7 +510,7 @@ impl methods::LayoutMethodTrait for Method {
                     &boundary_points,
                     &mut on_boundary,
                     step_size
-                );
+                )?;
                 layout_out = self.lay_out_coils_sym(
                     surface,
                     symmetry_plane,
@@ -419,7 +531,7 @@ impl methods::LayoutMethodTrait for Method {
                     &boundary_points,
                     &mut on_boundary,
                     step_size
-                );
+                )?;
                 layout_out = self.lay_out_coils(surface, &new_circles, false)?;
     
                 // Update radii
@@ -431,14 +543,15 @@ impl methods::LayoutMethodTrait for Method {
                     &boundary_points,
                     &mut on_boundary,
                     step_size
-                );
+                )?;
                 layout_out = self.lay_out_coils(surface, &new_circles, false)?;
             }
 
             // Print statistics
-            println!("Objective: {:.2}", (objective / new_close_coils as f32).sqrt());
+            println!("Objective: {:.2}", rms_coupling(objective, new_close_coils));
+            progress::iteration(rms_coupling(objective, new_close_coils));
             if close_coils != new_close_coils {
-                println!("WARNING: Number of close coils changed! ({} -> {})", close_coils, new_close_coils);
+                warnings::warn_grouped("Number of close coils changed", &format!("Number of close coils changed! ({} -> {})", close_coils, new_close_coils));
             }
             println!();
             println!("Step size: {:.2}", step_size);
@@ -448,8 +561,7 @@ impl methods::LayoutMethodTrait for Method {
 
         // Print statistics
         if self.statistics {
-            let mut objective = 0.0;
-            let mut close_coils = 0;
+            let stats = layout::statistics(&layout_out, self.close_cutoff, &layout::StatisticsOptions{all_pairs: true, ..Default::default()});
 
             println!("Final Coils:");
             for (coil_id, coil) in layout_out.coils.iter().enumerate() {
@@ -458,41 +570,26 @@ impl methods::LayoutMethodTrait for Method {
             println!();
 
             println!("Coupling factor estimates:");
-            for (coil_id, coil) in layout_out.coils.iter().enumerate() {
-                // TODO: Make more efficient by calculating the self inductance once
-                // TODO: Include intersecting static coils in close_coils and objective function
-
-                for (other_id, other_coil) in layout_out.coils.iter().enumerate() {
-                    if coil_id < other_id {
-                        let coupling = coil.coupling_factor(other_coil, 1.0);
-                        print!("Coil {} to Coil {}:", coil_id, other_id);
-                        if coupling.signum() > 0.0 {
-                            println!("  {:.3}", coupling);
-                        } else {
-                            println!(" {:.3}", coupling);
-                        }
-
-                        // Track in objective if close
-                        let vec_from_other = coil.center - other_coil.center;
-                        let distance_scale = new_circles[coil_id].coil_radius + new_circles[other_id].coil_radius;
-                        let d_rel = vec_from_other.norm() / distance_scale;
-                        if d_rel < self.close_cutoff {
-                            close_coils += 1;
-                            objective += coupling * coupling * 1.0e6;
-                        }
-                    }
+            let mut pairs: Vec<&(usize, usize, f32, f32)> = stats.close_pairs.iter().chain(stats.far_pairs.iter()).collect();
+            pairs.sort_by_key(|(coil_id, other_id, _, _)| (*coil_id, *other_id));
+            for (coil_id, other_id, _, coupling) in pairs {
+                print!("Coil {} to Coil {}:", coil_id, other_id);
+                if coupling.signum() > 0.0 {
+                    println!("  {:.3}", coupling);
+                } else {
+                    println!(" {:.3}", coupling);
                 }
             }
             println!();
 
             println!("Self inductance estimates");
-            for (coil_id, coil) in layout_out.coils.iter().enumerate() {
-                let self_inductance = coil.self_inductance(1.0);
-                println!("Coil {}: {:.3}", coil_id, self_inductance);
+            for (coil_id, coil) in stats.coils.iter().enumerate() {
+                println!("Coil {}: {:.3}", coil_id, coil.self_inductance);
             }
             println!();
 
-            println!("Objective: {:.2}", (objective / close_coils as f32).sqrt());
+            // Same scale as the objective during the iterations (RMS coupling times 1e3)
+            println!("Objective: {:.2}", stats.rms_coupling.map_or(0.0, |rms| rms * 1.0e3));
             println!();
         }
 
@@ -506,13 +603,8 @@ impl methods::LayoutMethodTrait for Method {
         for (coil_id, coil) in layout_out.coils.iter_mut().enumerate() {
             let break_count = new_circles[coil_id].break_count;
             let break_angle_offset_rad = new_circles[coil_id].break_angle_offset * std::f32::consts::PI / 180.0;
-            let zero_angle_vector = {
-                if coil.normal.normalize().dot(&self.zero_angle_vector.normalize()) < 0.95 {
-                    self.zero_angle_vector
-                } else {
-                    self.backup_zero_angle_vector
-                }
-            }.normalize();
+            let zero_angle_vector = new_circles[coil_id].zero_angle_vector.unwrap_or(self.zero_angle_vector);
+            let zero_angle_vector = zero_angle_reference(&coil.normal, &[zero_angle_vector, self.backup_zero_angle_vector]);
 
             add_even_breaks_by_angle(coil, break_count, break_angle_offset_rad, zero_angle_vector)?;
         }
@@ -521,8 +613,59 @@ impl methods::LayoutMethodTrait for Method {
     }
 }
 
+/// Symmetric, positive, and negative circles; the objective function; and the number of close coils
+type SymCircleUpdate = (Vec<CircleArgs>, Vec<CircleArgs>, Vec<CircleArgs>, f32, usize);
+
 impl Method {
 
+    /// Get the symmetry plane, if fixed.
+    /// An `auto` plane is fitted at the start of `do_layout`, so it counts as no plane before then.
+    fn symmetry_plane(&self) -> Option<Plane> {
+        self.symmetry_plane.and_then(|symmetry_plane| symmetry_plane.fixed())
+    }
+
+    /// Get the step size for an iteration (counting from 0).
+    /// The step halves every `step_halflife` iterations, and a `step_halflife` of 0 or less keeps it constant.
+    fn step_size(&self, iteration: usize) -> f32 {
+        if self.step_halflife > 0.0 {
+            self.initial_step * 0.5_f32.powf(iteration as f32 / self.step_halflife)
+        } else {
+            self.initial_step
+        }
+    }
+
+    /// Get the objective function (sum of squared coupling factors, scaled by 1e6) and the number of close coils of a laid-out pass.
+    /// Coils are close if their centers are nearer than `close_cutoff` times the sum of their circle radii,
+    /// and close to a static coil if any of its vertices is nearer than `close_cutoff` times the circle radius, as in `update_radii`.
+    fn layout_objective(&self, layout_out: &layout::Layout, circles: &[CircleArgs], static_layout: &Option<layout::Layout>) -> (f32, usize) {
+        let mut objective = 0.0;
+        let mut close_coils = 0;
+        for (coil_id, coil) in layout_out.coils.iter().enumerate() {
+            for (other_id, other_coil) in layout_out.coils.iter().enumerate().skip(coil_id + 1) {
+                let distance_scale = circles[coil_id].coil_radius + circles[other_id].coil_radius;
+                if (coil.center - other_coil.center).norm() / distance_scale < self.close_cutoff {
+                    let coupling = coil.coupling_factor(other_coil, 1.0);
+                    close_coils += 1;
+                    objective += coupling * coupling * 1.0e6;
+                }
+            }
+
+            // Include static coils
+            if let Some(static_layout) = static_layout.as_ref() {
+                for static_coil in static_layout.coils.iter() {
+                    let close = static_coil.vertices.iter()
+                        .any(|vertex| (coil.center - vertex.point).norm() / circles[coil_id].coil_radius < self.close_cutoff);
+                    if close {
+                        let coupling = coil.coupling_factor(static_coil, 1.0);
+                        close_coils += 1;
+                        objective += coupling * coupling * 1.0e6;
+                    }
+                }
+            }
+        }
+        (objective, close_coils)
+    }
+
     /// Do a single pass of spherical intersection on the circles
     fn lay_out_coils(
         &self,
@@ -546,22 +689,21 @@ impl Method {
             let vec_to_surface = &circle_args.center - surface;
             let center = circle_args.center - vec_to_surface;
 
-            // Create the circle through surface intersection with sphere
-            let (cid, points, point_normals) = sphere_intersect(surface, center, coil_radius, self.epsilon);
-            let coil_normal = surface.vertices[cid].normal;
-
-            let coil = clean_coil_by_angle(
+            // Create the circle on the surface
+            let coil = circle_coil(
+                surface,
                 center,
-                coil_normal,
-                coil_radius, 
+                coil_radius,
                 self.wire_radius,
-                points,
-                point_normals,
+                self.epsilon,
                 self.pre_shift,
-                false
+                self.min_vertices,
+                self.projection_mode
             )?;
 
             layout_out.coils.push(coil);
+
+            progress::emit(ProgressEvent::CoilLaidOut{id: layout_out.coils.len() - 1});
         }
 
         // Do overlaps
@@ -590,27 +732,23 @@ impl Method {
             let coil_radius = circle_args.coil_radius;
             let center = circle_args.center;
 
-            // Create the circle through surface intersection with sphere
-            let (cid, points, point_normals) =
-                sphere_intersect(surface, center, coil_radius, self.epsilon);
-            let coil_normal = surface.vertices[cid].normal.normalize();
-
-            if verbose { println!("Uncleaned point count: {}", points.len()) };
-
-            let coil = clean_coil_by_angle(
+            // Create the circle on the surface
+            let coil = circle_coil(
+                surface,
                 center,
-                coil_normal,
                 coil_radius,
                 self.wire_radius,
-                points,
-                point_normals,
+                self.epsilon,
                 self.pre_shift,
-                false
+                self.min_vertices,
+                self.projection_mode
             )?;
     
             if verbose { println!("Cleaned point count: {}", coil.vertices.len()) };
     
             layout_out.coils.push(coil);
+    
+            progress::emit(ProgressEvent::CoilLaidOut{id: layout_out.coils.len() - 1});
         }
 
         // Create the coils for the positive circles
@@ -620,27 +758,23 @@ impl Method {
             let coil_radius = circle_args.coil_radius;
             let center = circle_args.center;
 
-            // Create the circle through surface intersection with sphere
-            let (cid, points, point_normals) =
-                sphere_intersect(surface, center, coil_radius, self.epsilon);
-            let coil_normal = surface.vertices[cid].normal.normalize();
-
-            if verbose { println!("Uncleaned point count: {}", points.len()) };
-
-            let coil = clean_coil_by_angle(
+            // Create the circle on the surface
+            let coil = circle_coil(
+                surface,
                 center,
-                coil_normal,
                 coil_radius,
                 self.wire_radius,
-                points,
-                point_normals,
+                self.epsilon,
                 self.pre_shift,
-                false
+                self.min_vertices,
+                self.projection_mode
             )?;
     
             if verbose { println!("Cleaned point count: {}", coil.vertices.len()) };
     
             layout_out.coils.push(coil);
+    
+            progress::emit(ProgressEvent::CoilLaidOut{id: layout_out.coils.len() - 1});
         }
 
         // Create the coils for the flipped circles
@@ -654,6 +788,7 @@ impl Method {
                 coil.vertices.iter().map(|vertex| vertex.surface_normal.reflect_across(&symmetry_plane.get_normal())).rev().collect()
             )?;
             layout_out.coils.push(neg_coil);
+            progress::emit(ProgressEvent::CoilLaidOut{id: layout_out.coils.len() - 1});
         }
 
         // Do overlaps
@@ -674,10 +809,10 @@ impl Method {
         boundary_points: &Vec::<Point>,
         on_boundary: &mut Vec::<bool>,
         step_size: f32
-    ) -> Vec<CircleArgs> {
+    ) -> layout::ProcResult<Vec<CircleArgs>> {
 
         let mut new_circles = circles.clone();
-        assert!(new_circles.len() == layout_out.coils.len());
+        check_coil_count(new_circles.len(), layout_out.coils.len())?;
 
         let mut coil_forces = vec![Vec::<GeoVector>::new(); layout_out.coils.len()];
         let mut self_inductances = vec![0.0; layout_out.coils.len()];
@@ -776,18 +911,19 @@ impl Method {
             // Check and update boundary condition
             // If on the boundary, add a normal force keeping the coil from crossing the boundary
             if on_boundary[coil_id] {
-                let boundary_point = closest_point(&center, boundary_points);
-                let flat_vec_to_boundary = (center - *boundary_point).rej_onto(&coil.normal).normalize();
-                let boundary_component = delta_c.proj_onto(&flat_vec_to_boundary);
-                if boundary_component.norm() >= 0.0 {
-                    delta_c = delta_c - boundary_component;
-                } else {
-                    on_boundary[coil_id] = false;
+                if let Some(boundary_point) = closest_point(&center, boundary_points) {
+                    let flat_vec_to_boundary = (center - *boundary_point).rej_onto(&coil.normal).normalize();
+                    let boundary_component = delta_c.proj_onto(&flat_vec_to_boundary);
+                    if boundary_component.norm() >= 0.0 {
+                        delta_c = delta_c - boundary_component;
+                    } else {
+                        on_boundary[coil_id] = false;
+                    }
                 }
             }
 
             // Update the center
-            let center_bound = self.center_freedom * original_radius;
+            let center_bound = self.center_freedom_mode.center_bound(self.center_freedom, original_radius);
             let total_delta = center + (delta_c.rej_onto(&coil.normal)) - original_center;
             if total_delta.norm() > center_bound {
                 delta_c += total_delta.normalize() * (center_bound - total_delta.norm());
@@ -795,14 +931,15 @@ impl Method {
             center = center + delta_c.rej_onto(&coil.normal);
 
             // If center is too close to the boundary, move it away. Iterate 10 times and then shrink the radius
-            let boundary_point = closest_point(&center, boundary_points);
-            for i in 0..10 {
-                let vec_to_boundary = center - *boundary_point;
-                let distance_to_boundary = vec_to_boundary.norm();
-                if distance_to_boundary < radius {
-                    on_boundary[coil_id] = true;
-                    if i < 9 {center = *boundary_point + vec_to_boundary.normalize() * radius;}
-                    else {radius = distance_to_boundary;}
+            if let Some(boundary_point) = closest_point(&center, boundary_points) {
+                for i in 0..10 {
+                    let vec_to_boundary = center - *boundary_point;
+                    let distance_to_boundary = vec_to_boundary.norm();
+                    if distance_to_boundary < radius {
+                        on_boundary[coil_id] = true;
+                        if i < 9 {center = *boundary_point + vec_to_boundary.normalize() * radius;}
+                        else {radius = distance_to_boundary;}
+                    }
                 }
             }
 
@@ -810,7 +947,7 @@ impl Method {
         }
 
         // Return the updated circles
-        new_circles
+        Ok(new_circles)
     }
     
     /// Update the positions of the circles with symmetry
@@ -828,7 +965,9 @@ impl Method {
         boundary_points: &Vec::<Point>,
         on_boundary: &mut Vec::<bool>,
         step_size: f32
-    ) -> (Vec<CircleArgs>, Vec<CircleArgs>, Vec<CircleArgs>) {
+    ) -> layout::ProcResult<(Vec<CircleArgs>, Vec<CircleArgs>, Vec<CircleArgs>)> {
+
+        check_sym_groups(sym_circles.len(), pos_circles.len(), neg_circles.len(), layout_out.coils.len())?;
 
         let mut new_circles = concat(vec![sym_circles.clone(), pos_circles.clone(), neg_circles.clone()]);
 
@@ -842,7 +981,7 @@ impl Method {
             boundary_points,
             on_boundary,
             step_size
-        );
+        )?;
 
         // Split the circles back into their respective groups
         let mut new_sym_circles = Vec::<CircleArgs>::new();
@@ -864,14 +1003,23 @@ impl Method {
             circle.center = circle.center - (&circle.center - surface).rej_onto(&symmetry_plane.get_normal());
         }
 
-        // Average the positive and negative circles (flipped) to keep them symmetric
-        for (pos_circle, neg_circle) in new_pos_circles.iter_mut().zip(new_neg_circles.iter_mut()) {
-            pos_circle.center = ((GeoVector::from(pos_circle.center) + GeoVector::from(neg_circle.center.reflect_across(&symmetry_plane))) / 2.0).into();
+        // Average the positive and negative circles (flipped) to keep them symmetric,
+        // weighted towards whichever side is pinned at the boundary (see `sym_pair_center`)
+        let pos_offset = sym_circles.len();
+        let neg_offset = sym_circles.len() + pos_circles.len();
+        for (pair_id, (pos_circle, neg_circle)) in new_pos_circles.iter_mut().zip(new_neg_circles.iter_mut()).enumerate() {
+            pos_circle.center = sym_pair_center(
+                pos_circle.center,
+                neg_circle.center,
+                symmetry_plane,
+                on_boundary[pos_offset + pair_id],
+                on_boundary[neg_offset + pair_id],
+            );
             neg_circle.center = pos_circle.center.reflect_across(&symmetry_plane);
         }
 
         // Return the updated circles
-        (new_sym_circles, new_pos_circles, new_neg_circles)
+        Ok((new_sym_circles, new_pos_circles, new_neg_circles))
     }
 
     /// Update the radii of the circles
@@ -885,10 +1033,10 @@ impl Method {
         boundary_points: &Vec::<Point>,
         on_boundary: &mut Vec::<bool>,
         step_size: f32
-    ) -> (Vec<CircleArgs>, f32, usize) {
+    ) -> layout::ProcResult<(Vec<CircleArgs>, f32, usize)> {
 
         let mut new_circles = circles.clone();
-        assert!(new_circles.len() == layout_out.coils.len());
+        check_coil_count(new_circles.len(), layout_out.coils.len())?;
 
         // Initialize objective function and number of close coils
         let mut objective = 0.0;
@@ -920,6 +1068,7 @@ impl Method {
             // Get previous values
             let center = coil.center;
             let mut radius = circles[coil_id].coil_radius;
+            let radius_reg = circles[coil_id].radius_reg.unwrap_or(self.radius_reg);
 
             // Check all other coils
             for (other_id, other_coil) in layout_out.coils.iter().enumerate() {
@@ -946,7 +1095,7 @@ impl Method {
                         // Include regularization term: radius_reg * (r - r0)
                         let adjustment = -step_size * 
                             (2.0 * m * dr / (self_inductances[coil_id] * self_inductances[other_id]) 
-                            + self.radius_reg * rel_radial_err[coil_id]);
+                            + radius_reg * rel_radial_err[coil_id]);
 
                         // Add the force to the coil
                         net_radial_change[coil_id] += adjustment;
@@ -984,7 +1133,7 @@ impl Method {
                         // Include regularization term: radius_reg * (r - r0)
                         let adjustment = -step_size * 
                             (2.0 * m * dr / (self_inductances[coil_id] * static_self_inductances[static_id].unwrap())
-                            + self.radius_reg * rel_radial_err[coil_id]);
+                            + radius_reg * rel_radial_err[coil_id]);
 
                         // Add the force to the coil
                         net_radial_change[coil_id] += adjustment;
@@ -1002,8 +1151,8 @@ impl Method {
             else if radius > max_radii[coil_id] {radius = max_radii[coil_id];}
 
             // Check boundary status, cap at boundary
-            let boundary_point = closest_point(&center, boundary_points);
-            let distance_to_boundary = (*boundary_point - center).norm();
+            let distance_to_boundary = closest_point(&center, boundary_points)
+                .map_or(f32::INFINITY, |boundary_point| (*boundary_point - center).norm());
             if radius > distance_to_boundary {
                 radius = distance_to_boundary;
                 on_boundary[coil_id] = true;
@@ -1014,7 +1163,7 @@ impl Method {
             new_circles[coil_id].coil_radius = radius;
         }
 
-        (new_circles, objective, close_coils)
+        Ok((new_circles, objective, close_coils))
     }
 
     /// Update the radii of the circles with symmetry
@@ -1030,7 +1179,9 @@ impl Method {
         boundary_points: &Vec::<Point>,
         on_boundary: &mut Vec::<bool>,
         step_size: f32
-    ) -> (Vec<CircleArgs>, Vec<CircleArgs>, Vec<CircleArgs>, f32, usize) {
+    ) -> layout::ProcResult<SymCircleUpdate> {
+
+        check_sym_groups(sym_circles.len(), pos_circles.len(), neg_circles.len(), layout_out.coils.len())?;
 
         let mut new_circles = concat(vec![sym_circles.clone(), pos_circles.clone(), neg_circles.clone()]);
 
@@ -1045,7 +1196,7 @@ impl Method {
             boundary_points,
             on_boundary,
             step_size
-        );
+        )?;
 
         // Split the circles back into their respective groups
         let mut new_sym_circles = Vec::<CircleArgs>::new();
@@ -1068,11 +1219,16 @@ impl Method {
         }
 
         // Return the updated circles
-        (new_sym_circles, new_pos_circles, new_neg_circles, objective, close_coils)
+        Ok((new_sym_circles, new_pos_circles, new_neg_circles, objective, close_coils))
     }
 
     /// Do overlaps between the coils
+    /// Leaves the coils as intersected if `resolve_overlaps` is off.
     fn mousehole_overlap(&self, layout_out: &mut layout::Layout, circles: &Vec::<CircleArgs>) {
+        if !self.resolve_overlaps {
+            return;
+        }
+        let _timer = timing::phase("Overlap resolution");
         let intersections = self.get_intersections(layout_out, 2.0, circles);
         
         // Structure for managing intersecting segments
@@ -1114,8 +1270,8 @@ impl Method {
             
             // Get all the intersections between a coil and a coil of higher coil id than it. 
             let mut any_intersections = false;
-            for other_id in coil_id+1..circles.len() {
-                let other_intersection = &intersections[coil_id][other_id];
+            for (other_id, other_circle) in circles.iter().enumerate().skip(coil_id + 1) {
+                let other_intersection = intersections.get(&(coil_id, other_id)).map_or(&[][..], |points| points.as_slice());
 
                 // Ignore loops entirely contained within other loops
                 if coil.vertices.len() - other_intersection.len() < 2 {
@@ -1167,14 +1323,14 @@ impl Method {
                 }
 
                 // Update wire crossings
-                let other_center = circles[other_id].center;
+                let other_center = other_circle.center;
                 let distance_to_other_coil = |p: usize| -> f32 {
                     let point = coil.vertices[p].point;
                     let vec_to_center = point - other_center;
                     vec_to_center.norm()
                 };
                 let inside_other_coil = |p: usize| -> bool {
-                    distance_to_other_coil(p) < circles[other_id].coil_radius
+                    distance_to_other_coil(p) < other_circle.coil_radius
                 };
                 for segment in segments.iter_mut() {
                     let mut p_prev = segment.start;
@@ -1353,6 +1509,7 @@ impl Method {
 
                 let start_anchor = (segment.start + coil.vertices.len() - 1) % coil.vertices.len();
 
+                coil.overlap_segments.push((segment.start, segment.end));
                 for p in segment.start..=unwrapped_end {
                     let pid = p % coil.vertices.len();
                     coil.vertices[pid].point = coil.vertices[pid].point - coil.vertices[pid].surface_normal * offset(point_distance(start_anchor, pid));
@@ -1391,20 +1548,24 @@ impl Method {
         adjacency
     }
 
-    /// Get a matrix of vectors of intersection points between cleaned coils
-    #[allow(dead_code)]
-    fn get_intersections(&self, intersecting_layout: &layout::Layout, clearance_scale: f32, circles: &Vec::<CircleArgs>) -> Vec<Vec<Vec<usize>>> {
-        let mut intersections: Vec<Vec<Vec<usize>>> = vec![vec![vec![]; circles.len()]; circles.len()];
+    /// Get the intersection points between cleaned coils (see `Intersections`).
+    /// Only pairs whose bounding spheres come within the tolerance of each other are checked.
+    fn get_intersections(&self, intersecting_layout: &layout::Layout, clearance_scale: f32, circles: &Vec::<CircleArgs>) -> Intersections {
+        let mut intersections = Intersections::new();
+        let bounding_radii = bounding_radii(intersecting_layout);
         for (i, coil) in intersecting_layout.coils.iter().enumerate() {
             for (j, other_coil) in intersecting_layout.coils.iter().enumerate() {
-                if i != j {
-                    for (k, vertex) in coil.vertices.iter().enumerate() {
-                        if ((vertex.point - other_coil.center).norm() - circles[j].coil_radius).abs() < 
-                            (coil.wire_radius + other_coil.wire_radius + self.clearance) * clearance_scale {
-                            
-                            intersections[i][j].push(k);
-                        }
-                    }
+                let tolerance = (coil.wire_radius + other_coil.wire_radius + self.clearance) * clearance_scale;
+                let outline_radius = circles[j].coil_radius;
+                if i == j || (coil.center - other_coil.center).norm() >= bounding_radii[i] + outline_radius + tolerance {
+                    continue;
+                }
+                let points: Vec<usize> = coil.vertices.iter().enumerate()
+                    .filter(|(_, vertex)| ((vertex.point - other_coil.center).norm() - circles[j].coil_radius).abs() < tolerance)
+                    .map(|(k, _)| k)
+                    .collect();
+                if !points.is_empty() {
+                    intersections.insert((i, j), points);
                 }
             }
         }
@@ -1421,3 +1582,76 @@ mod debug {
         println!("{}", s);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::flat_surface;
+
+    #[test]
+    fn zero_halflife_keeps_the_step_constant() {
+        let method = Method{step_halflife: 0.0, ..Default::default()};
+        for iteration in 0..10 {
+            assert_eq!(method.step_size(iteration), method.initial_step);
+        }
+
+        let decaying = Method{step_halflife: 2.0, ..Default::default()};
+        assert_eq!(decaying.step_size(0), decaying.initial_step);
+        assert!((decaying.step_size(4) - 0.25 * decaying.initial_step).abs() < 1e-4 * decaying.initial_step);
+    }
+
+    #[test]
+    fn zero_iterations_keep_the_single_pass() {
+        use methods::LayoutMethodTrait;
+
+        let surface = flat_surface();
+        let circles = vec![
+            CircleArgs{center: Point::new(-6.0, 0.0, 0.0), coil_radius: 8.0, break_count: 4, ..CircleArgs::default()},
+            CircleArgs{center: Point::new(6.0, 0.0, 0.0), coil_radius: 8.0, break_count: 4, ..CircleArgs::default()},
+        ];
+        let method = Method{
+            symmetry_plane: None,
+            layout_in_path: None,
+            circles: circles.clone(),
+            iterations: 0,
+            statistics: true,
+            final_cfg_output: None,
+            ..Default::default()
+        };
+
+        let layout_out = method.do_layout(&surface).unwrap();
+        assert_eq!(layout_out.coils.len(), 2);
+        for (coil, circle) in layout_out.coils.iter().zip(circles.iter()) {
+            assert!(coil.center.approx_eq(&circle.center, 1e-3), "Coil moved to {:.2} without iterations", coil.center);
+            assert!(coil.port.is_some());
+            assert_eq!(coil.breaks.len() + 1, circle.break_count);
+        }
+
+        // The mousehole is recorded, so the mesher can keep breaks out of it
+        assert!(layout_out.coils.iter().any(|coil| !coil.overlap_segments.is_empty()));
+
+        // The overlapping pair is close, with a finite, nonzero objective
+        let (objective, close_coils) = method.layout_objective(&layout_out, &circles, &None);
+        assert_eq!(close_coils, 1);
+        assert!(objective.is_finite() && objective > 0.0, "Objective {}", objective);
+
+        // A static coil overlapping the first one counts too
+        let static_layout = layout::Layout{coils: vec![crate::test_fixtures::circle_coil(Point::new(-14.0, 0.0, 0.0), 8.0)]};
+        let (static_objective, static_close_coils) = method.layout_objective(&layout_out, &circles, &Some(static_layout));
+        assert_eq!(static_close_coils, 2);
+        assert!(static_objective > objective, "Objective {} with the static coil, {} without", static_objective, objective);
+    }
+
+    #[test]
+    fn auto_symmetry_plane_mirrors_the_circles() {
+        use methods::LayoutMethodTrait;
+
+        let method: Method = serde_json::from_value(serde_json::json!({
+            "symmetry_plane": "auto",
+            "iterations": 0,
+            "circles": [{"center": {"x": 10.0, "y": 5.0, "z": 0.0}, "radius": 5.0}],
+        })).unwrap();
+        let layout_out = method.do_layout(&flat_surface()).unwrap();
+        assert_eq!(layout_out.coils.len(), 2);
+    }
+}
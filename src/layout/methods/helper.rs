@@ -1,6 +1,9 @@
 use crate::layout;
 use crate::geo_3d::*;
+use crate::warnings;
+use crate::timing;
 use std::f32::consts::PI;
+use serde::{Serialize, Deserialize};
 
 #[derive(Debug, Clone, Copy)]
 struct AngleFormat {
@@ -55,7 +58,688 @@ pub fn sphere_intersect(
     (cid, new_points, new_normals)
 }
 
+/// How a circular coil is generated on the surface.
+/// `SphereIntersect` slices the surface with a sphere around the center, curving the coil over the surface.
+/// `PlanarDisc` lays a circle in the tangent plane at the center and projects each point onto the surface.
+/// `GeodesicCircle` traces the loop of constant surface distance from the center.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProjectionMode {
+    #[default]
+    SphereIntersect,
+    PlanarDisc,
+    GeodesicCircle,
+}
+
+/// How the `coil_radius` of each circle is read.
+/// `Absolute` is a radius in surface units. `FractionOfExtent` is a fraction of the diagonal of the surface bounding box
+/// (see `Surface::bounding_box`), so the same config gives proportionally sized coils on a scaled surface.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RadiusMode {
+    #[default]
+    Absolute,
+    FractionOfExtent,
+}
+impl RadiusMode {
+    /// Get the factor that turns a configured `coil_radius` into an absolute radius on the surface.
+    pub fn radius_scale(&self, surface: &Surface) -> f32 {
+        match self {
+            RadiusMode::Absolute => 1.0,
+            RadiusMode::FractionOfExtent => {
+                let (min, max) = surface.bounding_box();
+                min.distance(&max)
+            },
+        }
+    }
+}
+
+/// How the `center_freedom` bound on center movement is read.
+/// `RelativeToRadius` is a fraction of each coil's original radius, so bigger coils may move farther.
+/// `Absolute` is a distance in surface units, shared by every coil.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CenterFreedomMode {
+    #[default]
+    RelativeToRadius,
+    Absolute,
+}
+impl CenterFreedomMode {
+    /// Get the farthest a center may move from its original position.
+    pub fn center_bound(&self, center_freedom: f32, original_radius: f32) -> f32 {
+        match self {
+            CenterFreedomMode::RelativeToRadius => center_freedom * original_radius,
+            CenterFreedomMode::Absolute => center_freedom,
+        }
+    }
+}
+
+/// How the capacitor breaks are spaced around a coil.
+/// `ByAngle` spreads them evenly in angle around the coil center (`add_even_breaks_by_angle`),
+/// `ByLength` then re-spaces them evenly in arc length from the port (`Coil::redistribute_breaks_by_length`),
+/// which keeps them even when overlap offsets have lengthened part of the wire.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BreakSpacing {
+    #[default]
+    ByAngle,
+    ByLength,
+}
+
+/// How the raw sphere-intersection points are shifted before a coil is cleaned by angle (see `clean_coil_by_angle`).
+/// `None` keeps the points as intersected.
+/// `Centroid` slides the points across the coil plane so their centroid sits on the coil axis,
+/// undoing the lopsided intersection where the surface curves away on one side of the coil.
+/// `NormalAlign` slides each point along the surface tangent (the radial direction with the surface normal removed)
+/// until it is the coil radius from the center, leaving points where that tangent is nearly perpendicular to the radius.
+/// In the config, `true` is `NormalAlign` and `false` is `None`, as for the old boolean setting.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[derive(Serialize, Deserialize)]
+#[serde(try_from = "PreShiftInput", into = "PreShiftInput")]
+pub enum PreShift {
+    None,
+    Centroid,
+    #[default]
+    NormalAlign,
+}
+/// Config form of `PreShift`: either a mode name or the old boolean.
+#[derive(Clone)]
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum PreShiftInput {
+    Flag(bool),
+    Keyword(String),
+}
+impl TryFrom<PreShiftInput> for PreShift {
+    type Error = String;
+
+    fn try_from(input: PreShiftInput) -> Result<Self, Self::Error> {
+        match input {
+            PreShiftInput::Flag(true) => Ok(PreShift::NormalAlign),
+            PreShiftInput::Flag(false) => Ok(PreShift::None),
+            PreShiftInput::Keyword(keyword) => match keyword.as_str() {
+                "none" => Ok(PreShift::None),
+                "centroid" => Ok(PreShift::Centroid),
+                "normal_align" => Ok(PreShift::NormalAlign),
+                _ => Err(format!("Unknown pre-shift '{}' (expected \"none\", \"centroid\", \"normal_align\" or a boolean)", keyword)),
+            },
+        }
+    }
+}
+impl From<PreShift> for PreShiftInput {
+    fn from(pre_shift: PreShift) -> Self {
+        let keyword = match pre_shift {
+            PreShift::None => "none",
+            PreShift::Centroid => "centroid",
+            PreShift::NormalAlign => "normal_align",
+        };
+        PreShiftInput::Keyword(keyword.to_string())
+    }
+}
+
+/// ADAM circle options that the other circle methods cannot honor.
+const ADAM_ONLY_CIRCLE_OPTIONS: [&str; 3] = ["aspect_ratio", "rotation_angle", "crossing_priority"];
+
+/// Deserialize the circles of a method other than ADAM, rejecting the `ADAM_ONLY_CIRCLE_OPTIONS` rather than ignoring them.
+/// Other unknown circle options are still ignored.
+pub fn deserialize_non_adam_circles<'de, D, T>(deserializer: D) -> Result<Vec<T>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: serde::de::DeserializeOwned,
+{
+    let circles = Vec::<serde_json::Value>::deserialize(deserializer)?;
+    circles.into_iter().enumerate().map(|(circle_id, circle)| {
+        if let Some(option) = ADAM_ONLY_CIRCLE_OPTIONS.iter().find(|option| circle.get(**option).is_some()) {
+            return Err(serde::de::Error::custom(format!(
+                "Circle {circle_id}: `{option}` is only supported by the adam_circles layout method")));
+        }
+        serde_json::from_value(circle).map_err(serde::de::Error::custom)
+    }).collect()
+}
+
+/// How to treat a coil nested entirely inside another coil.
+/// `Warn` reports the nested pair and leaves it, `Offset` moves the inner coil out onto the outer coil's wire
+/// (so it becomes an ordinary overlapping neighbor), and `Ignore` leaves it silently.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NestingPolicy {
+    #[default]
+    Warn,
+    Offset,
+    Ignore,
+}
+
+/// How to treat a coil on the symmetry plane whose wire only covers one side of the plane,
+/// e.g. on a surface trimmed at the plane, where the sphere intersection finds just half of the loop.
+/// `Warn` reports the coil and leaves it, `Mirror` rebuilds it from its fuller side and that side's reflection (see `mirror_complete`),
+/// and `Error` stops the layout.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OneSidedPolicy {
+    #[default]
+    Warn,
+    Mirror,
+    Error,
+}
+
+/// Static layout file(s) that new coils are laid out around, given as a single path or a list.
+/// A list is loaded and concatenated into one static layout, e.g. the outputs of several earlier passes over an array.
+#[derive(Debug, Clone, PartialEq)]
+#[derive(Serialize, Deserialize)]
+#[serde(from = "StaticLayoutPathsInput", into = "StaticLayoutPathsInput")]
+pub struct StaticLayoutPaths(pub Vec<String>);
+impl StaticLayoutPaths {
+    /// Create from a single path.
+    pub fn single(path: &str) -> Self {
+        StaticLayoutPaths(vec![path.to_string()])
+    }
+
+    /// Get the paths, in order.
+    pub fn paths(&self) -> &[String] {
+        &self.0
+    }
+
+    /// Resolve the paths against a base directory.
+    pub fn resolve(&mut self, base_dir: &std::path::Path) {
+        for path in self.0.iter_mut() {
+            *path = crate::io::resolve_path(path, Some(base_dir));
+        }
+    }
+
+    /// Load the layouts and concatenate their coils, in order.
+    /// Returns a `ProcResult` with the static `Layout`, or an `Err` if the list is empty or a file fails to load.
+    pub fn load(&self) -> layout::ProcResult<layout::Layout> {
+        if self.0.is_empty() {
+            return layout::err_str("Static layout list is empty");
+        }
+        let mut static_layout = layout::Layout::new();
+        for path in self.0.iter() {
+            let mut part = crate::io::load_deser_from::<layout::Layout>(path)?;
+            static_layout.coils.append(&mut part.coils);
+        }
+        Ok(static_layout)
+    }
+}
+/// Config form of `StaticLayoutPaths`: either a single path or a list.
+#[derive(Clone)]
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum StaticLayoutPathsInput {
+    Single(String),
+    Multiple(Vec<String>),
+}
+impl From<StaticLayoutPathsInput> for StaticLayoutPaths {
+    fn from(input: StaticLayoutPathsInput) -> Self {
+        match input {
+            StaticLayoutPathsInput::Single(path) => StaticLayoutPaths(vec![path]),
+            StaticLayoutPathsInput::Multiple(paths) => StaticLayoutPaths(paths),
+        }
+    }
+}
+impl From<StaticLayoutPaths> for StaticLayoutPathsInput {
+    fn from(paths: StaticLayoutPaths) -> Self {
+        match <[String; 1]>::try_from(paths.0) {
+            Ok([path]) => StaticLayoutPathsInput::Single(path),
+            Err(paths) => StaticLayoutPathsInput::Multiple(paths),
+        }
+    }
+}
+
+/// Symmetry plane setting.
+/// `Fixed` is a plane given in the config, and `Auto` (the string `"auto"` in the config)
+/// fits the best symmetry plane to the surface with `Surface::best_symmetry_plane`.
+#[derive(Debug, Clone, Copy)]
+#[derive(Serialize, Deserialize)]
+#[serde(try_from = "SymmetryPlaneInput", into = "SymmetryPlaneInput")]
+pub enum SymmetryPlane {
+    Auto,
+    Fixed(Plane),
+}
+impl SymmetryPlane {
+    /// Get the fixed plane, or `None` if the plane is still to be fitted.
+    pub fn fixed(&self) -> Option<Plane> {
+        match self {
+            SymmetryPlane::Auto => None,
+            SymmetryPlane::Fixed(plane) => Some(*plane),
+        }
+    }
+
+    /// Resolve the setting to a plane, fitting the best symmetry plane to the surface if set to `Auto`.
+    /// Reports the residual asymmetry of the plane, and warns if it is larger than the mean edge length.
+    pub fn resolve(&self, surface: &Surface) -> layout::ProcResult<Plane> {
+        let plane = match self {
+            SymmetryPlane::Fixed(plane) => return Ok(*plane),
+            SymmetryPlane::Auto => match surface.best_symmetry_plane() {
+                Some(plane) => plane,
+                None => return layout::err_str("Cannot fit a symmetry plane to an empty surface"),
+            },
+        };
+
+        let residual = surface.symmetry_residual(&plane);
+        println!("Fitted symmetry plane: {} (RMS asymmetry {:.3})", plane, residual);
+        if residual > surface.mean_edge_length() {
+            warnings::warn(&format!("Surface is poorly symmetric about the fitted plane (RMS asymmetry {:.3}, mean edge length {:.3})",
+                residual, surface.mean_edge_length()
+            ));
+        }
+        Ok(plane)
+    }
+}
+/// Config form of `SymmetryPlane`: either a keyword or a plane.
+#[derive(Clone)]
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum SymmetryPlaneInput {
+    Keyword(String),
+    Plane(Plane),
+}
+impl TryFrom<SymmetryPlaneInput> for SymmetryPlane {
+    type Error = String;
+
+    fn try_from(input: SymmetryPlaneInput) -> Result<Self, Self::Error> {
+        match input {
+            SymmetryPlaneInput::Keyword(keyword) if keyword == "auto" => Ok(SymmetryPlane::Auto),
+            SymmetryPlaneInput::Keyword(keyword) => Err(format!("Unknown symmetry plane '{}' (expected a plane or \"auto\")", keyword)),
+            SymmetryPlaneInput::Plane(plane) => Ok(SymmetryPlane::Fixed(plane)),
+        }
+    }
+}
+impl From<SymmetryPlane> for SymmetryPlaneInput {
+    fn from(symmetry_plane: SymmetryPlane) -> Self {
+        match symmetry_plane {
+            SymmetryPlane::Auto => SymmetryPlaneInput::Keyword("auto".to_string()),
+            SymmetryPlane::Fixed(plane) => SymmetryPlaneInput::Plane(plane),
+        }
+    }
+}
+
+/// Sparse intersection data between coils: the vertex indices of a coil near another coil's outline,
+/// keyed by (coil, other coil), with entries only for pairs that intersect.
+/// Ordered by key, so iteration is deterministic.
+pub type Intersections = std::collections::BTreeMap<(usize, usize), Vec<usize>>;
+
+/// Get the distance from each coil center to its farthest vertex.
+pub fn bounding_radii(layout_out: &layout::Layout) -> Vec<f32> {
+    layout_out.coils.iter().map(|coil| {
+        coil.vertices.iter().fold(0.0, |max: f32, vertex| max.max((vertex.point - coil.center).norm()))
+    }).collect()
+}
+
+/// Find coils nested entirely inside other coils.
+/// Returns (outer, inner) coil index pairs.
+pub fn nested_pairs(layout_out: &layout::Layout) -> Vec<(usize, usize)> {
+    let mut pairs = Vec::new();
+    for (outer_id, outer) in layout_out.coils.iter().enumerate() {
+        for (inner_id, inner) in layout_out.coils.iter().enumerate() {
+            if outer_id != inner_id && outer.contains(inner) {
+                pairs.push((outer_id, inner_id));
+            }
+        }
+    }
+    pairs
+}
+
+/// Small seeded pseudo-random number generator (SplitMix64), for reproducible perturbations.
+#[derive(Debug, Clone)]
+pub struct SeededRng {
+    state: u64,
+}
+impl SeededRng {
+    pub fn new(seed: u64) -> Self {
+        SeededRng{state: seed}
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform value in [0, 1).
+    pub fn next_f32(&mut self) -> f32 {
+        (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    /// Uniform vector in the unit ball.
+    pub fn unit_ball_vector(&mut self) -> GeoVector {
+        loop {
+            let vector = GeoVector::new(
+                2.0 * self.next_f32() - 1.0,
+                2.0 * self.next_f32() - 1.0,
+                2.0 * self.next_f32() - 1.0,
+            );
+            if vector.norm_sq() <= 1.0 {
+                return vector;
+            }
+        }
+    }
+}
+
+/// Generate a circular coil on a surface, using the chosen projection mode.
+/// The center is expected to already be on the surface.
+/// Sphere-intersected coils are upsampled to at least `min_vertices` vertices (see `clean_coil_by_angle`).
+#[allow(clippy::too_many_arguments)]
+pub fn circle_coil(
+    surface: &Surface,
+    center: Point,
+    radius: f32,
+    wire_radius: f32,
+    epsilon: f32,
+    pre_shift: PreShift,
+    min_vertices: usize,
+    projection_mode: ProjectionMode,
+) -> layout::ProcResult<layout::Coil> {
+    let _timer = timing::phase("Laying out coils");
+
+    // The sphere intersection also finds the closest surface vertex, used for the coil normal
+    let (cid, points, point_normals) = sphere_intersect(surface, center, radius, epsilon);
+    let coil_normal = surface.vertices[cid].normal.normalize();
+
+    match projection_mode {
+        ProjectionMode::SphereIntersect => clean_coil_by_angle(
+            center,
+            coil_normal,
+            radius,
+            wire_radius,
+            points,
+            point_normals,
+            pre_shift,
+            min_vertices,
+            false
+        ),
+        ProjectionMode::PlanarDisc => {
+            let (points, point_normals) = planar_disc_project(surface, center, coil_normal, radius);
+            layout::Coil::new(center, coil_normal, points, wire_radius, point_normals)
+        },
+        ProjectionMode::GeodesicCircle => {
+            let (points, point_normals) = geodesic_circle(surface, center, coil_normal, radius)?;
+            layout::Coil::new(center, coil_normal, points, wire_radius, point_normals)
+        },
+    }
+}
+
+/// Generate a circle in the plane through `center` with the given normal, then project each point onto the surface.
+/// The point count is chosen from the mean edge length of the surface, so the coil is at least as fine as the mesh.
+/// Points are ordered by angle in the same direction as `clean_coil_by_angle`.
+/// Returns the projected points and the normals of the closest surface vertices.
+pub fn planar_disc_project(
+    surface: &Surface,
+    center: Point,
+    normal: GeoVector,
+    radius: f32,
+) -> (Vec::<Point>, Vec::<GeoVector>) {
+    let normal = normal.normalize();
+    let zhat = GeoVector::zhat();
+    let zero_theta_vec = if normal.dot(&zhat).abs() < 0.999 {
+        zhat.rej_onto(&normal).normalize()
+    } else {
+        GeoVector::yhat().rej_onto(&normal).normalize()
+    };
+    let pi2_theta_vec = zero_theta_vec.cross(&normal).normalize();
+
+    let mean_edge_length = surface.edges.iter()
+        .map(|e| surface.vertices[e.vertices[0]].point.distance(&surface.vertices[e.vertices[1]].point))
+        .sum::<f32>() / surface.edges.len().max(1) as f32;
+    let point_count = if mean_edge_length > 0.0 {
+        ((2.0 * PI * radius / mean_edge_length).ceil() as usize).max(16)
+    } else {
+        16
+    };
+
+    let mut points = Vec::<Point>::with_capacity(point_count);
+    let mut point_normals = Vec::<GeoVector>::with_capacity(point_count);
+    for i in 0..point_count {
+        let theta = 2.0 * PI * i as Angle / point_count as Angle;
+        let disc_point = center + radius * (zero_theta_vec * theta.cos() + pi2_theta_vec * theta.sin());
+        let point = disc_point - (&disc_point - surface);
+        points.push(point);
+        point_normals.push(surface.vertices[point.nearest_point_idx(surface)].normal.normalize());
+    }
+
+    (points, point_normals)
+}
+
+/// Stretch a coil along an axis in its tangent plane, then project the points back onto the surface.
+/// The component of each point's offset from the center along `axis` is scaled by `scale`.
+pub fn stretch_coil(coil: &mut layout::Coil, surface: &Surface, axis: GeoVector, scale: f32) {
+    let axis = axis.rej_onto(&coil.normal).normalize();
+    for vertex in coil.vertices.iter_mut() {
+        let along = (vertex.point - coil.center).dot(&axis);
+        let point = vertex.point + axis * (along * (scale - 1.0));
+        vertex.point = point - (&point - surface);
+        vertex.surface_normal = surface.vertices[vertex.point.nearest_point_idx(surface)].normal.normalize();
+        vertex.wire_radius_normal = vertex.surface_normal;
+    }
+}
+
+/// Heap entry for the geodesic distance field (min-heap on distance).
+#[derive(Debug, Clone, Copy)]
+struct DistanceEntry {
+    distance: f32,
+    vertex_id: usize,
+}
+impl PartialEq for DistanceEntry {
+    fn eq(&self, other: &Self) -> bool {
+        self.distance.total_cmp(&other.distance).is_eq()
+    }
+}
+impl Eq for DistanceEntry {}
+impl PartialOrd for DistanceEntry {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+impl Ord for DistanceEntry {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        other.distance.total_cmp(&self.distance)
+    }
+}
+
+/// Calculate the geodesic (along-surface) distance from a point on the surface to every vertex.
+/// Uses Dijkstra ordering with a fast-marching style update across each triangle,
+/// so distances are not restricted to paths along the mesh edges.
+/// Vertices further than `max_distance` are left at infinity.
+pub fn geodesic_distances(surface: &Surface, center: Point, max_distance: f32) -> Vec<f32> {
+    let mut distances = vec![f32::INFINITY; surface.vertices.len()];
+    let mut finalized = vec![false; surface.vertices.len()];
+    let mut heap = std::collections::BinaryHeap::<DistanceEntry>::new();
+
+    if surface.vertices.is_empty() {
+        return distances;
+    }
+
+    // Seed the closest vertex and its neighbors with their straight-line distances
+    let cid = center.nearest_point_idx(surface);
+    let mut seeds = vec![cid];
+    for edge_id in surface.vertices[cid].adj_edges.iter() {
+        seeds.extend_from_slice(&surface.edges[*edge_id].vertices);
+    }
+    for vertex_id in seeds.into_iter() {
+        let distance = surface.vertices[vertex_id].point.distance(&center);
+        if distance < distances[vertex_id] {
+            distances[vertex_id] = distance;
+            heap.push(DistanceEntry{distance, vertex_id});
+        }
+    }
+
+    // Distance to c through the triangle (a, b, c), from a virtual source unfolded into the triangle plane
+    let triangle_update = |a: usize, b: usize, c: usize, da: f32, db: f32| -> Option<f32> {
+        let pa = surface.vertices[a].point;
+        let pb = surface.vertices[b].point;
+        let pc = surface.vertices[c].point;
+        let ab = pb - pa;
+        let ab_len = ab.norm();
+        if ab_len <= 0.0 {
+            return None;
+        }
+
+        // Lay out the triangle in 2D, with a at the origin and b along the x axis
+        let cx = (pc - pa).dot(&ab) / ab_len;
+        let cy = (pc - pa).cross(&ab).norm() / ab_len;
+        let sx = (da * da - db * db + ab_len * ab_len) / (2.0 * ab_len);
+        let sy_sq = da * da - sx * sx;
+        if sy_sq < 0.0 {
+            return None;
+        }
+        let sy = -sy_sq.sqrt();
+
+        // The straight path from the source must pass through the edge ab
+        let t = -sy / (cy - sy);
+        let crossing_x = sx + t * (cx - sx);
+        if crossing_x < 0.0 || crossing_x > ab_len {
+            return None;
+        }
+        Some(((cx - sx) * (cx - sx) + (cy - sy) * (cy - sy)).sqrt())
+    };
+
+    while let Some(DistanceEntry{distance, vertex_id}) = heap.pop() {
+        if finalized[vertex_id] || distance > distances[vertex_id] {
+            continue;
+        }
+        finalized[vertex_id] = true;
+        if distance > max_distance {
+            break;
+        }
+
+        // Update the neighbors through each adjacent face
+        for face_id in surface.vertices[vertex_id].adj_faces.iter() {
+            let face = &surface.faces[*face_id];
+            for other_id in face.vertices.iter().filter(|v| **v != vertex_id) {
+                let other_id = *other_id;
+                if finalized[other_id] {
+                    continue;
+                }
+                let third_id = face.vertices.iter().find(|v| **v != vertex_id && **v != other_id).copied().unwrap();
+
+                let mut new_distance = distance + surface.vertices[vertex_id].point.distance(&surface.vertices[other_id].point);
+                if finalized[third_id] {
+                    if let Some(d) = triangle_update(vertex_id, third_id, other_id, distance, distances[third_id]) {
+                        new_distance = new_distance.min(d);
+                    }
+                }
+
+                if new_distance < distances[other_id] {
+                    distances[other_id] = new_distance;
+                    heap.push(DistanceEntry{distance: new_distance, vertex_id: other_id});
+                }
+            }
+        }
+    }
+
+    distances
+}
+
+/// Trace the geodesic circle (constant surface distance `radius` from `center`) on a surface.
+/// The loop is the `radius`-isocontour of the geodesic distance field, interpolated along the mesh edges.
+/// Points are ordered in the same direction as `clean_coil_by_angle` around `normal`.
+/// Returns the points and the interpolated surface normals.
+pub fn geodesic_circle(
+    surface: &Surface,
+    center: Point,
+    normal: GeoVector,
+    radius: f32,
+) -> layout::ProcResult<(Vec::<Point>, Vec::<GeoVector>)> {
+    let distances = geodesic_distances(surface, center, 2.0 * radius);
+    let inside = |vertex_id: usize| distances[vertex_id] < radius;
+
+    // Find the crossing point on each mesh edge that straddles the radius
+    let mut crossing_ids = std::collections::HashMap::<(usize, usize), usize>::new();
+    let mut crossing_faces = Vec::<Vec<usize>>::new();
+    let mut face_crossings = std::collections::HashMap::<usize, Vec<usize>>::new();
+    let mut points = Vec::<Point>::new();
+    let mut point_normals = Vec::<GeoVector>::new();
+    for (face_id, face) in surface.faces.iter().enumerate() {
+        for i in 0..3 {
+            let (v1, v2) = (face.vertices[i], face.vertices[(i + 1) % 3]);
+            if inside(v1) == inside(v2) {
+                continue;
+            }
+            let key = (v1.min(v2), v1.max(v2));
+            let crossing_id = *crossing_ids.entry(key).or_insert_with(|| {
+                let (d1, d2) = (distances[key.0], distances[key.1]);
+                let t = if d2.is_finite() { ((radius - d1) / (d2 - d1)).clamp(0.0, 1.0) } else { 0.0 };
+                let (s1, s2) = (&surface.vertices[key.0], &surface.vertices[key.1]);
+                points.push(s1.point + (s2.point - s1.point) * t);
+                point_normals.push((s1.normal * (1.0 - t) + s2.normal * t).normalize());
+                crossing_faces.push(Vec::new());
+                points.len() - 1
+            });
+            crossing_faces[crossing_id].push(face_id);
+            face_crossings.entry(face_id).or_default().push(crossing_id);
+        }
+    }
+
+    if points.len() < 3 {
+        return layout::err_str(&format!("Geodesic circle of radius {radius} around {center} does not cross the surface"));
+    }
+
+    // Chain the crossings into loops through the faces, keeping the longest loop
+    let mut visited = vec![false; points.len()];
+    let mut best_loop = Vec::<usize>::new();
+    for start_id in 0..points.len() {
+        if visited[start_id] {
+            continue;
+        }
+        let mut chain = vec![start_id];
+        visited[start_id] = true;
+        let mut face_id = crossing_faces[start_id][0];
+        let mut crossing_id = start_id;
+        loop {
+            let next_id = match face_crossings[&face_id].iter().find(|c| **c != crossing_id) {
+                Some(next_id) => *next_id,
+                None => return layout::err_str(&format!(
+                    "Geodesic circle of radius {radius} around {center} has a single crossing on face {face_id} (non-manifold surface?)")),
+            };
+            if next_id == start_id {
+                break;
+            }
+            if visited[next_id] {
+                return layout::err_str(&format!(
+                    "Geodesic circle of radius {radius} around {center} crosses itself (non-manifold surface?)"));
+            }
+            visited[next_id] = true;
+            chain.push(next_id);
+
+            face_id = match crossing_faces[next_id].iter().find(|f| **f != face_id) {
+                Some(next_face_id) => *next_face_id,
+                None => return layout::err_str(&format!(
+                    "Geodesic circle of radius {radius} around {center} reaches the surface boundary")),
+            };
+            crossing_id = next_id;
+        }
+        if chain.len() > best_loop.len() {
+            best_loop = chain;
+        }
+    }
+
+    let mut loop_points: Vec<Point> = best_loop.iter().map(|id| points[*id]).collect();
+    let mut loop_normals: Vec<GeoVector> = best_loop.iter().map(|id| point_normals[*id]).collect();
+
+    // Match the ordering of clean_coil_by_angle (clockwise around the normal)
+    let mut winding = 0.0;
+    for (i, point) in loop_points.iter().enumerate() {
+        let next_point = loop_points[(i + 1) % loop_points.len()];
+        winding += (*point - center).cross(&(next_point - center)).dot(&normal);
+    }
+    if winding > 0.0 {
+        loop_points.reverse();
+        loop_normals.reverse();
+    }
+
+    Ok((loop_points, loop_normals))
+}
+
 /// Clean a set of points by filtering
+/// If fewer than `min_vertices` points remain, more are interpolated by angle between neighbors,
+/// keeping the path of the coil but refining it for the inductance and meshing.
 #[allow(dead_code)]
 pub fn clean_coil_by_angle(
     center: Point,
@@ -64,7 +748,8 @@ pub fn clean_coil_by_angle(
     wire_radius: f32,
     mut points: Vec<Point>,
     point_normals: Vec<GeoVector>,
-    pre_shift: bool,
+    pre_shift: PreShift,
+    min_vertices: usize,
     verbose: bool,
 ) -> layout::ProcResult<layout::Coil> {
     if points.len() < 3 {
@@ -80,9 +765,21 @@ pub fn clean_coil_by_angle(
     let normal = normal.normalize();
 
     
+    // Shift the points (see `PreShift`)
+    if pre_shift == PreShift::Centroid {
+        let sum = points.iter().fold(GeoVector::zero(), |sum, point| sum + (*point - center));
+        let centroid_offset = (sum / points.len() as f32).rej_onto(&normal);
+        for point in points.iter_mut() {
+            *point += -centroid_offset;
+        }
+    }
+
     // Shift points along the surface tangent to the right radius
-    if pre_shift {
+    if pre_shift == PreShift::NormalAlign {
         for (point_id, point) in points.iter_mut().enumerate() {
+            if point.distance(&center) == 0.0 {
+                layout::err_str(&format!("Intersection point {} is at the coil center {} -- the coil is too small for the mesh", point_id, center))?;
+            }
             let vec_to_point = (*point - center).normalize();
             let radial_tangent = vec_to_point.rej_onto(&point_normals[point_id]).normalize();
             let r_err = radius - point.distance(&center);
@@ -301,20 +998,24 @@ pub fn clean_coil_by_angle(
 
     // Smooth the angles by averaging with neighbors
     // Smooth the normals as well
+    // Each pass averages both neighbors from the previous pass, so the result doesn't depend on where the loop starts
+    // or which way it runs (a mirror-symmetric point set stays mirror-symmetric)
     // TODO: Make smooth count a variable
     let smooth_count = 8;
     for _ in 0..smooth_count {
-        let mut prev_i = angles.len() - 1;
-        let mut next_i = 1;
+        let (prev_angles, prev_normals) = (angles.clone(), new_normals.clone());
         for i in 0..angles.len() {
+            let prev_i = (i + angles.len() - 1) % angles.len();
+            let next_i = (i + 1) % angles.len();
+
             // Grab the angles and normals
-            let mut angle_pair = angles[i];
-            let mut prev_angle_pair = angles[prev_i];
-            let mut next_angle_pair = angles[next_i];
+            let mut angle_pair = prev_angles[i];
+            let mut prev_angle_pair = prev_angles[prev_i];
+            let mut next_angle_pair = prev_angles[next_i];
 
-            let mut point_normal = new_normals[i];
-            let prev_normal = new_normals[prev_i];
-            let next_normal = new_normals[next_i];
+            let mut point_normal = prev_normals[i];
+            let prev_normal = prev_normals[prev_i];
+            let next_normal = prev_normals[next_i];
             
             // Account for angles that wrap around
             if prev_angle_pair.theta - angle_pair.theta > PI {
@@ -340,13 +1041,40 @@ pub fn clean_coil_by_angle(
             // Store the new angles and normals
             angles[i] = angle_pair;
             new_normals[i] = point_normal;
-
-            // Update the indices
-            prev_i = i;
-            next_i = (i + 1) % angles.len();
         } 
     }
 
+    // Upsample coarse coils, splitting every span evenly (the shorter way around in theta)
+    if angles.len() < min_vertices {
+        let span_splits = min_vertices.div_ceil(angles.len());
+        let mut upsampled_angles = Vec::<AngleFormat>::with_capacity(angles.len() * span_splits);
+        let mut upsampled_normals = Vec::<GeoVector>::with_capacity(angles.len() * span_splits);
+        for i in 0..angles.len() {
+            let next_i = (i + 1) % angles.len();
+            let (angle_pair, next_angle_pair) = (angles[i], angles[next_i]);
+            let mut dtheta = next_angle_pair.theta - angle_pair.theta;
+            if dtheta > PI {
+                dtheta -= 2.0 * PI;
+            } else if dtheta < -PI {
+                dtheta += 2.0 * PI;
+            }
+            let dphi = next_angle_pair.phi - angle_pair.phi;
+
+            for split in 0..span_splits {
+                let t = split as f32 / span_splits as f32;
+                upsampled_angles.push(AngleFormat{
+                    theta: angle_pair.theta + t * dtheta,
+                    phi: angle_pair.phi + t * dphi,
+                    point_id: angle_pair.point_id,
+                });
+                upsampled_normals.push((new_normals[i] * (1.0 - t) + new_normals[next_i] * t).normalize());
+            }
+        }
+        if verbose { println!("Upsampled from {} to {} points", angles.len(), upsampled_angles.len()) };
+        angles = upsampled_angles;
+        new_normals = upsampled_normals;
+    }
+
 
     // Reconstruct the coil
     let mut points = Vec::<Point>::new();
@@ -375,14 +1103,83 @@ pub fn clean_coil_by_angle(
     Ok(layout::Coil::new(center, normal, points, wire_radius, new_normals)?)
 }
 
-/// Add evenly distributed breaks to a coil by angle
+/// Largest |cosine| between a coil normal and a zero-angle vector for the vector to set the break angles.
+const ZERO_ANGLE_MAX_ALIGNMENT: f32 = 0.95;
+
+/// Pick the zero-angle reference for the breaks of a coil: the first of `candidates` (e.g. the configured and backup
+/// zero-angle vectors) not nearly parallel or antiparallel to the coil normal, normalized.
+/// If every candidate is too close to the normal (e.g. around a sphere), falls back to the coordinate axis most perpendicular
+/// to the normal, made perpendicular to it by Gram-Schmidt, so the break angles stay well-defined.
+pub fn zero_angle_reference(normal: &GeoVector, candidates: &[GeoVector]) -> GeoVector {
+    let normal = normal.normalize();
+    if let Some(candidate) = candidates.iter().map(|candidate| candidate.normalize())
+        .find(|candidate| !candidate.has_nan() && normal.dot(candidate).abs() < ZERO_ANGLE_MAX_ALIGNMENT) {
+        return candidate;
+    }
+    let axis = [GeoVector::xhat(), GeoVector::yhat(), GeoVector::zhat()].into_iter()
+        .min_by(|a, b| normal.dot(a).abs().partial_cmp(&normal.dot(b).abs()).unwrap())
+        .unwrap();
+    (axis - normal * normal.dot(&axis)).normalize()
+}
+
+/// Add evenly distributed breaks to a coil by angle.
+/// The first break is used as the port.
+/// Every break lands on a distinct vertex -- breaks whose angle bin has no vertex are nudged to the nearest free vertex.
+/// A `break_count` of 0 leaves an uninterrupted loop with no port or breaks.
+/// That's fine for the inductance and field calculations, but the GMSH mesher rejects coils without a port.
+/// Returns the vertex indices actually used (port first), or an error if the coil has fewer vertices than breaks.
 #[allow(dead_code)]
 pub fn add_even_breaks_by_angle(
     coil: &mut layout::Coil,
     break_count: usize,
     break_angle_offset: Angle,
     zero_angle_vec: GeoVector,
-) -> layout::ProcResult<()> {
+) -> layout::ProcResult<Vec<usize>> {
+    if break_count == 0 {
+        coil.port = None;
+        coil.breaks.clear();
+        return Ok(Vec::new());
+    }
+
+    let (binned_points, nudges) = even_break_vertices(coil, break_count, break_angle_offset, zero_angle_vec)?;
+    for (group, message) in nudges.iter() {
+        warnings::warn_grouped(group, message);
+    }
+
+    coil.breaks = Vec::<usize>::new();
+    coil.port = Some(binned_points[0]);
+    coil.breaks.extend(binned_points[1..].iter().cloned());
+
+    Ok(binned_points)
+}
+
+/// Get the vertex the port lands on when `add_even_breaks_by_angle` adds breaks to a coil, without changing the coil.
+/// Doesn't warn about nudged breaks, so it can be called on every layout pass.
+/// Returns `None` for a `break_count` of 0, or if the breaks can't be placed.
+pub fn even_break_port(
+    coil: &layout::Coil,
+    break_count: usize,
+    break_angle_offset: Angle,
+    zero_angle_vec: GeoVector,
+) -> Option<usize> {
+    if break_count == 0 {
+        return None;
+    }
+    let (binned_points, _) = even_break_vertices(coil, break_count, break_angle_offset, zero_angle_vec).ok()?;
+    Some(binned_points[0])
+}
+
+/// Binned point indices and the (group, message) warnings for the nudged bins.
+type AngleBins = (Vec<usize>, Vec<(String, String)>);
+
+/// Bin the vertices of a coil by angle for `add_even_breaks_by_angle`.
+/// Returns the binned vertex indices (port first) and the nudge warnings as (group, message) pairs.
+fn even_break_vertices(
+    coil: &layout::Coil,
+    break_count: usize,
+    break_angle_offset: Angle,
+    zero_angle_vec: GeoVector,
+) -> layout::ProcResult<AngleBins> {
     let center = coil.center;
     let axis = coil.normal;
     let points = &coil.vertices.iter().map(|v| v.point).collect::<Vec<Point>>();
@@ -393,17 +1190,19 @@ pub fn add_even_breaks_by_angle(
     }
     let offset_zero_angle_vec = zero_angle_vec.rotate_around(&axis, break_angle_offset);
 
-    let binned_points = bin_by_angle(points, break_count, center, axis, offset_zero_angle_vec)?;
-
-    coil.breaks = Vec::<usize>::new();
-    coil.port = Some(binned_points[0]);
-    coil.breaks.extend(binned_points[1..].iter().cloned());
-
-    Ok(())
+    bin_by_angle(points, break_count, center, axis, offset_zero_angle_vec)
 }
 
-/// Bin points by angle
-pub fn bin_by_angle(points: &Vec::<Point>, bin_count: usize, center: Point, axis: GeoVector, zero_angle_vec: GeoVector) -> layout::ProcResult<Vec::<usize>> {
+/// Bin points by angle, returning one distinct point per bin.
+/// Each bin takes the point closest to (and after) its starting angle.
+/// Empty bins are filled with the unused point closest in angle to the bin start.
+/// Returns the binned points and a (group, message) warning for each nudged bin, for the caller to report or drop.
+/// Errors if there are fewer points than bins.
+pub fn bin_by_angle(points: &Vec::<Point>, bin_count: usize, center: Point, axis: GeoVector, zero_angle_vec: GeoVector) -> layout::ProcResult<AngleBins> {
+
+    if points.len() < bin_count {
+        return layout::err_str(&format!("Not enough points ({}) for that many breaks ({}) -- breaks must be on distinct points", points.len(), bin_count));
+    }
 
     // Initialize the angle bins
     let angle_step: Angle = (2.0 * PI) / bin_count as Angle;
@@ -416,11 +1215,8 @@ pub fn bin_by_angle(points: &Vec::<Point>, bin_count: usize, center: Point, axis
     }
 
     // Iterate through points to bin
+    let mut angles = Vec::<Angle>::with_capacity(points.len());
     for (point_id, point) in points.iter().enumerate() {
-        if points.len() < bin_count {
-            layout::err_str(&format!("Not enough points ({}) for that many breaks ({})", points.len(), bin_count))?;
-        }
-        
         // Calculate the angles
 
         // Get the relevant vectors
@@ -432,6 +1228,7 @@ pub fn bin_by_angle(points: &Vec::<Point>, bin_count: usize, center: Point, axis
         if out_vec.cross(&zero_angle_vec).dot(&axis) < 0.0 && angle > 1e-6{
             angle = (2.0 * PI) - angle;
         }
+        angles.push(angle);
 
         // Bin the point
         let bin_id = (angle / angle_step) as usize;
@@ -445,89 +1242,103 @@ pub fn bin_by_angle(points: &Vec::<Point>, bin_count: usize, center: Point, axis
         }
     }
 
-    // Error if any bins are empty
-    if binned_points.iter().any(|id| id.is_none()) {
-        panic!("Math error: Angle binning (break count: {bin_count}) failed (no points within some bins)");
+    // Nudge empty bins to the closest unused point, so no two breaks share a point
+    let mut nudges = Vec::<(String, String)>::new();
+    let mut used = vec![false; points.len()];
+    for point_id in binned_points.iter().flatten() {
+        used[*point_id] = true;
+    }
+    for (bin_id, binned_point) in binned_points.iter_mut().enumerate() {
+        if binned_point.is_some() {
+            continue;
+        }
+        let bin_angle = bin_id as Angle * angle_step;
+        let angle_distance = |angle: Angle| {
+            let d = (angle - bin_angle).abs();
+            d.min(2.0 * PI - d)
+        };
+        let nudged_id = (0..points.len())
+            .filter(|point_id| !used[*point_id])
+            .min_by(|a, b| angle_distance(angles[*a]).total_cmp(&angle_distance(angles[*b])))
+            .expect("BUG! helper::bin_by_angle: Ran out of points after checking the point count");
+        nudges.push(("Break bin with no point nudged".to_string(), format!("No point within break bin {} ({:.1} deg), nudged to point {} ({:.1} deg)",
+            bin_id, bin_angle.to_degrees(), nudged_id, angles[nudged_id].to_degrees())));
+        used[nudged_id] = true;
+        *binned_point = Some(nudged_id);
     }
 
     // Unwrap the points
-    Ok(binned_points.iter().map(|id| id.unwrap()).collect())
+    Ok((binned_points.iter().map(|id| id.unwrap()).collect(), nudges))
 }
 
-/// Merge two segments of a coil
-/// Returns whether the first segment is used for the start and the end, respectively
+/// Merge two overlapping segments of a coil.
+/// Segments are inclusive ranges of vertex indices around the closed coil, and a segment with `end < start` wraps past index 0.
+/// Segments that share at least one vertex overlap (sharing just an endpoint counts, being adjacent doesn't).
+/// Returns `None` if the segments don't overlap.
+/// Otherwise returns whether the first segment is used for the start and the end of the merged segment, respectively,
+/// so the merged segment covers exactly the vertices of both.
+/// When both segments give the same start (or end), the first segment is used, so the result doesn't depend on input order.
+/// If a wrapping segment and the other together cover the whole coil, the merged range may not represent it (the vertex count isn't known here):
+/// the merge takes the wrapping segment's start and the other segment's end (if both wrap, the smaller start and the larger end).
 pub fn merge_segments(first_start: usize, first_end: usize, second_start: usize, second_end: usize) -> Option::<(bool, bool)> {
 
-    Some(
-        match (first_end < first_start, second_end < second_start) {
-            (true, true) => { // Both wrap
-                match (first_start <= second_start, first_end <= second_end) {
-                    (true, true) => (true, false),
-                    (true, false) => (true, true),
-                    (false, true) => (false, false),
-                    (false, false) => (false, true),
-                }
-            },
-            (true, false) => { // First wraps
-                if first_start <= second_start {
-                    (true, true)
-                }
-                else if first_end >= second_end {
-                    (true, true)
-                }
-                else if first_end >= second_start {
-                    (true, false)
-                }
-                else if first_start <= second_end {
-                    (false, true)
-                }
-                else {
+    Some(match (first_end < first_start, second_end < second_start) {
+        (true, true) => { // Both wrap
+            (first_start <= second_start, first_end >= second_end)
+        },
+        (true, false) => { // First wraps
+            if first_start <= second_start || first_end >= second_end {
+                (true, true)
+            }
+            else if first_end >= second_start {
+                (true, false)
+            }
+            else if first_start <= second_end {
+                (false, true)
+            }
+            else {
+                return None; // No intersection
+            }
+        },
+        (false, true) => { // Second wraps
+            if second_start < first_start || second_end > first_end {
+                (false, false)
+            }
+            else if second_end >= first_start {
+                (false, true)
+            }
+            else if second_start <= first_end {
+                (true, false)
+            }
+            else {
+                return None; // No intersection
+            }
+        },
+        (false, false) => { // Neither wrap
+            if first_start <= second_start {
+                if first_end < second_start {
                     return None; // No intersection
                 }
-            },
-            (false, true) => { // Second wraps
-                if second_start <= first_start {
-                    (false, false)
-                }
-                else if second_end >= first_end {
-                    (false, false)
-                }
-                else if second_end >= first_start {
-                    (false, true)
-                }
-                else if second_start <= first_end {
+                else if first_end < second_end {
                     (true, false)
                 }
                 else {
+                    (true, true)
+                }
+            }
+            else {
+                if second_end < first_start {
                     return None; // No intersection
                 }
-            },
-            (false, false) => { // Neither wrap
-                if first_start <= second_start {
-                    if first_end < second_start {
-                        return None; // No intersection
-                    }
-                    else if first_end <= second_end {
-                        (true, false)
-                    }
-                    else {
-                        (true, true)
-                    }
+                else if second_end <= first_end {
+                    (false, true)
                 }
                 else {
-                    if second_end < first_start {
-                        return None; // No intersection
-                    }
-                    else if second_end <= first_end {
-                        (false, true)
-                    }
-                    else {
-                        (false, false)
-                    }
+                    (false, false)
                 }
-            },
-        }
-    )
+            }
+        },
+    })
 }
 
 pub fn k_means(points: &Vec<Point>, k: usize, max_iter: usize, verbose: bool) -> Vec<Point> {
@@ -622,18 +1433,199 @@ pub fn k_means_initialized(points: &Vec<Point>, starting_centers: &Vec<Point>, m
     centers
 }
 
-/// Get the closest point in a collection of points
-pub fn closest_point<'a>(point: &Point, points: &'a Vec::<Point>) -> &'a Point {
-    let mut closest = &points[0];
-    let mut closest_distance = (point - closest).norm();
+/// Distance metric for finding the closest boundary point to a coil center.
+/// `Euclidean` measures straight through space, and `Tangent` drops the component along the surface normal at the center,
+/// so a center is matched to the boundary along the surface rather than through space, e.g. where the boundary curves away.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DistanceMetric {
+    #[default]
+    Euclidean,
+    Tangent,
+}
+impl DistanceMetric {
+    /// Get the vector from `from` to `to` under the metric, with `normal` the surface normal at `from`.
+    pub fn vector(&self, from: &Point, to: &Point, normal: &GeoVector) -> GeoVector {
+        let vector = *to - *from;
+        match self {
+            DistanceMetric::Euclidean => vector,
+            DistanceMetric::Tangent => vector.rej_onto(normal),
+        }
+    }
+
+    /// Get the distance from `from` to `to` under the metric, with `normal` the surface normal at `from`.
+    pub fn distance(&self, from: &Point, to: &Point, normal: &GeoVector) -> f32 {
+        self.vector(from, to, normal).norm()
+    }
+}
+
+/// Get the closest point in a collection of points by Euclidean distance, the first one on ties.
+/// Returns `None` if there are no points, e.g. the boundary of a closed surface,
+/// so the boundary handling of the circle methods is skipped there.
+pub fn closest_point<'a>(point: &Point, points: &'a Vec::<Point>) -> Option<&'a Point> {
+    closest_point_by(point, points, |a, b| (a - b).norm())
+}
+
+/// Get the closest point in a collection of points by the given distance, the first one on ties (see `closest_point`).
+pub fn closest_point_by<'a>(point: &Point, points: &'a [Point], distance: impl Fn(&Point, &Point) -> f32) -> Option<&'a Point> {
+    let mut closest = points.first()?;
+    let mut closest_distance = distance(point, closest);
     for test_point in points.iter().skip(1) {
-        let distance = (point - test_point).norm();
+        let distance = distance(point, test_point);
         if distance < closest_distance {
             closest = test_point;
             closest_distance = distance;
         }
     }
-    closest
+    Some(closest)
+}
+
+/// Check that the circles being updated match the coils laid out from them.
+/// Returns an error with both counts on a mismatch.
+pub fn check_coil_count(circle_count: usize, coil_count: usize) -> layout::ProcResult<()> {
+    if circle_count != coil_count {
+        return layout::err_str(&format!("Coil count mismatch: updating {} circles, but the layout has {} coils", circle_count, coil_count));
+    }
+    Ok(())
+}
+
+/// Check that the config has at least one circle to lay out.
+pub fn check_circle_count(circle_count: usize) -> layout::ProcResult<()> {
+    if circle_count == 0 {
+        return layout::err_str("No circles to lay out -- the config needs at least one circle");
+    }
+    Ok(())
+}
+
+/// Get the RMS coupling from the objective (the sum of squared couplings) over the close coil pairs.
+/// Without any close pairs (e.g. a single coil) there's no coupling, so this is 0 rather than NaN.
+pub fn rms_coupling(objective: f32, close_coils: usize) -> f32 {
+    if close_coils == 0 {
+        return 0.0;
+    }
+    (objective / close_coils as f32).sqrt()
+}
+
+/// Largest sensible ratio of the sphere intersection `epsilon` to the mean surface edge length (see `check_epsilon`).
+const EPSILON_MAX_EDGE_RATIO: f32 = 4.0;
+
+/// Check the sphere intersection `epsilon` against the surface edge lengths before laying out, and warn on a mismatch.
+/// Below the mean edge length, the intersection band can miss vertices and leave gaps or empty loops;
+/// far above it, the band grabs several rings of vertices and smears the coil.
+/// The edge length statistics are only printed if `verbose` is set; the warnings always are.
+pub fn check_epsilon(surface: &Surface, epsilon: f32, verbose: bool) {
+    let (min, mean, max) = surface.edge_length_stats();
+    if verbose {
+        println!("Surface edge lengths: min {:.3}, mean {:.3}, max {:.3} (epsilon {})", min, mean, max, epsilon);
+    }
+    if epsilon < mean {
+        warnings::warn(&format!("Epsilon {} is smaller than the mean edge length {:.3} -- coils may be under-sampled or empty", epsilon, mean));
+    } else if epsilon > EPSILON_MAX_EDGE_RATIO * mean {
+        warnings::warn(&format!("Epsilon {} is more than {} times the mean edge length {:.3} -- coils may grab too many vertices",
+            epsilon, EPSILON_MAX_EDGE_RATIO, mean
+        ));
+    }
+}
+
+/// Check the wire parameters before laying out: the wire must be thinner than every coil, and the clearance can't be negative.
+/// A typo like a wire radius of 6.45 instead of 0.645 otherwise only fails later, cryptically, in the mesher.
+pub fn check_wire_params(wire_radius: f32, clearance: f32, coil_radii: &[f32]) -> layout::ProcResult<()> {
+    if clearance < 0.0 {
+        return layout::err_str(&format!("Clearance must not be negative, got {}", clearance));
+    }
+    for (circle_id, coil_radius) in coil_radii.iter().enumerate() {
+        if wire_radius >= *coil_radius {
+            return layout::err_str(&format!("Wire radius ({}) must be smaller than the coil radius of circle {} ({})", wire_radius, circle_id, coil_radius));
+        }
+    }
+    Ok(())
+}
+
+/// Check the symmetric circle groups (on-plane, positive, negative) against the coils laid out from them.
+/// Returns an error naming the grouping that diverged.
+pub fn check_sym_groups(sym_count: usize, pos_count: usize, neg_count: usize, coil_count: usize) -> layout::ProcResult<()> {
+    if pos_count != neg_count {
+        return layout::err_str(&format!("Symmetric grouping diverged: {} positive circles, but {} negative (reflected) circles", pos_count, neg_count));
+    }
+    let circle_count = sym_count + pos_count + neg_count;
+    if circle_count != coil_count {
+        return layout::err_str(&format!("Symmetric grouping diverged: {} on-plane + {} positive + {} negative = {} circles, but the layout has {} coils",
+            sym_count, pos_count, neg_count, circle_count, coil_count));
+    }
+    Ok(())
+}
+
+/// Get the shared center of a symmetric coil pair, in the positive side's frame.
+/// The negative center is reflected across the symmetry plane, then the two are averaged,
+/// weighted by each side's freedom at the boundary:
+/// a side pinned at the boundary can't move outward, so it takes the full weight against a free side.
+/// If both or neither side is pinned, this is the plain mean.
+pub fn sym_pair_center(
+    pos_center: Point,
+    neg_center: Point,
+    symmetry_plane: &Plane,
+    pos_on_boundary: bool,
+    neg_on_boundary: bool,
+) -> Point {
+    let pos_weight = match (pos_on_boundary, neg_on_boundary) {
+        (true, false) => 1.0,
+        (false, true) => 0.0,
+        _ => 0.5,
+    };
+    let reflected_neg = GeoVector::from(neg_center.reflect_across(symmetry_plane));
+    (GeoVector::from(pos_center) * pos_weight + reflected_neg * (1.0 - pos_weight)).into()
+}
+
+/// Check whether a coil on the symmetry plane is one-sided:
+/// fewer than half as many vertices on one side of the plane as on the other.
+/// Vertices within `tolerance` of the plane count for neither side.
+pub fn is_one_sided(coil: &layout::Coil, symmetry_plane: &Plane, tolerance: f32) -> bool {
+    let distances = coil.vertices.iter().map(|vertex| symmetry_plane.distance_to_point(&vertex.point));
+    let (pos_count, neg_count) = distances.fold((0, 0), |(pos_count, neg_count), distance| {
+        (pos_count + (distance > tolerance) as usize, neg_count + (distance < -tolerance) as usize)
+    });
+    2 * pos_count.min(neg_count) < pos_count.max(neg_count)
+}
+
+/// Rebuild a one-sided coil on the symmetry plane (see `is_one_sided`) from its fuller side and that side's reflection.
+/// The vertices on the fuller side (or within `tolerance` of the plane) are kept as an arc, starting after the widest gap
+/// between consecutive kept vertices, where the loop ran off the other side. The reflected arc then closes the loop.
+/// The center is projected onto the plane, and the normal loses its component across the plane.
+/// Returns a `ProcResult` with the completed coil, or an `Err` if it can't be rebuilt.
+pub fn mirror_complete(coil: &layout::Coil, symmetry_plane: &Plane, tolerance: f32) -> layout::ProcResult<layout::Coil> {
+    let plane_normal = symmetry_plane.get_normal();
+    let pos_sum = coil.vertices.iter().map(|vertex| symmetry_plane.distance_to_point(&vertex.point).signum()).sum::<f32>();
+    let side = if pos_sum >= 0.0 { 1.0 } else { -1.0 };
+    let kept: Vec<&layout::CoilVertex> = coil.vertices.iter()
+        .filter(|vertex| side * symmetry_plane.distance_to_point(&vertex.point) >= -tolerance)
+        .collect();
+    if kept.len() < 2 {
+        return layout::err_str("Too few vertices to mirror-complete a one-sided coil");
+    }
+
+    // Start the arc after the widest gap
+    let start = (0..kept.len())
+        .max_by(|a, b| {
+            let gap = |id: usize| kept[id].point.distance(&kept[(id + 1) % kept.len()].point);
+            gap(*a).partial_cmp(&gap(*b)).unwrap()
+        })
+        .map_or(0, |widest| (widest + 1) % kept.len());
+    let arc: Vec<&layout::CoilVertex> = kept[start..].iter().chain(kept[..start].iter()).copied().collect();
+
+    let mut points: Vec<Point> = arc.iter().map(|vertex| vertex.point).collect();
+    let mut normals: Vec<GeoVector> = arc.iter().map(|vertex| vertex.surface_normal).collect();
+    for vertex in arc.iter().rev().filter(|vertex| !symmetry_plane.contains_point(&vertex.point, tolerance)) {
+        points.push(vertex.point.reflect_across(symmetry_plane));
+        normals.push(vertex.surface_normal.reflect_across(&plane_normal));
+    }
+
+    let normal = coil.normal.rej_onto(&plane_normal);
+    let normal = if normal.norm() > 0.0 { normal.normalize() } else { coil.normal };
+    let mut completed = layout::Coil::new(symmetry_plane.project_point(&coil.center), normal, points, coil.wire_radius, normals)?;
+    completed.label = coil.label.clone();
+    completed.group = coil.group;
+    Ok(completed)
 }
 
 mod debug {
@@ -652,3 +1644,367 @@ mod debug {
         println!("]");
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn merge_segments_matches_vertex_union() {
+        // Every pair of segments on an 8 vertex coil, checked against the union of their vertex sets
+        let vertex_count = 8;
+        let vertices = |start: usize, end: usize| -> Vec<bool> {
+            let mut covered = vec![false; vertex_count];
+            let mut v = start;
+            loop {
+                covered[v] = true;
+                if v == end { break; }
+                v = (v + 1) % vertex_count;
+            }
+            covered
+        };
+        for first_start in 0..vertex_count { for first_end in 0..vertex_count {
+            for second_start in 0..vertex_count { for second_end in 0..vertex_count {
+                // Skip ranges covering the whole coil, which aren't segments
+                if (first_end + 1) % vertex_count == first_start || (second_end + 1) % vertex_count == second_start {
+                    continue;
+                }
+                let first = vertices(first_start, first_end);
+                let second = vertices(second_start, second_end);
+                let union: Vec<bool> = first.iter().zip(second.iter()).map(|(a, b)| *a || *b).collect();
+                let overlaps = first.iter().zip(second.iter()).any(|(a, b)| *a && *b);
+                let case = format!("[{}, {}] with [{}, {}]", first_start, first_end, second_start, second_end);
+
+                let merged = merge_segments(first_start, first_end, second_start, second_end);
+                if !overlaps {
+                    assert!(merged.is_none(), "{} should not merge", case);
+                    continue;
+                }
+                let (first_starts, first_ends) = merged.unwrap_or_else(|| panic!("{} should merge", case));
+                let start = if first_starts { first_start } else { second_start };
+                let end = if first_ends { first_end } else { second_end };
+
+                // Without the vertex count, a whole-coil union past index 0 can't be one segment
+                let first_wraps = first_end < first_start;
+                let second_wraps = second_end < second_start;
+                if vertices(start, end) != union && union.iter().all(|covered| *covered) && (first_wraps || second_wraps) {
+                    let expected = match (first_wraps, second_wraps) {
+                        (true, true) => (first_start.min(second_start), first_end.max(second_end)),
+                        (true, false) => (first_start, second_end),
+                        _ => (second_start, first_end),
+                    };
+                    assert_eq!((start, end), expected, "{} covers the whole coil", case);
+                    continue;
+                }
+                assert_eq!(vertices(start, end), union, "{} merged to [{}, {}]", case, start, end);
+
+                // Ties go to the first segment
+                if first_start == second_start {
+                    assert!(first_starts, "{}", case);
+                }
+                if first_end == second_end {
+                    assert!(first_ends, "{}", case);
+                }
+            }}
+        }}
+    }
+
+    #[test]
+    fn merge_segments_cases() {
+        // Disjoint, adjacent, touching
+        assert_eq!(merge_segments(2, 4, 7, 9), None);
+        assert_eq!(merge_segments(2, 4, 5, 9), None);
+        assert_eq!(merge_segments(2, 4, 4, 9), Some((true, false)));
+        // Nested, either way round
+        assert_eq!(merge_segments(2, 9, 4, 6), Some((true, true)));
+        assert_eq!(merge_segments(4, 6, 2, 9), Some((false, false)));
+        // Wrapping past index 0
+        assert_eq!(merge_segments(8, 2, 1, 5), Some((true, false)));
+        assert_eq!(merge_segments(1, 5, 8, 2), Some((false, true)));
+        assert_eq!(merge_segments(8, 2, 9, 1), Some((true, true)));
+        assert_eq!(merge_segments(8, 2, 4, 6), None);
+        // Reaching all the way around
+        assert_eq!(merge_segments(8, 2, 2, 8), Some((true, false)));
+    }
+
+    #[test]
+    fn wire_params_are_checked() {
+        assert!(check_wire_params(0.645, 0.1, &[5.0, 8.0]).is_ok());
+
+        let error = check_wire_params(6.45, 0.1, &[8.0, 5.0]).unwrap_err().to_string();
+        assert!(error.contains("Wire radius (6.45) must be smaller than the coil radius of circle 1 (5)"), "Unexpected error: {}", error);
+
+        let error = check_wire_params(0.645, -0.1, &[5.0]).unwrap_err().to_string();
+        assert!(error.contains("Clearance must not be negative"), "Unexpected error: {}", error);
+    }
+
+    #[test]
+    fn small_epsilon_on_coarse_mesh_warns() {
+        let surface = Surface::square_grid(40.0, 8);
+        let (min, mean, max) = surface.edge_length_stats();
+        assert!((min - 10.0).abs() < 1e-4 && (max - 10.0 * 2.0_f32.sqrt()).abs() < 1e-3, "Edge lengths {} to {}", min, max);
+        assert!(min < mean && mean < max);
+
+        let warnings_for = |epsilon: f32| {
+            warnings::clear();
+            check_epsilon(&surface, epsilon, false);
+            let summary = warnings::summary();
+            warnings::clear();
+            summary
+        };
+        let too_small = warnings_for(1.5);
+        assert_eq!(too_small.len(), 1);
+        assert!(too_small[0].0.starts_with("Epsilon 1.5 is smaller than the mean edge length"), "{:?}", too_small);
+        assert!(warnings_for(12.0).is_empty());
+        assert!(warnings_for(100.0)[0].0.contains("too many vertices"));
+    }
+
+    fn circle_coil_with(surface: &Surface, center: Point, projection_mode: ProjectionMode) -> layout::Coil {
+        circle_coil(surface, center, 10.0, 0.645, 1.5, PreShift::NormalAlign, 0, projection_mode).unwrap()
+    }
+
+    #[test]
+    fn projection_modes_match_on_flat_surface() {
+        let surface = Surface::square_grid(30.0, 60);
+        let center = Point::new(0.0, 0.0, 0.0);
+
+        let sphere_coil = circle_coil_with(&surface, center, ProjectionMode::SphereIntersect);
+        let disc_coil = circle_coil_with(&surface, center, ProjectionMode::PlanarDisc);
+
+        for coil in [&sphere_coil, &disc_coil] {
+            assert!((coil.average_radius() - 10.0).abs() < 0.1, "Average radius {}", coil.average_radius());
+            assert!(coil.vertices.iter().all(|v| v.point.z.abs() < 1e-4));
+        }
+        assert!((sphere_coil.wire_length() - disc_coil.wire_length()).abs() < 0.02 * disc_coil.wire_length());
+    }
+
+    #[test]
+    fn projection_modes_differ_on_sphere() {
+        let surface = Surface::sphere(Point::zero(), 20.0, 40);
+        let center = Point::new(0.0, 0.0, 20.0);
+
+        let sphere_coil = circle_coil_with(&surface, center, ProjectionMode::SphereIntersect);
+        let disc_coil = circle_coil_with(&surface, center, ProjectionMode::PlanarDisc);
+
+        // Projecting the tangent disc down onto the sphere pulls the points in towards the center
+        for vertex in disc_coil.vertices.iter() {
+            assert!((vertex.point.distance(&Point::zero()) - 20.0).abs() < 0.1, "{}", vertex.point);
+        }
+        assert!((sphere_coil.average_radius() - 10.0).abs() < 0.2, "Average radius {}", sphere_coil.average_radius());
+        assert!(sphere_coil.average_radius() - disc_coil.average_radius() > 0.5,
+            "Sphere radius {}, disc radius {}", sphere_coil.average_radius(), disc_coil.average_radius());
+    }
+
+    /// Flat grid bent upwards by 45 degrees along the line x = 0.
+    fn creased_grid() -> Surface {
+        let grid = Surface::square_grid(30.0, 60);
+        let points = grid.vertices.iter()
+            .map(|vertex| Point::new(vertex.point.x, vertex.point.y, vertex.point.x.max(0.0)))
+            .collect();
+        let triangles = grid.faces.iter().map(|face| face.vertices).collect();
+        Surface::from_triangles(points, triangles)
+    }
+
+    /// Distance from a point to the surface of `creased_grid`.
+    fn crease_offset(point: Point) -> f32 {
+        let flat = if point.x <= 0.0 { point.z.abs() } else { (point.x * point.x + point.z * point.z).sqrt() };
+        let bent = if point.x + point.z >= 0.0 { (point.z - point.x).abs() / 2.0_f32.sqrt() } else { (point.x * point.x + point.z * point.z).sqrt() };
+        flat.min(bent)
+    }
+
+    fn max_crease_offset(coil: &layout::Coil) -> f32 {
+        coil.vertices.iter().map(|vertex| crease_offset(vertex.point)).fold(0.0, f32::max)
+    }
+
+    #[test]
+    fn pre_shift_modes_differ_near_crease() {
+        let surface = creased_grid();
+        let center = Point::new(-6.0, 0.0, 0.0);
+        let coil_with = |pre_shift: PreShift| {
+            circle_coil(&surface, center, 10.0, 0.645, 1.5, pre_shift, 0, ProjectionMode::SphereIntersect).unwrap()
+        };
+
+        let unshifted = coil_with(PreShift::None);
+        let aligned = coil_with(PreShift::NormalAlign);
+        let centroid = coil_with(PreShift::Centroid);
+
+        // Sliding the intersected points along the surface keeps the rebuilt coil on the bent side of the crease
+        let unshifted_offset = max_crease_offset(&unshifted);
+        let aligned_offset = max_crease_offset(&aligned);
+        assert!(aligned_offset < 0.5 * unshifted_offset,
+            "Aligned offset {}, unshifted offset {}", aligned_offset, unshifted_offset);
+        assert!(centroid.vertices.iter().zip(unshifted.vertices.iter())
+            .any(|(shifted, plain)| shifted.point.distance(&plain.point) > 0.1));
+    }
+
+    #[test]
+    fn coarse_coil_is_upsampled_to_min_vertices() {
+        // Eight points around a flat circle, as from a very coarse mesh
+        let center = Point::zero();
+        let points: Vec<Point> = (0..8).map(|i| {
+            let theta = 2.0 * PI * i as f32 / 8.0;
+            Point::new(10.0 * theta.cos(), 10.0 * theta.sin(), 0.0)
+        }).collect();
+        let clean_with = |min_vertices: usize| {
+            clean_coil_by_angle(center, GeoVector::zhat(), 10.0, 0.645, points.clone(), vec![GeoVector::zhat(); 8], PreShift::NormalAlign, min_vertices, false).unwrap()
+        };
+
+        assert_eq!(clean_with(0).vertices.len(), 8);
+        let upsampled = clean_with(50);
+        assert!(upsampled.vertices.len() >= 50, "Upsampled to only {} vertices", upsampled.vertices.len());
+        for vertex in upsampled.vertices.iter() {
+            assert!((vertex.point.distance(&center) - 10.0).abs() < 1e-3, "Vertex {} is off the circle", vertex.point);
+            assert!(vertex.point.z.abs() < 1e-3);
+        }
+        assert!((upsampled.wire_length() - 2.0 * PI * 10.0).abs() < 0.01 * 2.0 * PI * 10.0);
+    }
+
+    #[test]
+    fn pre_shift_reads_bools_and_keywords() {
+        let read = |text: &str| serde_json::from_str::<PreShift>(text);
+        assert_eq!(read("true").unwrap(), PreShift::NormalAlign);
+        assert_eq!(read("false").unwrap(), PreShift::None);
+        assert_eq!(read("\"centroid\"").unwrap(), PreShift::Centroid);
+        assert_eq!(read("\"normal_align\"").unwrap(), PreShift::NormalAlign);
+        assert!(read("\"sideways\"").is_err());
+        assert_eq!(serde_json::to_string(&PreShift::Centroid).unwrap(), "\"centroid\"");
+    }
+
+    #[test]
+    fn geodesic_circle_has_uniform_surface_distance_on_sphere() {
+        let sphere_radius = 20.0;
+        let coil_radius = 15.0;
+        let surface = Surface::sphere(Point::zero(), sphere_radius, 60);
+        let center = Point::new(0.0, 0.0, sphere_radius);
+
+        // Exact great-circle distance from the pole
+        let surface_distance = |p: &Point| sphere_radius * (p.z / p.distance(&Point::zero())).clamp(-1.0, 1.0).acos();
+        let relative_errors = |coil: &layout::Coil| -> Vec<f32> {
+            coil.vertices.iter().map(|v| (surface_distance(&v.point) - coil_radius).abs() / coil_radius).collect()
+        };
+
+        let geodesic_coil = circle_coil(&surface, center, coil_radius, 0.645, 1.5, PreShift::NormalAlign, 0, ProjectionMode::GeodesicCircle).unwrap();
+        let sphere_coil = circle_coil(&surface, center, coil_radius, 0.645, 1.5, PreShift::NormalAlign, 0, ProjectionMode::SphereIntersect).unwrap();
+
+        let geodesic_errors = relative_errors(&geodesic_coil);
+        let sphere_errors = relative_errors(&sphere_coil);
+        let mean = |errors: &Vec<f32>| errors.iter().sum::<f32>() / errors.len() as f32;
+
+        assert!(geodesic_errors.iter().all(|e| *e < 0.02), "Max geodesic error {:?}", geodesic_errors.iter().cloned().fold(0.0, f32::max));
+        assert!(mean(&geodesic_errors) < mean(&sphere_errors),
+            "Geodesic mean error {}, sphere-intersect mean error {}", mean(&geodesic_errors), mean(&sphere_errors));
+    }
+
+    #[test]
+    fn geodesic_circle_errors_on_non_manifold_surface() {
+        // Flat hexagonal fan around the origin
+        let mut points = vec![Point::zero()];
+        points.extend((0..6).map(|i| {
+            let theta = std::f32::consts::PI * i as f32 / 3.0;
+            Point::new(10.0 * theta.cos(), 10.0 * theta.sin(), 0.0)
+        }));
+        let triangles = (0..6).map(|i| [0, 1 + i, 1 + (i + 1) % 6]).collect();
+        let mut surface = Surface::from_triangles(points, triangles);
+        assert!(geodesic_circle(&surface, Point::zero(), GeoVector::zhat(), 5.0).is_ok());
+
+        // A fin standing up on the first spoke makes it a third face on that edge
+        surface.vertices.push(SurfaceVertex::new_from_point(Point::new(5.0, 0.0, 10.0)));
+        let fin = [0, 1, surface.vertices.len() - 1];
+        let face_id = surface.faces.len();
+        surface.faces.push(SurfaceFace::new(fin, [0; 3], GeoVector::yhat(), 50.0));
+        for vertex_id in fin.iter() {
+            surface.vertices[*vertex_id].adj_faces.push(face_id);
+        }
+        surface.vertices[fin[2]].normal = GeoVector::yhat();
+
+        let error = geodesic_circle(&surface, Point::zero(), GeoVector::zhat(), 5.0).unwrap_err().to_string();
+        assert!(error.contains("non-manifold"), "{}", error);
+    }
+
+    /// Build a flat coil in the xy-plane with points at the given angles (in degrees)
+    fn coil_at_angles(angles_deg: &[f32]) -> layout::Coil {
+        let points = angles_deg.iter()
+            .map(|a| Point::new(10.0 * a.to_radians().cos(), 10.0 * a.to_radians().sin(), 0.0))
+            .collect::<Vec<Point>>();
+        let normals = vec![GeoVector::zhat(); points.len()];
+        layout::Coil::new(Point::zero(), GeoVector::zhat(), points, 0.645, normals).unwrap()
+    }
+
+    #[test]
+    fn breaks_land_on_distinct_vertices() {
+        // Only one point in the upper half, so two of the four bins are empty
+        let mut coil = coil_at_angles(&[0.0, 10.0, 20.0, 30.0, 40.0, 200.0]);
+        let breaks = add_even_breaks_by_angle(&mut coil, 4, 0.0, GeoVector::xhat()).unwrap();
+
+        assert_eq!(breaks.len(), 4);
+        let mut unique = breaks.clone();
+        unique.sort();
+        unique.dedup();
+        assert_eq!(unique.len(), 4, "Breaks {:?} are not distinct", breaks);
+        assert_eq!(coil.port, Some(breaks[0]));
+        assert_eq!(coil.breaks, breaks[1..]);
+    }
+
+    #[test]
+    fn more_breaks_than_vertices_is_an_error() {
+        let mut coil = coil_at_angles(&[0.0, 45.0, 90.0, 135.0, 180.0, 225.0, 270.0, 315.0]);
+        assert!(add_even_breaks_by_angle(&mut coil, 12, 0.0, GeoVector::xhat()).is_err());
+        assert!(coil.port.is_none());
+    }
+
+    #[test]
+    fn zero_breaks_leave_an_uninterrupted_loop() {
+        let mut coil = coil_at_angles(&[0.0, 45.0, 90.0, 135.0, 180.0, 225.0, 270.0, 315.0]);
+        add_even_breaks_by_angle(&mut coil, 4, 0.0, GeoVector::xhat()).unwrap();
+        assert!(add_even_breaks_by_angle(&mut coil, 0, 0.0, GeoVector::xhat()).unwrap().is_empty());
+        assert!(coil.port.is_none());
+        assert!(coil.breaks.is_empty());
+        assert!(coil.redistribute_breaks_by_length(0).unwrap().is_empty());
+        assert!(coil.port.is_none());
+    }
+
+    #[test]
+    fn sym_pair_respects_boundary_limited_side() {
+        // Positive side is pinned against a boundary at x = 0 with radius 5,
+        // negative side (reflected across y = 0) wants to sit past that boundary
+        let symmetry_plane = Plane::from_normal_and_offset(GeoVector::yhat(), 0.0);
+        let boundary_x = 0.0;
+        let radius = 5.0;
+        let pos_center = Point::new(boundary_x + radius, 10.0, 0.0);
+        let neg_center = Point::new(boundary_x + 2.0, -10.0, 0.0);
+
+        let center = sym_pair_center(pos_center, neg_center, &symmetry_plane, true, false);
+        assert!(center.x - boundary_x >= radius - 1e-6, "Shared center {} crosses the boundary limit", center);
+        assert!((center.y - 10.0).abs() < 1e-6);
+
+        // Pinned on the negative side instead, the negative side's limit holds after reflecting back
+        let center = sym_pair_center(neg_center, pos_center, &symmetry_plane, false, true);
+        assert!(center.reflect_across(&symmetry_plane).x - boundary_x >= radius - 1e-6);
+
+        // Unconstrained pairs fall back to the plain mean
+        let center = sym_pair_center(pos_center, neg_center, &symmetry_plane, false, false);
+        assert!((center.x - (boundary_x + 3.5)).abs() < 1e-6);
+    }
+
+    #[test]
+    fn tangent_metric_follows_the_surface_above_a_curved_boundary() {
+        // A center 10 mm above the surface plane, with a boundary that curves up from 3 mm away in the plane to level with it
+        let center = Point::new(0.0, 0.0, 10.0);
+        let normal = GeoVector::zhat();
+        let boundary_points: Vec<Point> = (0..=20).map(|i| {
+            let theta = std::f32::consts::PI * (1.0 - 0.5 * i as f32 / 20.0);
+            Point::new(13.0 + 10.0 * theta.cos(), 0.0, 10.0 * theta.sin())
+        }).collect();
+
+        // Through space, the nearest point is partway up the curve
+        let euclidean = closest_point(&center, &boundary_points).unwrap();
+        let by_metric = closest_point_by(&center, &boundary_points, |a, b| DistanceMetric::Euclidean.distance(a, b, &normal)).unwrap();
+        assert!(euclidean.approx_eq(by_metric, 1e-6));
+        assert!(euclidean.x > 4.0, "Euclidean closest point {:.2}", euclidean);
+
+        // Along the surface, it's the foot of the curve
+        let tangent = closest_point_by(&center, &boundary_points, |a, b| DistanceMetric::Tangent.distance(a, b, &normal)).unwrap();
+        assert!(tangent.approx_eq(&Point::new(3.0, 0.0, 0.0), 1e-4), "Tangent closest point {:.2}", tangent);
+        assert!((DistanceMetric::Tangent.distance(&center, tangent, &normal) - 3.0).abs() < 1e-4);
+    }
+}
@@ -6,13 +6,20 @@
 
 use crate::layout;
 use crate::geo_3d::*;
+use crate::warnings;
 use layout::methods;
 use methods::adam_circles::Method as AdamCirclesMethod;
 use methods::adam_circles::CircleArgs as Circle;
 use methods::helper::{
+    ProjectionMode,
+    CenterFreedomMode,
+    PreShift,
+    SymmetryPlane,
     k_means,
     k_means_initialized,
     closest_point,
+    check_wire_params,
+    check_circle_count,
 };
 
 use serde::{Serialize, Deserialize};
@@ -47,9 +54,15 @@ pub struct Method {
     #[serde(default = "Method::default_epsilon")]
     pub epsilon: f32,
     #[serde(default = "Method::default_pre_shift")]
-    pub pre_shift: bool,
+    pub pre_shift: PreShift,
+    #[serde(default = "Method::default_min_vertices")]
+    pub min_vertices: usize,
+    #[serde(default = "Method::default_projection_mode", alias = "projection")]
+    pub projection_mode: ProjectionMode,
 
     // Overlap handling parameters
+    #[serde(default = "Method::default_resolve_overlaps")]
+    pub resolve_overlaps: bool,
     #[serde(default = "Method::default_clearance")]
     pub clearance: f32,
     #[serde(default = "Method::default_wire_radius")]
@@ -58,6 +71,8 @@ pub struct Method {
     pub zero_angle_vector: GeoVector,
     #[serde(default = "Method::default_backup_zero_angle_vector")]
     pub backup_zero_angle_vector: GeoVector,
+    #[serde(default = "Method::default_port_keepout_radius", alias = "port_keepout")]
+    pub port_keepout_radius: f32,
 
     // Iteration parameters
     #[serde(default = "Method::default_iterations")]
@@ -74,6 +89,8 @@ pub struct Method {
     pub radius_freedom: f32,
     #[serde(default = "Method::default_center_freedom")]
     pub center_freedom: f32,
+    #[serde(default = "Method::default_center_freedom_mode")]
+    pub center_freedom_mode: CenterFreedomMode,
     #[serde(default = "Method::default_close_cutoff")]
     pub close_cutoff: f32,
 
@@ -123,10 +140,19 @@ impl Method {
     pub fn default_epsilon() -> f32 {
         1.5
     }
-    pub fn default_pre_shift() -> bool {
-        true
+    pub fn default_pre_shift() -> PreShift {
+        PreShift::default()
+    }
+    pub fn default_min_vertices() -> usize {
+        0
+    }
+    pub fn default_projection_mode() -> ProjectionMode {
+        ProjectionMode::SphereIntersect
     }
 
+    pub fn default_resolve_overlaps() -> bool {
+        true
+    }
     pub fn default_clearance() -> f32 {
         1.29
     }
@@ -139,6 +165,9 @@ impl Method {
     pub fn default_backup_zero_angle_vector() -> GeoVector {
         GeoVector::yhat()
     }
+    pub fn default_port_keepout_radius() -> f32 {
+        AdamCirclesMethod::default_port_keepout_radius()
+    }
 
     pub fn default_iterations() -> usize {
         0
@@ -158,6 +187,9 @@ impl Method {
     pub fn default_center_freedom() -> f32 {
         0.95
     }
+    pub fn default_center_freedom_mode() -> CenterFreedomMode {
+        CenterFreedomMode::default()
+    }
     pub fn default_radius_freedom() -> f32 {
         0.65
     }
@@ -196,17 +228,22 @@ impl Default for Method{
 
             epsilon: Self::default_epsilon(),
             pre_shift: Self::default_pre_shift(),
+            min_vertices: Self::default_min_vertices(),
+            projection_mode: Self::default_projection_mode(),
 
+            resolve_overlaps: Self::default_resolve_overlaps(),
             clearance: Self::default_clearance(),
             wire_radius: Self::default_wire_radius(),
             zero_angle_vector: Self::default_zero_angle_vector(),
             backup_zero_angle_vector: Self::default_backup_zero_angle_vector(),
+            port_keepout_radius: Self::default_port_keepout_radius(),
 
             iterations: Self::example_iterations(),
             step_size: Self::default_step_size(),
             first_moment_decay: Self::default_first_moment_decay(),
             second_moment_decay: Self::default_second_moment_decay(),
             center_freedom: Self::default_center_freedom(),
+            center_freedom_mode: Self::default_center_freedom_mode(),
             radius_freedom: Self::default_radius_freedom(),
             close_cutoff: Self::default_close_cutoff(),
             radius_reg: Self::default_radius_reg(),
@@ -227,8 +264,21 @@ impl methods::LayoutMethodTrait for Method {
         "K-means Isometric Circles"
     }
 
+    /// Whether the method was asked for verbose output.
+    fn is_verbose(&self) -> bool {
+        self.verbose
+    }
+
+    fn effective_args(&self) -> &dyn erased_serde::Serialize {
+        self
+    }
+
     fn do_layout(&self, surface: &Surface) -> layout::ProcResult<layout::Layout> {
 
+        // The coil radius isn't known until the centers are placed, it's checked against the wire after
+        check_wire_params(self.wire_radius, self.clearance, &[])?;
+        check_circle_count(self.initial_centers.as_ref().map_or(self.circles, |initial_centers| initial_centers.len()))?;
+
         let mut centers = Vec::<Point>::new();
         let mut radius = 5.0;
         let boundary_points = surface.get_boundary_vertex_indices().iter().map(|v| surface.vertices[*v].point).collect();
@@ -275,10 +325,11 @@ impl methods::LayoutMethodTrait for Method {
                 }
 
                 // Track distance to boundary for centers closer to the boundary than other centers
-                let boundary_point = *closest_point(&centers[i], &boundary_points);
-                if boundary_point.distance(&centers[i]) - boundary_trim < min_dist {
-                    boundary_dist += boundary_point.distance(&centers[i]);
-                    centers_near_boundary += 1;
+                if let Some(boundary_point) = closest_point(&centers[i], &boundary_points) {
+                    if boundary_point.distance(&centers[i]) - boundary_trim < min_dist {
+                        boundary_dist += boundary_point.distance(&centers[i]);
+                        centers_near_boundary += 1;
+                    }
                 }
 
                 // Calculate the average distance to nearby centers
@@ -322,6 +373,7 @@ impl methods::LayoutMethodTrait for Method {
 
         // Just display the centers if visualize
         radius = if self.visualize { 5.0 } else { radius };
+        check_wire_params(self.wire_radius, self.clearance, &[radius])?;
 
         // Save centers if requested
         if let Some(output_path) = &self.centers_output {
@@ -332,57 +384,84 @@ impl methods::LayoutMethodTrait for Method {
         // Map to circles
         let circles = if let Some(symmetry_plane) = &self.symmetry_plane {
             centers.iter()
-            .filter(|c| symmetry_plane.distance_to_point(c) >= -1e-6)
+            .filter(|c| symmetry_plane.distance_to_point(c) >= -GEO_TOLERANCE)
             .map(|c| Circle{
-                center: *c, 
-                coil_radius: radius, 
-                break_count: Circle::default_break_count(),
-                break_angle_offset: Circle::default_break_angle_offset(),
-                on_symmetry_plane: symmetry_plane.distance_to_point(c).abs() < 1e-6,
+                center: *c,
+                coil_radius: radius,
+                on_symmetry_plane: symmetry_plane.contains_point(c, GEO_TOLERANCE),
+                ..Circle::default()
             }).collect()
         } else {
             centers.iter().map(|c| Circle{
-                center: *c, 
-                coil_radius: radius, 
-                break_count: Circle::default_break_count(),
-                break_angle_offset: Circle::default_break_angle_offset(),
+                center: *c,
+                coil_radius: radius,
                 on_symmetry_plane: false,
+                ..Circle::default()
             }).collect()
         };
 
         let iterations = if self.visualize { 0 } else { self.iterations };
         if iterations != self.iterations {
-            println!("WARNING: Visualization mode enabled, setting iterations to 0.")
+            warnings::warn("Visualization mode enabled, setting iterations to 0.");
         }
 
         // Create method
         let method = AdamCirclesMethod{
-            symmetry_plane: self.symmetry_plane,
+            symmetry_plane: self.symmetry_plane.map(SymmetryPlane::Fixed),
             layout_in_path: None,
+            shield_plane: AdamCirclesMethod::default_shield_plane(),
 
             circles,
+            radius_mode: AdamCirclesMethod::default_radius_mode(),
+            max_coils: AdamCirclesMethod::default_max_coils(),
+            skip_failed_coils: AdamCirclesMethod::default_skip_failed_coils(),
             epsilon: self.epsilon,
             pre_shift: self.pre_shift,
+            min_vertices: self.min_vertices,
+            projection_mode: self.projection_mode,
+            curvature_threshold: AdamCirclesMethod::default_curvature_threshold(),
 
+            resolve_overlaps: self.resolve_overlaps,
             clearance: self.clearance,
+            min_bend_radius: AdamCirclesMethod::default_min_bend_radius(),
+            max_stack_offset: AdamCirclesMethod::default_max_stack_offset(),
             wire_radius: self.wire_radius,
             zero_angle_vector: self.zero_angle_vector,
             backup_zero_angle_vector: self.backup_zero_angle_vector,
+            port_keepout_radius: self.port_keepout_radius,
+            nesting_policy: AdamCirclesMethod::default_nesting_policy(),
+            one_sided_policy: AdamCirclesMethod::default_one_sided_policy(),
+            break_spacing: AdamCirclesMethod::default_break_spacing(),
 
             iterations: self.iterations,
             step_size: self.step_size,
             first_moment_decay: self.first_moment_decay,
             second_moment_decay: self.second_moment_decay,
             center_freedom: self.center_freedom,
+            center_freedom_mode: self.center_freedom_mode,
+            boundary_metric: AdamCirclesMethod::default_boundary_metric(),
+            allowed_centers: AdamCirclesMethod::default_allowed_centers(),
             radius_freedom: self.radius_freedom,
             close_cutoff: self.close_cutoff,
+            coupling_floor: AdamCirclesMethod::default_coupling_floor(),
+            lock_aspect: AdamCirclesMethod::default_lock_aspect(),
             radius_reg: self.radius_reg,
 
+            restarts: AdamCirclesMethod::default_restarts(),
+            jitter: AdamCirclesMethod::default_jitter(),
+            seed: AdamCirclesMethod::default_seed(),
+
+            coarse_stages: AdamCirclesMethod::default_coarse_stages(),
+            polish_iterations: AdamCirclesMethod::default_polish_iterations(),
+
             verbose: self.verbose,
             warn_on_shift: self.warn_on_shift,
             statistics_level: self.statistics_level,
+            larmor_mhz: AdamCirclesMethod::default_larmor_mhz(),
+            coupling_sign_check: AdamCirclesMethod::default_coupling_sign_check(),
 
             final_cfg_output: self.final_cfg_output.clone(),
+            trajectory_output: AdamCirclesMethod::default_trajectory_output(),
         };
 
         // Run method
@@ -543,3 +622,22 @@ impl Method {
     }
 
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::flat_surface;
+    use layout::LayoutMethodTrait;
+
+    #[test]
+    fn wire_thicker_than_the_placed_coils_is_rejected() {
+        let method = Method{
+            initial_centers: Some(vec![Point::new(-10.0, 0.0, 0.0), Point::new(10.0, 0.0, 0.0)]),
+            visualize: true,
+            wire_radius: 6.0,
+            ..Method::default()
+        };
+        let error = method.do_layout(&flat_surface()).unwrap_err().to_string();
+        assert!(error.contains("Wire radius (6) must be smaller than the coil radius"), "Unexpected error: {}", error);
+    }
+}
@@ -0,0 +1,120 @@
+use serde::Serialize;
+
+use crate::layout;
+use crate::geo_3d::*;
+
+/// Per-coil statistics of a layout.
+#[derive(Debug, Clone)]
+#[derive(Serialize)]
+pub struct CoilStatistics {
+    /// Average distance from the center to the wire, in mm.
+    pub radius: f32,
+    /// Coil center.
+    pub center: Point,
+    /// Wire length, in mm.
+    pub wire_length: f32,
+    /// Minimum bend radius, in mm.
+    pub min_bend_radius: f32,
+    /// Self-inductance, in nH.
+    pub self_inductance: f32,
+    /// Largest distance of the wire from the surface (see `Coil::max_liftoff`), in mm, if the surface is known.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_liftoff: Option<f32>,
+}
+
+/// Statistics of a layout, independent of the method that produced it.
+#[derive(Debug, Clone)]
+#[derive(Serialize)]
+pub struct LayoutStatistics {
+    /// Per-coil statistics, indexed like the layout coils.
+    pub coils: Vec<CoilStatistics>,
+    /// Mutual inductance (nH) and coupling factor of each close pair, as (coil, other coil, M, k).
+    pub close_pairs: Vec<(usize, usize, f32, f32)>,
+    /// The same for the pairs that are not close, only gathered if asked for (see `StatisticsOptions::all_pairs`).
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub far_pairs: Vec<(usize, usize, f32, f32)>,
+    /// RMS coupling factor over the close pairs.
+    pub rms_coupling: Option<f32>,
+    /// Maximum coupling factor magnitude over the close pairs.
+    pub max_coupling: Option<f32>,
+}
+
+/// Optional inputs of the layout statistics.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StatisticsOptions<'a> {
+    /// Surface to measure the lift-off of each coil against.
+    pub surface: Option<&'a Surface>,
+    /// Pairs beyond this cutoff use the cheaper dipole approximation (see `Coil::mutual_inductance_with_cutoff`).
+    /// Close pairs are well within any useful cutoff, so it only matters with `all_pairs`.
+    pub dipole_cutoff: Option<f32>,
+    /// Shield plane, for the shielded self-inductances and the coupling through the counter-wound shield images.
+    pub shield_plane: Option<&'a Plane>,
+    /// Also gather the coupling of the pairs that are not close.
+    pub all_pairs: bool,
+}
+impl StatisticsOptions<'_> {
+    /// Check the options: the dipole cutoff has to be positive.
+    /// Returns a `ProcResult` with `()` or an `Err` describing the problem.
+    pub fn check(&self) -> layout::ProcResult<()> {
+        if let Some(dipole_cutoff) = self.dipole_cutoff {
+            if dipole_cutoff.is_nan() || dipole_cutoff <= 0.0 {
+                return layout::err_str(&format!("Dipole cutoff must be positive, got {}", dipole_cutoff));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Gather the statistics of a layout.
+/// Coils are close if their centers are nearer than `close_cutoff` times the sum of their average radii,
+/// and the coupling sums are over close pairs only, as in the layout methods.
+pub fn statistics(layout_in: &layout::Layout, close_cutoff: f32, options: &StatisticsOptions) -> LayoutStatistics {
+    let coils: Vec<CoilStatistics> = layout_in.coils.iter().map(|coil| CoilStatistics{
+        radius: coil.average_radius(),
+        center: coil.center,
+        wire_length: coil.wire_length(),
+        min_bend_radius: coil.min_bend_radius(),
+        self_inductance: match options.shield_plane {
+            Some(shield_plane) => coil.shielded_self_inductance(shield_plane, 1.0),
+            None => coil.self_inductance(1.0),
+        },
+        max_liftoff: options.surface.map(|surface| coil.max_liftoff(surface)),
+    }).collect();
+
+    // Shield images, counter-wound
+    let images: Vec<Option<layout::Coil>> = layout_in.coils.iter()
+        .map(|coil| options.shield_plane.map(|shield_plane| coil.image_across(shield_plane)))
+        .collect();
+
+    let mut close_pairs = Vec::new();
+    let mut far_pairs = Vec::new();
+    for (coil_id, coil) in layout_in.coils.iter().enumerate() {
+        for (other_id, other_coil) in layout_in.coils.iter().enumerate().skip(coil_id + 1) {
+            let distance_scale = coils[coil_id].radius + coils[other_id].radius;
+            let close = (coil.center - other_coil.center).norm() / distance_scale < close_cutoff;
+            if !close && !options.all_pairs {
+                continue;
+            }
+
+            // Coupling includes the path through the other coil's shield image
+            let m = coil.mutual_inductance_with_cutoff(other_coil, 1.0, options.dipole_cutoff)
+                + images[other_id].as_ref().map_or(0.0, |image| coil.mutual_inductance_with_cutoff(image, 1.0, options.dipole_cutoff));
+            let k = m / (coils[coil_id].self_inductance * coils[other_id].self_inductance).sqrt();
+            if close {
+                close_pairs.push((coil_id, other_id, m, k));
+            } else {
+                far_pairs.push((coil_id, other_id, m, k));
+            }
+        }
+    }
+
+    let (rms_coupling, max_coupling) = if close_pairs.is_empty() {
+        (None, None)
+    } else {
+        let sum_sq = close_pairs.iter().map(|(_, _, _, k)| k * k).sum::<f32>();
+        let max_k = close_pairs.iter().fold(0.0, |max: f32, (_, _, _, k)| max.max(k.abs()));
+        (Some((sum_sq / close_pairs.len() as f32).sqrt()), Some(max_k))
+    };
+
+    LayoutStatistics{coils, close_pairs, far_pairs, rms_coupling, max_coupling}
+}
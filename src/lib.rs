@@ -4,9 +4,17 @@ pub mod sim;
 pub mod matching;
 pub mod args;
 pub mod example;
+pub mod analyze;
 pub mod io;
 pub mod geo_3d;
+pub mod warnings;
+pub mod timing;
+pub mod progress;
+#[cfg(any(test, feature = "bench"))]
+pub mod bench;
 mod crate_errors;
+#[cfg(test)]
+mod test_fixtures;
 
 use strum::IntoEnumIterator;
 
@@ -24,6 +32,44 @@ pub struct Targets{
     pub sim_target: Option<sim::SimTarget>,
     pub matching_target: Option<()>, // TODO THIS IS A DUMMY
     pub shared_args: args::SharedArgs,
+    /// Surface of the layout stage, shared with the later stages.
+    pub surface: Option<std::rc::Rc<SharedSurface>>,
+    /// Extracted bundle, if the inputs came from one. Held so its files last until the run is done.
+    pub bundle: Option<io::bundle::Bundle>,
+}
+
+/// Surface shared by the stages of one run.
+/// It's parsed on first use and kept, so stages after the layout don't read the file again.
+pub struct SharedSurface {
+    path: String,
+    surface: std::cell::OnceCell<geo_3d::Surface>,
+    parse_count: std::cell::Cell<usize>,
+}
+impl SharedSurface {
+    /// Create a handle to the surface at `path`, without parsing it yet.
+    pub fn new(path: &str) -> Self {
+        SharedSurface{path: path.to_string(), surface: std::cell::OnceCell::new(), parse_count: std::cell::Cell::new(0)}
+    }
+
+    /// Get the surface filepath.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Get the surface, parsing it from the path with `parse` if it hasn't been yet.
+    pub fn get_or_parse<E>(&self, parse: impl FnOnce(&str) -> Result<geo_3d::Surface, E>) -> Result<&geo_3d::Surface, E> {
+        if let Some(surface) = self.surface.get() {
+            return Ok(surface);
+        }
+        let surface = parse(&self.path)?;
+        self.parse_count.set(self.parse_count.get() + 1);
+        Ok(self.surface.get_or_init(|| surface))
+    }
+
+    /// Get the number of times the surface has been parsed.
+    pub fn parse_count(&self) -> usize {
+        self.parse_count.get()
+    }
 }
 
 /// [Stage 1.]
@@ -49,12 +95,23 @@ pub fn build_targets(cli_args: args::RunArgs) -> ComradeResult<Targets>{
         println!("Stages to run: {} through {} ...", cli_args.start_stage, end_stage);
     }
 
+    // Extract the bundle, if given
+    let bundle = match &cli_args.bundle {
+        Some(bundle_path) => {
+            println!("Extracting bundle: {}...", bundle_path);
+            Some(io::bundle::extract_bundle(bundle_path).map_err(args::ArgError::from)?)
+        },
+        None => None,
+    };
+
     let mut targets = Targets{
         layout_target: None,
         mesh_target: None,
         sim_target: None,
         matching_target: None,
         shared_args: cli_args.shared_args,
+        surface: None,
+        bundle: None,
     };
 
     for stage in args::RunStage::iter() {
@@ -69,32 +126,48 @@ pub fn build_targets(cli_args: args::RunArgs) -> ComradeResult<Targets>{
 
         match stage {
             args::RunStage::Layout => {
-                if let Some(layout_cfg_file) = &cli_args.layout_cfg {
+                let (layout_cfg, input_dir) = stage_cfg(&cli_args.layout_cfg, &bundle, |b| &b.layout_cfg);
+                if let Some(layout_cfg_file) = &layout_cfg {
                     println!("Loading layout config file: {}...", layout_cfg_file);
-                    targets.layout_target = Some(layout::LayoutTarget::from_cfg_file(
+                    let layout_target = layout::LayoutTarget::from_cfg_file(
                         layout_cfg_file,
+                        input_dir,
+                        cli_args.surface.as_deref(),
                         is_last
-                    )?);
+                    )?;
+                    if let Some(effective_cfg_path) = &cli_args.dump_effective_config {
+                        layout_target.save_effective_config(effective_cfg_path).map_err(args::ArgError::from)?;
+                    }
+                    targets.surface = Some(std::rc::Rc::new(SharedSurface::new(&layout_target.input_path)));
+                    targets.layout_target = Some(layout_target);
                 }
                 else {
                     args::err_str("Layout config file not specified")?;
                 }
             },
             args::RunStage::Mesh => {
-                if let Some(mesh_cfg_file) = &cli_args.mesh_cfg {
+                let (mesh_cfg, input_dir) = stage_cfg(&cli_args.mesh_cfg, &bundle, |b| &b.mesh_cfg);
+                if let Some(mesh_cfg_file) = &mesh_cfg {
                     println!("Loading mesh config file: {}...", mesh_cfg_file);
-                    targets.mesh_target = Some(mesh::MeshTarget::from_cfg_file(
+                    let mesh_target = mesh::MeshTarget::from_cfg_file(
                         mesh_cfg_file,
-                        is_first,
+                        input_dir,
                         is_last
-                    )?);
+                    )?;
+
+                    // Without a layout stage before it, the mesh stage has to load its layout
+                    if is_first {
+                        mesh_target.check_input_path()?;
+                    }
+                    targets.mesh_target = Some(mesh_target);
                 }
                 else {
                     args::err_str("Mesh config file not specified")?;
                 }
             },
             args::RunStage::Sim => {
-                if let Some(sim_cfg_file) = &cli_args.sim_cfg {
+                let (sim_cfg, _) = stage_cfg(&cli_args.sim_cfg, &bundle, |b| &b.sim_cfg);
+                if let Some(sim_cfg_file) = &sim_cfg {
                     println!("Loading simulation config file: {}...", sim_cfg_file);
                     targets.sim_target = Some(sim::SimTarget::from_cfg_file(
                         sim_cfg_file,
@@ -106,7 +179,8 @@ pub fn build_targets(cli_args: args::RunArgs) -> ComradeResult<Targets>{
                 }
             },
             args::RunStage::Match => {
-                if let Some(matching_cfg_file) = &cli_args.matching_cfg {
+                let (matching_cfg, _) = stage_cfg(&cli_args.matching_cfg, &bundle, |b| &b.matching_cfg);
+                if let Some(matching_cfg_file) = &matching_cfg {
                     println!("Loading matching config file: {}...", matching_cfg_file);
                     args::err_str("Matching config not yet implemented!!!")?;
                 }
@@ -117,9 +191,30 @@ pub fn build_targets(cli_args: args::RunArgs) -> ComradeResult<Targets>{
         }
     }
 
+    if cli_args.dump_effective_config.is_some() && targets.layout_target.is_none() {
+        args::err_str("Dumping the effective config needs the layout stage")?;
+    }
+
+    targets.bundle = bundle;
     Ok(targets)
 }
 
+/// Pick the config file for a stage, preferring the command line over the bundle.
+/// Returns the config path and, for bundled configs, the directory to resolve their inputs against.
+fn stage_cfg<'a>(
+    cli_cfg: &Option<String>,
+    bundle: &'a Option<io::bundle::Bundle>,
+    bundled_cfg: impl Fn(&io::bundle::Bundle) -> &Option<String>,
+) -> (Option<String>, Option<&'a std::path::Path>) {
+    if cli_cfg.is_some() {
+        return (cli_cfg.clone(), None);
+    }
+    match bundle {
+        Some(bundle) => (bundled_cfg(bundle).clone(), Some(bundle.dir.path())),
+        None => (None, None),
+    }
+}
+
 /// [Stage 2.] TODO UNFINISHED FUNCTION
 /// Run the process on the targets (layout, matching, or both).
 /// Returns a `ProcResult` with `()` or an `Err`.
@@ -127,14 +222,18 @@ pub fn build_targets(cli_args: args::RunArgs) -> ComradeResult<Targets>{
 pub fn run_process(targets: Targets) -> ComradeResult<()> {
 
     // 2.1 Run the layout process
-    let layout_out = match targets.layout_target {
+    let layout_out = match targets.layout_target.as_ref() {
         Some(layout_target) => {
             println!();
             println!("#################");
             println!("Running layout...");
             println!("#################");
             println!();
-            let layout_out = layout::do_layout(&layout_target)?;
+            let surface = match targets.surface.as_ref() {
+                Some(surface) => surface.get_or_parse(|path| layout_target.method.load_surface(path))?,
+                None => panic!("BUG: Running the layout, but missing the surface! Should've been set!"),
+            };
+            let layout_out = layout::do_layout_on(layout_target, surface)?;
 
             if layout_target.save {
                 let output_path = match layout_target.output_path.as_ref() {
@@ -142,7 +241,13 @@ pub fn run_process(targets: Targets) -> ComradeResult<()> {
                     None => panic!("BUG: Running the layout, but missing output path! Should've been checked!"),
                 };
                 println!("Saving layout to {}...", output_path);
-                layout::save_layout(&layout_out, output_path)?;
+                layout::save_layout_with_precision(&layout_out, output_path, layout_target.output_precision)?;
+            }
+            if layout_target.manifest {
+                layout::save_manifest(layout_target, &layout_out)?;
+            }
+            if layout_target.capacitor_summary.is_some() {
+                layout::save_capacitor_summary(layout_target, &layout_out)?;
             }
             Some(layout_out)
         },
@@ -167,6 +272,13 @@ pub fn run_process(targets: Targets) -> ComradeResult<()> {
                 layout::load_layout(input_path)?
             }
         };
+
+        // Report how far the coils lift off the surface, if the layout stage loaded one
+        if let (Some(surface), Some(layout_target)) = (targets.surface.as_ref(), targets.layout_target.as_ref()) {
+            let surface = surface.get_or_parse(|path| layout_target.method.load_surface(path))?;
+            let max_liftoff = layout_in.coils.iter().map(|coil| coil.max_liftoff(surface)).fold(0.0, f32::max);
+            println!("Maximum coil lift-off from the surface: {:.2}", max_liftoff);
+        }
         mesh::do_mesh(&mesh_target, &layout_in)?;
     }
 
@@ -190,12 +302,245 @@ pub fn run_process(targets: Targets) -> ComradeResult<()> {
         matching::err_str("Matching not yet implemented!!!")?;
     }
 
+    warnings::print_summary();
+
     Ok(())
 }
 
 /// Top-level tests
 #[cfg(test)]
 mod tests {
-    
-    // TODO: Add tests
+    use super::*;
+    use crate::test_fixtures::temp_dir;
+
+    #[test]
+    fn build_targets_from_bundle() {
+        let work_dir = io::TempDir::new("comrade_bundle_test").unwrap();
+        let work_dir = work_dir.path();
+
+        // Plane surface, adam config with a relative input path, and a static layout
+        let surface_path = work_dir.join("plane.stl");
+        io::stl::save_stl_from_surface(&geo_3d::Surface::square_grid(20.0, 20), surface_path.to_str().unwrap()).unwrap();
+        let static_layout_path = work_dir.join("static.json");
+        io::save_ser_to(static_layout_path.to_str().unwrap(), &layout::Layout::new()).unwrap();
+        let output_path = work_dir.join("layout.json");
+        let cfg_path = work_dir.join("layout.yaml");
+        std::fs::write(&cfg_path, format!(
+"input_path: plane.stl
+output_path: {}
+method:
+  name: adam_circles
+  args:
+    static_layout: static.json
+    iterations: 0
+    circles:
+      - center: {{x: 0.0, y: 0.0, z: 0.0}}
+        radius: 5.0
+", output_path.display())).unwrap();
+        let entries = [(&surface_path, "plane.stl"), (&static_layout_path, "static.json"), (&cfg_path, "layout.yaml")];
+
+        // The same inputs, bundled as a tar and as a zip archive
+        let tar_path = work_dir.join("case.comrade");
+        let mut builder = tar::Builder::new(std::fs::File::create(&tar_path).unwrap());
+        for (path, name) in entries {
+            builder.append_path_with_name(path, name).unwrap();
+        }
+        builder.finish().unwrap();
+        drop(builder);
+        let zip_path = work_dir.join("zipped.comrade");
+        let mut writer = zip::ZipWriter::new(std::fs::File::create(&zip_path).unwrap());
+        for (path, name) in entries {
+            writer.start_file(name, zip::write::FileOptions::default()).unwrap();
+            std::io::Write::write_all(&mut writer, &std::fs::read(path).unwrap()).unwrap();
+        }
+        writer.finish().unwrap();
+        drop(writer);
+
+        for bundle_path in [tar_path, zip_path] {
+            let cli = args::parse_cli_from(["comrade", "run", "layout", "--bundle", bundle_path.to_str().unwrap()]);
+            let run_args = match cli.subcommand {
+                args::SubCommand::Run(run_args) => run_args,
+                _ => panic!("Expected the run subcommand"),
+            };
+            let targets = build_targets(run_args).unwrap();
+            let extracted_dir = targets.bundle.as_ref().unwrap().dir.path().to_path_buf();
+
+            let layout_target = targets.layout_target.as_ref().unwrap();
+            assert!(std::path::Path::new(&layout_target.input_path).is_absolute());
+            assert!(std::path::Path::new(&layout_target.input_path).is_file());
+            assert_eq!(layout_target.output_path.as_deref(), output_path.to_str());
+            assert_eq!(layout_target.method.get_config_key(), "adam_circles");
+            let layout_in_paths: Vec<String> = match &serde_json::to_value(&layout_target.method).unwrap()["args"]["layout_in"] {
+                serde_json::Value::String(path) => vec![path.clone()],
+                paths => serde_json::from_value(paths.clone()).unwrap(),
+            };
+            for layout_in_path in layout_in_paths {
+                assert!(std::path::Path::new(&layout_in_path).is_file(), "Static layout not found at {}", layout_in_path);
+            }
+
+            // The extracted files are cleaned up with the targets
+            drop(targets);
+            assert!(!extracted_dir.exists(), "Bundle left behind at {}", extracted_dir.display());
+        }
+    }
+
+    #[test]
+    fn dumped_effective_config_reproduces_the_layout() {
+        let test_dir = temp_dir("effective_cfg_test");
+        let work_dir = test_dir.path();
+        let surface_path = work_dir.join("plane.stl");
+        io::stl::save_stl_from_surface(&geo_3d::Surface::square_grid(30.0, 30), surface_path.to_str().unwrap()).unwrap();
+        let cfg_path = work_dir.join("layout.yaml");
+        std::fs::write(&cfg_path, format!(
+"input_path: {}
+output_path: {}
+method:
+  name: adam_circles
+  args:
+    iterations: 3
+    circles:
+      - center: {{x: -8.0, y: 0.0, z: 0.0}}
+        radius: 10.0
+      - center: {{x: 8.0, y: 0.0, z: 0.0}}
+        radius: 10.0
+", surface_path.display(), work_dir.join("layout.json").display())).unwrap();
+
+        let effective_path = work_dir.join("effective.yaml");
+        let cli = args::parse_cli_from([
+            "comrade", "run", "layout",
+            "--layout-cfg", cfg_path.to_str().unwrap(),
+            "--dump-effective-config", effective_path.to_str().unwrap(),
+        ]);
+        let run_args = match cli.subcommand {
+            args::SubCommand::Run(run_args) => run_args,
+            _ => panic!("Expected the run subcommand"),
+        };
+        let layout_target = build_targets(run_args).unwrap().layout_target.unwrap();
+
+        // Every default is written out
+        let effective_cfg = std::fs::read_to_string(&effective_path).unwrap();
+        for field in ["radius_reg", "close_cutoff", "wire_radius", "break_count"] {
+            assert!(effective_cfg.contains(field), "Effective config is missing {}:\n{}", field, effective_cfg);
+        }
+
+        // Running the dumped config gives the same layout
+        let effective_target = layout::LayoutTarget::from_cfg_file(effective_path.to_str().unwrap(), None, None, false).unwrap();
+        let layout_out = layout::do_layout(&layout_target).unwrap();
+        let effective_out = layout::do_layout(&effective_target).unwrap();
+        assert_eq!(layout_out.coils.len(), effective_out.coils.len());
+        for (coil, effective_coil) in layout_out.coils.iter().zip(effective_out.coils.iter()) {
+            assert_eq!(coil.vertices.len(), effective_coil.vertices.len());
+            for (vertex, effective_vertex) in coil.vertices.iter().zip(effective_coil.vertices.iter()) {
+                assert!(vertex.point.approx_eq(&effective_vertex.point, 1e-5));
+            }
+        }
+    }
+
+    #[test]
+    fn surface_is_parsed_once_across_layout_and_mesh() {
+        let test_dir = temp_dir("shared_surface_test");
+        let work_dir = test_dir.path();
+        let surface_path = work_dir.join("plane.stl");
+        io::stl::save_stl_from_surface(&geo_3d::Surface::square_grid(20.0, 20), surface_path.to_str().unwrap()).unwrap();
+        let layout_cfg_path = work_dir.join("layout.yaml");
+        std::fs::write(&layout_cfg_path, format!(
+"input_path: {}
+method:
+  name: adam_circles
+  args:
+    iterations: 0
+    circles:
+      - center: {{x: 0.0, y: 0.0, z: 0.0}}
+        radius: 5.0
+", surface_path.display())).unwrap();
+        let mesh_output_path = work_dir.join("array");
+        let mesh_cfg_path = work_dir.join("mesh.yaml");
+        std::fs::write(&mesh_cfg_path, format!("output_path: {}\nmethod:\n  name: stl_polygons\n  args: {{poly_num: 4}}\n", mesh_output_path.display())).unwrap();
+
+        let cli = args::parse_cli_from([
+            "comrade", "run", "layout", "--to", "mesh",
+            "--layout-cfg", layout_cfg_path.to_str().unwrap(),
+            "--mesh-cfg", mesh_cfg_path.to_str().unwrap(),
+        ]);
+        let run_args = match cli.subcommand {
+            args::SubCommand::Run(run_args) => run_args,
+            _ => panic!("Expected the run subcommand"),
+        };
+        let targets = build_targets(run_args).unwrap();
+        let surface = targets.surface.clone().unwrap();
+        assert_eq!(surface.path(), surface_path.to_str().unwrap());
+        assert_eq!(surface.parse_count(), 0);
+
+        run_process(targets).unwrap();
+        assert_eq!(surface.parse_count(), 1);
+        assert!(work_dir.join("array.stl").is_file());
+    }
+
+    #[test]
+    fn env_fallbacks_fill_only_missing_flags() {
+        let test_dir = temp_dir("env_fallback_test");
+        let work_dir = test_dir.path();
+        let surface_path = work_dir.join("plane.stl");
+        io::stl::save_stl_from_surface(&geo_3d::Surface::square_grid(20.0, 20), surface_path.to_str().unwrap()).unwrap();
+        let cfg_path = work_dir.join("layout.yaml");
+        std::fs::write(&cfg_path, format!(
+"input_path: missing.stl
+output_path: {}
+method:
+  name: adam_circles
+  args:
+    iterations: 0
+    circles:
+      - center: {{x: 0.0, y: 0.0, z: 0.0}}
+        radius: 5.0
+", work_dir.join("layout.json").display())).unwrap();
+
+        let env = std::collections::HashMap::from([
+            ("COMRADE_LAYOUT_CFG", cfg_path.to_str().unwrap().to_string()),
+            ("COMRADE_SURFACE", surface_path.to_str().unwrap().to_string()),
+            ("COMRADE_MESH_CFG", String::new()),
+        ]);
+        let env_var = |key: &str| env.get(key).cloned();
+        let run_args = |cli_args: &[&str]| match args::parse_cli_from(cli_args.iter().copied()).subcommand {
+            args::SubCommand::Run(run_args) => run_args.with_env_fallbacks(env_var),
+            _ => panic!("Expected the run subcommand"),
+        };
+
+        // Without the flags, the environment fills them in, and the surface replaces the configured input path
+        let from_env = run_args(&["comrade", "run", "layout"]);
+        assert_eq!(from_env.layout_cfg.as_deref(), cfg_path.to_str());
+        assert!(from_env.mesh_cfg.is_none());
+        let layout_target = build_targets(from_env).unwrap().layout_target.unwrap();
+        assert_eq!(layout_target.input_path, surface_path.to_str().unwrap());
+
+        // The flags win over the environment
+        let from_flags = run_args(&["comrade", "run", "layout", "--layout-cfg", "flag.yaml", "--surface", "flag.stl"]);
+        assert_eq!(from_flags.layout_cfg.as_deref(), Some("flag.yaml"));
+        assert_eq!(from_flags.surface.as_deref(), Some("flag.stl"));
+    }
+
+    #[test]
+    fn mesh_first_without_input_path_fails_at_build() {
+        let test_dir = temp_dir("mesh_first_test");
+        let work_dir = test_dir.path();
+        let cfg_path = work_dir.join("mesh.yaml");
+        std::fs::write(&cfg_path, format!(
+"output_path: {}
+method:
+  name: gmsh
+  args: {{}}
+", work_dir.join("array").display())).unwrap();
+
+        let cli = args::parse_cli_from(["comrade", "run", "mesh", "--mesh-cfg", cfg_path.to_str().unwrap()]);
+        let run_args = match cli.subcommand {
+            args::SubCommand::Run(run_args) => run_args,
+            _ => panic!("Expected the run subcommand"),
+        };
+        let error = match build_targets(run_args) {
+            Ok(_) => panic!("Expected an error for a first mesh stage without an input path"),
+            Err(error) => error,
+        };
+        assert!(matches!(error, ComradeError::ArgError(_)));
+        assert!(error.to_string().contains("Mesh input path not specified"), "Unexpected error: {}", error);
+    }
 }
@@ -10,6 +10,19 @@ fn main() {
             }
             return;
         },
+        comrade::args::SubCommand::Analyze(analyze_args) => {
+            if let Err(err) = comrade::analyze::run_analysis(analyze_args) {
+                println!("{}", err);
+            }
+        },
+        comrade::args::SubCommand::List => {
+            comrade::example::display_methods();
+        },
+        comrade::args::SubCommand::Diff(diff_args) => {
+            if let Err(err) = comrade::analyze::run_diff(diff_args) {
+                println!("{}", err);
+            }
+        },
         comrade::args::SubCommand::Run(run_args) => {
             let targets = match comrade::build_targets(run_args) {
                 Ok(targets) => targets,
@@ -3,6 +3,7 @@ mod cfg;
 mod methods;
 
 use crate::layout;
+use crate::progress::{self, ProgressEvent};
 
 // Re-export errors
 pub use proc_errors::{
@@ -23,8 +24,11 @@ pub fn do_mesh(mesh_target: &MeshTarget, layout_in: &layout::Layout) -> ProcResu
     let mesh_method = &mesh_target.method;
 
     println!("Meshing...");
+    progress::emit(ProgressEvent::StageStarted{stage: "mesh"});
 
     // Run the meshing method
     println!("Running meshing method: {}", mesh_method.get_method_display_name());
-    mesh_method.save_mesh(&layout_in, &mesh_target.output_path)
+    mesh_method.save_mesh(&layout_in, &mesh_target.output_path)?;
+    progress::emit(ProgressEvent::Finished{stage: "mesh"});
+    Ok(())
 }
@@ -28,22 +28,12 @@ pub struct MeshTarget {
 }
 impl MeshTarget {
     /// Construct a mesh target from a config file.
-    pub fn from_cfg_file(cfg_file: &str, is_first: bool, is_last: bool) -> args::ProcResult<Self> {
+    /// If `input_dir` is given (e.g. an extracted bundle), a relative input path is resolved against it.
+    pub fn from_cfg_file(cfg_file: &str, input_dir: Option<&std::path::Path>, is_last: bool) -> args::ProcResult<Self> {
         let mut mesh_target: MeshTarget = io::load_deser_from(cfg_file)?;
 
-        // Check the input path
-        if is_first {
-            if let Some(input_path) = mesh_target.input_path.as_ref() {
-                if !input_path.ends_with(".json")
-                {
-                    args::err_str("Mesh input path must end with .json")?;
-                }
-                let _ = crate::io::open(input_path)?;
-            }
-            else {
-                args::err_str("Mesh input path not specified, but input path is required at the first stage")?;
-            }
-        }
+        // Resolve the input path
+        mesh_target.input_path = mesh_target.input_path.map(|input_path| io::resolve_path(&input_path, input_dir));
 
         let _ = crate::io::create(&format!("{}.{}", &mesh_target.output_path, mesh_target.method.get_output_extension()))?;
 
@@ -51,4 +41,53 @@ impl MeshTarget {
 
         Ok(mesh_target)
     }
+
+    /// Check that the input layout path is set and readable.
+    /// Required when meshing is the first stage, since there's no layout handed over in memory.
+    pub fn check_input_path(&self) -> args::ProcResult<()> {
+        if let Some(input_path) = self.input_path.as_ref() {
+            if !input_path.ends_with(".json")
+            {
+                args::err_str("Mesh input path must end with .json")?;
+            }
+            let _ = crate::io::open(input_path)?;
+        }
+        else {
+            args::err_str("Mesh input path not specified, but input path is required at the first stage")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::temp_dir;
+
+    #[test]
+    fn gmsh_cfg_loads_from_json_and_toml() {
+        let test_dir = temp_dir("mesh_cfg_test");
+        let cfg_dir = test_dir.path();
+        let output_path = cfg_dir.join("array").to_string_lossy().to_string();
+        let cfgs = [
+            ("json", format!(r#"{{"output": "{}", "method": {{"name": "gmsh", "args": {{"poly_count": 6, "polygonal": true}}}}}}"#, output_path)),
+            ("toml", format!("output = \"{}\"\n[method]\nname = \"gmsh\"\n[method.args]\npoly_count = 6\npolygonal = true\n", output_path)),
+            ("yaml", format!("output: {}\nmethod:\n  name: gmsh\n  args:\n    poly_count: 6\n    polygonal: true\n", output_path)),
+        ];
+
+        let mut loaded_methods = Vec::new();
+        for (extension, contents) in cfgs.iter() {
+            let cfg_file = cfg_dir.join(format!("mesh.{}", extension)).to_string_lossy().to_string();
+            std::fs::write(&cfg_file, contents).unwrap();
+            let mesh_target = MeshTarget::from_cfg_file(&cfg_file, None, true).unwrap();
+            assert!(matches!(mesh_target.method, mesh::MethodEnum::Gmsh(_)));
+            assert_eq!(mesh_target.output_path, output_path);
+            loaded_methods.push(serde_json::to_value(&mesh_target.method).unwrap());
+        }
+
+        // Every format gives the same method arguments
+        assert_eq!(loaded_methods[0]["args"]["poly_count"], 6);
+        assert_eq!(loaded_methods[0], loaded_methods[1]);
+        assert_eq!(loaded_methods[0], loaded_methods[2]);
+    }
 }
@@ -52,6 +52,16 @@ pub enum MethodEnum {
     #[serde(rename = "gmsh")]
     Gmsh(gmsh::Method),
 }
+impl MethodEnum {
+    /// Get a one-line description of the meshing method, for listing.
+    pub fn get_method_description(&self) -> &'static str {
+        match self {
+            MethodEnum::StlPolygons(_) => "STL of polygonal wire cross-sections swept along each coil",
+            MethodEnum::StlSlot(_) => "STL of slots along each coil, for cutting wire channels in CAD models",
+            MethodEnum::Gmsh(_) => "GMSH geometry and MARIE port/lumped element files for simulation",
+        }
+    }
+}
 
 //
 // ------------------------------------------------------------
@@ -1,6 +1,7 @@
 use crate::{
     layout,
     mesh,
+    warnings,
 };
 use crate::geo_3d::*;
 use mesh::methods;
@@ -14,6 +15,9 @@ use std::f32::consts::PI;
 
 /// GMSH Method struct.
 /// This struct contains all the parameters for the GMSH meshing method.
+/// Every coil needs a port, so coils laid out with a break count of 0 (uninterrupted loops) are rejected.
+/// With `cap_breaks`, the wire ring at each break is closed with a flat end surface, in its own physical surface.
+/// A `_points.json` next to the output maps each coil vertex to its GMSH point ids (see `PointMap`).
 #[derive(Debug, Clone)]
 #[derive(Serialize, Deserialize)]
 #[serde(deny_unknown_fields)]
@@ -28,8 +32,17 @@ pub struct Method {
     lc: f32,
     #[serde(default = "Method::default_larmor_mhz")]
     larmor_mhz: f32,
+    /// Spans per coil edge for the tuning inductance (see `Coil::subdivided`). Meshing only, the layout objective doesn't subdivide.
+    #[serde(default = "Method::default_segment_subdivisions", alias = "subdivisions")]
+    segment_subdivisions: usize,
     #[serde(default = "GeoVector::zero")]
     origin_offset: GeoVector,
+    #[serde(default = "Method::default_write_combined", alias = "combined")]
+    write_combined: bool,
+    #[serde(default = "Method::default_annotate_capacitance", alias = "annotate")]
+    annotate_capacitance: bool,
+    #[serde(default = "Method::default_cap_breaks", alias = "caps")]
+    cap_breaks: bool,
 }
 impl Method {
     pub fn default_single_surface() -> bool {
@@ -47,6 +60,23 @@ impl Method {
     pub fn default_larmor_mhz() -> f32 {
         127.73
     }
+    pub fn default_segment_subdivisions() -> usize {
+        1
+    }
+    pub fn default_write_combined() -> bool {
+        true
+    }
+    pub fn default_annotate_capacitance() -> bool {
+        false
+    }
+    pub fn default_cap_breaks() -> bool {
+        false
+    }
+
+    /// Total series capacitance (pF) that resonates a self-inductance (nH) at the Larmor frequency
+    fn tuning_capacitance_pf(&self, self_inductance_nh: f32) -> f32 {
+        1.0e9 / ((2.0 * PI * self.larmor_mhz).powi(2) * self_inductance_nh)
+    }
 }
 impl Default for Method {
     fn default() -> Self {
@@ -56,7 +86,11 @@ impl Default for Method {
             poly_count: Method::default_poly_count(),
             lc: Method::default_lc(),
             larmor_mhz: Method::default_larmor_mhz(),
+            segment_subdivisions: Method::default_segment_subdivisions(),
             origin_offset: GeoVector::zero(),
+            write_combined: Method::default_write_combined(),
+            annotate_capacitance: Method::default_annotate_capacitance(),
+            cap_breaks: Method::default_cap_breaks(),
         }
     }
 }
@@ -73,17 +107,93 @@ struct Arc {
 struct Spline {
     points: Vec<usize>,
 }
+/// GMSH point ids generated for one coil vertex, numbered as in the coil's own .geo file (from 1)
+#[derive(Debug, Clone)]
+#[derive(Serialize)]
+pub struct VertexPoints {
+    /// Coil vertex index.
+    pub vertex: usize,
+    /// Cross-section ring points, in order around the wire.
+    pub ring: Vec<usize>,
+    /// Wire (center) point, used as the arc center.
+    pub wire: usize,
+}
+/// GMSH point ids of one coil
+#[derive(Debug, Clone)]
+#[derive(Serialize)]
+pub struct CoilPoints {
+    pub coil: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    /// Add to the ids to get the point ids in the combined .geo file.
+    pub combined_offset: usize,
+    pub vertices: Vec<VertexPoints>,
+}
+/// Mapping from coil vertices to GMSH point ids, written next to the .geo output
+#[derive(Debug, Clone)]
+#[derive(Serialize)]
+pub struct PointMap {
+    pub poly_count: usize,
+    pub coils: Vec<CoilPoints>,
+}
+
 /// Collection of points, arcs, and splines for GMSH
 #[derive(Clone)]
 struct Loop {
     points: Vec<Point>,
+    vertex_points: Vec<VertexPoints>,
     arcs: Vec<Arc>,
     splines: Vec<Spline>,
+    break_count: usize,
     self_inductance_nh: f32,
+    label: Option<String>,
+    group: Option<u32>,
 }
 impl Loop {
     pub fn new() -> Self {
-        Loop{points: Vec::new(), arcs: Vec::new(), splines: Vec::new(), self_inductance_nh: 0.0}
+        Loop{points: Vec::new(), vertex_points: Vec::new(), arcs: Vec::new(), splines: Vec::new(), break_count: 0, self_inductance_nh: 0.0, label: None, group: None}
+    }
+
+    /// Check that the arcs and splines line up with the break count for the cross-section `poly_count`.
+    /// The line loops, ports and lumped elements are all indexed as `segment * poly_count + side`,
+    /// so a mismatch (e.g. a loop built with a different `poly_count`) would silently write a malformed file.
+    /// Returns a `ProcResult` with `()`, or an `Err` describing the mismatch.
+    fn check_counts(&self, loop_n: usize, poly_count: usize) -> mesh::ProcResult<()> {
+        // The port and the capacitor breaks on either side of it
+        if self.break_count < 3 {
+            return mesh::err_str(&format!("Coil {} has {} breaks in the mesh, expected at least 3 (the port and a capacitor break on either side)", loop_n, self.break_count));
+        }
+        let expected = self.break_count * poly_count;
+        if self.arcs.len() != expected {
+            return mesh::err_str(&format!(
+                "Coil {} has {} arcs, expected {} ({} breaks x poly_count {}) -- was it built with a different poly_count?",
+                loop_n, self.arcs.len(), expected, self.break_count, poly_count
+            ));
+        }
+        if self.splines.len() != expected {
+            return mesh::err_str(&format!(
+                "Coil {} has {} splines, expected {} ({} segments x poly_count {}) -- was it built with a different poly_count?",
+                loop_n, self.splines.len(), expected, self.break_count, poly_count
+            ));
+        }
+        if let Some(point_id) = self.arcs.iter().flat_map(|arc| [arc.start, arc.center, arc.end])
+            .chain(self.splines.iter().flat_map(|spline| spline.points.iter().copied()))
+            .find(|point_id| *point_id >= self.points.len()) {
+            return mesh::err_str(&format!("Coil {} references point {}, but only has {} points", loop_n, point_id, self.points.len()));
+        }
+        Ok(())
+    }
+
+    /// Get the comment header for the loop, including any user label and group.
+    fn comment(&self, loop_n: usize) -> String {
+        let mut comment = format!("// Coil {}", loop_n);
+        if let Some(label) = self.label.as_ref() {
+            comment.push_str(&format!(" label: {}", crate::io::escape_comment(label)));
+        }
+        if let Some(group) = self.group {
+            comment.push_str(&format!(" group: {}", group));
+        }
+        comment
     }
 }
 
@@ -103,119 +213,49 @@ impl methods::MeshMethodTrait for Method {
         let output_path = output_path.to_string() + ".geo";
 
         let poly_count = self.poly_count;
+        if poly_count < 3 {
+            return mesh::err_str(&format!("Wire cross-section needs at least 3 sides (poly_count), got {}", poly_count));
+        }
 
+        // Only hold every coil in memory if the combined file is written
         let mut full_loops = Vec::<Loop>::new();
+        let mut point_map = PointMap{poly_count, coils: Vec::new()};
+        let mut combined_offset = 0;
         
         // Mesh each coil
         for (coil_n, coil) in layout.coils.iter().enumerate() {
-            let radius = coil.wire_radius;
-
-            // Initialize the GMSH vectors
-            let mut single_loop = Loop::new();
-            single_loop.self_inductance_nh = coil.self_inductance(1.0);
-            
-            // Add the radial polygon points for each coil vertex (and center, used for arcs)
-            let center = coil.center;
-            for vertex in coil.vertices.iter() {
-                let point = &vertex.point;
-
-                // Get the relevant vectors
-                let vec_to_point = *point - center;
-                let up_vec = vertex.wire_radius_normal;
-                let out_vec = vec_to_point.rej_onto(&up_vec).normalize();
-                
-                // Add the spline points to the list
-                for i in 0..poly_count {
-                    let theta = (i as f32 - 0.5) * 2.0 * PI / poly_count as f32; // -0.5 gives a flat bottom
-                    let point = vertex.point + up_vec * radius * theta.cos() + out_vec * radius * theta.sin();
-                    single_loop.points.push(point + self.origin_offset);
-                }
-                // Add the wire point to the list (some may be unused)
-                single_loop.points.push(vertex.point + self.origin_offset);
-            }
-
-            // Add two capacitor breaks on either side of the first binned break (the port)
-            // Position them at the first points in either direction from the port that are at least 2*lc away
-            let port_id = if let Some(id) = coil.port {
-                id
-            } else {
-                0
-            };
-
-            // Upper side capacitor break:
-            let mut upper_capacitor_break_id = port_id;
-            let mut distance = 0.0;
-            while distance < 2.0 * self.lc {
-                let previously_checked_id = upper_capacitor_break_id;
-                upper_capacitor_break_id = (upper_capacitor_break_id + 1) % coil.vertices.len();
-                distance += (coil.vertices[upper_capacitor_break_id].point - coil.vertices[previously_checked_id].point).norm();
-                if coil.breaks.len() > 1 && upper_capacitor_break_id == coil.breaks[0] {
-                    mesh::err_str("Math error: Nearby capacitor break (positive idx direction) not found before first break -- lc too large")?;
-                }
-            }
-            
-            // Lower side capacitor break:
-            let mut lower_capacitor_break_id = port_id;
-            let mut distance = 0.0;
-            while distance < 2.0 * self.lc {
-                let previously_checked_id = lower_capacitor_break_id;
-                lower_capacitor_break_id = lower_capacitor_break_id - 1 % coil.vertices.len();
-                distance += (coil.vertices[lower_capacitor_break_id].point - coil.vertices[previously_checked_id].point).norm();
-                if coil.breaks.len() > 1 && lower_capacitor_break_id == coil.breaks[coil.breaks.len() - 1] {
-                    mesh::err_str("Math error: Nearby capacitor break (negative idx direction) not found before last break -- lc too large")?;
-                }
-            }
-
-            let mut break_points = vec![port_id, upper_capacitor_break_id];
-            break_points.extend(coil.breaks.clone());
-            break_points.push(lower_capacitor_break_id);
-
-            // Add the arcs
-            for id in break_points.iter() {
-                // Add the arcs per point (equal to spline poly count, 4 by default)
-                for i in 0..poly_count {
-                    let start = id * (poly_count + 1) + i;
-                    let center = id * (poly_count + 1) + poly_count;
-                    let end = id * (poly_count + 1) + (i + 1) % poly_count;
-                    single_loop.arcs.push(Arc{start, center, end});
-                }
-            }
-
-            // Add the splines.
-            for break_number in 0..break_points.len() {
-                
-                let id = break_points[break_number];
-                let next_id = break_points[(break_number + 1) % break_points.len()];
-                // Add the splines per layout point
-                for i in 0..poly_count {
-                    let mut spline_points = Vec::<usize>::new();
-
-                    // Handle potential wraparound
-                    if next_id < id {
-                        for j in id..coil.vertices.len() {
-                            spline_points.push(j * (poly_count + 1) + i);
-                        }
-                        for j in 0..=next_id {
-                            spline_points.push(j * (poly_count + 1) + i);
-                        }
-                    } else {
-                        for j in id..=next_id {
-                            spline_points.push(j * (poly_count + 1) + i);
-                        }
-                    }
-                    single_loop.splines.push(Spline{points: spline_points});
-                }
-            }
+            let single_loop = self.build_loop(coil_n, coil)?;
 
             // Save each coil to a separate file
             let numbered_output_path = output_path.replace(".geo", &format!("_c{}.geo", coil_n));
             println!("Saving coil {} to {}...", coil_n, numbered_output_path);
-            self.save_geo(&vec![single_loop.clone()], &numbered_output_path)?;
+            self.save_geo(std::slice::from_ref(&single_loop), &numbered_output_path)?;
             let txt_output_path = output_path.replace(".geo", &format!("_c{}.txt", coil_n));
-            self.save_marie_txt(&vec![single_loop.clone()], &txt_output_path)?;
+            self.save_marie_txt(std::slice::from_ref(&single_loop), &txt_output_path)?;
+
+            // Track where the coil's points land, in its own file and the combined one
+            point_map.coils.push(CoilPoints{
+                coil: coil_n,
+                label: single_loop.label.clone(),
+                combined_offset,
+                vertices: single_loop.vertex_points.clone(),
+            });
+            combined_offset += single_loop.points.len();
 
             // Add the coil to the full set
-            full_loops.push(single_loop);
+            if self.write_combined {
+                full_loops.push(single_loop);
+            }
+        }
+
+        // Save the mapping from coil vertices to point ids
+        let point_map_path = output_path.replace(".geo", "_points.json");
+        println!("Saving point mapping to {}...", point_map_path);
+        crate::io::save_ser_to(&point_map_path, &point_map)?;
+
+        if !self.write_combined {
+            println!("Skipping full array output (write_combined is off)");
+            return Ok(());
         }
 
         // Save a full set of coils (often just for visualization)
@@ -244,8 +284,139 @@ const COL_WIDTH : [usize; 11] = [
 
 impl Method {
 
-    /// Save a GMSH .geo file
-    fn save_geo(&self, loop_vec: &Vec<Loop>, output_path: &str) -> mesh::ProcResult<()> {
+    /// Build the GMSH points, arcs and splines for a coil.
+    /// Returns a `ProcResult` with the `Loop`, or an `Err` if the coil has nothing to drive it from or `lc` is too large.
+    fn build_loop(&self, coil_n: usize, coil: &layout::Coil) -> mesh::ProcResult<Loop> {
+        let poly_count = self.poly_count;
+        let radius = coil.wire_radius;
+
+        // Initialize the GMSH vectors
+        let mut single_loop = Loop::new();
+        single_loop.self_inductance_nh = coil.self_inductance_subdivided(1.0, self.segment_subdivisions);
+        single_loop.label = coil.label.clone();
+        single_loop.group = coil.group;
+        
+        // Add the radial polygon points for each coil vertex (and center, used for arcs)
+        let center = coil.center;
+        for (vertex_id, vertex) in coil.vertices.iter().enumerate() {
+            let point = &vertex.point;
+            let first_point_id = single_loop.points.len() + 1;
+
+            // Get the relevant vectors
+            let vec_to_point = *point - center;
+            let up_vec = vertex.wire_radius_normal;
+            let out_vec = vec_to_point.rej_onto(&up_vec).normalize();
+            
+            // Add the spline points to the list
+            for i in 0..poly_count {
+                let theta = (i as f32 - 0.5) * 2.0 * PI / poly_count as f32; // -0.5 gives a flat bottom
+                let point = vertex.point + up_vec * radius * theta.cos() + out_vec * radius * theta.sin();
+                single_loop.points.push(point + self.origin_offset);
+            }
+            // Add the wire point to the list (some may be unused)
+            single_loop.points.push(vertex.point + self.origin_offset);
+            single_loop.vertex_points.push(VertexPoints{
+                vertex: vertex_id,
+                ring: (first_point_id..first_point_id + poly_count).collect(),
+                wire: first_point_id + poly_count,
+            });
+        }
+
+        // Add two capacitor breaks on either side of the first binned break (the port)
+        // Position them at the first points in either direction from the port that are at least 2*lc away
+        // Uninterrupted loops (a break count of 0) have nothing to drive the coil from
+        let port_id = match coil.port {
+            Some(id) => id,
+            None if coil.breaks.is_empty() => {
+                return mesh::err_str(&format!("Coil {} has no port or breaks (break count 0) -- GMSH meshing needs at least one break for the port", coil_n));
+            },
+            None => 0,
+        };
+
+        // Upper side capacitor break:
+        let mut upper_capacitor_break_id = port_id;
+        let mut distance = 0.0;
+        while distance < 2.0 * self.lc {
+            let previously_checked_id = upper_capacitor_break_id;
+            upper_capacitor_break_id = (upper_capacitor_break_id + 1) % coil.vertices.len();
+            distance += (coil.vertices[upper_capacitor_break_id].point - coil.vertices[previously_checked_id].point).norm();
+            if coil.breaks.len() > 1 && upper_capacitor_break_id == coil.breaks[0] {
+                mesh::err_str("Math error: Nearby capacitor break (positive idx direction) not found before first break -- lc too large")?;
+            }
+        }
+        
+        // Lower side capacitor break:
+        let mut lower_capacitor_break_id = port_id;
+        let mut distance = 0.0;
+        while distance < 2.0 * self.lc {
+            let previously_checked_id = lower_capacitor_break_id;
+            lower_capacitor_break_id = (lower_capacitor_break_id + coil.vertices.len() - 1) % coil.vertices.len();
+            distance += (coil.vertices[lower_capacitor_break_id].point - coil.vertices[previously_checked_id].point).norm();
+            if coil.breaks.len() > 1 && lower_capacitor_break_id == coil.breaks[coil.breaks.len() - 1] {
+                mesh::err_str("Math error: Nearby capacitor break (negative idx direction) not found before last break -- lc too large")?;
+            }
+        }
+
+        let mut break_points = vec![port_id, upper_capacitor_break_id];
+        break_points.extend(coil.breaks.clone());
+        break_points.push(lower_capacitor_break_id);
+
+        // Warn about breaks inside a mousehole, where the offset and bent wire distorts the capacitor geometry
+        for break_id in break_points.iter() {
+            if coil.in_overlap(*break_id) {
+                warnings::warn_grouped("Break inside a mousehole overlap", &format!(
+                    "Coil {} has a break at vertex {} inside a mousehole overlap -- the capacitor geometry will be distorted",
+                    coil_n, break_id
+                ));
+            }
+        }
+
+        // Add the arcs
+        single_loop.break_count = break_points.len();
+        for id in break_points.iter() {
+            // Add the arcs per point (equal to spline poly count, 4 by default)
+            for i in 0..poly_count {
+                let start = id * (poly_count + 1) + i;
+                let center = id * (poly_count + 1) + poly_count;
+                let end = id * (poly_count + 1) + (i + 1) % poly_count;
+                single_loop.arcs.push(Arc{start, center, end});
+            }
+        }
+
+        // Add the splines.
+        for break_number in 0..break_points.len() {
+            
+            let id = break_points[break_number];
+            let next_id = break_points[(break_number + 1) % break_points.len()];
+            // Add the splines per layout point
+            for i in 0..poly_count {
+                let mut spline_points = Vec::<usize>::new();
+
+                // Handle potential wraparound
+                if next_id < id {
+                    for j in id..coil.vertices.len() {
+                        spline_points.push(j * (poly_count + 1) + i);
+                    }
+                    for j in 0..=next_id {
+                        spline_points.push(j * (poly_count + 1) + i);
+                    }
+                } else {
+                    for j in id..=next_id {
+                        spline_points.push(j * (poly_count + 1) + i);
+                    }
+                }
+                single_loop.splines.push(Spline{points: spline_points});
+            }
+        }
+
+        Ok(single_loop)
+    }
+
+    /// Save a GMSH .geo file, after checking each loop's counts (see `Loop::check_counts`)
+    fn save_geo(&self, loop_vec: &[Loop], output_path: &str) -> mesh::ProcResult<()> {
+        for (loop_n, single_loop) in loop_vec.iter().enumerate() {
+            single_loop.check_counts(loop_n, self.poly_count)?;
+        }
         match self.save_geo_direct(loop_vec, output_path) {
             Ok(_) => Ok(()),
             Err(error) => {
@@ -254,8 +425,11 @@ impl Method {
         }
     }
 
-    /// Save a MARIE .txt file for ports and lumped elements
-    fn save_marie_txt(&self, loop_vec: &Vec<Loop>, output_path: &str) -> mesh::ProcResult<()> {
+    /// Save a MARIE .txt file for ports and lumped elements, after checking each loop's counts
+    fn save_marie_txt(&self, loop_vec: &[Loop], output_path: &str) -> mesh::ProcResult<()> {
+        for (loop_n, single_loop) in loop_vec.iter().enumerate() {
+            single_loop.check_counts(loop_n, self.poly_count)?;
+        }
         match self.save_marie_txt_direct(loop_vec, output_path) {
             Ok(_) => Ok(()),
             Err(error) => {
@@ -265,7 +439,7 @@ impl Method {
     }
 
     /// Save a GMSH .geo file -- direct interface with the GMSH io
-    fn save_geo_direct(&self, loop_vec: &Vec<Loop>, output_path: &str) -> std::io::Result<()> {
+    fn save_geo_direct(&self, loop_vec: &[Loop], output_path: &str) -> std::io::Result<()> {
         let file = OpenOptions::new().write(true).create(true).truncate(true).open(&output_path)?;
 
         let mut file = LineWriter::new(file);
@@ -285,7 +459,7 @@ impl Method {
         writeln!(file, "// Points")?;
         writeln!(file, "// ------------------------------------------")?;
         for (loop_n, single_loop) in loop_vec.iter().enumerate() {
-            writeln!(file, "// Coil {}", loop_n)?;
+            writeln!(file, "{}", single_loop.comment(loop_n))?;
             for (point_id, point) in single_loop.points.iter().enumerate() {
                 writeln!(file, "Point({}) = {{{}, {}, {}, lc}};", point_id + point_offsets[loop_n], point.x * 1e-3, point.y * 1e-3, point.z * 1e-3)?;
             }
@@ -350,7 +524,7 @@ impl Method {
         writeln!(file, "// ------------------------------------------")?;
         for (loop_n, single_loop) in loop_vec.iter().enumerate() {
             writeln!(file, "// Coil {}", loop_n)?;
-            let break_count = single_loop.arcs.len() / poly_count;
+            let break_count = single_loop.break_count;
             for segment_n in 0..break_count {
                 for i in 0..poly_count {
                     let first_arc_id = segment_n * poly_count + i + arc_offsets[loop_n];
@@ -378,7 +552,7 @@ impl Method {
         writeln!(file, "// ------------------------------------------")?;
         for (loop_n, single_loop) in loop_vec.iter().enumerate() {
             writeln!(file, "// Coil {}", loop_n)?;
-            let break_count = single_loop.arcs.len() / poly_count;
+            let break_count = single_loop.break_count;
             for segment_n in 0..break_count {
                 for i in 0..poly_count {
                     let surface_id = segment_n * poly_count + i + line_loop_offsets[loop_n];
@@ -392,6 +566,29 @@ impl Method {
         writeln!(file, "// ------------------------------------------")?;
         writeln!(file)?;
 
+        // Write the break caps, closing the ring of arcs at each break (flat for both arcs and polygon lines)
+        let mut cap_ids = Vec::<usize>::new();
+        if self.cap_breaks {
+            let mut cap_id = 1 + loop_vec.iter().map(|single_loop| single_loop.break_count * poly_count).sum::<usize>();
+            writeln!(file, "// Break Caps")?;
+            writeln!(file, "// ------------------------------------------")?;
+            for (loop_n, single_loop) in loop_vec.iter().enumerate() {
+                writeln!(file, "// Coil {}", loop_n)?;
+                for segment_n in 0..single_loop.break_count {
+                    let arc_ids = (0..poly_count).map(|i| (i + segment_n * poly_count + arc_offsets[loop_n]).to_string()).collect::<Vec<String>>();
+                    writeln!(file, "Line Loop({}) = {{{}}};", cap_id, arc_ids.join(", "))?;
+                    writeln!(file, "Plane Surface({}) = {{{}}};", cap_id, cap_id)?;
+                    cap_ids.push(cap_id);
+                    cap_id += 1;
+                }
+                if loop_n < loop_vec.len() - 1 {
+                    writeln!(file)?;
+                }
+            }
+            writeln!(file, "// ------------------------------------------")?;
+            writeln!(file)?;
+        }
+
         
         // Write the physical lines for the ports first (first break in each loop, made of arcs)...
         writeln!(file, "// Ports")?;
@@ -425,7 +622,7 @@ impl Method {
         writeln!(file, "// ------------------------------------------")?;
         for (loop_n, single_loop) in loop_vec.iter().enumerate() {
             writeln!(file, "// Coil {}", loop_n)?;
-            let break_count = single_loop.arcs.len() / poly_count;
+            let break_count = single_loop.break_count;
             for segment_n in 1..break_count {
                 let arc_ids = (0..poly_count).map(|i| i + segment_n * poly_count + arc_offsets[loop_n]).collect::<Vec<usize>>();
                 let line_id = (segment_n - 1) + physical_line_offsets[loop_n];
@@ -453,7 +650,7 @@ impl Method {
         let single_surface = self.single_surface;
         let mut physical_surface_str = "".to_string();
         for (loop_n, single_loop) in loop_vec.iter().enumerate() {
-            let break_count = single_loop.arcs.len() / poly_count;
+            let break_count = single_loop.break_count;
             if !single_surface || loop_n == 0 {
                 if single_surface { writeln!(file, "// Single Surface")?; } else { writeln!(file, "// Coil {}", loop_n)?; }
                 physical_surface_str = format!("Physical Surface({}) = {{", loop_n + 1);
@@ -477,6 +674,14 @@ impl Method {
                 physical_surface_str.push_str(", ");
             }
         }
+        // Keep the caps out of the conductor, so they don't short the port and capacitor gaps
+        if !cap_ids.is_empty() {
+            let cap_surface_id = if single_surface { 2 } else { loop_vec.len() + 1 };
+            let cap_ids = cap_ids.iter().map(|cap_id| cap_id.to_string()).collect::<Vec<String>>();
+            writeln!(file)?;
+            writeln!(file, "// Break Caps")?;
+            writeln!(file, "Physical Surface({}) = {{{}}};", cap_surface_id, cap_ids.join(", "))?;
+        }
         writeln!(file, "// ------------------------------------------")?;
         writeln!(file)?;
 
@@ -486,7 +691,7 @@ impl Method {
     }
 
     /// Save a MARIE .txt file for ports and lumped elements -- direct interface with the .txt io
-    fn save_marie_txt_direct(&self, loop_vec: &Vec<Loop>, output_path: &str) -> std::io::Result<()> {
+    fn save_marie_txt_direct(&self, loop_vec: &[Loop], output_path: &str) -> std::io::Result<()> {
         let file = OpenOptions::new().write(true).create(true).truncate(true).open(&output_path)?;
         let push_column = |line_str: &mut String, input: &str, col_width: usize| {
             line_str.push_str(input);
@@ -497,10 +702,8 @@ impl Method {
 
         let mut file = LineWriter::new(file);
 
-        let poly_count = self.poly_count;
-
         // Write the ports
-        for (loop_n, _) in loop_vec.iter().enumerate() {
+        for (loop_n, single_loop) in loop_vec.iter().enumerate() {
             let mut line_str = "".to_string();
             push_column(&mut line_str, &format!("{}", loop_n + 1), COL_WIDTH[0]);
             push_column(&mut line_str, "port", COL_WIDTH[1]);
@@ -514,6 +717,10 @@ impl Method {
             push_column(&mut line_str, "1e-12", COL_WIDTH[9]);
             push_column(&mut line_str, "150e-12", COL_WIDTH[10]);
             line_str.push_str(&format!("{}", loop_n + 1));
+            if self.annotate_capacitance {
+                line_str.push_str(&format!(" % L = {:.4} nH, C_tune = {:.4} pF at {} MHz",
+                    single_loop.self_inductance_nh, self.tuning_capacitance_pf(single_loop.self_inductance_nh), self.larmor_mhz));
+            }
 
             writeln!(file, "{}", line_str)?;
         }
@@ -524,9 +731,9 @@ impl Method {
 
         // ... then write the lumped elements
         for (loop_n, single_loop) in loop_vec.iter().enumerate() {
-            let break_count = single_loop.arcs.len() / poly_count;
+            let break_count = single_loop.break_count;
             let capacitor_count = break_count - 2;
-            let break_cap_pf = capacitor_count as f32 * 1.0e9 / ((2.0 * PI * self.larmor_mhz).powi(2) * single_loop.self_inductance_nh);
+            let break_cap_pf = capacitor_count as f32 * self.tuning_capacitance_pf(single_loop.self_inductance_nh);
             for segment_n in 1..break_count {
 
                 let mut line_str = "".to_string();
@@ -550,6 +757,9 @@ impl Method {
                 } else {
                     line_str.push_str(&format!("{}", loop_vec.len() + 2 * loop_n + 2));
                 }
+                if self.annotate_capacitance {
+                    line_str.push_str(&format!(" % coil {} tuning", loop_n + 1));
+                }
 
                 writeln!(file, "{}", line_str)?;
             }
@@ -561,4 +771,255 @@ impl Method {
         Ok(())
     }
         
-}   
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::{circle_coil, temp_dir};
+    use methods::MeshMethodTrait;
+
+    fn two_coil_layout() -> layout::Layout {
+        let mut layout_in = layout::Layout::new();
+        for center in [Point::new(0.0, 0.0, 0.0), Point::new(25.0, 0.0, 0.0)] {
+            let mut coil = circle_coil(center, 10.0);
+            coil.port = Some(8);
+            coil.breaks = vec![24, 40, 56];
+            layout_in.coils.push(coil);
+        }
+        layout_in
+    }
+
+    #[test]
+    fn poly_count_below_three_is_rejected() {
+        let test_dir = temp_dir("gmsh_poly_count_test");
+        let output_path = test_dir.path().join("array").to_string_lossy().to_string();
+        let method = Method{poly_count: 2, ..Default::default()};
+        let error = method.save_mesh(&two_coil_layout(), &output_path).unwrap_err().to_string();
+        assert!(error.contains("at least 3 sides"), "Unexpected error: {}", error);
+    }
+
+    #[test]
+    fn loop_built_with_a_different_poly_count_is_rejected() {
+        let test_dir = temp_dir("gmsh_count_mismatch_test");
+        let output_dir = test_dir.path();
+        let output_path = output_dir.join("mismatch.geo").to_string_lossy().to_string();
+        let _ = std::fs::remove_file(&output_path);
+
+        let layout_in = two_coil_layout();
+        let single_loop = Method::default().build_loop(0, &layout_in.coils[0]).unwrap();
+        assert_eq!(single_loop.break_count, 6);
+        assert!(single_loop.check_counts(0, 4).is_ok());
+
+        // poly_count changed between building and writing: 24 arcs no longer split into 6 breaks of 6 sides
+        let method = Method{poly_count: 6, ..Default::default()};
+        let error = method.save_geo(std::slice::from_ref(&single_loop), &output_path).unwrap_err().to_string();
+        assert!(error.contains("Coil 0 has 24 arcs, expected 36"), "Unexpected error: {}", error);
+        assert!(!std::path::Path::new(&output_path).exists());
+        assert!(method.save_marie_txt(std::slice::from_ref(&single_loop), &output_path).is_err());
+
+        // Arcs that don't divide evenly into breaks are caught too
+        let mut truncated = single_loop.clone();
+        truncated.arcs.pop();
+        let error = Method::default().save_geo(&[single_loop, truncated], &output_path).unwrap_err().to_string();
+        assert!(error.contains("Coil 1 has 23 arcs, expected 24"), "Unexpected error: {}", error);
+        assert!(!std::path::Path::new(&output_path).exists());
+    }
+
+    #[test]
+    fn break_caps_close_each_ring() {
+        let layout_in = two_coil_layout();
+        let test_dir = temp_dir("gmsh_cap_breaks_test");
+        let output_dir = test_dir.path();
+        let output_path = output_dir.join("array").to_string_lossy().to_string();
+
+        let surface_counts = |geo: &str| -> (usize, usize) {
+            (geo.lines().filter(|line| line.starts_with("Ruled Surface(")).count(), geo.lines().filter(|line| line.starts_with("Plane Surface(")).count())
+        };
+
+        Method::default().save_mesh(&layout_in, &output_path).unwrap();
+        let (plain_ruled, plain_caps) = surface_counts(&std::fs::read_to_string(output_path.clone() + ".geo").unwrap());
+        assert_eq!(plain_caps, 0);
+
+        // 6 breaks per coil (the port, its two capacitor breaks and 3 more), one cap each
+        for polygonal in [false, true] {
+            let method = Method{cap_breaks: true, polygonal, ..Default::default()};
+            method.save_mesh(&layout_in, &output_path).unwrap();
+            let geo = std::fs::read_to_string(output_path.clone() + ".geo").unwrap();
+            let (ruled, caps) = surface_counts(&geo);
+            assert_eq!(ruled, plain_ruled);
+            assert_eq!(caps, 2 * 6);
+
+            // Caps continue the surface numbering and sit in their own physical surface
+            let first_cap = plain_ruled + 1;
+            assert!(geo.contains(&format!("Line Loop({}) = {{1, 2, 3, 4}};", first_cap)), "Missing first cap in:\n{}", geo);
+            assert!(geo.contains(&format!("Physical Surface(2) = {{{}, ", first_cap)));
+        }
+    }
+
+    #[test]
+    fn point_map_covers_every_point_once() {
+        let layout_in = two_coil_layout();
+        let test_dir = temp_dir("gmsh_point_map_test");
+        let output_dir = test_dir.path();
+        let output_path = output_dir.join("array").to_string_lossy().to_string();
+
+        let method = Method{poly_count: 5, ..Default::default()};
+        method.save_mesh(&layout_in, &output_path).unwrap();
+        let point_map: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(output_path.clone() + "_points.json").unwrap()).unwrap();
+        assert_eq!(point_map["poly_count"], 5);
+
+        let geo = std::fs::read_to_string(output_path.clone() + ".geo").unwrap();
+        let point_count = geo.lines().filter(|line| line.starts_with("Point(")).count();
+        let mut seen = vec![0; point_count + 1];
+        for (coil_id, coil_points) in point_map["coils"].as_array().unwrap().iter().enumerate() {
+            assert_eq!(coil_points["coil"], coil_id);
+            let vertices = coil_points["vertices"].as_array().unwrap();
+            assert_eq!(vertices.len(), layout_in.coils[coil_id].vertices.len());
+            let combined_offset = coil_points["combined_offset"].as_u64().unwrap() as usize;
+            for (vertex_id, vertex_points) in vertices.iter().enumerate() {
+                assert_eq!(vertex_points["vertex"], vertex_id);
+                assert_eq!(vertex_points["ring"].as_array().unwrap().len(), 5);
+                let ids = vertex_points["ring"].as_array().unwrap().iter().chain(std::iter::once(&vertex_points["wire"]));
+                for id in ids {
+                    seen[id.as_u64().unwrap() as usize + combined_offset] += 1;
+                }
+            }
+        }
+        assert_eq!(seen[0], 0);
+        assert!(seen[1..].iter().all(|count| *count == 1), "Points not covered exactly once: {:?}", seen);
+
+        // The wire point of a vertex sits on the coil wire
+        let wire_id = point_map["coils"][1]["vertices"][3]["wire"].as_u64().unwrap();
+        let single_geo = std::fs::read_to_string(output_path + "_c1.geo").unwrap();
+        let vertex = layout_in.coils[1].vertices[3].point;
+        assert!(single_geo.contains(&format!("Point({}) = {{{}, {}, {}, lc}};", wire_id, vertex.x * 1e-3, vertex.y * 1e-3, vertex.z * 1e-3)));
+    }
+
+    #[test]
+    fn zero_break_coil_is_rejected() {
+        let test_dir = temp_dir("gmsh_zero_break_test");
+        let output_path = test_dir.path().join("array").to_string_lossy().to_string();
+        let mut layout_in = two_coil_layout();
+
+        // A break count of 0 leaves an uninterrupted loop with no port
+        layout_in.coils[1].redistribute_breaks_by_length(0).unwrap();
+        let error = Method::default().save_mesh(&layout_in, &output_path).unwrap_err().to_string();
+        assert!(error.contains("Coil 1 has no port or breaks"), "Unexpected error: {}", error);
+
+        // A single break is just the port, which can sit on the first vertex
+        layout_in.coils[1].port = Some(0);
+        layout_in.coils[1].redistribute_breaks_by_length(1).unwrap();
+        assert!(layout_in.coils[1].breaks.is_empty());
+        Method::default().save_mesh(&layout_in, &output_path).unwrap();
+    }
+
+    #[test]
+    fn combined_output_is_optional() {
+        let layout_in = two_coil_layout();
+
+        let test_dir = temp_dir("gmsh_combined_test");
+        let output_dir = test_dir.path();
+        let output_path = output_dir.join("array").to_string_lossy().to_string();
+        let _ = std::fs::remove_file(output_path.clone() + ".geo");
+
+        let method = Method{write_combined: false, ..Default::default()};
+        method.save_mesh(&layout_in, &output_path).unwrap();
+        assert!(std::path::Path::new(&(output_path.clone() + "_c0.geo")).exists());
+        assert!(std::path::Path::new(&(output_path.clone() + "_c1.geo")).exists());
+        assert!(!std::path::Path::new(&(output_path.clone() + ".geo")).exists());
+
+        let method = Method::default();
+        method.save_mesh(&layout_in, &output_path).unwrap();
+        assert!(std::path::Path::new(&(output_path.clone() + ".geo")).exists());
+    }
+
+    #[test]
+    fn coil_labels_survive_save_load_and_geo_output() {
+        let mut layout_in = two_coil_layout();
+        layout_in.coils[0].label = Some("row_a".to_string());
+        layout_in.coils[0].group = Some(2);
+
+        let test_dir = temp_dir("gmsh_label_test");
+        let output_dir = test_dir.path();
+        let layout_path = output_dir.join("layout.json").to_string_lossy().to_string();
+        layout::save_layout(&layout_in, &layout_path).unwrap();
+        let layout_loaded = layout::load_layout(&layout_path).unwrap();
+        assert_eq!(layout_loaded.coils[0].label.as_deref(), Some("row_a"));
+        assert_eq!(layout_loaded.coils[0].group, Some(2));
+        assert_eq!(layout_loaded.coils[1].label, None);
+
+        let output_path = output_dir.join("array").to_string_lossy().to_string();
+        Method::default().save_mesh(&layout_loaded, &output_path).unwrap();
+        let geo = std::fs::read_to_string(output_path.clone() + ".geo").unwrap();
+        assert!(geo.contains("// Coil 0 label: row_a group: 2\n"));
+        assert!(geo.contains("// Coil 1\n"));
+        let single_geo = std::fs::read_to_string(output_path + "_c0.geo").unwrap();
+        assert!(single_geo.contains("label: row_a"));
+    }
+
+    #[test]
+    fn label_line_breaks_stay_inside_the_geo_comment() {
+        let mut layout_in = two_coil_layout();
+        layout_in.coils[0].label = Some("row_a\nPoint(1) = {0, 0, 0};".to_string());
+
+        let test_dir = temp_dir("gmsh_label_escape_test");
+        let output_path = test_dir.path().join("array").to_string_lossy().to_string();
+        Method::default().save_mesh(&layout_in, &output_path).unwrap();
+        let geo = std::fs::read_to_string(output_path + ".geo").unwrap();
+        assert!(geo.contains("// Coil 0 label: row_a\\nPoint(1) = {0, 0, 0};\n"));
+        assert!(!geo.lines().any(|line| line.starts_with("Point(1) = {0, 0, 0};")));
+    }
+
+    #[test]
+    fn break_inside_overlap_is_warned() {
+        let mut layout_in = two_coil_layout();
+        layout_in.coils[0].overlap_segments = vec![(20, 28)];
+        layout_in.coils[1].overlap_segments = vec![(60, 4)];
+
+        let test_dir = temp_dir("gmsh_overlap_break_test");
+        let output_dir = test_dir.path();
+        let output_path = output_dir.join("array").to_string_lossy().to_string();
+
+        warnings::clear();
+        Method::default().save_mesh(&layout_in, &output_path).unwrap();
+        assert_eq!(warnings::summary(), vec![("Break inside a mousehole overlap".to_string(), 1)]);
+
+        // Clear of the breaks, no warning
+        layout_in.coils[0].overlap_segments = vec![(26, 30)];
+        warnings::clear();
+        Method::default().save_mesh(&layout_in, &output_path).unwrap();
+        assert!(warnings::summary().is_empty());
+    }
+
+    #[test]
+    fn annotated_capacitance_resonates_at_larmor() {
+        let layout_in = two_coil_layout();
+        let test_dir = temp_dir("gmsh_annotate_test");
+        let output_dir = test_dir.path();
+        let output_path = output_dir.join("array").to_string_lossy().to_string();
+
+        let method = Method{annotate_capacitance: true, ..Default::default()};
+        method.save_mesh(&layout_in, &output_path).unwrap();
+        let txt = std::fs::read_to_string(output_path + ".txt").unwrap();
+
+        let value_after = |line: &str, key: &str| -> f32 {
+            line.split(key).nth(1).unwrap().split_whitespace().next().unwrap().parse().unwrap()
+        };
+        let port_line = txt.lines().find(|line| line.starts_with("1 ") && line.contains("port")).unwrap();
+        let inductance_nh = value_after(port_line, "L = ");
+        let capacitance_pf = value_after(port_line, "C_tune = ");
+        assert!((inductance_nh - layout_in.coils[0].self_inductance(1.0)).abs() < 1e-2);
+
+        // f = 1 / (2 pi sqrt(LC)), in Hz for H and F
+        let resonance_mhz = 1.0e-6 / (2.0 * PI * (inductance_nh * 1.0e-9 * capacitance_pf * 1.0e-12).sqrt());
+        assert!((resonance_mhz - method.larmor_mhz).abs() / method.larmor_mhz < 1e-3, "Resonance at {} MHz", resonance_mhz);
+
+        // The written series capacitors add up to the annotated tuning capacitance
+        let inverse_sum: f32 = txt.lines()
+            .filter(|line| line.ends_with("% coil 1 tuning"))
+            .map(|line| 1.0 / line.split_whitespace().nth(3).unwrap().trim_end_matches("e-12").parse::<f32>().unwrap())
+            .sum();
+        assert!(((1.0 / inverse_sum) - capacitance_pf).abs() / capacitance_pf < 1e-2);
+    }
+}
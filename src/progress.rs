@@ -0,0 +1,104 @@
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+
+/// Structured progress event, for library consumers (e.g. a GUI) that can't follow the printed output.
+/// Events are emitted alongside the usual printing, which is unchanged.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ProgressEvent {
+    /// A stage (`"layout"`, `"mesh"`, `"sim"`) started.
+    StageStarted { stage: &'static str },
+    /// An optimization iteration finished, with the RMS coupling it started from.
+    /// Iterations count from 1 across all the optimization passes of a run (see `start_iterations`).
+    Iteration { i: usize, total: usize, rms: f32 },
+    /// A coil was laid out on the surface.
+    CoilLaidOut { id: usize },
+    /// A stage finished successfully.
+    Finished { stage: &'static str },
+}
+
+/// Shared progress callback, so it can be called without holding the borrow of the stored one.
+type Callback = Rc<dyn Fn(ProgressEvent)>;
+
+thread_local! {
+    /// Progress callback for this thread, if any.
+    static CALLBACK: RefCell<Option<Callback>> = const { RefCell::new(None) };
+    /// Iterations emitted so far in the current run, and the planned total.
+    static ITERATIONS: Cell<(usize, usize)> = const { Cell::new((0, 0)) };
+}
+
+/// Set the progress callback for runs on this thread, replacing any previous one.
+pub fn set_callback(callback: impl Fn(ProgressEvent) + 'static) {
+    CALLBACK.with(|stored| *stored.borrow_mut() = Some(Rc::new(callback)));
+}
+
+/// Remove the progress callback for this thread.
+pub fn clear_callback() {
+    CALLBACK.with(|stored| *stored.borrow_mut() = None);
+}
+
+/// Send an event to the progress callback, if one is set.
+/// The callback may itself emit events or replace the callback.
+pub fn emit(event: ProgressEvent) {
+    let callback = CALLBACK.with(|stored| stored.borrow().clone());
+    if let Some(callback) = callback {
+        callback(event);
+    }
+}
+
+/// Start numbering the iterations of a run planned to take `total` iterations over all its optimization passes
+/// (e.g. coarse stages and restarts), so the iteration events count up across the passes.
+pub fn start_iterations(total: usize) {
+    ITERATIONS.with(|iterations| iterations.set((0, total)));
+}
+
+/// Emit the event for the next iteration of the run, with the RMS coupling it started from.
+pub fn iteration(rms: f32) {
+    let (i, total) = ITERATIONS.with(|iterations| {
+        let (done, total) = iterations.get();
+        iterations.set((done + 1, total));
+        (done + 1, total.max(done + 1))
+    });
+    emit(ProgressEvent::Iteration{i, total, rms});
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::rc::Rc;
+
+    #[test]
+    fn events_reach_the_callback_until_cleared() {
+        let events = Rc::new(RefCell::new(Vec::new()));
+        let recorder = events.clone();
+        set_callback(move |event| recorder.borrow_mut().push(event));
+        emit(ProgressEvent::StageStarted{stage: "layout"});
+        emit(ProgressEvent::CoilLaidOut{id: 3});
+        clear_callback();
+        emit(ProgressEvent::Finished{stage: "layout"});
+
+        assert_eq!(*events.borrow(), vec![
+            ProgressEvent::StageStarted{stage: "layout"},
+            ProgressEvent::CoilLaidOut{id: 3},
+        ]);
+    }
+
+    #[test]
+    fn callback_can_emit_and_clear_itself() {
+        let events = Rc::new(RefCell::new(Vec::new()));
+        let recorder = events.clone();
+        set_callback(move |event| {
+            recorder.borrow_mut().push(event);
+            if let ProgressEvent::StageStarted{stage} = event {
+                emit(ProgressEvent::Finished{stage});
+                clear_callback();
+            }
+        });
+        emit(ProgressEvent::StageStarted{stage: "mesh"});
+        emit(ProgressEvent::CoilLaidOut{id: 0});
+
+        assert_eq!(*events.borrow(), vec![
+            ProgressEvent::StageStarted{stage: "mesh"},
+            ProgressEvent::Finished{stage: "mesh"},
+        ]);
+    }
+}
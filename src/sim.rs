@@ -1,8 +1,10 @@
 mod proc_errors;
 mod cfg;
 mod methods;
+mod field;
 
 use serde::{Serialize, Deserialize};
+use crate::progress::{self, ProgressEvent};
 
 pub use proc_errors::{
     SimError,
@@ -11,6 +13,17 @@ pub use proc_errors::{
 };
 // Re-export cfg handling
 pub use cfg::SimTarget;
+// Re-export field evaluation
+pub use field::{
+    RoiBox,
+    FieldCombination,
+    b_field,
+    coil_field_maps,
+    combined_field,
+    combined_sensitivity,
+    fov_uniformity,
+    save_roi_map,
+};
 // Re-export simulation methods
 pub use methods::{
     MethodEnum,
@@ -53,9 +66,12 @@ pub fn do_simulation(sim_target: &SimTarget) -> ProcResult<SimOutput> {
     let sim_method = &sim_target.method;
 
     println!("Simulating...");
+    progress::emit(ProgressEvent::StageStarted{stage: "sim"});
 
     // Run the simulation method
     println!("Running simulation method: {}", sim_method.get_method_display_name());
-    sim_method.do_simulation()
+    let sim_output = sim_method.do_simulation()?;
+    progress::emit(ProgressEvent::Finished{stage: "sim"});
+    Ok(sim_output)
 }
 
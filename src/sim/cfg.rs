@@ -52,9 +52,10 @@ impl SimTarget {
 
         if sim_target.save {
             if let Some(output_path) = sim_target.output_path.as_ref() {
-                if !output_path.ends_with(".json")
+                let extension = sim_target.method.get_output_extension();
+                if !output_path.ends_with(&format!(".{}", extension))
                 {
-                    args::err_str("Simulation output path must end with .json")?;
+                    args::err_str(&format!("Simulation output path must end with .{}", extension))?;
                 }
                 let _ = crate::io::create(output_path)?;
             }
@@ -0,0 +1,231 @@
+use crate::{
+    sim,
+    layout,
+    geo_3d::*,
+};
+use serde::{Serialize, Deserialize};
+
+const MU0_OVER_4PI: f32 = 1.0e-4; // mu0 / 4pi in T mm/A
+
+/// Box-shaped region of interest, sampled on a regular grid.
+/// Samples sit at the centers of `samples_per_axis` equal cells along each axis.
+#[derive(Debug, Clone, Copy)]
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RoiBox {
+    pub min: Point,
+    pub max: Point,
+    #[serde(default = "RoiBox::default_samples_per_axis", alias = "samples")]
+    pub samples_per_axis: usize,
+}
+impl RoiBox {
+    pub fn default_samples_per_axis() -> usize {
+        8
+    }
+
+    /// Check the box has samples and a positive extent along every axis.
+    /// Returns a `ProcResult` with `()` or an `Err` describing the problem.
+    pub fn check(&self) -> sim::ProcResult<()> {
+        if self.samples_per_axis == 0 {
+            return sim::err_str("Region of interest needs at least one sample per axis");
+        }
+        let extent = self.max - self.min;
+        if extent.x <= 0.0 || extent.y <= 0.0 || extent.z <= 0.0 {
+            return sim::err_str(&format!("Region of interest maximum {} must be above its minimum {} on every axis", self.max, self.min));
+        }
+        Ok(())
+    }
+
+    /// Get the sample points of the box, x fastest.
+    pub fn grid_points(&self) -> Vec<Point> {
+        let n = self.samples_per_axis;
+        let step = (self.max - self.min) / n as f32;
+        let mut points = Vec::with_capacity(n * n * n);
+        for k in 0..n {
+            for j in 0..n {
+                for i in 0..n {
+                    points.push(self.min + step.el_mul(&GeoVector::new(i as f32 + 0.5, j as f32 + 0.5, k as f32 + 0.5)));
+                }
+            }
+        }
+        points
+    }
+}
+
+/// How the per-coil |B1| are combined at each sample point.
+/// `SumOfMagnitudes` adds the magnitudes, and `Rss` takes the root sum of squares.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[derive(Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum FieldCombination {
+    SumOfMagnitudes,
+    #[default]
+    Rss,
+}
+
+/// Quasi-static Biot-Savart field of a coil at a point, per amp of current (T/A, coordinates in mm).
+/// Each straight wire segment uses the exact finite-segment formula.
+/// Segments the point lies on contribute nothing.
+pub fn b_field(coil: &layout::Coil, point: Point) -> GeoVector {
+    let vertex_count = coil.vertices.len();
+    let mut field = GeoVector::zero();
+    for (vertex_id, vertex) in coil.vertices.iter().enumerate() {
+        let next_vertex = &coil.vertices[(vertex_id + 1) % vertex_count];
+        let r1 = point - vertex.point;
+        let r2 = point - next_vertex.point;
+        let (r1_norm, r2_norm) = (r1.norm(), r2.norm());
+        let denominator = r1_norm * r2_norm * (r1_norm * r2_norm + r1.dot(&r2));
+        if denominator <= f32::EPSILON {
+            continue;
+        }
+        field += r1.cross(&r2) * ((r1_norm + r2_norm) / denominator);
+    }
+    field * MU0_OVER_4PI
+}
+
+/// Field of each coil at each sample point of the region of interest (T/A), indexed `[coil][sample]`.
+/// Each coil span is split into `segment_subdivisions` segments first (see `Coil::subdivided`).
+pub fn coil_field_maps(coils: &[layout::Coil], roi: &RoiBox, segment_subdivisions: usize) -> Vec<Vec<GeoVector>> {
+    let points = roi.grid_points();
+    coils.iter().map(|coil| {
+        let coil = coil.subdivided(segment_subdivisions);
+        points.iter().map(|point| b_field(&coil, *point)).collect()
+    }).collect()
+}
+
+/// Combined |B1| of the coils at each sample point of the region of interest (T/A).
+/// Each coil span is split into `segment_subdivisions` segments first (see `Coil::subdivided`).
+pub fn combined_field(coils: &[layout::Coil], roi: &RoiBox, combination: FieldCombination, segment_subdivisions: usize) -> Vec<f32> {
+    let field_maps = coil_field_maps(coils, roi, segment_subdivisions);
+    combine_magnitudes(&field_maps, roi.grid_points().len(), combination)
+}
+
+/// Combine the per-coil |B1| of the field maps (see `coil_field_maps`) at each of the `sample_count` samples.
+fn combine_magnitudes(field_maps: &[Vec<GeoVector>], sample_count: usize, combination: FieldCombination) -> Vec<f32> {
+    (0..sample_count).map(|sample_id| {
+        let magnitudes = field_maps.iter().map(|field_map| field_map[sample_id].norm());
+        match combination {
+            FieldCombination::SumOfMagnitudes => magnitudes.sum(),
+            FieldCombination::Rss => magnitudes.map(|magnitude| magnitude * magnitude).sum::<f32>().sqrt(),
+        }
+    }).collect()
+}
+
+/// Combined sensitivity of the array from the per-coil field maps (see `coil_field_maps`), in T/A.
+/// Without phases, this is the root sum of squares of the per-coil |B1|, the usual phased-array magnitude combination.
+/// With a phase per coil (degrees), the fields are summed coherently instead, as |sum of B1 exp(j phase)|.
+/// Returns a `ProcResult` with one value per sample, or an `Err` if the maps or phases don't line up.
+pub fn combined_sensitivity(field_maps: &[Vec<GeoVector>], phases: Option<&[f32]>) -> sim::ProcResult<Vec<f32>> {
+    let sample_count = field_maps.first().map_or(0, |field_map| field_map.len());
+    if let Some(coil_id) = field_maps.iter().position(|field_map| field_map.len() != sample_count) {
+        return sim::err_str(&format!("Field map of coil {} has {} samples, expected {}", coil_id, field_maps[coil_id].len(), sample_count));
+    }
+
+    let Some(phases) = phases else {
+        return Ok(combine_magnitudes(field_maps, sample_count, FieldCombination::Rss));
+    };
+    if phases.len() != field_maps.len() {
+        return sim::err_str(&format!("Got {} phases for {} coils", phases.len(), field_maps.len()));
+    }
+    let phasors: Vec<(f32, f32)> = phases.iter().map(|phase| phase.to_radians().sin_cos()).collect();
+    Ok((0..sample_count).map(|sample_id| {
+        let (mut real, mut imag) = (GeoVector::zero(), GeoVector::zero());
+        for (field_map, (sin, cos)) in field_maps.iter().zip(phasors.iter()) {
+            real += field_map[sample_id] * *cos;
+            imag += field_map[sample_id] * *sin;
+        }
+        (real.norm_sq() + imag.norm_sq()).sqrt()
+    }).collect())
+}
+
+/// Save a map over the region of interest (one value per sample, see `RoiBox::grid_points`) as a single VTK volume.
+/// Returns a `ProcResult` with `()` or an `Err`.
+pub fn save_roi_map(path: &str, roi: &RoiBox, field_name: &str, values: &[f32]) -> sim::ProcResult<()> {
+    let n = roi.samples_per_axis;
+    let spacing = (roi.max - roi.min) / n as f32;
+    crate::io::vtk::save_grid_scalars(path, roi.min + spacing * 0.5, spacing, [n, n, n], field_name, values)?;
+    Ok(())
+}
+
+/// Field-of-view uniformity: the coefficient of variation (standard deviation over mean) of the combined |B1|
+/// over the region of interest (see `combined_field`). Lower is more uniform.
+/// Returns a `ProcResult` with the coefficient of variation, or an `Err` if the region has no samples or no field.
+pub fn fov_uniformity(coils: &[layout::Coil], roi: &RoiBox, combination: FieldCombination, segment_subdivisions: usize) -> sim::ProcResult<f32> {
+    let field = combined_field(coils, roi, combination, segment_subdivisions);
+    if field.is_empty() {
+        return sim::err_str("Region of interest has no sample points");
+    }
+
+    let mean = field.iter().sum::<f32>() / field.len() as f32;
+    if mean <= 0.0 {
+        return sim::err_str("Combined field is zero over the region of interest");
+    }
+    let variance = field.iter().map(|value| (value - mean).powi(2)).sum::<f32>() / field.len() as f32;
+    Ok(variance.sqrt() / mean)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::circle_coil;
+    use std::f32::consts::PI;
+
+    fn cube(center: Point, half_width: f32) -> RoiBox {
+        let half_diagonal = GeoVector::new(half_width, half_width, half_width);
+        RoiBox{min: center - half_diagonal, max: center + half_diagonal, samples_per_axis: 6}
+    }
+
+    #[test]
+    fn loop_center_field_matches_analytic() {
+        // B = mu0 I / 2R at the center of a circular loop
+        let coil = circle_coil(Point::zero(), 50.0);
+        let expected = 4.0e-7 * PI / (2.0 * 0.05);
+        let field = b_field(&coil, Point::zero());
+        assert!((field.z - expected).abs() < 0.01 * expected, "Center field {}, expected {}", field.z, expected);
+        assert!(field.x.abs() < 1e-3 * expected && field.y.abs() < 1e-3 * expected);
+    }
+
+    #[test]
+    fn identical_coils_combine_by_rss_and_phase() {
+        let roi = cube(Point::new(10.0, 0.0, 20.0), 5.0);
+        let single = coil_field_maps(&[circle_coil(Point::zero(), 50.0)], &roi, 1);
+        let pair = coil_field_maps(&[circle_coil(Point::zero(), 50.0), circle_coil(Point::zero(), 50.0)], &roi, 1);
+
+        let single_map = combined_sensitivity(&single, None).unwrap();
+        let rss_map = combined_sensitivity(&pair, None).unwrap();
+        for (one, both) in single_map.iter().zip(rss_map.iter()) {
+            assert!((both - 2.0_f32.sqrt() * one).abs() < 1e-4 * one, "RSS {} for a single coil map {}", both, one);
+        }
+
+        // Coherent sums add in phase and cancel out of phase
+        let in_phase = combined_sensitivity(&pair, Some(&[0.0, 0.0])).unwrap();
+        let out_of_phase = combined_sensitivity(&pair, Some(&[0.0, 180.0])).unwrap();
+        for ((one, added), cancelled) in single_map.iter().zip(in_phase.iter()).zip(out_of_phase.iter()) {
+            assert!((added - 2.0 * one).abs() < 1e-4 * one);
+            assert!(*cancelled < 1e-4 * one);
+        }
+        assert!(combined_sensitivity(&pair, Some(&[0.0])).is_err());
+    }
+
+    #[test]
+    fn empty_and_inverted_regions_are_rejected() {
+        assert!(cube(Point::zero(), 5.0).check().is_ok());
+        assert!(RoiBox{samples_per_axis: 0, ..cube(Point::zero(), 5.0)}.check().is_err());
+        let inverted = RoiBox{min: Point::new(5.0, -5.0, -5.0), max: Point::new(-5.0, 5.0, 5.0), samples_per_axis: 4};
+        let error = inverted.check().unwrap_err().to_string();
+        assert!(error.contains("must be above its minimum"), "Unexpected error: {}", error);
+    }
+
+    #[test]
+    fn single_loop_is_more_uniform_on_axis() {
+        let coils = vec![circle_coil(Point::zero(), 50.0)];
+        for combination in [FieldCombination::SumOfMagnitudes, FieldCombination::Rss] {
+            let on_axis = fov_uniformity(&coils, &cube(Point::new(0.0, 0.0, 30.0), 5.0), combination, 1).unwrap();
+            let off_axis = fov_uniformity(&coils, &cube(Point::new(40.0, 0.0, 10.0), 5.0), combination, 1).unwrap();
+            assert!(on_axis < off_axis, "On-axis variation {}, off-axis variation {}", on_axis, off_axis);
+        }
+
+        let empty_roi = RoiBox{samples_per_axis: 0, ..cube(Point::zero(), 5.0)};
+        assert!(fov_uniformity(&coils, &empty_roi, FieldCombination::Rss, 1).is_err());
+    }
+}
@@ -39,6 +39,14 @@ pub enum MethodEnum {
     #[serde(rename = "load_marie_output")]
     LoadMarieOutput(load_marie_output::Method),
 }
+impl MethodEnum {
+    /// Get a one-line description of the simulation method, for listing.
+    pub fn get_method_description(&self) -> &'static str {
+        match self {
+            MethodEnum::LoadMarieOutput(_) => "Load the output of a MARIE simulation that was already run",
+        }
+    }
+}
 
 //
 // ------------------------------------------------------------
@@ -61,6 +69,12 @@ pub trait SimMethodTrait {
     
     /// Get a vector of viable input filetypes for the simulation method.
     fn get_input_filetypes(&self) -> Vec<&'static str>;
+
+    /// Get the output file extension for the simulation method.
+    /// Defaults to JSON.
+    fn get_output_extension(&self) -> &'static str {
+        "json"
+    }
     
     /// Run the simulation process with the given arguments.
     /// Uses the `sim` module.
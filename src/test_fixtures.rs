@@ -0,0 +1,56 @@
+//! Fixtures shared by the unit tests.
+
+use crate::geo_3d::*;
+use crate::io;
+use crate::layout::{self, AdamCirclesMethod, CircleArgs};
+use std::f32::consts::PI;
+
+/// Flat circular coil in the xy-plane, with 64 vertices and the default wire radius.
+pub fn circle_coil(center: Point, radius: f32) -> layout::Coil {
+    let points = (0..64).map(|i| {
+        let theta = 2.0 * PI * i as f32 / 64.0;
+        center + GeoVector::new(radius * theta.cos(), radius * theta.sin(), 0.0)
+    }).collect();
+    layout::Coil::new(center, GeoVector::zhat(), points, 0.645, vec![GeoVector::zhat(); 64]).unwrap()
+}
+
+/// Flat 80 mm square surface, finely meshed, for the layout method tests.
+pub fn flat_surface() -> Surface {
+    Surface::square_grid(40.0, 80)
+}
+
+/// ADAM method for laying out `circles` on a flat surface.
+/// The example defaults that reach outside the test are off: no symmetry plane, static layout, config output, or port keepout.
+pub fn flat_adam(circles: Vec<CircleArgs>) -> AdamCirclesMethod {
+    AdamCirclesMethod{
+        circles,
+        symmetry_plane: None,
+        layout_in_path: None,
+        final_cfg_output: None,
+        port_keepout_radius: 0.0,
+        ..Default::default()
+    }
+}
+
+/// Layout target running `method` with the config defaults: no input or output paths, and nothing saved.
+pub fn layout_target(method: layout::MethodEnum) -> layout::LayoutTarget {
+    layout::LayoutTarget{
+        input_path: String::new(),
+        output_path: None,
+        save: false,
+        manifest: false,
+        capacitor_summary: None,
+        output_precision: None,
+        smooth_iterations: 0,
+        normalize_winding: false,
+        dedup_vertices: layout::LayoutTarget::default_dedup_vertices(),
+        dedup_tolerance: layout::LayoutTarget::default_dedup_tolerance(),
+        max_liftoff: None,
+        method: method.into(),
+    }
+}
+
+/// Fresh temporary directory for one test, unique to the run, and removed when dropped.
+pub fn temp_dir(name: &str) -> io::TempDir {
+    io::TempDir::new(&format!("comrade_{}", name)).unwrap()
+}
@@ -0,0 +1,106 @@
+use std::cell::{Cell, RefCell};
+use std::time::{Duration, Instant};
+
+thread_local! {
+    /// Whether phases are being timed this run.
+    static ENABLED: Cell<bool> = const { Cell::new(false) };
+    /// Time spent in each phase so far this run, with how many times it ran, in first-seen order.
+    static PHASES: RefCell<Vec<(String, Duration, usize)>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Timer for one pass through a phase, recorded when dropped (so early returns still count).
+/// Does nothing unless timing is enabled (see `start`).
+pub struct PhaseTimer {
+    phase: &'static str,
+    start: Option<Instant>,
+}
+impl Drop for PhaseTimer {
+    fn drop(&mut self) {
+        if let Some(start) = self.start {
+            record(self.phase, start.elapsed());
+        }
+    }
+}
+
+/// Clear the recorded phases, and turn timing on or off for the run.
+pub fn start(enabled: bool) {
+    PHASES.with(|phases| phases.borrow_mut().clear());
+    ENABLED.with(|cell| cell.set(enabled));
+}
+
+/// Turn timing off, keeping the phases recorded so far.
+pub fn stop() {
+    ENABLED.with(|cell| cell.set(false));
+}
+
+/// Start timing a pass through a phase, until the returned timer is dropped.
+/// Phases may nest, in which case the outer phase includes the inner one.
+pub fn phase(phase: &'static str) -> PhaseTimer {
+    let enabled = ENABLED.with(|cell| cell.get());
+    PhaseTimer{phase, start: if enabled { Some(Instant::now()) } else { None }}
+}
+
+/// Add a pass through a phase to the record.
+fn record(phase: &str, elapsed: Duration) {
+    PHASES.with(|phases| {
+        let mut phases = phases.borrow_mut();
+        match phases.iter_mut().find(|(key, _, _)| key == phase) {
+            Some((_, total, count)) => {
+                *total += elapsed;
+                *count += 1;
+            },
+            None => phases.push((phase.to_string(), elapsed, 1)),
+        }
+    });
+}
+
+/// Get the recorded phases, with their total time and number of passes, in first-seen order.
+pub fn phases() -> Vec<(String, Duration, usize)> {
+    PHASES.with(|phases| phases.borrow().clone())
+}
+
+/// Format the timing breakdown of the recorded phases, one line per phase.
+/// Returns an empty string if nothing was recorded.
+pub fn report() -> String {
+    let phases = phases();
+    if phases.is_empty() {
+        return String::new();
+    }
+    let mut report = String::from("Timing breakdown:\n");
+    for (phase, total, count) in phases {
+        if count > 1 {
+            report.push_str(&format!("- {}: {:.3} s (x{})\n", phase, total.as_secs_f64(), count));
+        } else {
+            report.push_str(&format!("- {}: {:.3} s\n", phase, total.as_secs_f64()));
+        }
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn passes_are_totalled_only_while_enabled() {
+        start(true);
+        for _ in 0..3 {
+            let _timer = phase("Inner");
+        }
+        {
+            let _outer = phase("Outer");
+            let _inner = phase("Inner");
+        }
+        stop();
+        let _ignored = phase("Stopped");
+
+        let recorded: Vec<(String, usize)> = phases().into_iter().map(|(phase, _, count)| (phase, count)).collect();
+        assert_eq!(recorded, vec![("Inner".to_string(), 4), ("Outer".to_string(), 1)]);
+        assert!(report().starts_with("Timing breakdown:\n- Inner: "));
+        assert!(report().contains(" s (x4)\n"));
+
+        start(false);
+        let _timer = phase("Inner");
+        assert!(phases().is_empty());
+    }
+}
@@ -0,0 +1,83 @@
+use std::cell::RefCell;
+
+thread_local! {
+    /// Warnings seen so far this run, grouped by key, in first-seen order.
+    static WARNINGS: RefCell<Vec<(String, usize)>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Print a warning and record it for the end-of-run summary.
+/// Identical messages are grouped together.
+pub fn warn(message: &str) {
+    warn_grouped(message, message);
+}
+
+/// Print a warning and record it under a group key for the end-of-run summary.
+/// Use this when the printed message carries details (positions, distances) that would stop repeats from grouping.
+pub fn warn_grouped(group: &str, message: &str) {
+    println!("WARNING: {}", message);
+    record(group);
+}
+
+/// Record a warning under a group key without printing it.
+/// Use this for warnings that can repeat every iteration and would flood the output.
+pub fn record(group: &str) {
+    WARNINGS.with(|warnings| {
+        let mut warnings = warnings.borrow_mut();
+        match warnings.iter_mut().find(|(key, _)| key == group) {
+            Some((_, count)) => *count += 1,
+            None => warnings.push((group.to_string(), 1)),
+        }
+    });
+}
+
+/// Get the grouped warnings recorded so far, with their counts, in first-seen order.
+pub fn summary() -> Vec<(String, usize)> {
+    WARNINGS.with(|warnings| warnings.borrow().clone())
+}
+
+/// Clear the recorded warnings.
+pub fn clear() {
+    WARNINGS.with(|warnings| warnings.borrow_mut().clear());
+}
+
+/// Print the deduplicated, counted warning summary and clear the recorded warnings.
+/// Prints nothing if there were no warnings.
+pub fn print_summary() {
+    let summary = summary();
+    clear();
+    if summary.is_empty() {
+        return;
+    }
+    println!();
+    println!("Warning summary:");
+    for (group, count) in summary {
+        if count > 1 {
+            println!("- {} (x{})", group, count);
+        } else {
+            println!("- {}", group);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn repeats_are_grouped_with_counts() {
+        clear();
+        warn("Number of close coils changed! (3 -> 4)");
+        warn_grouped("Coil 3 shifted to boundary", "Coil 3 too close to boundary, center shifted by |0.10|");
+        record("Coil 3 shifted to boundary");
+        warn("Number of close coils changed! (3 -> 4)");
+        record("Coil 3 shifted to boundary");
+
+        assert_eq!(summary(), vec![
+            ("Number of close coils changed! (3 -> 4)".to_string(), 2),
+            ("Coil 3 shifted to boundary".to_string(), 3),
+        ]);
+
+        print_summary();
+        assert!(summary().is_empty());
+    }
+}